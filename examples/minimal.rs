@@ -38,12 +38,13 @@ use hex_fmt::HexFmt;
 use log::LevelFilter;
 use routing::{
     event::{Connected, Event},
-    NetworkConfig, Node,
+    FullId, NetworkConfig, Node,
 };
 use std::{
     collections::HashSet,
     convert::TryInto,
     net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::PathBuf,
     thread::{self, JoinHandle},
 };
 use structopt::StructOpt;
@@ -86,6 +87,23 @@ struct Options {
         hide_default_value = true
     )]
     count: usize,
+    /// File to keep the node identity in, so the node keeps its name across restarts. If the file
+    /// exists, the identity is loaded from it, otherwise a new identity is generated and saved to
+    /// it.
+    ///
+    /// If starting multiple nodes (see --count), the index of the node is appended to the file
+    /// name of all but the first node.
+    #[structopt(short, long, value_name = "PATH", parse(from_os_str))]
+    key_file: Option<PathBuf>,
+    /// Passphrase to encrypt the key file with (see --key-file). If omitted, the key file is not
+    /// encrypted.
+    #[structopt(
+        long,
+        value_name = "PASSPHRASE",
+        env = "ROUTING_KEY_PASSPHRASE",
+        hide_env_values = true
+    )]
+    passphrase: Option<String>,
     /// Enable verbose output. Can be specified multiple times for increased verbosity.
     #[structopt(short, parse(from_occurrences))]
     verbosity: u8,
@@ -99,8 +117,19 @@ fn main() {
     let opts = Options::from_args();
     init_log(opts.verbosity);
 
+    let key_file = opts.key_file.map(|path| KeyFile {
+        path,
+        passphrase: opts.passphrase,
+    });
+
     if opts.count <= 1 {
-        start_single_node(opts.first, opts.bootstrap_contacts, opts.ip, opts.port)
+        start_single_node(
+            opts.first,
+            opts.bootstrap_contacts,
+            opts.ip,
+            opts.port,
+            key_file,
+        )
     } else {
         start_multiple_nodes(
             opts.count,
@@ -108,6 +137,7 @@ fn main() {
             opts.bootstrap_contacts,
             opts.ip,
             opts.port,
+            key_file,
         )
     }
 }
@@ -118,8 +148,17 @@ fn start_single_node(
     contacts: Vec<SocketAddr>,
     ip: Option<IpAddr>,
     port: Option<u16>,
+    key_file: Option<KeyFile>,
 ) {
-    start_node(0, first, contacts.into_iter().collect(), ip, port, None)
+    start_node(
+        0,
+        first,
+        contacts.into_iter().collect(),
+        ip,
+        port,
+        key_file,
+        None,
+    )
 }
 
 // Starts `count` nodes and blocks until all of them terminate.
@@ -132,11 +171,12 @@ fn start_multiple_nodes(
     contacts: Vec<SocketAddr>,
     ip: Option<IpAddr>,
     base_port: Option<u16>,
+    key_file: Option<KeyFile>,
 ) {
     let mut join_handles = Vec::with_capacity(count);
 
     let (first_index, first_contact) = if first {
-        let (join_handle, first_contact) = spawn_first_node(ip, base_port);
+        let (join_handle, first_contact) = spawn_first_node(ip, base_port, key_file.clone());
         join_handles.push(join_handle);
         (1, Some(first_contact))
     } else {
@@ -146,17 +186,30 @@ fn start_multiple_nodes(
     let contacts: HashSet<_> = contacts.into_iter().chain(first_contact).collect();
 
     for index in first_index..count {
-        let join_handle = spawn_other_node(index, contacts.clone(), ip, base_port);
+        let join_handle =
+            spawn_other_node(index, contacts.clone(), ip, base_port, key_file.clone());
         join_handles.push(join_handle);
     }
 }
 
 // Spawns the first (genesis) node in its own thread and blocks until its contact info becomes
 // available.
-fn spawn_first_node(ip: Option<IpAddr>, base_port: Option<u16>) -> (ScopedJoinHandle, SocketAddr) {
+fn spawn_first_node(
+    ip: Option<IpAddr>,
+    base_port: Option<u16>,
+    key_file: Option<KeyFile>,
+) -> (ScopedJoinHandle, SocketAddr) {
     let (contact_tx, contact_rx) = crossbeam_channel::bounded(0);
     let join_handle = thread::spawn(move || {
-        start_node(0, true, HashSet::default(), ip, base_port, Some(contact_tx))
+        start_node(
+            0,
+            true,
+            HashSet::default(),
+            ip,
+            base_port,
+            key_file,
+            Some(contact_tx),
+        )
     })
     .into();
     let contact = contact_rx
@@ -174,8 +227,9 @@ fn spawn_other_node(
     contacts: HashSet<SocketAddr>,
     ip: Option<IpAddr>,
     base_port: Option<u16>,
+    key_file: Option<KeyFile>,
 ) -> ScopedJoinHandle {
-    thread::spawn(move || start_node(index, false, contacts, ip, base_port, None)).into()
+    thread::spawn(move || start_node(index, false, contacts, ip, base_port, key_file, None)).into()
 }
 
 // Starts a single node and blocks until it terminates.
@@ -185,6 +239,7 @@ fn start_node(
     contacts: HashSet<SocketAddr>,
     ip: Option<IpAddr>,
     base_port: Option<u16>,
+    key_file: Option<KeyFile>,
     contact_tx: Option<Sender<SocketAddr>>,
 ) {
     let ip = ip.unwrap_or_else(|| Ipv4Addr::LOCALHOST.into());
@@ -205,19 +260,63 @@ fn start_node(
 
     log::info!("Node #{} starting...", index);
 
-    // The returned triple is:
+    // The returned pair is:
     // - The routing node itself.
     // - The receiver for events that the node notifies the application about.
-    // - The receiver for client network events. We don't support clients in this example, so we
-    //   can ignore it
-    let (node, event_rx, _client_network_event_rx) = Node::builder()
-        .first(first)
-        .network_config(network_config)
-        .create();
+    let mut builder = Node::builder().first(first).network_config(network_config);
+    if let Some(key_file) = key_file {
+        builder = builder.full_id(key_file.load_or_create(index));
+    }
+
+    let (node, event_rx) = builder.create();
 
     run_node(index, node, event_rx, contact_tx)
 }
 
+// Location of the node identity and the passphrase it is encrypted with, if any.
+#[derive(Clone, Debug)]
+struct KeyFile {
+    path: PathBuf,
+    passphrase: Option<String>,
+}
+
+impl KeyFile {
+    // Loads the identity of the `index`-th node, or generates and saves a new one if the key file
+    // doesn't exist yet.
+    fn load_or_create(&self, index: usize) -> FullId {
+        let path = self.node_path(index);
+        let passphrase = self.passphrase.as_deref();
+
+        if path.exists() {
+            let full_id = FullId::load_from_file(&path, passphrase).unwrap_or_else(|error| {
+                panic!("Failed to load key file {}: {}", path.display(), error)
+            });
+            log::info!("Node #{} loaded key file {}", index, path.display());
+            full_id
+        } else {
+            // The default rng is the OS one.
+            let full_id = FullId::gen(&mut Default::default());
+            full_id
+                .save_to_file(&path, passphrase)
+                .unwrap_or_else(|error| {
+                    panic!("Failed to save key file {}: {}", path.display(), error)
+                });
+            log::info!("Node #{} saved key file {}", index, path.display());
+            full_id
+        }
+    }
+
+    fn node_path(&self, index: usize) -> PathBuf {
+        if index == 0 {
+            return self.path.clone();
+        }
+
+        let mut file_name = self.path.file_name().unwrap_or_default().to_os_string();
+        file_name.push(format!(".{}", index));
+        self.path.with_file_name(file_name)
+    }
+}
+
 // Runs the nodes event loop. Blocks until terminated.
 fn run_node(
     index: usize,
@@ -228,9 +327,8 @@ fn run_node(
     loop {
         // We need receive from multiple channels. As a minimum, we need to receive from the
         // channels used internally by `Node` and from the node event channel. Additionally we might
-        // want to receive from the client network event channel (not used in this example) or from
-        // any other channel used by the application. To achieve this, we use the `Select`
-        // mechanism of `crossbeam-channel`.
+        // want to receive from any other channel used by the application. To achieve this, we use
+        // the `Select` mechanism of `crossbeam-channel`.
 
         // First create an instance of `Select`.
         let mut select = Select::new();
@@ -310,13 +408,44 @@ fn handle_event(
         Event::SectionSplit(prefix) => {
             log::info!("Node #{} section split - new prefix: {:b}", index, prefix);
         }
-        Event::MessageReceived { content, src, dst } => log::info!(
-            "Node #{} received message - src: {:?}, dst: {:?}, content: {}",
+        Event::SectionMerged(prefix) => {
+            log::info!("Node #{} section merged - new prefix: {:b}", index, prefix);
+        }
+        Event::MessageReceived {
+            id,
+            content,
+            src,
+            dst,
+        } => log::info!(
+            "Node #{} received message {:?} - src: {:?}, dst: {:?}, content: {}",
             index,
+            id,
             src,
             dst,
             HexFmt(content)
         ),
+        Event::MessageDelivered(id) => log::info!("Node #{} delivered message {:?}", index, id),
+        Event::MessageDeliveryFailed(id) => {
+            log::info!("Node #{} failed to deliver message {:?}", index, id)
+        }
+        Event::ReadyToSend => log::info!("Node #{} can send messages again", index),
+        Event::ClientMessage { client, content } => log::info!(
+            "Node #{} received client message - client: {:?}, content: {}",
+            index,
+            client,
+            HexFmt(content)
+        ),
+        Event::ClientRejected {
+            client,
+            peer_addr,
+            rejection,
+        } => log::info!(
+            "Node #{} rejected client message - client: {:?}, peer: {}, rejection: {:?}",
+            index,
+            client,
+            peer_addr,
+            rejection
+        ),
         Event::Consensus(payload) => log::info!(
             "Node #{} reached consensus - payload: {}",
             index,
@@ -7,15 +7,16 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use crate::{
+    client::ClientId,
     error::RoutingError,
+    id::P2pNode,
     location::{DstLocation, SrcLocation},
+    messages::MessageId,
+    resource_proof::ResourceProof,
 };
-use bytes::Bytes;
 use hex_fmt::HexFmt;
-use quic_p2p::Token;
 use std::{
     fmt::{self, Debug, Formatter},
-    net::SocketAddr,
     sync::mpsc::Sender,
 };
 
@@ -31,19 +32,25 @@ pub enum Action {
         src: SrcLocation,
         dst: DstLocation,
         content: Vec<u8>,
-        result_tx: Sender<Result<(), RoutingError>>,
+        // Whether the destination should acknowledge the delivery of the message.
+        ack: bool,
+        result_tx: Sender<Result<MessageId, RoutingError>>,
     },
     HandleTimeout(u64),
     DisconnectClient {
-        peer_addr: SocketAddr,
+        client: ClientId,
         result_tx: Sender<Result<(), RoutingError>>,
     },
-    SendMessageToClient {
-        peer_addr: SocketAddr,
-        msg: Bytes,
-        token: Token,
+    SendToClient {
+        client: ClientId,
+        content: Vec<u8>,
         result_tx: Sender<Result<(), RoutingError>>,
     },
+    // A resource challenge from `elder` was solved in the background.
+    ResourceProofSolved {
+        elder: P2pNode,
+        proof: ResourceProof,
+    },
 }
 
 impl Debug for Action {
@@ -55,15 +62,26 @@ impl Debug for Action {
                 HexFmt(content)
             ),
             Self::HandleTimeout(token) => write!(formatter, "Action::HandleTimeout({})", token),
-            Self::DisconnectClient { peer_addr, .. } => {
-                write!(formatter, "Action::DisconnectClient: {}", peer_addr)
+            Self::DisconnectClient { ref client, .. } => {
+                write!(formatter, "Action::DisconnectClient: {:?}", client)
             }
-            Self::SendMessageToClient {
-                peer_addr, token, ..
+            Self::SendToClient {
+                ref client,
+                ref content,
+                ..
+            } => write!(
+                formatter,
+                "Action::SendToClient {{ {:?}, \"{:<8}\" }}",
+                client,
+                HexFmt(content)
+            ),
+            Self::ResourceProofSolved {
+                ref elder,
+                ref proof,
             } => write!(
                 formatter,
-                "Action::SendMessageToClient: {}, token: {}",
-                peer_addr, token
+                "Action::ResourceProofSolved {{ {}, {:?} }}",
+                elder, proof
             ),
         }
     }
@@ -0,0 +1,284 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::{
+    action::Action,
+    error::RoutingError,
+    event::Event,
+    location::{DstLocation, SrcLocation},
+    messages::MessageId,
+    node::{Builder, Node},
+    NetworkEvent,
+};
+use crossbeam_channel as mpmc;
+use futures::{
+    channel::{mpsc, oneshot},
+    stream::Stream,
+    task::{Context, Poll, Waker},
+    Future,
+};
+use std::{
+    mem,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+// Maximal number of messages passed to the driver and not yet handled by it. Further messages are
+// rejected with `RoutingError::Backpressure`.
+const MAX_PENDING_MESSAGES: usize = 1024;
+
+impl Builder {
+    /// Creates new `Node` driven asynchronously.
+    ///
+    /// Returns the handle to interact with the node and the `Driver` which must be spawned as a
+    /// task on a local executor (e.g. `tokio::task::LocalSet`).
+    pub fn create_async(self) -> (AsyncNode, Driver) {
+        let (node, user_event_rx) = self.create();
+        AsyncNode::new(node, user_event_rx)
+    }
+}
+
+/// Asynchronous interface to a `Node`.
+///
+/// The node itself is owned by the `Driver` task, this is only a handle to it which can be cloned
+/// and sent to other threads. All the requests fail with `RoutingError::InvalidState` once the
+/// driver is dropped or the node terminates.
+#[derive(Clone)]
+pub struct AsyncNode {
+    command_tx: mpsc::UnboundedSender<Command>,
+    pending_messages: Arc<AtomicUsize>,
+}
+
+impl AsyncNode {
+    /// Wraps an already created `Node` and the receiver of its events.
+    pub fn new(node: Node, user_event_rx: mpmc::Receiver<Event>) -> (Self, Driver) {
+        let (command_tx, command_rx) = mpsc::unbounded();
+        let pending_messages = Arc::new(AtomicUsize::new(0));
+        let watcher = Watcher::spawn(node.input_channels());
+
+        let driver = Driver {
+            node,
+            user_event_rx,
+            command_rx,
+            pending_messages: Arc::clone(&pending_messages),
+            subscribers: Vec::new(),
+            event_backlog: Vec::new(),
+            watcher,
+        };
+
+        (
+            Self {
+                command_tx,
+                pending_messages,
+            },
+            driver,
+        )
+    }
+
+    /// Returns a stream of the events raised by the node from now on. The events raised before
+    /// the first call to this function are not lost - they are delivered to the first stream.
+    pub fn events(&self) -> impl Stream<Item = Event> + Unpin {
+        let (event_tx, event_rx) = mpsc::unbounded();
+        let _ = self.command_tx.unbounded_send(Command::Subscribe(event_tx));
+        event_rx
+    }
+
+    /// Send a message. Resolves to the id of the message once the node has handled the request.
+    ///
+    /// Fails with `RoutingError::Backpressure` if too many messages are waiting for the driver or
+    /// in the outbound queues of the node, see `Node::send_message`.
+    pub async fn send_message(
+        &self,
+        src: SrcLocation,
+        dst: DstLocation,
+        content: Vec<u8>,
+    ) -> Result<MessageId, RoutingError> {
+        if self.pending_messages.fetch_add(1, Ordering::SeqCst) >= MAX_PENDING_MESSAGES {
+            let _ = self.pending_messages.fetch_sub(1, Ordering::SeqCst);
+            return Err(RoutingError::Backpressure);
+        }
+
+        let (result_tx, result_rx) = oneshot::channel();
+        if self
+            .command_tx
+            .unbounded_send(Command::SendMessage {
+                src,
+                dst,
+                content,
+                result_tx,
+            })
+            .is_err()
+        {
+            let _ = self.pending_messages.fetch_sub(1, Ordering::SeqCst);
+            return Err(RoutingError::InvalidState);
+        }
+
+        result_rx.await.map_err(|_| RoutingError::InvalidState)?
+    }
+}
+
+/// Task driving a `Node` created by `Builder::create_async`. It resolves when the node terminates.
+///
+/// When built with the `mock` feature, timers and network events are only processed while the
+/// driver is polled, so the tests need to keep polling it after advancing the clock or polling the
+/// mock network.
+pub struct Driver {
+    node: Node,
+    user_event_rx: mpmc::Receiver<Event>,
+    command_rx: mpsc::UnboundedReceiver<Command>,
+    pending_messages: Arc<AtomicUsize>,
+    subscribers: Vec<mpsc::UnboundedSender<Event>>,
+    // Events raised before anyone subscribed to them.
+    event_backlog: Vec<Event>,
+    watcher: Watcher,
+}
+
+impl Driver {
+    fn handle_command(&mut self, command: Command) {
+        match command {
+            Command::SendMessage {
+                src,
+                dst,
+                content,
+                result_tx,
+            } => {
+                let _ = self.pending_messages.fetch_sub(1, Ordering::SeqCst);
+                let result = self.node.send_message(src, dst, content);
+                let _ = result_tx.send(result);
+            }
+            Command::Subscribe(event_tx) => {
+                for event in mem::take(&mut self.event_backlog) {
+                    let _ = event_tx.unbounded_send(event);
+                }
+                self.subscribers.push(event_tx);
+            }
+        }
+    }
+
+    fn forward_events(&mut self) {
+        while let Ok(event) = self.user_event_rx.try_recv() {
+            self.subscribers
+                .retain(|event_tx| event_tx.unbounded_send(event.clone()).is_ok());
+
+            if self.subscribers.is_empty() {
+                self.event_backlog.push(event);
+            }
+        }
+    }
+
+    // Handles all the currently available inputs of the node. Returns `false` if the node
+    // terminated.
+    fn step_node(&mut self) -> bool {
+        loop {
+            let mut select = mpmc::Select::new();
+            self.node.register(&mut select);
+
+            let op_index = match select.try_ready() {
+                Ok(op_index) => op_index,
+                Err(_) => return true,
+            };
+
+            if self.node.handle_selected_operation(op_index).is_err() {
+                return false;
+            }
+        }
+    }
+}
+
+impl Future for Driver {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let driver = self.get_mut();
+
+        while let Poll::Ready(Some(command)) = Pin::new(&mut driver.command_rx).poll_next(cx) {
+            driver.handle_command(command);
+        }
+
+        let running = driver.step_node();
+        driver.forward_events();
+
+        if running {
+            driver.watcher.arm(cx.waker().clone());
+            Poll::Pending
+        } else {
+            Poll::Ready(())
+        }
+    }
+}
+
+enum Command {
+    SendMessage {
+        src: SrcLocation,
+        dst: DstLocation,
+        content: Vec<u8>,
+        result_tx: oneshot::Sender<Result<MessageId, RoutingError>>,
+    },
+    Subscribe(mpsc::UnboundedSender<Event>),
+}
+
+// The node channels can't notify a task, so this thread blocks on them instead and wakes the
+// driver once any of them has an input ready.
+struct Watcher {
+    waker: Arc<Mutex<Option<Waker>>>,
+    arm_tx: mpmc::Sender<()>,
+    // Never sent to, only dropped to stop the thread.
+    _stop_tx: mpmc::Sender<()>,
+}
+
+impl Watcher {
+    fn spawn(
+        (network_rx, action_rx): (mpmc::Receiver<NetworkEvent>, mpmc::Receiver<Action>),
+    ) -> Self {
+        let waker = Arc::new(Mutex::new(None::<Waker>));
+        let (arm_tx, arm_rx) = mpmc::bounded(1);
+        let (stop_tx, stop_rx) = mpmc::bounded::<()>(0);
+
+        let thread_waker = Arc::clone(&waker);
+        let result = thread::Builder::new()
+            .name("routing-driver-watcher".to_string())
+            .spawn(move || {
+                while arm_rx.recv().is_ok() {
+                    let mut select = mpmc::Select::new();
+                    let _ = select.recv(&network_rx);
+                    let _ = select.recv(&action_rx);
+                    let _ = select.recv(&stop_rx);
+                    let _ = select.ready();
+
+                    let waker = thread_waker.lock().ok().and_then(|mut waker| waker.take());
+                    if let Some(waker) = waker {
+                        waker.wake();
+                    }
+                }
+            });
+
+        if let Err(error) = result {
+            error!("Failed to spawn the driver watcher thread: {:?}", error);
+        }
+
+        Self {
+            waker,
+            arm_tx,
+            _stop_tx: stop_tx,
+        }
+    }
+
+    // Wake the given waker once the node has any input ready.
+    fn arm(&self, waker: Waker) {
+        if let Ok(mut current) = self.waker.lock() {
+            *current = Some(waker);
+        }
+
+        // If the watcher is already armed, it picks up the new waker.
+        let _ = self.arm_tx.try_send(());
+    }
+}
@@ -0,0 +1,68 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::member_info::MIN_AGE;
+use std::fmt::Debug;
+
+/// Rules by which the members of a section age.
+///
+/// Every member has an age counter which is incremented on churn events in its section. The age of
+/// the member is the base 2 logarithm of its age counter, so with the default increment of one,
+/// a member of age `n` has to experience `2^n` churn events to reach age `n + 1`. The age never
+/// drops below `MIN_AGE`.
+///
+/// All the nodes of a network must use the same policy, otherwise they won't agree on the ages of
+/// the members of their sections.
+pub trait AgeingPolicy: Debug + Send + Sync {
+    /// Name identifying the policy. It is stored when a node is paused, so that it can't be
+    /// resumed with a different policy.
+    fn id(&self) -> &str;
+
+    /// Age of a node joining the network for the first time. Values below `MIN_AGE` are treated as
+    /// `MIN_AGE`.
+    fn start_age(&self) -> u8;
+
+    /// Amount by which the age counter of a member of the given age is incremented on a churn
+    /// event. Zero means the member doesn't age.
+    fn age_counter_increment(&self, age: u8) -> u32;
+
+    /// Whether a member of the given age is mature (an adult) as opposed to an infant.
+    fn is_mature(&self, age: u8) -> bool;
+
+    /// Whether a member whose age changed from `old_age` to `new_age` on a churn event should be
+    /// relocated to another section.
+    fn should_relocate(&self, old_age: u8, new_age: u8) -> bool;
+}
+
+/// The default ageing policy: nodes start at `MIN_AGE`, their age counter is incremented by one
+/// on every churn event, they become adults above `MIN_AGE` and are relocated every time their age
+/// increases.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultAgeingPolicy;
+
+impl AgeingPolicy for DefaultAgeingPolicy {
+    fn id(&self) -> &str {
+        "default"
+    }
+
+    fn start_age(&self) -> u8 {
+        MIN_AGE
+    }
+
+    fn age_counter_increment(&self, _age: u8) -> u32 {
+        1
+    }
+
+    fn is_mature(&self, age: u8) -> bool {
+        age > MIN_AGE
+    }
+
+    fn should_relocate(&self, old_age: u8, new_age: u8) -> bool {
+        new_age > old_age
+    }
+}
@@ -0,0 +1,114 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::id::{P2pNode, PublicId};
+use std::{collections::BTreeMap, net::SocketAddr};
+
+/// Default number of events the section agrees on before a ban is lifted. In a busy section
+/// that's a matter of seconds, in an idle one it can take much longer.
+pub const DEFAULT_BAN_EXPIRY: u64 = 1000;
+
+/// Identity or address a node can be banned by.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BanTarget {
+    /// Node with this identity.
+    Id(PublicId),
+    /// Node connecting from this address, under any identity.
+    Addr(SocketAddr),
+}
+
+/// Nodes the section agreed to refuse to bootstrap and join.
+///
+/// The expiry of a ban is counted in events agreed by the section rather than in time, so every
+/// elder lifts it at the same point of the consensus order. Every accumulated event counts, so how
+/// long a ban lasts in time depends on how busy the section is. A section which agrees on nothing
+/// never lifts its bans, but then it has no joining nodes to refuse either.
+///
+/// The list is part of the `GenesisPfxInfo` sent to joining and promoted nodes since protocol
+/// version 3, and of the `Chain` saved in the paused state format version 2.
+#[derive(Clone, Default, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BanList {
+    // Number of agreed events each ban lasts for yet.
+    entries: BTreeMap<BanTarget, u64>,
+}
+
+impl BanList {
+    /// Bans the target for the next `expiry` agreed events. Banning it again renews the ban.
+    pub fn insert(&mut self, target: BanTarget, expiry: u64) {
+        if expiry > 0 {
+            let _ = self.entries.insert(target, expiry);
+        }
+    }
+
+    /// Returns whether the target is banned.
+    pub fn contains(&self, target: &BanTarget) -> bool {
+        self.entries.contains_key(target)
+    }
+
+    /// Returns whether the node is banned, by its identity or its address.
+    pub fn contains_node(&self, p2p_node: &P2pNode) -> bool {
+        self.contains(&BanTarget::Id(*p2p_node.public_id()))
+            || self.contains(&BanTarget::Addr(*p2p_node.peer_addr()))
+    }
+
+    /// Counts one agreed event against every ban, lifting the expired ones.
+    pub fn tick(&mut self) {
+        self.entries.retain(|_, remaining| {
+            *remaining -= 1;
+            *remaining > 0
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{id::FullId, rng, unwrap};
+
+    #[test]
+    fn ban_by_id_or_addr() {
+        let mut rng = rng::new();
+        let banned_id = *FullId::gen(&mut rng).public_id();
+        let other_id = *FullId::gen(&mut rng).public_id();
+        let banned_addr: SocketAddr = unwrap!("127.0.0.1:5000".parse());
+        let other_addr: SocketAddr = unwrap!("127.0.0.1:5001".parse());
+
+        let mut ban_list = BanList::default();
+        ban_list.insert(BanTarget::Id(banned_id), 10);
+        ban_list.insert(BanTarget::Addr(banned_addr), 10);
+
+        assert!(ban_list.contains_node(&P2pNode::new(banned_id, other_addr)));
+        assert!(ban_list.contains_node(&P2pNode::new(other_id, banned_addr)));
+        assert!(!ban_list.contains_node(&P2pNode::new(other_id, other_addr)));
+    }
+
+    #[test]
+    fn lift_expired_bans() {
+        let mut rng = rng::new();
+        let pub_id = *FullId::gen(&mut rng).public_id();
+        let addr: SocketAddr = unwrap!("127.0.0.1:5000".parse());
+
+        let mut ban_list = BanList::default();
+        ban_list.insert(BanTarget::Id(pub_id), 2);
+        ban_list.insert(BanTarget::Addr(addr), 3);
+
+        ban_list.tick();
+        assert!(ban_list.contains(&BanTarget::Id(pub_id)));
+        ban_list.tick();
+        assert!(!ban_list.contains(&BanTarget::Id(pub_id)));
+        assert!(ban_list.contains(&BanTarget::Addr(addr)));
+
+        // Banning again renews the ban.
+        ban_list.insert(BanTarget::Addr(addr), 3);
+        ban_list.tick();
+        ban_list.tick();
+        assert!(ban_list.contains(&BanTarget::Addr(addr)));
+        ban_list.tick();
+        assert!(ban_list.entries.is_empty());
+    }
+}
@@ -8,30 +8,33 @@
 
 use super::{
     chain_accumulator::{AccumulatingProof, ChainAccumulator, InsertError},
+    section_history::{SectionHistory, SectionHistoryEntry},
     shared_state::{SectionKeyInfo, SectionProofBlock, SharedState, SplitCache},
     AccumulatedEvent, AccumulatingEvent, AgeCounter, EldersChange, EldersInfo, GenesisPfxInfo,
-    MemberInfo, MemberPersona, MemberState, NetworkEvent, NetworkParams, Proof, ProofSet,
-    SectionProofSlice,
+    MemberInfo, MemberPersona, MemberState, MergeDetails, NetworkEvent, NetworkParams, Proof,
+    ProofSet, SectionProofSlice,
 };
 use crate::{
     error::RoutingError,
     id::{P2pNode, PublicId},
     location::{DstLocation, SrcLocation},
     parsec::{DkgResult, DkgResultWrapper},
-    relocation::{self, RelocateDetails},
+    relocation::{self, RelocateDetails, RelocationContext, SectionSize},
     utils::LogIdent,
     xor_space::Xorable,
     Prefix, XorName,
 };
 use bincode::serialize;
+use bls::serde_impl::SerdeSecret;
 use itertools::Itertools;
-use serde::Serialize;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::{
     cmp::Ordering,
     collections::{btree_map::Entry, BTreeMap, BTreeSet},
     fmt::{self, Debug, Display, Formatter},
     iter, mem,
     net::SocketAddr,
+    sync::Arc,
 };
 
 #[cfg(feature = "mock_base")]
@@ -44,6 +47,7 @@ pub const fn delivery_group_size(n: usize) -> usize {
 }
 
 /// Data chain.
+#[derive(Serialize, Deserialize)]
 pub struct Chain {
     /// Network parameters
     network_cfg: NetworkParams,
@@ -70,7 +74,11 @@ pub struct Chain {
     /// first member in DKG participants and new ElderInfo. We only store 2 items during split, and
     /// then members are disjoint. We are working around not having access to the prefix for the
     /// DkgResult but only the list of participants.
+    #[serde(with = "serde_dkg_results")]
     new_section_bls_keys: BTreeMap<XorName, DkgResult>,
+    /// Log of the changes of our section. Not part of the shared state, so it's neither agreed on
+    /// nor sent to the nodes joining the section.
+    section_history: SectionHistory,
 }
 
 #[allow(clippy::len_without_is_empty)]
@@ -87,7 +95,12 @@ impl Chain {
 
     /// Returns the full `NetworkParams` structure (if present)
     pub fn network_cfg(&self) -> NetworkParams {
-        self.network_cfg
+        self.network_cfg.clone()
+    }
+
+    /// Replaces the network params, e.g. to restore the policies which are not serialised.
+    pub fn set_network_cfg(&mut self, network_cfg: NetworkParams) {
+        self.network_cfg = network_cfg;
     }
 
     pub fn our_section_bls_keys(&self) -> &bls::PublicKeySet {
@@ -121,18 +134,25 @@ impl Chain {
         let is_elder = gen_info.first_info.is_member(&our_id);
         let secret_key_share = secret_key_share
             .and_then(|key| SectionKeyShare::new(key, &our_id, &gen_info.first_info));
+        let state = SharedState::new(
+            gen_info.first_info,
+            gen_info.first_bls_keys.clone(),
+            gen_info.first_ages,
+            network_cfg.ageing_policy.start_age(),
+            gen_info.ban_list,
+        );
+        let section_history = SectionHistory::new(
+            state.our_info().clone(),
+            state.our_history.last_key_info().clone(),
+        );
         Self {
             network_cfg,
             our_id,
             our_section_bls_keys: SectionKeys {
-                public_key_set: gen_info.first_bls_keys.clone(),
+                public_key_set: gen_info.first_bls_keys,
                 secret_key_share,
             },
-            state: SharedState::new(
-                gen_info.first_info,
-                gen_info.first_bls_keys,
-                gen_info.first_ages,
-            ),
+            state,
             is_elder,
             chain_accumulator: Default::default(),
             event_cache: Default::default(),
@@ -140,6 +160,7 @@ impl Chain {
             relocation_in_progress: false,
             members_changed: false,
             new_section_bls_keys: Default::default(),
+            section_history,
         }
     }
 
@@ -155,7 +176,9 @@ impl Chain {
         // On split membership may need to be checked again.
         self.members_changed = true;
         self.state
-            .update_with_genesis_related_info(related_info, &LogIdent::new(self))
+            .update_with_genesis_related_info(related_info, &LogIdent::new(self))?;
+        self.sync_section_history();
+        Ok(())
     }
 
     /// Handles a completed parsec DKG Observation.
@@ -237,6 +260,10 @@ impl Chain {
             return Ok(Some(PollAccumulated::AccumulatedEvent(event)));
         }
 
+        if let Some(details) = self.poll_merge() {
+            return Ok(Some(PollAccumulated::MergeWithSibling(details)));
+        }
+
         // Note: it's important that `promote_and_demote_elders` happens before `poll_relocation`,
         // otherwise we might relocate a node that we still need.
         if let Some(new_infos) = self.promote_and_demote_elders()? {
@@ -282,6 +309,9 @@ impl Chain {
         event: AccumulatingEvent,
         proofs: AccumulatingProof,
     ) -> Result<Option<AccumulatedEvent>, RoutingError> {
+        // Bans expire after a number of agreed events, see `BanList`.
+        self.state.ban_list.tick();
+
         match event {
             AccumulatingEvent::SectionInfo(ref info, ref key_info) => {
                 let change = EldersChangeBuilder::new(self);
@@ -312,6 +342,34 @@ impl Chain {
             AccumulatingEvent::Relocate(_) => {
                 self.relocation_in_progress = false;
             }
+            AccumulatingEvent::Merge(ref details) => {
+                // The lead only merges once its sibling committed to the merge, so that it never
+                // merges alone.
+                if !self.state.merge_in_progress
+                    && details.elders_info.prefix() == &self.our_prefix().sibling()
+                    && details.committed == self.is_merge_lead()
+                {
+                    self.state.merge_cache = Some(details.clone());
+                }
+            }
+            AccumulatingEvent::NeighbourMemberCount(prefix, count) => {
+                if self.state.neighbour_infos.contains_key(&prefix) {
+                    let _ = self.state.neighbour_member_counts.insert(prefix, count);
+                }
+            }
+            AccumulatingEvent::AbortMerge => {
+                if self.merging_into().is_some() {
+                    info!("{} - Merge with our sibling aborted.", self);
+                    self.state.merge_in_progress = false;
+                    self.state.merge_cache = None;
+                    self.churn_in_progress = false;
+                }
+            }
+            AccumulatingEvent::Ban(ref target) => {
+                self.state
+                    .ban_list
+                    .insert(*target, self.network_cfg.ban_expiry);
+            }
             AccumulatingEvent::Online(_)
             | AccumulatingEvent::Offline(_)
             | AccumulatingEvent::StartDkg(_)
@@ -367,11 +425,12 @@ impl Chain {
     pub fn increment_age_counters(&mut self, trigger_node: &PublicId) {
         let our_section_size = self.state.our_joined_members().count();
         let safe_section_size = self.safe_section_size();
+        let ageing_policy = Arc::clone(&self.network_cfg.ageing_policy);
 
         if our_section_size >= safe_section_size
             && self
                 .state
-                .get_persona(trigger_node)
+                .get_persona(trigger_node, &*ageing_policy)
                 .map(|persona| persona == MemberPersona::Infant)
                 .unwrap_or(true)
         {
@@ -389,6 +448,8 @@ impl Chain {
 
         let our_prefix = *self.state.our_prefix();
         let relocating_state = self.state.create_relocating_state();
+        let relocation_policy = Arc::clone(&self.network_cfg.relocation_policy);
+        let known_sections = self.known_section_sizes();
         let mut details_to_add = Vec::new();
 
         struct PartialRelocateDetails {
@@ -402,12 +463,22 @@ impl Chain {
                 continue;
             }
 
-            if !member_info.increment_age_counter() {
+            let old_age = member_info.age();
+            member_info.increment_age_counter(ageing_policy.age_counter_increment(old_age));
+            if !ageing_policy.should_relocate(old_age, member_info.age()) {
                 continue;
             }
 
-            let destination =
-                relocation::compute_destination(&our_prefix, name, trigger_node.name());
+            let destination = relocation::compute_destination(
+                &*relocation_policy,
+                &RelocationContext {
+                    src_prefix: &our_prefix,
+                    relocated_name: name,
+                    trigger_name: trigger_node.name(),
+                    sections: &known_sections,
+                    safe_section_size,
+                },
+            );
             if our_prefix.matches(&destination) {
                 // Relocation destination inside the current section - ignoring.
                 trace!(
@@ -443,6 +514,30 @@ impl Chain {
         }
     }
 
+    // Returns the sizes of our section and all the sections we know, ordered by prefix. The sizes
+    // of our neighbours are the member counts they last told us, or the number of their elders if
+    // they haven't yet.
+    fn known_section_sizes(&self) -> Vec<SectionSize> {
+        let our_prefix = *self.state.our_prefix();
+        let our_size = self.state.our_joined_members().count();
+
+        self.all_sections()
+            .map(|(prefix, info)| SectionSize {
+                prefix: *prefix,
+                size: if *prefix == our_prefix {
+                    our_size
+                } else {
+                    self.state
+                        .neighbour_member_counts
+                        .get(prefix)
+                        .map(|count| *count as usize)
+                        .unwrap_or_else(|| info.len())
+                },
+            })
+            .sorted_by_key(|section| section.prefix)
+            .collect()
+    }
+
     /// Returns the details of the next scheduled relocation to be voted for, if any.
     fn poll_relocation(&mut self) -> Option<RelocateDetails> {
         // Delay relocation until all backlogged churn events have been handled and no
@@ -489,6 +584,7 @@ impl Chain {
             && !self.churn_in_progress
             && !self.relocation_in_progress
             && !self.state.split_in_progress
+            && !self.state.merge_in_progress
     }
 
     /// Validate if can call add_member on this node.
@@ -522,6 +618,7 @@ impl Chain {
                         self,
                         p2p_node,
                     );
+                    return;
                 }
             }
             Entry::Vacant(entry) => {
@@ -529,10 +626,54 @@ impl Chain {
                 let _ = entry.insert(MemberInfo::new(age, p2p_node.clone()));
             }
         }
+
+        let section_version = self.state.our_version();
+        self.section_history
+            .push(SectionHistoryEntry::MemberJoined {
+                member: p2p_node,
+                age,
+                section_version,
+            });
     }
 
     /// Remove a member from our section. Returns the state of the member before the removal.
     pub fn remove_member(&mut self, pub_id: &PublicId) -> MemberState {
+        let member_state = self.do_remove_member(pub_id);
+        if member_state != MemberState::Left {
+            let section_version = self.state.our_version();
+            self.section_history.push(SectionHistoryEntry::MemberLeft {
+                member: *pub_id,
+                section_version,
+            });
+        }
+        member_state
+    }
+
+    /// Remove a member relocated to another section. Returns the state of the member before the
+    /// removal.
+    pub fn relocate_member(&mut self, details: &RelocateDetails) -> MemberState {
+        let member_state = self.do_remove_member(&details.pub_id);
+        let section_version = self.state.our_version();
+        match member_state {
+            MemberState::Relocating { .. } => {
+                self.section_history
+                    .push(SectionHistoryEntry::MemberRelocated {
+                        member: details.pub_id,
+                        destination: details.destination,
+                        age: details.age,
+                        section_version,
+                    })
+            }
+            MemberState::Joined => self.section_history.push(SectionHistoryEntry::MemberLeft {
+                member: details.pub_id,
+                section_version,
+            }),
+            MemberState::Left => (),
+        }
+        member_state
+    }
+
+    fn do_remove_member(&mut self, pub_id: &PublicId) -> MemberState {
         self.assert_no_prefix_change("remove member");
         self.members_changed = true;
 
@@ -585,28 +726,47 @@ impl Chain {
             self.members_changed = false;
             Ok(None)
         } else {
-            let old_size = self.state.our_info().len();
-
             let new_info = EldersInfo::new(
                 expected_elders_map,
                 *self.state.our_info().prefix(),
                 Some(self.state.our_info()),
             )?;
 
-            if self.state.our_info().len() < self.elder_size() && old_size >= self.elder_size() {
-                panic!(
-                    "Merging situation encountered! Not supported: {:?}: {:?}",
-                    self.our_id(),
-                    self.state.our_info()
-                );
-            }
-
             self.members_changed = false;
             self.churn_in_progress = true;
             Ok(Some(vec![new_info]))
         }
     }
 
+    /// Returns the details our sibling sent us when asking to merge, once we are ready to merge
+    /// with it as well.
+    ///
+    /// If we are the merge lead, the members of our sibling are added to our members, so that the
+    /// merged section info can be created from all of them.
+    fn poll_merge(&mut self) -> Option<MergeDetails> {
+        if !self.can_poll_churn() || !self.should_merge() {
+            return None;
+        }
+
+        let details = self.state.merge_cache.take()?;
+        if details.elders_info.prefix() != &self.our_prefix().sibling() {
+            return None;
+        }
+
+        self.state.merge_in_progress = true;
+        self.churn_in_progress = true;
+
+        if self.is_merge_lead() {
+            for (name, member_info) in &details.members {
+                if let Entry::Vacant(entry) = self.state.our_members.entry(*name) {
+                    let _ = entry.insert(member_info.clone());
+                }
+            }
+        }
+
+        Some(details)
+    }
+
     /// Gets the data needed to initialise a new Parsec instance
     pub fn prepare_parsec_reset(
         &mut self,
@@ -625,6 +785,7 @@ impl Chain {
                 first_ages: self.get_age_counters(),
                 latest_info: self.our_info().clone(),
                 parsec_version,
+                ban_list: self.state.ban_list.clone(),
             },
             cached_events: remaining
                 .cached_events
@@ -644,6 +805,8 @@ impl Chain {
         // TODO: Bring back using their_knowledge to clean_older section in our_infos
         self.check_and_clean_neighbour_infos(None);
         self.state.split_in_progress = false;
+        self.state.merge_in_progress = false;
+        self.state.merge_cache = None;
 
         info!("{} - finalise_prefix_change: {:?}", self, self.our_prefix());
         trace!("{} - finalise_prefix_change state: {:?}", self, self.state);
@@ -666,11 +829,38 @@ impl Chain {
         self.state.our_prefix()
     }
 
+    /// Returns the log of the changes of our section, see `SectionHistory`.
+    pub fn section_history(&self) -> &SectionHistory {
+        &self.section_history
+    }
+
+    /// Continues the section history of the chain this one replaces.
+    pub fn continue_section_history(&mut self, section_history: SectionHistory) {
+        self.section_history = section_history;
+        self.sync_section_history();
+    }
+
+    // Starts the section history again from our current elders if it doesn't lead up to them,
+    // e.g. because we missed some changes while we were paused or not yet a member.
+    fn sync_section_history(&mut self) {
+        if !self.section_history.is_current(self.state.our_info()) {
+            self.section_history = SectionHistory::new(
+                self.state.our_info().clone(),
+                self.state.our_history.last_key_info().clone(),
+            );
+        }
+    }
+
     /// Returns whether our section is in the process of splitting.
     pub fn split_in_progress(&self) -> bool {
         self.state.split_in_progress
     }
 
+    /// Returns whether our section is in the process of merging with its sibling.
+    pub fn merge_in_progress(&self) -> bool {
+        self.state.merge_in_progress
+    }
+
     /// Returns whether a churn (elders change) is in progress.
     pub fn churn_in_progress(&self) -> bool {
         self.churn_in_progress
@@ -700,6 +890,11 @@ impl Chain {
             .unwrap_or(false)
     }
 
+    /// Check if the section agreed to ban the node, by its identity or its address.
+    pub fn is_banned(&self, p2p_node: &P2pNode) -> bool {
+        self.state.ban_list.contains_node(p2p_node)
+    }
+
     /// Returns a section member `P2pNode`
     pub fn get_member_p2p_node(&self, name: &XorName) -> Option<&P2pNode> {
         self.state
@@ -853,6 +1048,11 @@ impl Chain {
             .map(|(_, info)| &info.p2p_node)
     }
 
+    /// Returns an iterator over the members that have state == `Joined`.
+    pub fn our_joined_members(&self) -> impl Iterator<Item = &MemberInfo> {
+        self.state.our_joined_members().map(|(_, info)| info)
+    }
+
     /// Returns the members in our section and elders we know.
     pub fn known_nodes(&self) -> impl Iterator<Item = &P2pNode> {
         self.our_active_members()
@@ -987,8 +1187,13 @@ impl Chain {
                 }
 
                 // Do not process yet any version that is not the immediate follower of the one we have.
+                // A merged section follows all the sections it merged from, so its version only
+                // needs to be newer than theirs.
                 let not_follow = |i: &EldersInfo| {
-                    info.prefix().is_compatible(i.prefix()) && info.version() != (i.version() + 1)
+                    let is_merge = i.prefix().is_extension_of(info.prefix());
+                    info.prefix().is_compatible(i.prefix())
+                        && ((is_merge && info.version() <= i.version())
+                            || (!is_merge && info.version() != (i.version() + 1)))
                 };
                 if self
                     .compatible_neighbour_info(info)
@@ -1008,7 +1213,11 @@ impl Chain {
             | AccumulatingEvent::AckMessage(_)
             | AccumulatingEvent::User(_)
             | AccumulatingEvent::Relocate(_)
-            | AccumulatingEvent::RelocatePrepare(_, _) => {
+            | AccumulatingEvent::RelocatePrepare(_, _)
+            | AccumulatingEvent::Merge(_)
+            | AccumulatingEvent::Ban(_)
+            | AccumulatingEvent::AbortMerge
+            | AccumulatingEvent::NeighbourMemberCount(..) => {
                 !self.state.split_in_progress && self.our_info().is_quorum(proofs)
             }
             AccumulatingEvent::StartDkg(_) => {
@@ -1086,7 +1295,6 @@ impl Chain {
         key_info: SectionKeyInfo,
         proofs: AccumulatingProof,
     ) -> Result<bool, RoutingError> {
-        // Split handling alone. wouldn't cater to merge
         if elders_info.prefix().is_extension_of(self.our_prefix()) {
             match self.state.split_cache.take() {
                 None => {
@@ -1113,6 +1321,7 @@ impl Chain {
                 }
             }
         } else {
+            // Our own section info, or the info of the section we merged into.
             self.do_add_elders_info(elders_info, key_info, proofs)?;
             Ok(true)
         }
@@ -1131,6 +1340,11 @@ impl Chain {
             mem::replace(&mut self.new_section_bls_keys, Default::default()),
         )?;
 
+        self.section_history
+            .push(SectionHistoryEntry::elders_changed(
+                elders_info.clone(),
+                &proof_block,
+            ));
         self.state.push_our_new_info(elders_info, proof_block);
         self.our_section_bls_keys = SectionKeys::new(our_new_key, self.our_id(), self.our_info());
 
@@ -1272,6 +1486,63 @@ impl Chain {
         Ok(our_new_size >= safe_section_size && sibling_new_size >= safe_section_size)
     }
 
+    /// Returns `true` if our section has become too small and should merge with its sibling.
+    pub fn should_merge(&self) -> bool {
+        if self.state.split_in_progress
+            || self.state.merge_in_progress
+            || self.our_prefix().bit_count() == 0
+        {
+            return false;
+        }
+
+        let sibling_pfx = self.our_prefix().sibling();
+        self.state.our_joined_members().count() < self.safe_section_size()
+            && self.state.neighbour_infos.contains_key(&sibling_pfx)
+    }
+
+    /// Returns the details to send to our sibling when asking it to merge with us, or when
+    /// committing to the merge it asked for.
+    pub fn merge_details(&self, parsec_version: u64, committed: bool) -> MergeDetails {
+        MergeDetails {
+            elders_info: self.our_info().clone(),
+            members: self
+                .state
+                .our_joined_members()
+                .map(|(name, info)| (*name, info.clone()))
+                .collect(),
+            parsec_version,
+            committed,
+        }
+    }
+
+    /// Returns whether our section leads the merge with our sibling. The lead section creates the
+    /// merged section info and the other section's nodes rejoin it as adults.
+    pub fn is_merge_lead(&self) -> bool {
+        *self.our_prefix() < self.our_prefix().sibling()
+    }
+
+    /// Returns the prefix of our sibling if we committed to merging into the section it leads, in
+    /// which case we wait for it to send us the genesis of the merged section's parsec.
+    pub fn merging_into(&self) -> Option<Prefix<XorName>> {
+        if self.state.merge_in_progress && !self.is_merge_lead() {
+            Some(self.our_prefix().sibling())
+        } else {
+            None
+        }
+    }
+
+    /// Generates the elders info of the section resulting from merging ours with our sibling's.
+    pub fn merge_self(&self, sibling_info: &EldersInfo) -> Result<EldersInfo, RoutingError> {
+        let merged_prefix = self.our_prefix().popped();
+        let merged_section = self.eldest_members_matching_prefix(&merged_prefix);
+
+        EldersInfo::new(
+            merged_section,
+            merged_prefix,
+            vec![self.state.our_info(), sibling_info],
+        )
+    }
+
     /// Splits our section and generates new elders infos for the child sections.
     fn split_self(&mut self) -> Result<(EldersInfo, EldersInfo), RoutingError> {
         let next_bit = self.our_id.name().bit(self.our_prefix().bit_count());
@@ -1306,12 +1577,11 @@ impl Chain {
                     return Some(*pfx);
                 }
 
-                // Remove older compatible neighbour prefixes.
-                // DO NOT SUPPORT MERGE: Not consider newer if the older one was extension (split).
+                // Remove older compatible neighbour prefixes. This includes the sections which
+                // merged into a newer one.
                 let is_newer = |(other_pfx, other_elders_info): (&Prefix<XorName>, &EldersInfo)| {
                     other_pfx.is_compatible(pfx)
                         && other_elders_info.version() > elders_info.version()
-                        && !pfx.is_extension_of(other_pfx)
                 };
 
                 if self.state.neighbour_infos.iter().any(is_newer) {
@@ -1324,6 +1594,16 @@ impl Chain {
         for pfx in to_remove {
             let _ = self.state.neighbour_infos.remove(&pfx);
         }
+
+        let neighbour_infos = &self.state.neighbour_infos;
+        self.state
+            .neighbour_member_counts
+            .retain(|pfx, _| neighbour_infos.contains_key(pfx));
+    }
+
+    /// Returns the number of joined members of our section.
+    pub fn our_member_count(&self) -> u64 {
+        self.state.our_joined_members().count() as u64
     }
 
     // Set of methods ported over from routing_table mostly as-is. The idea is to refactor and
@@ -1412,7 +1692,7 @@ impl Chain {
     ///     - if the prefix is compatible with our prefix and is fully-covered by prefixes in our
     ///       RT, returns all members in these prefixes except ourself; otherwise
     ///     - if the prefix is compatible with our prefix and is *not* fully-covered by prefixes in
-    ///       our RT, returns `Err(Error::CannotRoute)`; otherwise
+    ///       our RT, returns `Err(Error::CannotRoute { .. })`; otherwise
     ///     - returns the `N/3` closest members of the RT to the lower bound of the target
     ///       prefix
     ///
@@ -1429,7 +1709,8 @@ impl Chain {
                 if let Some(node) = self.get_p2p_node(target_name) {
                     return Ok((vec![node], 1));
                 }
-                self.candidates(target_name)?
+                self.candidates(target_name)
+                    .ok_or_else(|| self.cannot_route(dst))?
             }
             DstLocation::Section(ref target_name) => {
                 let (prefix, section) = self.closest_section_info(*target_name);
@@ -1446,7 +1727,8 @@ impl Chain {
                     let dg_size = section.len();
                     return Ok((section, dg_size));
                 }
-                self.candidates(target_name)?
+                self.candidates(target_name)
+                    .ok_or_else(|| self.cannot_route(dst))?
             }
             DstLocation::Prefix(ref prefix) => {
                 if prefix.is_compatible(self.our_prefix()) || prefix.is_neighbour(self.our_prefix())
@@ -1457,7 +1739,7 @@ impl Chain {
                     if prefix.is_compatible(self.our_prefix())
                         && !prefix.is_covered_by(self.prefixes().iter())
                     {
-                        return Err(RoutingError::CannotRoute);
+                        return Err(self.cannot_route(dst));
                     }
 
                     let is_compatible = |(pfx, section)| {
@@ -1480,16 +1762,24 @@ impl Chain {
                     let dg_size = targets.len();
                     return Ok((targets, dg_size));
                 }
-                self.candidates(&prefix.lower_bound())?
+                self.candidates(&prefix.lower_bound())
+                    .ok_or_else(|| self.cannot_route(dst))?
             }
-            DstLocation::Direct => return Err(RoutingError::CannotRoute),
+            DstLocation::Direct => return Err(self.cannot_route(dst)),
         };
 
         Ok((best_section, dg_size))
     }
 
+    fn cannot_route(&self, dst: &DstLocation) -> RoutingError {
+        RoutingError::CannotRoute {
+            dst: *dst,
+            known_prefixes: self.prefixes().into_iter().collect(),
+        }
+    }
+
     // Obtain the delivery group candidates for this target
-    fn candidates(&self, target_name: &XorName) -> Result<(Vec<&P2pNode>, usize), RoutingError> {
+    fn candidates(&self, target_name: &XorName) -> Option<(Vec<&P2pNode>, usize)> {
         let filtered_sections =
             self.closest_sections_info(*target_name)
                 .into_iter()
@@ -1522,9 +1812,9 @@ impl Chain {
         nodes_to_send.sort_by(|lhs, rhs| target_name.cmp_distance(lhs.name(), rhs.name()));
 
         if dg_size > 0 && nodes_to_send.len() >= dg_size {
-            Ok((nodes_to_send, dg_size))
+            Some((nodes_to_send, dg_size))
         } else {
-            Err(RoutingError::CannotRoute)
+            None
         }
     }
 
@@ -1568,7 +1858,7 @@ impl Chain {
     }
 
     fn assert_no_prefix_change(&self, label: &str) {
-        if self.state.split_in_progress {
+        if self.state.split_in_progress || self.state.merge_in_progress {
             log_or_panic!(
                 log::Level::Warn,
                 "{} - attempt to {} during prefix change.",
@@ -1578,6 +1868,16 @@ impl Chain {
         }
     }
 
+    /// Returns their_knowledge
+    pub fn get_their_knowledge(&self) -> &BTreeMap<Prefix<XorName>, u64> {
+        self.state.get_their_knowledge()
+    }
+
+    /// Returns the number of accumulated events waiting for the current churn to complete.
+    pub fn churn_event_backlog_len(&self) -> usize {
+        self.state.churn_event_backlog.len()
+    }
+
     /// Check if we know this node but have not yet processed it.
     pub fn is_in_online_backlog(&self, pub_id: &PublicId) -> bool {
         self.state.churn_event_backlog.iter().any(|evt| {
@@ -1601,6 +1901,11 @@ impl Debug for Chain {
             "\tsplit_in_progress: {}",
             self.state.split_in_progress
         )?;
+        writeln!(
+            formatter,
+            "\tmerge_in_progress: {}",
+            self.state.merge_in_progress
+        )?;
 
         writeln!(formatter, "\tour_infos: len {}", self.state.our_infos.len())?;
         for info in self.state.our_infos() {
@@ -1674,11 +1979,6 @@ impl Chain {
         }
     }
 
-    /// Returns their_knowledge
-    pub fn get_their_knowledge(&self) -> &BTreeMap<Prefix<XorName>, u64> {
-        self.state.get_their_knowledge()
-    }
-
     /// Return a minimum length prefix, favouring our prefix if it is one of the shortest.
     pub fn min_len_prefix(&self) -> Prefix<XorName> {
         *iter::once(self.our_prefix())
@@ -1694,6 +1994,11 @@ impl Chain {
             .get(name)
             .map(|member| member.age_counter_value())
     }
+
+    /// Returns the number of joined members of the given neighbour section, as last reported by it.
+    pub fn neighbour_member_count(&self, prefix: &Prefix<XorName>) -> Option<u64> {
+        self.state.neighbour_member_counts.get(prefix).copied()
+    }
 }
 
 #[cfg(test)]
@@ -1723,6 +2028,7 @@ pub enum PollAccumulated {
     AccumulatedEvent(AccumulatedEvent),
     RelocateDetails(RelocateDetails),
     PromoteDemoteElders(Vec<EldersInfo>),
+    MergeWithSibling(MergeDetails),
 }
 
 /// The outcome of a prefix change.
@@ -1736,11 +2042,12 @@ pub struct ParsecResetData {
 }
 
 /// The secret share of the section key.
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct SectionKeyShare {
     /// Index used to combine signature share and get PublicKeyShare from PublicKeySet.
     pub index: usize,
     /// Secret Key share
+    #[serde(with = "serde_secret_key_share")]
     pub key: bls::SecretKeyShare,
 }
 
@@ -1765,7 +2072,7 @@ impl SectionKeyShare {
 }
 
 /// All the key material needed to sign or combine signature for our section key.
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct SectionKeys {
     /// Public key set to verify threshold signatures and combine shares.
     pub public_key_set: bls::PublicKeySet,
@@ -1784,6 +2091,66 @@ impl SectionKeys {
     }
 }
 
+// Serialisation of the secret key share. `bls` only serialises secret keys when explicitly asked
+// to via the `SerdeSecret` wrapper.
+mod serde_secret_key_share {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        key: &bls::SecretKeyShare,
+        serialiser: S,
+    ) -> Result<S::Ok, S::Error> {
+        SerdeSecret(key.clone()).serialize(serialiser)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserialiser: D,
+    ) -> Result<bls::SecretKeyShare, D::Error> {
+        let key: SerdeSecret<bls::SecretKeyShare> = Deserialize::deserialize(deserialiser)?;
+        Ok(key.into_inner())
+    }
+}
+
+// Serialisation of the pending DKG results, including our secret key shares.
+mod serde_dkg_results {
+    use super::*;
+
+    type SerdeDkgResult = (bls::PublicKeySet, Option<SerdeSecret<bls::SecretKeyShare>>);
+
+    pub fn serialize<S: Serializer>(
+        results: &BTreeMap<XorName, DkgResult>,
+        serialiser: S,
+    ) -> Result<S::Ok, S::Error> {
+        results
+            .iter()
+            .map(|(name, result)| {
+                let result: SerdeDkgResult = (
+                    result.public_key_set.clone(),
+                    result.secret_key_share.clone().map(SerdeSecret),
+                );
+                (*name, result)
+            })
+            .collect::<BTreeMap<_, _>>()
+            .serialize(serialiser)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserialiser: D,
+    ) -> Result<BTreeMap<XorName, DkgResult>, D::Error> {
+        let results: BTreeMap<XorName, SerdeDkgResult> = Deserialize::deserialize(deserialiser)?;
+        Ok(results
+            .into_iter()
+            .map(|(name, (public_key_set, secret_key_share))| {
+                let result = DkgResult::new(
+                    public_key_set,
+                    secret_key_share.map(SerdeSecret::into_inner),
+                );
+                (name, result)
+            })
+            .collect())
+    }
+}
+
 struct EldersChangeBuilder {
     old_neighbour: BTreeSet<P2pNode>,
 }
@@ -1929,6 +2296,7 @@ mod tests {
             first_ages,
             latest_info: Default::default(),
             parsec_version: 0,
+            ban_list: Default::default(),
         };
 
         let mut chain = Chain::new(
@@ -23,7 +23,7 @@ pub const UNRESPONSIVE_THRESHOLD: usize = 48;
 /// The period (X consensued observations) during which node be considered as unresponsive.
 pub const UNRESPONSIVE_WINDOW: usize = 64;
 
-#[derive(Default)]
+#[derive(Default, Serialize, Deserialize)]
 struct VoteStatuses {
     tracked_events: VecDeque<Rc<AccumulatingEvent>>,
     unvoted: BTreeMap<PublicId, BTreeSet<Rc<AccumulatingEvent>>>,
@@ -76,7 +76,7 @@ impl VoteStatuses {
     }
 }
 
-#[derive(Default)]
+#[derive(Default, Serialize, Deserialize)]
 pub(super) struct ChainAccumulator {
     /// A map containing network events that have not been handled yet, together with their proofs
     /// that have been collected so far. We are still waiting for more proofs, or to reach a state
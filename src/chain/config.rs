@@ -6,15 +6,44 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::{ELDER_SIZE, SAFE_SECTION_SIZE};
+use super::{
+    ageing::{AgeingPolicy, DefaultAgeingPolicy},
+    DEFAULT_BAN_EXPIRY,
+};
+use crate::{
+    relocation::{DefaultRelocationPolicy, RelocationPolicy},
+    time::Duration,
+    ELDER_SIZE, SAFE_SECTION_SIZE,
+};
+use std::sync::Arc;
 
-/// Network parameters: number of elders, safe section size
-#[derive(Clone, Copy, Debug)]
+/// Default number of leading zero bits required in the resource proof hash.
+const RESOURCE_PROOF_DIFFICULTY: u8 = 10;
+/// Default size of the resource proof data, in bytes.
+const RESOURCE_PROOF_SIZE: usize = 64 * 1024;
+/// Default time a joining node has to answer the resource challenge.
+const RESOURCE_PROOF_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Network parameters: number of elders, safe section size, joining, ageing and relocation rules
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct NetworkParams {
     /// The number of elders per section
     pub elder_size: usize,
     /// Minimum number of nodes we consider safe in a section
     pub safe_section_size: usize,
+    /// Resource proof required from nodes joining the section. `None`, the default, disables it.
+    pub resource_proof: Option<ResourceProofParams>,
+    /// Rules by which the members of a section age. Only its id is stored when the node is paused,
+    /// see `Builder::resume_from_bytes`.
+    #[serde(skip, default = "default_ageing_policy")]
+    pub ageing_policy: Arc<dyn AgeingPolicy>,
+    /// Rules by which the destination of a relocated node is chosen. Only its id is stored when
+    /// the node is paused, like for the ageing policy.
+    #[serde(skip, default = "default_relocation_policy")]
+    pub relocation_policy: Arc<dyn RelocationPolicy>,
+    /// Number of events the section agrees on before a ban of a node is lifted. It's not a
+    /// duration: the busier the section, the sooner the ban expires.
+    pub ban_expiry: u64,
 }
 
 impl Default for NetworkParams {
@@ -22,6 +51,40 @@ impl Default for NetworkParams {
         Self {
             elder_size: ELDER_SIZE,
             safe_section_size: SAFE_SECTION_SIZE,
+            resource_proof: None,
+            ageing_policy: default_ageing_policy(),
+            relocation_policy: default_relocation_policy(),
+            ban_expiry: DEFAULT_BAN_EXPIRY,
+        }
+    }
+}
+
+fn default_ageing_policy() -> Arc<dyn AgeingPolicy> {
+    Arc::new(DefaultAgeingPolicy)
+}
+
+fn default_relocation_policy() -> Arc<dyn RelocationPolicy> {
+    Arc::new(DefaultRelocationPolicy)
+}
+
+/// Parameters of the resource challenge sent by the elders to a joining node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceProofParams {
+    /// Number of leading zero bits required in the proof hash. Each extra bit doubles the
+    /// expected amount of work.
+    pub difficulty: u8,
+    /// Size of the data the joining node has to send back, in bytes.
+    pub size: usize,
+    /// Time the joining node has to answer the challenge.
+    pub timeout: Duration,
+}
+
+impl Default for ResourceProofParams {
+    fn default() -> Self {
+        Self {
+            difficulty: RESOURCE_PROOF_DIFFICULTY,
+            size: RESOURCE_PROOF_SIZE,
+            timeout: RESOURCE_PROOF_TIMEOUT,
         }
     }
 }
@@ -23,14 +23,11 @@ impl AgeCounter {
         f64::from(self.0).log2() as u8
     }
 
-    /// Increment the counter and return whether the age increased.
-    pub fn increment(&mut self) -> bool {
-        if let Some(new_value) = self.0.checked_add(1) {
-            self.0 = new_value;
-            self.0.is_power_of_two()
-        } else {
-            false
-        }
+    /// Increment the counter by `amount` and return whether the age increased.
+    pub fn increment(&mut self, amount: u32) -> bool {
+        let old_age = self.age();
+        self.0 = self.0.saturating_add(amount);
+        self.age() > old_age
     }
 }
 
@@ -46,10 +43,8 @@ pub const MIN_AGE_COUNTER: AgeCounter = AgeCounter(16);
 /// relocations during the beginning of a node's lifetime.
 pub const MIN_AGE: u8 = 4;
 
-const MAX_INFANT_AGE: u32 = MIN_AGE as u32;
-
 /// Information about a member of our section.
-#[derive(Clone, Eq, PartialEq, Hash, Serialize, Deserialize, Debug)]
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize, Debug)]
 pub struct MemberInfo {
     pub age_counter: AgeCounter,
     pub state: MemberState,
@@ -74,13 +69,9 @@ impl MemberInfo {
         self.age_counter = AgeCounter::from_age(age);
     }
 
-    // Increment the age counter and return whether the age increased.
-    pub fn increment_age_counter(&mut self) -> bool {
-        self.age_counter.increment()
-    }
-
-    pub fn is_mature(&self) -> bool {
-        self.age_counter >= AgeCounter(2_u32.pow(MAX_INFANT_AGE + 1))
+    // Increment the age counter by `amount`.
+    pub fn increment_age_counter(&mut self, amount: u32) {
+        let _ = self.age_counter.increment(amount);
     }
 
     #[cfg(feature = "mock_base")]
@@ -96,7 +87,7 @@ pub enum MemberPersona {
     Elder,
 }
 
-#[derive(Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize, Debug)]
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize, Debug)]
 pub enum MemberState {
     Joined,
     Relocating {
@@ -124,10 +115,27 @@ mod tests {
         for age in MIN_AGE..16 {
             for _ in 0..2u32.pow(u32::from(age)) - 1 {
                 assert_eq!(age_counter.age(), age);
-                assert!(!age_counter.increment());
+                assert!(!age_counter.increment(1));
             }
 
-            assert!(age_counter.increment());
+            assert!(age_counter.increment(1));
         }
     }
+
+    #[test]
+    fn age_counter_increment_by_more_than_one() {
+        let mut age_counter = AgeCounter::from_age(MIN_AGE);
+
+        assert!(!age_counter.increment(15));
+        assert_eq!(age_counter.age(), MIN_AGE);
+        assert!(age_counter.increment(1));
+        assert_eq!(age_counter.age(), MIN_AGE + 1);
+
+        // Skipping over several powers of two still counts as a single increase.
+        assert!(age_counter.increment(200));
+        assert_eq!(age_counter.age(), 7);
+
+        let mut age_counter = AgeCounter(u32::max_value());
+        assert!(!age_counter.increment(1));
+    }
 }
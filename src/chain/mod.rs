@@ -9,6 +9,8 @@
 // The `chain` submodule contains the `Chain` implementation, which we reexport here.
 
 #[allow(clippy::module_inception)]
+mod ageing;
+mod ban_list;
 mod chain;
 mod chain_accumulator;
 mod config;
@@ -16,20 +18,27 @@ mod elders_info;
 mod member_info;
 mod network_event;
 mod proof;
+mod section_history;
 mod shared_state;
 
 pub use self::{
+    ageing::{AgeingPolicy, DefaultAgeingPolicy},
+    ban_list::{BanList, BanTarget, DEFAULT_BAN_EXPIRY},
     chain::{delivery_group_size, Chain, ParsecResetData, PollAccumulated, SectionKeyShare},
     chain_accumulator::AccumulatingProof,
-    config::NetworkParams,
+    config::{NetworkParams, ResourceProofParams},
     elders_info::{quorum_count, EldersInfo},
     member_info::{AgeCounter, MemberInfo, MemberPersona, MemberState, MIN_AGE, MIN_AGE_COUNTER},
     network_event::{
         AccumulatedEvent, AccumulatingEvent, AckMessagePayload, EldersChange, EventSigPayload,
-        IntoAccumulatingEvent, NetworkEvent, OnlinePayload, SendAckMessagePayload,
+        IntoAccumulatingEvent, MergeDetails, NetworkEvent, OnlinePayload, SendAckMessagePayload,
     },
     proof::{Proof, ProofSet},
-    shared_state::{SectionKeyInfo, SectionProofSlice, TrustStatus},
+    section_history::{
+        verify_section_history, SectionElders, SectionHistory, SectionHistoryEntry,
+        MAX_SECTION_HISTORY_ENTRIES, SECTION_HISTORY_FORMAT_VERSION,
+    },
+    shared_state::{ProofError, SectionKeyInfo, SectionProofSlice, TrustStatus},
 };
 use crate::PublicId;
 use std::{
@@ -52,6 +61,7 @@ pub struct GenesisPfxInfo {
     pub first_ages: BTreeMap<PublicId, AgeCounter>,
     pub latest_info: EldersInfo,
     pub parsec_version: u64,
+    pub ban_list: BanList,
 }
 
 impl Debug for GenesisPfxInfo {
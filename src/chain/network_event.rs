@@ -6,7 +6,7 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::{EldersInfo, SectionKeyInfo};
+use super::{BanTarget, EldersInfo, MemberInfo, SectionKeyInfo};
 use crate::{
     error::RoutingError,
     id::{P2pNode, PublicId},
@@ -17,7 +17,7 @@ use crate::{
 use hex_fmt::HexFmt;
 use serde::Serialize;
 use std::{
-    collections::BTreeSet,
+    collections::{BTreeMap, BTreeSet},
     fmt::{self, Debug, Formatter},
 };
 
@@ -74,6 +74,20 @@ pub struct OnlinePayload {
     pub their_knowledge: Option<u64>,
 }
 
+/// Details a section which is too small sends to its sibling to agree on a merge.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+pub struct MergeDetails {
+    /// The latest elders info of the section.
+    pub elders_info: EldersInfo,
+    /// The joined members of the section.
+    pub members: BTreeMap<XorName, MemberInfo>,
+    /// The version of the latest parsec instance of the section.
+    pub parsec_version: u64,
+    /// Whether the section already committed to the merge. Only the section which is not the
+    /// merge lead commits, and the lead merges only once its sibling did.
+    pub committed: bool,
+}
+
 /// Routing Network events
 // TODO: Box `SectionInfo`?
 #[allow(clippy::large_enum_variant)]
@@ -112,6 +126,18 @@ pub enum AccumulatingEvent {
 
     // Opaque user-defined event.
     User(Vec<u8>),
+
+    // Voted for received message from our sibling asking to merge with us.
+    Merge(MergeDetails),
+
+    // Voted for node to be refused bootstrapping to and joining our section.
+    Ban(BanTarget),
+
+    // Voted when our sibling didn't follow up on the merge we committed to in time.
+    AbortMerge,
+
+    // Voted for received message from a neighbour telling us its number of joined members.
+    NeighbourMemberCount(Prefix<XorName>, u64),
 }
 
 impl AccumulatingEvent {
@@ -151,6 +177,12 @@ impl Debug for AccumulatingEvent {
                 write!(formatter, "RelocatePrepare({:?}, {})", payload, count_down)
             }
             Self::User(payload) => write!(formatter, "User({:<8})", HexFmt(payload)),
+            Self::Merge(payload) => write!(formatter, "Merge({:?})", payload),
+            Self::Ban(target) => write!(formatter, "Ban({:?})", target),
+            Self::AbortMerge => write!(formatter, "AbortMerge"),
+            Self::NeighbourMemberCount(prefix, count) => {
+                write!(formatter, "NeighbourMemberCount({:?}, {})", prefix, count)
+            }
         }
     }
 }
@@ -0,0 +1,452 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Append-only log of the changes of our section, as seen by this node.
+//!
+//! # Retention
+//!
+//! The history is local to the node: it's not part of the state the section agrees on, so a node
+//! joining the section starts its history from the elders in office when it joined, and a node
+//! which missed some changes (e.g. while paused) starts it again from its current elders.
+//!
+//! The history holds at most `MAX_SECTION_HISTORY_ENTRIES` entries. Once full, the oldest entries
+//! are dropped and the genesis moves forward to the last elders change dropped, so the remaining
+//! log can still be verified.
+//!
+//! # Export format
+//!
+//! `Node::export_section_history` returns a bincode encoded tuple
+//! `(format_version: u32, history: Vec<u8>, signer: PublicId, signature: Signature)` where:
+//!
+//! - `format_version` is `SECTION_HISTORY_FORMAT_VERSION`. It is bumped whenever the layout of
+//!   the history changes.
+//! - `history` is the bincode encoded `SectionHistory`: the genesis elders and their BLS key,
+//!   followed by the `SectionHistoryEntry`s in the order they were agreed on by the section.
+//! - `signer` is the id of the node which exported the history and `signature` is its signature
+//!   of the bincode encoded `(format_version, history)` pair.
+//!
+//! Every `EldersChanged` entry carries the signature of the new section key by the previous one,
+//! so the section keys form a chain rooted at the genesis key which anyone can check with
+//! `verify_section_history`, without trusting the exporting node.
+
+use super::{
+    shared_state::{SectionProofBlock, SectionProofChain},
+    EldersInfo, SectionKeyInfo,
+};
+use crate::{
+    crypto::signing::Signature,
+    error::{Result, RoutingError},
+    id::{FullId, P2pNode, PublicId},
+    xor_space::{Prefix, XorName},
+};
+use bincode::{deserialize, serialize};
+
+/// Version of the exported section history format written by this version of routing.
+pub const SECTION_HISTORY_FORMAT_VERSION: u32 = 1;
+/// Maximal number of entries of the section history, see the retention section above.
+pub const MAX_SECTION_HISTORY_ENTRIES: usize = 10_000;
+
+/// The history of our section: its genesis elders and the changes agreed on since.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SectionHistory {
+    genesis: SectionElders,
+    entries: Vec<SectionHistoryEntry>,
+}
+
+impl SectionHistory {
+    pub(crate) fn new(elders_info: EldersInfo, key_info: SectionKeyInfo) -> Self {
+        Self {
+            genesis: SectionElders {
+                elders_info,
+                key_info,
+            },
+            entries: Vec::new(),
+        }
+    }
+
+    /// The elders of the section at the genesis of the history.
+    pub fn genesis(&self) -> &SectionElders {
+        &self.genesis
+    }
+
+    /// The changes of the section since the genesis, oldest first.
+    pub fn entries(&self) -> &[SectionHistoryEntry] {
+        &self.entries
+    }
+
+    /// Whether the latest elders of the history are the given ones.
+    pub(crate) fn is_current(&self, elders_info: &EldersInfo) -> bool {
+        let latest = self
+            .entries
+            .iter()
+            .rev()
+            .find_map(|entry| match entry {
+                SectionHistoryEntry::EldersChanged { elders, .. } => Some(elders),
+                _ => None,
+            })
+            .unwrap_or(&self.genesis);
+        latest.elders_info == *elders_info
+    }
+
+    pub(crate) fn push(&mut self, entry: SectionHistoryEntry) {
+        self.entries.push(entry);
+        self.truncate(MAX_SECTION_HISTORY_ENTRIES);
+    }
+
+    // Drops the oldest entries so at most `max_entries` remain, moving the genesis forward to the
+    // last elders change dropped.
+    fn truncate(&mut self, max_entries: usize) {
+        let excess = self.entries.len().saturating_sub(max_entries);
+        for entry in self.entries.drain(..excess) {
+            if let SectionHistoryEntry::EldersChanged { elders, .. } = entry {
+                self.genesis = elders;
+            }
+        }
+    }
+
+    /// Serialises the history in the export format and signs it with `full_id`.
+    pub(crate) fn to_signed_bytes(&self, full_id: &FullId) -> Result<Vec<u8>> {
+        let history = serialize(self)?;
+        let signature = full_id.sign(&serialize(&(SECTION_HISTORY_FORMAT_VERSION, &history))?);
+
+        Ok(serialize(&(
+            SECTION_HISTORY_FORMAT_VERSION,
+            history,
+            *full_id.public_id(),
+            signature,
+        ))?)
+    }
+
+    // Checks that the section keys form a valid chain from the genesis key and that the members
+    // changes are consistent with the elders changes preceding them.
+    fn validate(&self) -> Result<()> {
+        if !self.genesis.is_consistent() {
+            return Err(RoutingError::InvalidSectionHistory);
+        }
+
+        let mut blocks = Vec::new();
+        let mut current = &self.genesis;
+
+        for entry in &self.entries {
+            match entry {
+                SectionHistoryEntry::EldersChanged { elders, signature } => {
+                    if !elders.is_consistent() {
+                        return Err(RoutingError::InvalidSectionHistory);
+                    }
+
+                    blocks.push(SectionProofBlock::new(
+                        elders.key_info.clone(),
+                        signature.clone(),
+                    ));
+                    current = elders;
+                }
+                SectionHistoryEntry::MemberJoined {
+                    member,
+                    section_version,
+                    ..
+                } => {
+                    if *section_version != current.version()
+                        || !current.prefix().matches(member.name())
+                    {
+                        return Err(RoutingError::InvalidSectionHistory);
+                    }
+                }
+                SectionHistoryEntry::MemberLeft {
+                    section_version, ..
+                }
+                | SectionHistoryEntry::MemberRelocated {
+                    section_version, ..
+                } => {
+                    if *section_version != current.version() {
+                        return Err(RoutingError::InvalidSectionHistory);
+                    }
+                }
+            }
+        }
+
+        let proof_chain = SectionProofChain::from_blocks(self.genesis.key_info.clone(), blocks);
+        if proof_chain.validate() {
+            Ok(())
+        } else {
+            Err(RoutingError::InvalidSectionHistory)
+        }
+    }
+}
+
+/// One version of the elders of our section together with the section BLS public key.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SectionElders {
+    elders_info: EldersInfo,
+    key_info: SectionKeyInfo,
+}
+
+impl SectionElders {
+    /// The prefix of the section.
+    pub fn prefix(&self) -> &Prefix<XorName> {
+        self.elders_info.prefix()
+    }
+
+    /// The version of the section. It increases by one with every elders change.
+    pub fn version(&self) -> u64 {
+        self.elders_info.version()
+    }
+
+    /// The elders of the section.
+    pub fn elders(&self) -> impl Iterator<Item = &P2pNode> {
+        self.elders_info.member_nodes()
+    }
+
+    /// The BLS public key of the section.
+    pub fn key(&self) -> &bls::PublicKey {
+        self.key_info.key()
+    }
+
+    // Whether the key info belongs to the elders info.
+    fn is_consistent(&self) -> bool {
+        self.elders_info.prefix() == self.key_info.prefix()
+            && self.elders_info.version() == self.key_info.version()
+    }
+}
+
+/// A change of our section agreed on by its elders.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SectionHistoryEntry {
+    /// The elders of the section changed. `signature` is the signature of the new section key by
+    /// the previous one.
+    EldersChanged {
+        /// The new elders.
+        elders: SectionElders,
+        /// Signature of the new key info by the previous section key.
+        signature: bls::Signature,
+    },
+    /// A node joined the section.
+    MemberJoined {
+        /// The new member.
+        member: P2pNode,
+        /// The age the member joined with.
+        age: u8,
+        /// Version of the section when the member joined.
+        section_version: u64,
+    },
+    /// A member left the section, e.g. because it went offline.
+    MemberLeft {
+        /// The member which left.
+        member: PublicId,
+        /// Version of the section when the member left.
+        section_version: u64,
+    },
+    /// A member was relocated to another section.
+    MemberRelocated {
+        /// The relocated member.
+        member: PublicId,
+        /// Name matching the prefix of the section the member was relocated to.
+        destination: XorName,
+        /// The age of the member after the relocation.
+        age: u8,
+        /// Version of the section when the member was relocated.
+        section_version: u64,
+    },
+}
+
+impl SectionHistoryEntry {
+    pub(crate) fn elders_changed(elders_info: EldersInfo, proof_block: &SectionProofBlock) -> Self {
+        SectionHistoryEntry::EldersChanged {
+            elders: SectionElders {
+                elders_info,
+                key_info: proof_block.key_info().clone(),
+            },
+            signature: proof_block.signature().clone(),
+        }
+    }
+}
+
+/// Verifies a section history exported with `Node::export_section_history`.
+///
+/// Checks the signature of the exporting node and that the whole log is valid from the genesis:
+/// every section key must be signed by the previous one and every member change must be recorded
+/// against the section version in force at the time. Returns the id of the exporting node and the
+/// history.
+pub fn verify_section_history(bytes: &[u8]) -> Result<(PublicId, SectionHistory)> {
+    let (version, history, signer, signature): (u32, Vec<u8>, PublicId, Signature) =
+        deserialize(bytes)?;
+    if version != SECTION_HISTORY_FORMAT_VERSION {
+        return Err(RoutingError::UnsupportedSectionHistoryVersion(version));
+    }
+
+    if !signer.verify(&serialize(&(version, &history))?, &signature) {
+        return Err(RoutingError::FailedSignature);
+    }
+
+    let history: SectionHistory = deserialize(&history)?;
+    history.validate()?;
+
+    Ok((signer, history))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        parsec::generate_bls_threshold_secret_key,
+        rng::{self, MainRng},
+        unwrap,
+    };
+    use rand::Rng;
+    use std::{collections::BTreeMap, iter, str::FromStr};
+
+    fn gen_elders_info(rng: &mut MainRng, prefix: Prefix<XorName>, version: u64) -> EldersInfo {
+        let members: BTreeMap<_, _> = (0..3)
+            .map(|index| {
+                let pub_id = *FullId::within_range(rng, &prefix.range_inclusive()).public_id();
+                (
+                    pub_id,
+                    P2pNode::new(pub_id, ([127, 0, 0, 1], 9000 + index).into()),
+                )
+            })
+            .collect();
+        unwrap!(EldersInfo::new_for_test(members, prefix, version))
+    }
+
+    // Signs with the full key of a single participant key set.
+    fn sign(secret_key_set: &bls::SecretKeySet, data: &[u8]) -> bls::Signature {
+        let share = secret_key_set.secret_key_share(0).sign(data);
+        unwrap!(secret_key_set
+            .public_keys()
+            .combine_signatures(iter::once((0, &share))))
+    }
+
+    // Generates a history of `count` elders changes in the given prefix, with a member joining
+    // after each of them. Returns the history and the id of one of the joined members.
+    fn gen_history(rng: &mut MainRng, count: u64) -> (SectionHistory, PublicId) {
+        let prefix = unwrap!(Prefix::from_str("0"));
+        let mut secret_key_set = generate_bls_threshold_secret_key(rng, 1);
+        let elders_info = gen_elders_info(rng, prefix, 0);
+        let key_info = SectionKeyInfo::from_elders_info(
+            &elders_info,
+            secret_key_set.public_keys().public_key(),
+        );
+        let mut history = SectionHistory::new(elders_info, key_info);
+        let mut joined = None;
+
+        for version in 1..=count {
+            let elders_info = gen_elders_info(rng, prefix, version);
+            let new_secret_key_set = generate_bls_threshold_secret_key(rng, 1);
+            let key_info = SectionKeyInfo::from_elders_info(
+                &elders_info,
+                new_secret_key_set.public_keys().public_key(),
+            );
+            let signature = sign(
+                &secret_key_set,
+                &unwrap!(key_info.serialise_for_signature()),
+            );
+            let proof_block = SectionProofBlock::new(key_info, signature);
+            history.push(SectionHistoryEntry::elders_changed(
+                elders_info,
+                &proof_block,
+            ));
+            secret_key_set = new_secret_key_set;
+
+            let pub_id = *FullId::within_range(rng, &prefix.range_inclusive()).public_id();
+            history.push(SectionHistoryEntry::MemberJoined {
+                member: P2pNode::new(pub_id, ([127, 0, 0, 1], 9999).into()),
+                age: 4,
+                section_version: version,
+            });
+            joined = Some(pub_id);
+        }
+
+        (history, unwrap!(joined))
+    }
+
+    #[test]
+    fn export_and_verify() {
+        let mut rng = rng::new();
+        let full_id = FullId::gen(&mut rng);
+        let (mut history, member) = gen_history(&mut rng, 3);
+        history.push(SectionHistoryEntry::MemberRelocated {
+            member,
+            destination: rng.gen(),
+            age: 5,
+            section_version: 3,
+        });
+
+        let bytes = unwrap!(history.to_signed_bytes(&full_id));
+        let (signer, verified) = unwrap!(verify_section_history(&bytes));
+
+        assert_eq!(signer, *full_id.public_id());
+        assert_eq!(verified, history);
+        assert_eq!(verified.entries().len(), 7);
+    }
+
+    #[test]
+    fn truncate_keeps_history_verifiable() {
+        let mut rng = rng::new();
+        let full_id = FullId::gen(&mut rng);
+        let (mut history, _) = gen_history(&mut rng, 3);
+        let last_elders = history.entries[4].clone();
+
+        // Drops the first two elders changes and the join between them.
+        history.truncate(3);
+        assert_eq!(history.entries().len(), 3);
+        assert_eq!(history.genesis().version(), 2);
+
+        let bytes = unwrap!(history.to_signed_bytes(&full_id));
+        let (_, verified) = unwrap!(verify_section_history(&bytes));
+        assert_eq!(verified.entries()[1], last_elders);
+    }
+
+    #[test]
+    fn reject_broken_key_chain() {
+        let mut rng = rng::new();
+        let full_id = FullId::gen(&mut rng);
+        let (mut history, _) = gen_history(&mut rng, 3);
+
+        // Drop the second elders change, so the third key is not signed by its predecessor.
+        let _ = history.entries.remove(2);
+        let _ = history.entries.remove(2);
+
+        let bytes = unwrap!(history.to_signed_bytes(&full_id));
+        match verify_section_history(&bytes) {
+            Err(RoutingError::InvalidSectionHistory) => (),
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn reject_inconsistent_member_change() {
+        let mut rng = rng::new();
+        let full_id = FullId::gen(&mut rng);
+        let (mut history, member) = gen_history(&mut rng, 2);
+        history.push(SectionHistoryEntry::MemberLeft {
+            member,
+            section_version: 1,
+        });
+
+        let bytes = unwrap!(history.to_signed_bytes(&full_id));
+        match verify_section_history(&bytes) {
+            Err(RoutingError::InvalidSectionHistory) => (),
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn reject_tampered_export() {
+        let mut rng = rng::new();
+        let full_id = FullId::gen(&mut rng);
+        let (history, _) = gen_history(&mut rng, 1);
+
+        let mut bytes = unwrap!(history.to_signed_bytes(&full_id));
+        // The history payload starts after the format version and its own length prefix.
+        bytes[20] ^= 1;
+
+        match verify_section_history(&bytes) {
+            Err(RoutingError::FailedSignature) => (),
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+}
@@ -7,14 +7,19 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use super::{
-    AccumulatedEvent, AccumulatingProof, AgeCounter, EldersInfo, MemberInfo, MemberPersona,
-    MemberState, MIN_AGE_COUNTER,
+    AccumulatedEvent, AccumulatingProof, AgeCounter, AgeingPolicy, BanList, EldersInfo, MemberInfo,
+    MemberPersona, MemberState, MergeDetails,
 };
 use crate::{
-    error::RoutingError, id::PublicId, location::DstLocation, relocation::RelocateDetails,
-    utils::LogIdent, Prefix, XorName,
+    error::{RoutingError, UntrustedProof},
+    id::PublicId,
+    location::DstLocation,
+    relocation::RelocateDetails,
+    utils::LogIdent,
+    Prefix, XorName,
 };
 use bincode::{deserialize, serialize};
+use err_derive::Error;
 use itertools::Itertools;
 use std::{
     collections::{BTreeMap, VecDeque},
@@ -31,7 +36,7 @@ use crate::crypto::Digest256;
 const MAX_THEIR_RECENT_KEYS: usize = 20;
 
 /// Section state that is shared among all elders of a section via Parsec consensus.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SharedState {
     /// Indicate whether nodes are shared state because genesis event was seen
     pub handled_genesis_event: bool,
@@ -46,10 +51,16 @@ pub struct SharedState {
     /// Note that after a split, the neighbour's latest section info could be the one from the
     /// pre-split parent section, so the value's prefix doesn't always match the key.
     pub neighbour_infos: BTreeMap<Prefix<XorName>, EldersInfo>,
+    /// Maps our neighbours' prefixes to the latest number of joined members they told us.
+    pub neighbour_member_counts: BTreeMap<Prefix<XorName>, u64>,
     /// Is split of the section currently in progress.
     pub split_in_progress: bool,
     // The accumulated info during a split pfx change.
     pub split_cache: Option<SplitCache>,
+    /// Is merge with our sibling currently in progress.
+    pub merge_in_progress: bool,
+    /// The latest details our sibling sent us when asking to merge.
+    pub merge_cache: Option<MergeDetails>,
     /// Our section's key history for Secure Message Delivery
     pub our_history: SectionProofChain,
     /// BLS public keys of other sections
@@ -62,6 +73,8 @@ pub struct SharedState {
     pub churn_event_backlog: VecDeque<AccumulatedEvent>,
     /// Queue of pending relocations.
     pub relocate_queue: VecDeque<RelocateDetails>,
+    /// Nodes we refuse to let bootstrap to or join our section.
+    pub ban_list: BanList,
 }
 
 impl SharedState {
@@ -69,6 +82,8 @@ impl SharedState {
         elders_info: EldersInfo,
         bls_keys: bls::PublicKeySet,
         ages: BTreeMap<PublicId, AgeCounter>,
+        start_age: u8,
+        ban_list: BanList,
     ) -> Self {
         let pk_info = SectionKeyInfo::from_elders_info(&elders_info, bls_keys.public_key());
         let our_history = SectionProofChain::from_genesis(pk_info);
@@ -79,7 +94,10 @@ impl SharedState {
             .member_nodes()
             .map(|p2p_node| {
                 let info = MemberInfo {
-                    age_counter: *ages.get(p2p_node.public_id()).unwrap_or(&MIN_AGE_COUNTER),
+                    age_counter: ages
+                        .get(p2p_node.public_id())
+                        .copied()
+                        .unwrap_or_else(|| AgeCounter::from_age(start_age)),
                     state: MemberState::Joined,
                     p2p_node: p2p_node.clone(),
                 };
@@ -91,16 +109,20 @@ impl SharedState {
             handled_genesis_event: false,
             our_infos: NonEmptyList::new(elders_info),
             neighbour_infos: Default::default(),
+            neighbour_member_counts: Default::default(),
             our_members,
             post_split_sibling_members: Default::default(),
             split_in_progress: false,
             split_cache: None,
+            merge_in_progress: false,
+            merge_cache: None,
             our_history,
             their_keys,
             their_knowledge: Default::default(),
             their_recent_keys: Default::default(),
             churn_event_backlog: Default::default(),
             relocate_queue: VecDeque::new(),
+            ban_list,
         }
     }
 
@@ -126,6 +148,7 @@ impl SharedState {
             our_history,
             our_members,
             neighbour_infos,
+            neighbour_member_counts,
             their_keys,
             their_knowledge,
             their_recent_keys,
@@ -158,6 +181,12 @@ impl SharedState {
                 &self.neighbour_infos,
                 &neighbour_infos,
             );
+            update_with_genesis_related_info_check_same(
+                log_ident,
+                "neighbour_member_counts",
+                &self.neighbour_member_counts,
+                &neighbour_member_counts,
+            );
             update_with_genesis_related_info_check_same(
                 log_ident,
                 "their_keys",
@@ -193,6 +222,7 @@ impl SharedState {
         self.our_history = our_history;
         self.our_members = our_members;
         self.neighbour_infos = neighbour_infos;
+        self.neighbour_member_counts = neighbour_member_counts;
         self.their_keys = their_keys;
         self.their_knowledge = their_knowledge;
         self.their_recent_keys = their_recent_keys;
@@ -208,6 +238,7 @@ impl SharedState {
             &self.our_history,
             &self.our_members,
             &self.neighbour_infos,
+            &self.neighbour_member_counts,
             &self.their_keys,
             &self.their_knowledge,
             &self.their_recent_keys,
@@ -262,12 +293,16 @@ impl SharedState {
 
     /// Returns the current persona corresponding to the given PublicId or `None` if such a member
     /// doesn't exist
-    pub fn get_persona(&self, pub_id: &PublicId) -> Option<MemberPersona> {
+    pub fn get_persona(
+        &self,
+        pub_id: &PublicId,
+        ageing_policy: &dyn AgeingPolicy,
+    ) -> Option<MemberPersona> {
         if self.our_info().is_member(pub_id) {
             Some(MemberPersona::Elder)
         } else {
             self.our_members.get(pub_id.name()).map(|member| {
-                if member.is_mature() {
+                if ageing_policy.is_mature(member.age()) {
                     MemberPersona::Adult
                 } else {
                     MemberPersona::Infant
@@ -296,9 +331,38 @@ impl SharedState {
 
     /// Updates the entry in `their_keys` for `prefix` to the latest known key; if a split
     /// occurred in the meantime, the keys for sections covering the rest of the address space are
-    /// initialised to the old key that was stored for their common ancestor
-    /// NOTE: the function as it is currently is not merge-safe.
+    /// initialised to the old key that was stored for their common ancestor. If the key is for a
+    /// section that merged in the meantime, the keys of the merged sections are replaced.
     pub fn update_their_keys(&mut self, key_info: &SectionKeyInfo) {
+        let merged: Vec<_> = self
+            .their_keys
+            .iter()
+            .filter(|(pfx, _)| pfx.is_extension_of(key_info.prefix()))
+            .map(|(pfx, info)| (*pfx, info.version()))
+            .collect();
+        if !merged.is_empty() {
+            if merged
+                .iter()
+                .any(|(_, version)| *version >= key_info.version())
+            {
+                // Do not overwrite newer versions of the extensions
+                return;
+            }
+
+            for (pfx, _) in merged {
+                if let Some(old_key_info) = self.their_keys.remove(&pfx) {
+                    self.their_recent_keys.push_front((pfx, old_key_info));
+                }
+            }
+            while self.their_recent_keys.len() > MAX_THEIR_RECENT_KEYS {
+                let _ = self.their_recent_keys.pop_back();
+            }
+
+            trace!("    merged into {:?}", key_info);
+            let _ = self.their_keys.insert(*key_info.prefix(), key_info.clone());
+            return;
+        }
+
         if let Some((&old_pfx, old_version)) = self
             .their_keys
             .iter()
@@ -335,9 +399,36 @@ impl SharedState {
 
     /// Updates the entry in `their_knowledge` for `prefix` to the `version`; if a split
     /// occurred in the meantime, the versions for sections covering the rest of the address space
-    /// are initialised to the old version that was stored for their common ancestor
-    /// NOTE: the function as it is currently is not merge-safe.
+    /// are initialised to the old version that was stored for their common ancestor. If the
+    /// sections covered by `prefix` merged in the meantime, their entries are replaced.
     pub fn update_their_knowledge(&mut self, prefix: Prefix<XorName>, version: u64) {
+        let merged: Vec<_> = self
+            .their_knowledge
+            .keys()
+            .filter(|pfx| pfx.is_extension_of(&prefix))
+            .cloned()
+            .collect();
+        if !merged.is_empty() {
+            // Our history is linear, so the merged section knows at least the smallest version
+            // any of its parts knew.
+            let old_version = merged
+                .iter()
+                .filter_map(|pfx| self.their_knowledge.remove(pfx))
+                .min()
+                .unwrap_or(0);
+
+            trace!(
+                "    merged {:?} into {:?}/{:?}",
+                merged,
+                prefix,
+                version.max(old_version)
+            );
+            let _ = self
+                .their_knowledge
+                .insert(prefix, version.max(old_version));
+            return;
+        }
+
         if let Some((&old_pfx, &old_version)) = self
             .their_knowledge
             .iter()
@@ -387,7 +478,6 @@ impl SharedState {
             .chain(self.their_recent_keys.iter().map(|(p, k)| (p, k)))
     }
 
-    #[cfg(feature = "mock_base")]
     /// Returns their_knowledge
     pub fn get_their_knowledge(&self) -> &BTreeMap<Prefix<XorName>, u64> {
         &self.their_knowledge
@@ -438,7 +528,7 @@ fn update_with_genesis_related_info_check_same<T>(
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SplitCache {
     pub elders_info: EldersInfo,
     pub key_info: SectionKeyInfo,
@@ -501,6 +591,10 @@ impl SectionProofBlock {
         &self.key_info
     }
 
+    pub fn signature(&self) -> &bls::Signature {
+        &self.sig
+    }
+
     pub fn verify_with_pk(&self, pk: bls::PublicKey) -> bool {
         if let Ok(to_verify) = self.key_info.serialise_for_signature() {
             pk.verify(&self.sig, to_verify)
@@ -518,6 +612,9 @@ impl SectionProofBlock {
     }
 }
 
+/// A chain of section keys, each one signed by the previous one, proving the latest key of a
+/// section to someone who trusts an older key of that section. Obtained with `Node::prove` and
+/// checked with [`verify`](#method.verify).
 #[derive(Debug, Eq, PartialEq, Clone, Hash, Serialize, Deserialize)]
 pub struct SectionProofSlice {
     /// The version of the section key to use as root of trust.
@@ -530,7 +627,7 @@ pub struct SectionProofSlice {
 
 impl SectionProofSlice {
     #[cfg(any(feature = "mock_base", test))]
-    pub fn from_genesis(key_info: SectionKeyInfo) -> Self {
+    pub(crate) fn from_genesis(key_info: SectionKeyInfo) -> Self {
         Self {
             version: key_info.version,
             prefix: key_info.prefix,
@@ -538,7 +635,66 @@ impl SectionProofSlice {
         }
     }
 
-    pub fn last_prefix_version(&self) -> (&Prefix<XorName>, u64) {
+    /// Serialises the slice so it can be sent to and verified by someone else.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, RoutingError> {
+        Ok(serialize(self)?)
+    }
+
+    /// Deserialises a slice produced by `to_bytes`. The slice still has to be verified.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, RoutingError> {
+        Ok(deserialize(bytes)?)
+    }
+
+    /// Verifies the slice against a section key we trust, which is either the root of the slice
+    /// or one of the keys it contains. Returns the latest key info of the section proven by the
+    /// slice.
+    ///
+    /// The slice doesn't contain the key of its root, so the root is trusted only if its version
+    /// and prefix are those of `trusted`. Fails with `ProofError::UntrustedRoot` otherwise, which
+    /// includes an empty slice rooted anywhere else.
+    pub fn verify(&self, trusted: &SectionKeyInfo) -> Result<SectionKeyInfo, ProofError> {
+        let first_block = match self
+            .blocks
+            .iter()
+            .position(|block| block.key_info() == trusted)
+        {
+            Some(index) => index + 1,
+            None if self.version == trusted.version && self.prefix == trusted.prefix => 0,
+            None => return Err(ProofError::UntrustedRoot),
+        };
+
+        let mut current = trusted.clone();
+        for block in &self.blocks[first_block..] {
+            check_next_block(&current, block)?;
+            current = block.key_info().clone();
+        }
+
+        Ok(current)
+    }
+
+    /// Verifies the slice against a section key we trust, which is either the key of its root or
+    /// one of the keys it contains. Returns the latest key info of the section proven by the
+    /// slice.
+    ///
+    /// Only the key is trusted, not the version and prefix of the root, so an empty slice proves
+    /// nothing and fails with `ProofError::UntrustedRoot`.
+    pub fn verify_with_pk(&self, trusted: &bls::PublicKey) -> Result<SectionKeyInfo, ProofError> {
+        if let Some(block) = self
+            .blocks
+            .iter()
+            .find(|block| block.key_info().key() == trusted)
+        {
+            return self.verify(block.key_info());
+        }
+
+        if self.blocks.is_empty() {
+            return Err(ProofError::UntrustedRoot);
+        }
+
+        self.verify(&SectionKeyInfo::new(self.version, self.prefix, *trusted))
+    }
+
+    pub(crate) fn last_prefix_version(&self) -> (&Prefix<XorName>, u64) {
         self.blocks
             .last()
             .map(|block| (block.prefix(), block.version()))
@@ -546,7 +702,9 @@ impl SectionProofSlice {
     }
 
     #[cfg(all(test, feature = "mock"))]
-    pub fn all_prefix_version(&self) -> impl DoubleEndedIterator<Item = (&Prefix<XorName>, u64)> {
+    pub(crate) fn all_prefix_version(
+        &self,
+    ) -> impl DoubleEndedIterator<Item = (&Prefix<XorName>, u64)> {
         iter::once((&self.prefix, self.version)).chain(
             self.blocks
                 .iter()
@@ -554,7 +712,18 @@ impl SectionProofSlice {
         )
     }
 
-    pub fn last_new_key_info(&self) -> Option<&SectionKeyInfo> {
+    // Summary of this proof for an `UntrustedMessage` error.
+    pub(crate) fn untrusted(&self, too_new: bool) -> UntrustedProof {
+        let (prefix, last_version) = self.last_prefix_version();
+        UntrustedProof {
+            prefix: *prefix,
+            first_version: self.version,
+            last_version,
+            too_new,
+        }
+    }
+
+    pub(crate) fn last_new_key_info(&self) -> Option<&SectionKeyInfo> {
         self.blocks.last().map(|block| block.key_info())
     }
 
@@ -590,7 +759,7 @@ impl SectionProofSlice {
     }
 
     // Verify this proof chain against the given key infos.
-    pub fn check_trust<'a, I>(&'a self, their_key_infos: I) -> TrustStatus<'a>
+    pub(crate) fn check_trust<'a, I>(&'a self, their_key_infos: I) -> TrustStatus<'a>
     where
         I: IntoIterator<Item = (&'a Prefix<XorName>, &'a SectionKeyInfo)>,
     {
@@ -641,6 +810,13 @@ impl SectionProofChain {
         }
     }
 
+    pub fn from_blocks(genesis_key_info: SectionKeyInfo, blocks: Vec<SectionProofBlock>) -> Self {
+        Self {
+            genesis_key_info,
+            blocks,
+        }
+    }
+
     pub fn push(&mut self, block: SectionProofBlock) {
         if !validate_next_block(self.last_key_info(), &block) {
             log_or_panic!(
@@ -655,7 +831,6 @@ impl SectionProofChain {
         self.blocks.push(block)
     }
 
-    #[cfg(test)]
     pub fn validate(&self) -> bool {
         let mut current = &self.genesis_key_info;
         for block in &self.blocks {
@@ -707,23 +882,76 @@ impl SectionProofChain {
 }
 
 fn validate_next_block(last: &SectionKeyInfo, next: &SectionProofBlock) -> bool {
+    check_next_block(last, next).is_ok()
+}
+
+fn check_next_block(last: &SectionKeyInfo, next: &SectionProofBlock) -> Result<(), ProofError> {
     if next.version() != last.version() + 1 {
-        return false;
+        return Err(ProofError::Gap {
+            expected: last.version() + 1,
+            found: next.version(),
+        });
     }
 
     if !next.prefix().is_compatible(last.prefix())
         || next.prefix().bit_count() > last.prefix().bit_count() + 1
     {
-        return false;
+        return Err(ProofError::PrefixMismatch {
+            previous: *last.prefix(),
+            found: *next.prefix(),
+        });
     }
 
     if !next.verify_with_pk(*last.key()) {
-        return false;
+        return Err(ProofError::InvalidSignature {
+            version: next.version(),
+        });
     }
 
-    true
+    Ok(())
 }
 
+/// The reason why a `SectionProofSlice` failed verification.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Error)]
+pub enum ProofError {
+    /// The versions of two consecutive keys are not consecutive.
+    #[error(
+        display = "Gap in the proof: expected version {}, found {}.",
+        expected,
+        found
+    )]
+    Gap {
+        /// The version following the last trusted key.
+        expected: u64,
+        /// The version of the next key in the proof.
+        found: u64,
+    },
+    /// A key is not signed by the previous key.
+    #[error(display = "The key of version {} has an invalid signature.", version)]
+    InvalidSignature {
+        /// The version of the key with the invalid signature.
+        version: u64,
+    },
+    /// The prefix of a key is not the prefix of the previous key, nor its parent or one of its
+    /// children.
+    #[error(
+        display = "Prefix mismatch in the proof: {:?} can't follow {:?}.",
+        found,
+        previous
+    )]
+    PrefixMismatch {
+        /// The prefix of the last trusted key.
+        previous: Prefix<XorName>,
+        /// The prefix of the next key in the proof.
+        found: Prefix<XorName>,
+    },
+    /// The trusted key is neither the root of the proof nor one of its keys.
+    #[error(display = "The proof is not anchored at the trusted key.")]
+    UntrustedRoot,
+}
+
+/// The BLS public key of a section, together with the prefix and version of the section it was
+/// generated for.
 #[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Clone, Hash, Serialize, Deserialize)]
 pub struct SectionKeyInfo {
     /// The section version. This increases monotonically whenever the set of elders changes.
@@ -736,6 +964,7 @@ pub struct SectionKeyInfo {
 }
 
 impl SectionKeyInfo {
+    /// Creates a new key info.
     pub fn new(version: u64, prefix: Prefix<XorName>, key: bls::PublicKey) -> Self {
         Self {
             version,
@@ -744,23 +973,26 @@ impl SectionKeyInfo {
         }
     }
 
-    pub fn from_elders_info(elders_info: &EldersInfo, key: bls::PublicKey) -> Self {
+    pub(crate) fn from_elders_info(elders_info: &EldersInfo, key: bls::PublicKey) -> Self {
         Self::new(elders_info.version(), *elders_info.prefix(), key)
     }
 
+    /// The BLS public key of the section.
     pub fn key(&self) -> &bls::PublicKey {
         &self.key
     }
 
+    /// The prefix of the section.
     pub fn prefix(&self) -> &Prefix<XorName> {
         &self.prefix
     }
 
+    /// The version of the section.
     pub fn version(&self) -> u64 {
         self.version
     }
 
-    pub fn serialise_for_signature(&self) -> Result<Vec<u8>, RoutingError> {
+    pub(crate) fn serialise_for_signature(&self) -> Result<Vec<u8>, RoutingError> {
         Ok(serialize(&self)?)
     }
 }
@@ -781,7 +1013,7 @@ pub enum TrustStatus<'a> {
 mod test {
     use super::*;
     use crate::{
-        chain::EldersInfo,
+        chain::{EldersInfo, MIN_AGE},
         id::P2pNode,
         parsec::generate_bls_threshold_secret_key,
         rng::{self, MainRng},
@@ -844,7 +1076,7 @@ mod test {
             let start_section = unwrap!(keys_to_update.first());
             let info = start_section.1.clone();
             let keys = start_section.2.clone();
-            SharedState::new(info, keys, Default::default())
+            SharedState::new(info, keys, Default::default(), MIN_AGE, Default::default())
         };
 
         // Act
@@ -952,6 +1184,8 @@ mod test {
             gen_elders_info(rng, Default::default(), 0),
             generate_bls_threshold_secret_key(rng, 1).public_keys(),
             Default::default(),
+            MIN_AGE,
+            Default::default(),
         );
 
         for (prefix_str, version) in updates {
@@ -987,4 +1221,167 @@ mod test {
             vec![("10", 2), ("11", 2)],
         )
     }
+
+    // Generates `prefixes.len()` section keys, each signed by the previous one. Returns the keys
+    // and the proof chain linking them.
+    fn gen_proof_chain(
+        rng: &mut MainRng,
+        prefixes: &[&str],
+    ) -> (Vec<bls::SecretKeySet>, SectionProofChain) {
+        let secret_keys: Vec<_> = prefixes
+            .iter()
+            .map(|_| generate_bls_threshold_secret_key(rng, 1))
+            .collect();
+        let key_infos: Vec<_> = prefixes
+            .iter()
+            .zip(&secret_keys)
+            .enumerate()
+            .map(|(version, (pfx_str, secret_key_set))| {
+                let pfx = unwrap!(Prefix::<XorName>::from_str(pfx_str));
+                SectionKeyInfo::new(
+                    version as u64,
+                    pfx,
+                    secret_key_set.public_keys().public_key(),
+                )
+            })
+            .collect();
+
+        let mut chain = SectionProofChain::from_genesis(key_infos[0].clone());
+        for index in 1..key_infos.len() {
+            let signature = sign(
+                &secret_keys[index - 1],
+                &unwrap!(key_infos[index].serialise_for_signature()),
+            );
+            chain.push(SectionProofBlock::new(key_infos[index].clone(), signature));
+        }
+
+        (secret_keys, chain)
+    }
+
+    // Signs with the full key of a single participant key set.
+    fn sign(secret_key_set: &bls::SecretKeySet, data: &[u8]) -> bls::Signature {
+        let share = secret_key_set.secret_key_share(0).sign(data);
+        unwrap!(secret_key_set
+            .public_keys()
+            .combine_signatures(iter::once((0, &share))))
+    }
+
+    #[test]
+    fn verify_proof_slice() {
+        let mut rng = rng::new();
+        let (secret_keys, chain) = gen_proof_chain(&mut rng, &["0", "0", "01", "01"]);
+        let key_info = |version: usize, prefix: &str| {
+            SectionKeyInfo::new(
+                version as u64,
+                unwrap!(Prefix::from_str(prefix)),
+                secret_keys[version].public_keys().public_key(),
+            )
+        };
+        let last_key = secret_keys[3].public_keys().public_key();
+
+        let slice = chain.slice_from(0);
+        let proven = unwrap!(slice.verify(&key_info(0, "0")));
+        assert_eq!(*proven.key(), last_key);
+        assert_eq!(proven.version(), 3);
+        assert_eq!(*proven.prefix(), unwrap!(Prefix::from_str("01")));
+
+        // Trusting a key in the middle of the slice only checks the rest of it.
+        let proven = unwrap!(slice.verify(&key_info(2, "01")));
+        assert_eq!(*proven.key(), last_key);
+
+        // Round trip through the serialised form.
+        let slice = unwrap!(SectionProofSlice::from_bytes(&unwrap!(chain
+            .slice_from(2)
+            .to_bytes())));
+        let proven = unwrap!(slice.verify(&key_info(2, "01")));
+        assert_eq!(*proven.key(), last_key);
+
+        // An empty slice proves its root.
+        let slice = chain.slice_from(3);
+        assert_eq!(unwrap!(slice.verify(&key_info(3, "01"))), key_info(3, "01"));
+
+        // Trusting only a key, either the root one or one in the middle of the slice.
+        let slice = chain.slice_from(0);
+        for version in &[0, 2, 3] {
+            let trusted = secret_keys[*version].public_keys().public_key();
+            assert_eq!(unwrap!(slice.verify_with_pk(&trusted)), key_info(3, "01"));
+        }
+    }
+
+    #[test]
+    fn verify_proof_slice_failures() {
+        let mut rng = rng::new();
+        let (secret_keys, chain) = gen_proof_chain(&mut rng, &["0", "0", "01"]);
+        let first_key = secret_keys[0].public_keys().public_key();
+        let first_key_info = SectionKeyInfo::new(0, unwrap!(Prefix::from_str("0")), first_key);
+        let slice = chain.slice_from(0);
+
+        // Untrusted root key.
+        let other_key = generate_bls_threshold_secret_key(&mut rng, 1)
+            .public_keys()
+            .public_key();
+        assert_eq!(
+            slice.verify(&SectionKeyInfo::new(
+                0,
+                unwrap!(Prefix::from_str("0")),
+                other_key
+            )),
+            Err(ProofError::InvalidSignature { version: 1 })
+        );
+
+        // Root not matching the trusted key info, even though the key is right.
+        assert_eq!(
+            slice.verify(&SectionKeyInfo::new(
+                5,
+                unwrap!(Prefix::from_str("0")),
+                first_key
+            )),
+            Err(ProofError::UntrustedRoot)
+        );
+
+        // Empty slice rooted at a version and prefix of the attacker's choice.
+        let mut empty = chain.slice_from(2);
+        empty.version = 7;
+        empty.prefix = unwrap!(Prefix::from_str("1"));
+        assert_eq!(
+            empty.verify(&first_key_info),
+            Err(ProofError::UntrustedRoot)
+        );
+
+        // Only the key is trusted, so an empty slice proves nothing, not even its root.
+        assert_eq!(
+            chain.slice_from(2).verify_with_pk(&first_key),
+            Err(ProofError::UntrustedRoot)
+        );
+        assert_eq!(
+            slice.verify_with_pk(&other_key),
+            Err(ProofError::InvalidSignature { version: 1 })
+        );
+
+        // Missing block.
+        let mut gap = slice.clone();
+        let _ = gap.blocks.remove(0);
+        assert_eq!(
+            gap.verify(&first_key_info),
+            Err(ProofError::Gap {
+                expected: 1,
+                found: 2
+            })
+        );
+
+        // Block for an incompatible prefix.
+        let mut mismatch = slice;
+        mismatch.prefix = unwrap!(Prefix::from_str("1"));
+        assert_eq!(
+            mismatch.verify(&SectionKeyInfo::new(
+                0,
+                unwrap!(Prefix::from_str("1")),
+                first_key
+            )),
+            Err(ProofError::PrefixMismatch {
+                previous: unwrap!(Prefix::from_str("1")),
+                found: unwrap!(Prefix::from_str("0")),
+            })
+        );
+    }
 }
@@ -0,0 +1,658 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Clients connected to a node.
+//!
+//! A client is identified by its public signing key. When it connects, the node sends it a random
+//! challenge which the client signs to prove it holds the secret key. From then on the messages
+//! from that connection are attributed to the client. If the client reconnects from another
+//! address, it proves its identity again and the node sends it messages on the new connection.
+//!
+//! The rate of the messages a client can send is limited with a token bucket, kept per IP address
+//! until the handshake succeeds and per client identity afterwards, so reconnecting doesn't reset
+//! it. Messages over the limit are dropped and the client is told when it may retry. The session
+//! of a disconnected client is dropped if it doesn't reconnect within `SESSION_TIMEOUT`.
+
+use crate::{
+    crypto::{signing, Digest256},
+    error::Result,
+    messages::Priority,
+    rng::{MainRng, RngCompat},
+    time::{Duration, Instant},
+    xor_space::XorName,
+};
+use bincode::{deserialize, serialize};
+use bytes::Bytes;
+use hex_fmt::HexFmt;
+use std::{
+    collections::HashMap,
+    fmt::{self, Debug, Formatter},
+    net::{IpAddr, SocketAddr},
+    rc::Rc,
+};
+
+// Prefix of the data a client signs to answer a challenge, so the signature can't be reused for
+// anything else.
+const HANDSHAKE_DOMAIN: &[u8] = b"routing-client-handshake";
+
+/// Default number of messages a client can send in a burst.
+const RATE_LIMIT_BURST: u32 = 100;
+/// Default number of messages per second a client can send in the long run.
+const RATE_LIMIT_MESSAGES_PER_SECOND: u32 = 20;
+/// Time the session of a disconnected client is kept for it to reconnect.
+const SESSION_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Public identity of a client.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct ClientId {
+    public_key: signing::PublicKey,
+}
+
+impl ClientId {
+    /// Returns the public signing key of the client.
+    pub fn public_key(&self) -> &signing::PublicKey {
+        &self.public_key
+    }
+
+    /// Returns the name of the client, derived from its public key.
+    pub fn name(&self) -> XorName {
+        XorName(self.public_key.to_bytes())
+    }
+
+    /// Verifies the client signed the answer to the given challenge.
+    pub fn verify_handshake(&self, challenge: &Digest256, signature: &signing::Signature) -> bool {
+        self.public_key
+            .verify(&handshake_payload(challenge), signature)
+            .is_ok()
+    }
+}
+
+impl Debug for ClientId {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "ClientId({:10})",
+            HexFmt(self.public_key.as_bytes())
+        )
+    }
+}
+
+/// Full identity of a client, including its secret key. Used on the client side to answer the
+/// handshake challenge of a node.
+#[derive(Clone)]
+pub struct ClientFullId {
+    public_id: ClientId,
+    secret_key: Rc<signing::SecretKey>,
+}
+
+impl ClientFullId {
+    /// Construct a `ClientFullId` with a randomly generated key.
+    pub fn gen(rng: &mut MainRng) -> Self {
+        let secret_key = signing::SecretKey::generate(&mut RngCompat(rng));
+        let public_key = signing::PublicKey::from(&secret_key);
+
+        Self {
+            public_id: ClientId { public_key },
+            secret_key: Rc::new(secret_key),
+        }
+    }
+
+    /// Returns the public identity.
+    pub fn public_id(&self) -> &ClientId {
+        &self.public_id
+    }
+
+    /// Returns the answer to the given handshake challenge.
+    pub fn handshake_response(&self, challenge: &Digest256) -> ClientWireMessage {
+        ClientWireMessage::HandshakeResponse {
+            client_id: self.public_id,
+            signature: signing::sign(
+                &handshake_payload(challenge),
+                &self.public_id.public_key,
+                &self.secret_key,
+            ),
+        }
+    }
+}
+
+impl Debug for ClientFullId {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "ClientFullId({:?})", self.public_id)
+    }
+}
+
+/// Message exchanged between a node and a client.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ClientWireMessage {
+    /// Sent by the node when the client connects. The client has to sign it with its key.
+    Challenge(Digest256),
+    /// Sent by the client to prove its identity.
+    HandshakeResponse {
+        /// Identity of the client.
+        client_id: ClientId,
+        /// Signature of the challenge.
+        signature: signing::Signature,
+    },
+    /// Application message, in either direction. Messages from the client are only accepted once
+    /// the handshake succeeded.
+    Message(Vec<u8>),
+    /// Sent by the node when it dropped a message from the client.
+    Rejected(ClientRejection),
+}
+
+impl ClientWireMessage {
+    /// Serialises the message.
+    pub fn to_bytes(&self) -> Result<Bytes> {
+        Ok(serialize(self)?.into())
+    }
+
+    /// Deserialises a message.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        Ok(deserialize(bytes)?)
+    }
+
+    /// Priority class of the message in the outbound queues of the node.
+    pub(crate) fn priority(&self) -> Priority {
+        match self {
+            Self::Message(_) => Priority::Bulk,
+            Self::Challenge(_) | Self::HandshakeResponse { .. } | Self::Rejected(_) => {
+                Priority::Control
+            }
+        }
+    }
+}
+
+impl Debug for ClientWireMessage {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Challenge(challenge) => {
+                write!(formatter, "Challenge({:10})", HexFmt(challenge))
+            }
+            Self::HandshakeResponse { client_id, .. } => {
+                write!(formatter, "HandshakeResponse({:?})", client_id)
+            }
+            Self::Message(content) => write!(formatter, "Message({:<8})", HexFmt(content)),
+            Self::Rejected(rejection) => write!(formatter, "Rejected({:?})", rejection),
+        }
+    }
+}
+
+/// Reason why a node dropped a message from a client.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ClientRejection {
+    /// The client sent more messages than its rate limit allows. Further messages are dropped
+    /// until `retry_after` elapses. The client is only told once per such episode.
+    RateLimited {
+        /// Time after which the node accepts a message again.
+        retry_after: Duration,
+    },
+}
+
+/// Limits on the rate of messages a client can send to a node, enforced with a token bucket.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClientRateLimit {
+    /// Maximum number of messages accepted in a burst.
+    pub burst: u32,
+    /// Number of messages per second accepted in the long run.
+    pub messages_per_second: u32,
+}
+
+impl Default for ClientRateLimit {
+    fn default() -> Self {
+        Self {
+            burst: RATE_LIMIT_BURST,
+            messages_per_second: RATE_LIMIT_MESSAGES_PER_SECOND,
+        }
+    }
+}
+
+/// Outcome of checking a message from a client against its rate limit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RateCheck {
+    /// The message is within the limit.
+    Accept,
+    /// The message is over the limit and the client has to be told so.
+    Reject(ClientRejection),
+    /// The message is over the limit and the client has already been told so.
+    Drop,
+}
+
+/// Sessions of the clients connected to us.
+pub struct ClientSessions {
+    // Challenges sent to the connections which haven't completed the handshake yet.
+    challenges: HashMap<SocketAddr, Digest256>,
+    // Authenticated connections.
+    clients: HashMap<SocketAddr, ClientId>,
+    // Sessions of the clients that completed the handshake, including those currently
+    // disconnected.
+    sessions: HashMap<ClientId, ClientSession>,
+    // Rate limit buckets of the connections which haven't completed the handshake yet, shared by
+    // all the connections from the same IP address. A bucket outlives the connections until it
+    // refills, so reconnecting doesn't reset it.
+    unauthenticated_buckets: HashMap<IpAddr, TokenBucket>,
+    rate_limit: Option<ClientRateLimit>,
+}
+
+impl Default for ClientSessions {
+    fn default() -> Self {
+        Self {
+            challenges: HashMap::new(),
+            clients: HashMap::new(),
+            sessions: HashMap::new(),
+            unauthenticated_buckets: HashMap::new(),
+            rate_limit: Some(ClientRateLimit::default()),
+        }
+    }
+}
+
+impl ClientSessions {
+    /// Sets the rate limit applied to the messages from clients. `None` disables it.
+    pub fn set_rate_limit(&mut self, rate_limit: Option<ClientRateLimit>) {
+        self.rate_limit = rate_limit;
+    }
+
+    /// Checks a message received on `addr` against the rate limit of its sender and consumes a
+    /// token if it is accepted.
+    pub fn check_rate_limit(&mut self, addr: SocketAddr) -> RateCheck {
+        let rate_limit = match self.rate_limit {
+            Some(rate_limit) => rate_limit,
+            None => return RateCheck::Accept,
+        };
+
+        let bucket = match self.clients.get(&addr) {
+            Some(client_id) => self
+                .sessions
+                .get_mut(client_id)
+                .map(|session| &mut session.bucket),
+            None => None,
+        };
+
+        match bucket {
+            Some(bucket) => bucket.take(&rate_limit),
+            None => self
+                .unauthenticated_buckets
+                .entry(addr.ip())
+                .or_insert_with(|| TokenBucket::new(&rate_limit))
+                .take(&rate_limit),
+        }
+    }
+
+    /// Records the challenge sent to a newly connected client.
+    pub fn insert_challenge(&mut self, addr: SocketAddr, challenge: Digest256) {
+        let _ = self.challenges.insert(addr, challenge);
+    }
+
+    /// Checks the handshake response received from `addr`. On success, the connection is
+    /// attributed to the client, replacing any previous connection of the same client.
+    pub fn authenticate(
+        &mut self,
+        addr: SocketAddr,
+        client_id: ClientId,
+        signature: &signing::Signature,
+    ) -> bool {
+        let challenge = match self.challenges.remove(&addr) {
+            Some(challenge) => challenge,
+            None => return false,
+        };
+
+        if !client_id.verify_handshake(&challenge, signature) {
+            return false;
+        }
+
+        // A new session inherits the tokens its IP address used before the handshake, an existing
+        // one keeps its own.
+        let rate_limit = self.rate_limit.unwrap_or_default();
+        let bucket = self
+            .unauthenticated_buckets
+            .get(&addr.ip())
+            .cloned()
+            .unwrap_or_else(|| TokenBucket::new(&rate_limit));
+        let session = self
+            .sessions
+            .entry(client_id)
+            .or_insert_with(|| ClientSession {
+                addr: None,
+                disconnected_at: None,
+                bucket,
+            });
+        session.disconnected_at = None;
+        if let Some(old_addr) = session.addr.replace(addr) {
+            if old_addr != addr {
+                let _ = self.clients.remove(&old_addr);
+            }
+        }
+
+        let _ = self.clients.insert(addr, client_id);
+        true
+    }
+
+    /// Returns the client authenticated on the connection from `addr`, if any.
+    pub fn client_at(&self, addr: &SocketAddr) -> Option<&ClientId> {
+        self.clients.get(addr)
+    }
+
+    /// Returns the address of the current connection of the client, if it is connected.
+    pub fn addr_of(&self, client_id: &ClientId) -> Option<SocketAddr> {
+        self.sessions
+            .get(client_id)
+            .and_then(|session| session.addr)
+    }
+
+    /// Handles the connection from `addr` being closed. The session of the client is kept for
+    /// `SESSION_TIMEOUT`, so it can resume it by reconnecting.
+    pub fn disconnected(&mut self, addr: &SocketAddr) {
+        let _ = self.challenges.remove(addr);
+
+        if let Some(client_id) = self.clients.remove(addr) {
+            if let Some(session) = self.sessions.get_mut(&client_id) {
+                session.addr = None;
+                session.disconnected_at = Some(Instant::now());
+            }
+        }
+
+        self.remove_expired_sessions();
+        self.remove_refilled_buckets();
+    }
+
+    // Drops the unauthenticated buckets which are full again, as they are no different from new
+    // ones.
+    fn remove_refilled_buckets(&mut self) {
+        let rate_limit = self.rate_limit.unwrap_or_default();
+        self.unauthenticated_buckets
+            .retain(|_, bucket| !bucket.is_refilled(&rate_limit));
+    }
+
+    // Drops the sessions of the clients which didn't reconnect in time. Sessions only become
+    // disconnected in `disconnected`, so checking there is enough to bound their number.
+    fn remove_expired_sessions(&mut self) {
+        self.sessions.retain(|_, session| {
+            session.disconnected_at.map_or(true, |disconnected_at| {
+                disconnected_at.elapsed() < SESSION_TIMEOUT
+            })
+        });
+    }
+}
+
+struct ClientSession {
+    // Address of the current connection of the client, `None` if it is disconnected.
+    addr: Option<SocketAddr>,
+    // When the client disconnected, `None` while it is connected.
+    disconnected_at: Option<Instant>,
+    bucket: TokenBucket,
+}
+
+#[derive(Clone)]
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+    // Whether the last message was rejected, so the client was already told about it.
+    limited: bool,
+}
+
+impl TokenBucket {
+    fn new(rate_limit: &ClientRateLimit) -> Self {
+        Self {
+            tokens: f64::from(rate_limit.burst),
+            last_refill: Instant::now(),
+            limited: false,
+        }
+    }
+
+    fn is_refilled(&self, rate_limit: &ClientRateLimit) -> bool {
+        let rate = f64::from(rate_limit.messages_per_second);
+        let elapsed = self.last_refill.elapsed().as_secs_f64();
+        self.tokens + elapsed * rate >= f64::from(rate_limit.burst)
+    }
+
+    fn take(&mut self, rate_limit: &ClientRateLimit) -> RateCheck {
+        let rate = f64::from(rate_limit.messages_per_second);
+        let elapsed = self.last_refill.elapsed().as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(f64::from(rate_limit.burst));
+        self.last_refill = Instant::now();
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            self.limited = false;
+            return RateCheck::Accept;
+        }
+
+        if self.limited {
+            return RateCheck::Drop;
+        }
+
+        self.limited = true;
+        let retry_after = if rate > 0.0 {
+            Duration::from_secs_f64((1.0 - self.tokens) / rate)
+        } else {
+            Duration::from_secs(u64::max_value())
+        };
+
+        RateCheck::Reject(ClientRejection::RateLimited { retry_after })
+    }
+}
+
+fn handshake_payload(challenge: &Digest256) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(HANDSHAKE_DOMAIN.len() + challenge.len());
+    payload.extend_from_slice(HANDSHAKE_DOMAIN);
+    payload.extend_from_slice(challenge);
+    payload
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{rng, unwrap};
+    use rand::Rng;
+
+    fn gen_addr(rng: &mut MainRng) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], rng.gen()))
+    }
+
+    fn signature(message: &ClientWireMessage) -> signing::Signature {
+        match message {
+            ClientWireMessage::HandshakeResponse { signature, .. } => *signature,
+            _ => panic!("Unexpected message {:?}", message),
+        }
+    }
+
+    #[test]
+    fn handshake() {
+        let mut rng = rng::new();
+        let mut sessions = ClientSessions::default();
+        let client = ClientFullId::gen(&mut rng);
+        let addr = gen_addr(&mut rng);
+        let challenge: Digest256 = rng.gen();
+
+        sessions.insert_challenge(addr, challenge);
+        assert_eq!(sessions.client_at(&addr), None);
+
+        let response = client.handshake_response(&challenge);
+        assert!(sessions.authenticate(addr, *client.public_id(), &signature(&response)));
+        assert_eq!(sessions.client_at(&addr), Some(client.public_id()));
+        assert_eq!(sessions.addr_of(client.public_id()), Some(addr));
+
+        // The challenge can't be answered twice.
+        assert!(!sessions.authenticate(addr, *client.public_id(), &signature(&response)));
+    }
+
+    #[test]
+    fn reject_invalid_handshake() {
+        let mut rng = rng::new();
+        let mut sessions = ClientSessions::default();
+        let client = ClientFullId::gen(&mut rng);
+        let impostor = ClientFullId::gen(&mut rng);
+        let addr = gen_addr(&mut rng);
+        let challenge: Digest256 = rng.gen();
+
+        // Response without a challenge.
+        let response = client.handshake_response(&challenge);
+        assert!(!sessions.authenticate(addr, *client.public_id(), &signature(&response)));
+
+        // Response signed by another key.
+        sessions.insert_challenge(addr, challenge);
+        let response = impostor.handshake_response(&challenge);
+        assert!(!sessions.authenticate(addr, *client.public_id(), &signature(&response)));
+
+        // Response to another challenge.
+        sessions.insert_challenge(addr, challenge);
+        let response = client.handshake_response(&rng.gen());
+        assert!(!sessions.authenticate(addr, *client.public_id(), &signature(&response)));
+
+        assert_eq!(sessions.client_at(&addr), None);
+        assert_eq!(sessions.addr_of(client.public_id()), None);
+    }
+
+    #[test]
+    fn reconnect() {
+        let mut rng = rng::new();
+        let mut sessions = ClientSessions::default();
+        let client = ClientFullId::gen(&mut rng);
+        let old_addr = gen_addr(&mut rng);
+        let new_addr = gen_addr(&mut rng);
+
+        let challenge: Digest256 = rng.gen();
+        sessions.insert_challenge(old_addr, challenge);
+        let response = client.handshake_response(&challenge);
+        assert!(sessions.authenticate(old_addr, *client.public_id(), &signature(&response)));
+
+        sessions.disconnected(&old_addr);
+        assert_eq!(sessions.client_at(&old_addr), None);
+        assert_eq!(sessions.addr_of(client.public_id()), None);
+
+        let challenge: Digest256 = rng.gen();
+        sessions.insert_challenge(new_addr, challenge);
+        let response = client.handshake_response(&challenge);
+        assert!(sessions.authenticate(new_addr, *client.public_id(), &signature(&response)));
+        assert_eq!(sessions.addr_of(client.public_id()), Some(new_addr));
+
+        // A second connection of the same client replaces the first one.
+        let challenge: Digest256 = rng.gen();
+        sessions.insert_challenge(old_addr, challenge);
+        let response = client.handshake_response(&challenge);
+        assert!(sessions.authenticate(old_addr, *client.public_id(), &signature(&response)));
+        assert_eq!(sessions.addr_of(client.public_id()), Some(old_addr));
+        assert_eq!(sessions.client_at(&new_addr), None);
+    }
+
+    #[cfg(feature = "mock_base")]
+    #[test]
+    fn drop_expired_session() {
+        use fake_clock::FakeClock;
+
+        let mut rng = rng::new();
+        let mut sessions = ClientSessions::default();
+        let client = ClientFullId::gen(&mut rng);
+        let other_client = ClientFullId::gen(&mut rng);
+
+        let connect = |sessions: &mut ClientSessions, rng: &mut MainRng, client: &ClientFullId| {
+            let addr = gen_addr(rng);
+            let challenge: Digest256 = rng.gen();
+            sessions.insert_challenge(addr, challenge);
+            let response = client.handshake_response(&challenge);
+            assert!(sessions.authenticate(addr, *client.public_id(), &signature(&response)));
+            addr
+        };
+
+        let addr = connect(&mut sessions, &mut rng, &client);
+        let other_addr = connect(&mut sessions, &mut rng, &other_client);
+        sessions.disconnected(&addr);
+        assert!(sessions.sessions.contains_key(client.public_id()));
+
+        // Connected clients keep their session however long they stay connected.
+        FakeClock::advance_time(SESSION_TIMEOUT.as_secs() * 1000 + 1);
+        sessions.disconnected(&gen_addr(&mut rng));
+        assert!(!sessions.sessions.contains_key(client.public_id()));
+        assert!(sessions.sessions.contains_key(other_client.public_id()));
+        assert_eq!(
+            sessions.client_at(&other_addr),
+            Some(other_client.public_id())
+        );
+    }
+
+    #[test]
+    fn rate_limit() {
+        let mut rng = rng::new();
+        let mut sessions = ClientSessions::default();
+        sessions.set_rate_limit(Some(ClientRateLimit {
+            burst: 3,
+            messages_per_second: 1,
+        }));
+
+        let client = ClientFullId::gen(&mut rng);
+        let addr = gen_addr(&mut rng);
+        let challenge: Digest256 = rng.gen();
+        sessions.insert_challenge(addr, challenge);
+
+        // The tokens used before the handshake count towards the limit of the client.
+        assert_eq!(sessions.check_rate_limit(addr), RateCheck::Accept);
+        let response = client.handshake_response(&challenge);
+        assert!(sessions.authenticate(addr, *client.public_id(), &signature(&response)));
+
+        assert_eq!(sessions.check_rate_limit(addr), RateCheck::Accept);
+        assert_eq!(sessions.check_rate_limit(addr), RateCheck::Accept);
+        match sessions.check_rate_limit(addr) {
+            RateCheck::Reject(ClientRejection::RateLimited { retry_after }) => {
+                assert!(retry_after <= Duration::from_secs(1))
+            }
+            result => panic!("Unexpected result {:?}", result),
+        }
+        assert_eq!(sessions.check_rate_limit(addr), RateCheck::Drop);
+
+        // Reconnecting doesn't reset the limit.
+        let new_addr = gen_addr(&mut rng);
+        let challenge: Digest256 = rng.gen();
+        sessions.disconnected(&addr);
+        sessions.insert_challenge(new_addr, challenge);
+        let response = client.handshake_response(&challenge);
+        assert!(sessions.authenticate(new_addr, *client.public_id(), &signature(&response)));
+        assert_eq!(sessions.check_rate_limit(new_addr), RateCheck::Drop);
+
+        // Other clients have their own limit.
+        let other_addr = SocketAddr::from(([127, 0, 0, 2], rng.gen()));
+        assert_eq!(sessions.check_rate_limit(other_addr), RateCheck::Accept);
+
+        sessions.set_rate_limit(None);
+        assert_eq!(sessions.check_rate_limit(new_addr), RateCheck::Accept);
+    }
+
+    #[test]
+    fn unauthenticated_rate_limit_per_ip() {
+        let mut rng = rng::new();
+        let mut sessions = ClientSessions::default();
+        sessions.set_rate_limit(Some(ClientRateLimit {
+            burst: 2,
+            messages_per_second: 0,
+        }));
+
+        let addr = gen_addr(&mut rng);
+        let same_ip_addr = SocketAddr::new(addr.ip(), addr.port().wrapping_add(1));
+        let other_ip_addr = SocketAddr::from(([127, 0, 0, 2], addr.port()));
+
+        assert_eq!(sessions.check_rate_limit(addr), RateCheck::Accept);
+        sessions.disconnected(&addr);
+
+        // Reconnecting, even from another port, doesn't reset the limit.
+        assert_eq!(sessions.check_rate_limit(same_ip_addr), RateCheck::Accept);
+        match sessions.check_rate_limit(same_ip_addr) {
+            RateCheck::Reject(ClientRejection::RateLimited { .. }) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+
+        assert_eq!(sessions.check_rate_limit(other_ip_addr), RateCheck::Accept);
+    }
+
+    #[test]
+    fn wire_message_round_trip() {
+        let mut rng = rng::new();
+        let client = ClientFullId::gen(&mut rng);
+        let message = client.handshake_response(&rng.gen());
+
+        let bytes = unwrap!(message.to_bytes());
+        assert_eq!(unwrap!(ClientWireMessage::from_bytes(&bytes)), message);
+    }
+}
@@ -0,0 +1,164 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! End-to-end delivery acknowledgements of user messages.
+//!
+//! A message sent with `Node::send_message_with_ack` is sent as `Variant::UserMessageWithAck`.
+//! Its destination raises the usual `Event::MessageReceived` and sends a
+//! `Variant::MessageDeliveryAck` with the id of the message back to the source. For a section
+//! destination the ack is signed by the section, like any other section-sourced message. The
+//! sender raises `Event::MessageDelivered` when the ack arrives, or `Event::MessageDeliveryFailed`
+//! if it doesn't arrive within `DELIVERY_ACK_TIMEOUT`.
+//!
+//! An ack is accepted only from the destination of the message: the node itself for a node
+//! destination, or a section matching it for a section or prefix destination.
+
+use crate::{
+    location::{DstLocation, SrcLocation},
+    messages::MessageId,
+    time::Duration,
+};
+use std::collections::HashMap;
+
+/// Time to wait for the acknowledgement of a message before giving up on it.
+pub const DELIVERY_ACK_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// Messages we sent and are waiting to be acknowledged, with their destinations and the tokens of
+/// their timeouts.
+#[derive(Default)]
+pub struct PendingAcks {
+    pending: HashMap<MessageId, PendingAck>,
+    messages: HashMap<u64, MessageId>,
+}
+
+struct PendingAck {
+    dst: DstLocation,
+    timer_token: u64,
+}
+
+impl PendingAcks {
+    /// Starts waiting for the ack of the message sent to `dst`. Returns `false` if we are already
+    /// waiting for it.
+    pub fn insert(&mut self, id: MessageId, dst: DstLocation, timer_token: u64) -> bool {
+        if self.pending.contains_key(&id) {
+            return false;
+        }
+
+        let _ = self.pending.insert(id, PendingAck { dst, timer_token });
+        let _ = self.messages.insert(timer_token, id);
+        true
+    }
+
+    /// Handles an ack sent by `src`. Returns whether we were waiting for it from that source.
+    pub fn acknowledged(&mut self, id: &MessageId, src: &SrcLocation) -> bool {
+        match self.pending.get(id) {
+            Some(pending) if is_ack_source(&pending.dst, src) => (),
+            _ => return false,
+        }
+
+        if let Some(pending) = self.pending.remove(id) {
+            let _ = self.messages.remove(&pending.timer_token);
+        }
+        true
+    }
+
+    /// Handles a timeout. Returns the message whose ack didn't arrive in time, if any.
+    pub fn timed_out(&mut self, timer_token: u64) -> Option<MessageId> {
+        let id = self.messages.remove(&timer_token)?;
+        let _ = self.pending.remove(&id);
+        Some(id)
+    }
+}
+
+// Whether `src` can acknowledge a message sent to `dst`. Messages sent directly to a peer are
+// never sent with an ack.
+fn is_ack_source(dst: &DstLocation, src: &SrcLocation) -> bool {
+    match (dst, src) {
+        (DstLocation::Node(name), SrcLocation::Node(pub_id)) => pub_id.name() == name,
+        (DstLocation::Section(name), SrcLocation::Section(prefix)) => prefix.matches(name),
+        (DstLocation::Prefix(dst_prefix), SrcLocation::Section(prefix)) => {
+            prefix.is_compatible(dst_prefix)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        id::FullId,
+        messages::{Message, Variant},
+        rng, unwrap,
+        xor_space::{Prefix, XorName},
+    };
+    use rand::Rng;
+
+    fn message_id(content: &[u8]) -> MessageId {
+        let mut rng = rng::new();
+        let msg = unwrap!(Message::single_src(
+            &FullId::gen(&mut rng),
+            DstLocation::Direct,
+            Variant::UserMessage(content.to_vec()),
+        ));
+        unwrap!(msg.id())
+    }
+
+    #[test]
+    fn ack_before_timeout() {
+        let mut rng = rng::new();
+        let mut pending = PendingAcks::default();
+        let id = message_id(b"hello");
+        let dst_id = *FullId::gen(&mut rng).public_id();
+        let dst = DstLocation::Node(*dst_id.name());
+
+        assert!(pending.insert(id, dst, 1));
+        assert!(!pending.insert(id, dst, 2));
+        assert!(pending.acknowledged(&id, &SrcLocation::Node(dst_id)));
+        assert!(!pending.acknowledged(&id, &SrcLocation::Node(dst_id)));
+        assert_eq!(pending.timed_out(1), None);
+    }
+
+    #[test]
+    fn timeout_before_ack() {
+        let mut pending = PendingAcks::default();
+        let id0 = message_id(b"hello");
+        let id1 = message_id(b"world");
+        assert_ne!(id0, id1);
+        let dst = DstLocation::Prefix(Prefix::default());
+        let src = SrcLocation::Section(Prefix::default());
+
+        assert!(pending.insert(id0, dst, 1));
+        assert!(pending.insert(id1, dst, 2));
+        assert_eq!(pending.timed_out(1), Some(id0));
+        assert!(!pending.acknowledged(&id0, &src));
+        assert!(pending.acknowledged(&id1, &src));
+    }
+
+    #[test]
+    fn reject_ack_from_other_source() {
+        let mut rng = rng::new();
+        let mut pending = PendingAcks::default();
+        let id = message_id(b"hello");
+        let dst_id = *FullId::gen(&mut rng).public_id();
+        let other_id = *FullId::gen(&mut rng).public_id();
+        let prefix: Prefix<XorName> = unwrap!("0".parse());
+
+        assert!(pending.insert(id, DstLocation::Node(*dst_id.name()), 1));
+        assert!(!pending.acknowledged(&id, &SrcLocation::Node(other_id)));
+        assert!(!pending.acknowledged(&id, &SrcLocation::Section(Prefix::default())));
+        assert!(pending.acknowledged(&id, &SrcLocation::Node(dst_id)));
+
+        // A section destination must ack as a section, not as one of its nodes.
+        let name = prefix.substituted_in(rng.gen());
+        assert!(pending.insert(id, DstLocation::Section(name), 2));
+        assert!(!pending.acknowledged(&id, &SrcLocation::Node(dst_id)));
+        assert!(!pending.acknowledged(&id, &SrcLocation::Section(prefix.sibling())));
+        assert!(pending.acknowledged(&id, &SrcLocation::Section(prefix)));
+    }
+}
@@ -6,10 +6,15 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::{quic_p2p::QuicP2pError, xor_space::XorName};
-use bincode::ErrorKind;
+use crate::{
+    location::{DstLocation, SrcLocation},
+    xor_space::{Prefix, XorName},
+};
 use err_derive::Error;
-use std::sync::mpsc;
+use std::{
+    fmt::{self, Display, Formatter},
+    sync::mpsc,
+};
 
 /// The type returned by the routing message handling methods.
 pub type Result<T, E = RoutingError> = std::result::Result<T, E>;
@@ -22,24 +27,41 @@ pub enum RoutingError {
     BadLocation,
     #[error(display = "Failed signature check.")]
     FailedSignature,
-    #[error(display = "Cannot route.")]
-    CannotRoute,
-    #[error(display = "Network layer error.")]
-    Network(QuicP2pError),
+    #[error(
+        display = "Cannot route to {:?}, known prefixes: {:?}.",
+        dst,
+        known_prefixes
+    )]
+    CannotRoute {
+        dst: DstLocation,
+        known_prefixes: Vec<Prefix<XorName>>,
+    },
+    #[error(display = "Network layer error: {}", _0)]
+    #[from(ignore)]
+    Network(String),
     #[error(display = "The node is not in a state to handle the action.")]
     InvalidState,
     #[error(display = "Bincode error.")]
-    Bincode(Box<ErrorKind>),
+    Bincode(Box<bincode::ErrorKind>),
     #[error(display = "Peer not found.")]
     PeerNotFound(XorName),
+    #[error(display = "Client is not connected.")]
+    ClientNotConnected,
     #[error(display = "Invalid Source.")]
     InvalidSource,
-    #[error(display = "Content of a received message is inconsistent.")]
-    InvalidMessage,
+    #[error(
+        display = "Content of a {} message from {:?} is inconsistent.",
+        variant,
+        sender
+    )]
+    InvalidMessage {
+        variant: &'static str,
+        sender: SrcLocation,
+    },
     #[error(display = "A signed message's chain of proving sections is invalid.")]
     InvalidProvingSection,
-    #[error(display = "A signed message could not be trusted.")]
-    UntrustedMessage,
+    #[error(display = "A signed message could not be trusted: {}.", _0)]
+    UntrustedMessage(UntrustedProof),
     #[error(display = "A new SectionInfo is invalid.")]
     InvalidNewSectionInfo,
     #[error(display = "A Relocation is invalid.")]
@@ -48,4 +70,216 @@ pub enum RoutingError {
     InvalidElderDkgResult,
     #[error(display = "Error while trying to receive a message from a mpsc channel.")]
     MpscRecvError(mpsc::RecvError),
+    #[error(display = "Unsupported paused state format version {}.", _0)]
+    UnsupportedPausedStateVersion(u32),
+    #[error(display = "Unsupported key file format version {}.", _0)]
+    #[from(ignore)]
+    UnsupportedKeyFileVersion(u32),
+    #[error(display = "Unsupported section history format version {}.", _0)]
+    #[from(ignore)]
+    UnsupportedSectionHistoryVersion(u32),
+    #[error(display = "The section history is not a valid chain from its genesis.")]
+    InvalidSectionHistory,
+    #[error(display = "Wrong passphrase or corrupted key file.")]
+    InvalidKeyFile,
+    #[error(display = "I/O error.")]
+    Io(std::io::Error),
+    #[error(
+        display = "Incompatible protocol version: ours is {}, theirs is {}.",
+        ours,
+        theirs
+    )]
+    IncompatibleProtocolVersion { ours: u16, theirs: u16 },
+    #[error(display = "Compressed message is malformed.")]
+    MalformedCompression,
+    #[error(display = "The priority of the message doesn't match its content.")]
+    WrongPriority,
+    #[error(display = "The outbound queues are full.")]
+    Backpressure,
+    #[error(
+        display = "The node was paused with the {} policy {:?}, not {:?}.",
+        kind,
+        paused,
+        resumed
+    )]
+    PolicyMismatch {
+        kind: &'static str,
+        paused: String,
+        resumed: String,
+    },
+    #[error(
+        display = "The message has {} bytes, more than the maximum of {}.",
+        size,
+        max
+    )]
+    MessageTooLarge { size: usize, max: usize },
+}
+
+impl RoutingError {
+    /// Returns whether the operation which failed with this error may succeed if retried.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::CannotRoute { .. }
+            | Self::Network(_)
+            | Self::InvalidState
+            | Self::PeerNotFound(_)
+            | Self::ClientNotConnected
+            | Self::Backpressure => ErrorKind::Transient,
+            Self::UntrustedMessage(proof) => {
+                if proof.too_new {
+                    ErrorKind::Transient
+                } else {
+                    ErrorKind::Fatal
+                }
+            }
+            // Listed one by one, so that a new variant has to be classified explicitly.
+            Self::BadLocation
+            | Self::FailedSignature
+            | Self::Bincode(_)
+            | Self::InvalidSource
+            | Self::InvalidMessage { .. }
+            | Self::InvalidProvingSection
+            | Self::InvalidNewSectionInfo
+            | Self::InvalidRelocation
+            | Self::InvalidElderDkgResult
+            | Self::MpscRecvError(_)
+            | Self::UnsupportedPausedStateVersion(_)
+            | Self::UnsupportedKeyFileVersion(_)
+            | Self::UnsupportedSectionHistoryVersion(_)
+            | Self::InvalidSectionHistory
+            | Self::InvalidKeyFile
+            | Self::Io(_)
+            | Self::IncompatibleProtocolVersion { .. }
+            | Self::MalformedCompression
+            | Self::PolicyMismatch { .. }
+            | Self::MessageTooLarge { .. } => ErrorKind::Fatal,
+        }
+    }
+
+    /// Shorthand for `self.kind() == ErrorKind::Transient`.
+    pub fn is_transient(&self) -> bool {
+        self.kind() == ErrorKind::Transient
+    }
+}
+
+/// Classification of a `RoutingError`, telling the caller whether to retry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The operation may succeed if retried later, e.g. once the node joined its section, learnt
+    /// about more sections or reconnected to the peer.
+    Transient,
+    /// The operation will fail again if retried.
+    Fatal,
+}
+
+/// Details of a section proof chain which could not be trusted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UntrustedProof {
+    /// Prefix of the latest key of the proof.
+    pub prefix: Prefix<XorName>,
+    /// Version of the first key of the proof.
+    pub first_version: u64,
+    /// Version of the latest key of the proof.
+    pub last_version: u64,
+    /// Whether the proof starts after the latest key we know of that section, as opposed to being
+    /// invalid. Such a proof might be trusted once we learn about the newer keys.
+    pub too_new: bool,
+}
+
+impl Display for UntrustedProof {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "{} proof for {:?} v{}..=v{}",
+            if self.too_new { "too new" } else { "invalid" },
+            self.prefix,
+            self.first_version,
+            self.last_version
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{id::FullId, rng, unwrap};
+
+    fn untrusted(too_new: bool) -> RoutingError {
+        RoutingError::UntrustedMessage(UntrustedProof {
+            prefix: unwrap!("01".parse()),
+            first_version: 3,
+            last_version: 5,
+            too_new,
+        })
+    }
+
+    #[test]
+    fn classify_errors() {
+        let transient = vec![
+            RoutingError::CannotRoute {
+                dst: DstLocation::Direct,
+                known_prefixes: vec![],
+            },
+            RoutingError::Network("connection lost".to_string()),
+            RoutingError::InvalidState,
+            RoutingError::PeerNotFound(XorName::default()),
+            RoutingError::ClientNotConnected,
+            RoutingError::Backpressure,
+            untrusted(true),
+        ];
+        for error in transient {
+            assert_eq!(error.kind(), ErrorKind::Transient, "{:?}", error);
+            assert!(error.is_transient());
+        }
+
+        let fatal = vec![
+            RoutingError::FailedSignature,
+            RoutingError::InvalidProvingSection,
+            RoutingError::MalformedCompression,
+            RoutingError::IncompatibleProtocolVersion { ours: 3, theirs: 0 },
+            RoutingError::MessageTooLarge { size: 2, max: 1 },
+            untrusted(false),
+        ];
+        for error in fatal {
+            assert_eq!(error.kind(), ErrorKind::Fatal, "{:?}", error);
+            assert!(!error.is_transient());
+        }
+    }
+
+    #[test]
+    fn display_context() {
+        let error = RoutingError::CannotRoute {
+            dst: DstLocation::Section(XorName::default()),
+            known_prefixes: vec![unwrap!("0".parse()), unwrap!("10".parse())],
+        };
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "Cannot route to {:?}, known prefixes: [Prefix(0), Prefix(10)].",
+                DstLocation::Section(XorName::default())
+            )
+        );
+
+        let sender = SrcLocation::Node(*FullId::gen(&mut rng::new()).public_id());
+        let error = RoutingError::InvalidMessage {
+            variant: "NodeApproval",
+            sender,
+        };
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "Content of a NodeApproval message from {:?} is inconsistent.",
+                sender
+            )
+        );
+
+        assert_eq!(
+            untrusted(true).to_string(),
+            "A signed message could not be trusted: too new proof for Prefix(01) v3..=v5."
+        );
+        assert_eq!(
+            untrusted(false).to_string(),
+            "A signed message could not be trusted: invalid proof for Prefix(01) v3..=v5."
+        );
+    }
 }
@@ -7,11 +7,16 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use crate::{
+    client::{ClientId, ClientRejection},
     location::{DstLocation, SrcLocation},
+    messages::MessageId,
     xor_space::{Prefix, XorName},
 };
 use hex_fmt::HexFmt;
-use std::fmt::{self, Debug, Formatter};
+use std::{
+    fmt::{self, Debug, Formatter},
+    net::SocketAddr,
+};
 
 /// An Event raised as node complete joining
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -37,6 +42,9 @@ pub enum Event {
     Connected(Connected),
     /// Received a message.
     MessageReceived {
+        /// The id of the message. The same as the one returned by `Node::send_message` to the
+        /// sender, so it can be used to correlate requests and responses or to drop duplicates.
+        id: MessageId,
         /// The content of the message.
         content: Vec<u8>,
         /// The source location that sent the message.
@@ -56,6 +64,34 @@ pub enum Event {
     RestartRequired,
     /// Startup failed - terminate.
     Terminated,
+    /// Our own section has merged with its sibling, resulting in the included `Prefix` for our
+    /// new section.
+    SectionMerged(Prefix<XorName>),
+    /// Received a message from an authenticated client.
+    ClientMessage {
+        /// The client that sent the message.
+        client: ClientId,
+        /// The content of the message.
+        content: Vec<u8>,
+    },
+    /// Dropped a message from a client. The client has been sent the same rejection. Raised once
+    /// per episode of the client exceeding its rate limit, not for every dropped message.
+    ClientRejected {
+        /// The client, `None` if it hasn't completed the handshake yet.
+        client: Option<ClientId>,
+        /// Address of the client connection.
+        peer_addr: SocketAddr,
+        /// Why the message was dropped.
+        rejection: ClientRejection,
+    },
+    /// A message sent with `Node::send_message_with_ack` has been delivered to its destination.
+    MessageDelivered(MessageId),
+    /// The delivery of a message sent with `Node::send_message_with_ack` wasn't acknowledged in
+    /// time. The message might still have been delivered, but the ack got lost.
+    MessageDeliveryFailed(MessageId),
+    /// Sending a user message failed with `RoutingError::Backpressure` and the outbound queues
+    /// have since drained to half their capacity, so sending can resume.
+    ReadyToSend,
 }
 
 impl Debug for Event {
@@ -65,12 +101,14 @@ impl Debug for Event {
                 write!(formatter, "Event::Connected({:?})", connect_type)
             }
             Self::MessageReceived {
+                ref id,
                 ref content,
                 ref src,
                 ref dst,
             } => write!(
                 formatter,
-                "Event::MessageReceived {{ content: \"{:<8}\", src: {:?}, dst: {:?} }}",
+                "Event::MessageReceived {{ id: {:?}, content: \"{:<8}\", src: {:?}, dst: {:?} }}",
+                id,
                 HexFmt(content),
                 src,
                 dst
@@ -85,6 +123,34 @@ impl Debug for Event {
             }
             Self::RestartRequired => write!(formatter, "Event::RestartRequired"),
             Self::Terminated => write!(formatter, "Event::Terminated"),
+            Self::SectionMerged(ref prefix) => {
+                write!(formatter, "Event::SectionMerged({:?})", prefix)
+            }
+            Self::ClientMessage {
+                ref client,
+                ref content,
+            } => write!(
+                formatter,
+                "Event::ClientMessage {{ client: {:?}, content: \"{:<8}\" }}",
+                client,
+                HexFmt(content)
+            ),
+            Self::ClientRejected {
+                ref client,
+                peer_addr,
+                ref rejection,
+            } => write!(
+                formatter,
+                "Event::ClientRejected {{ client: {:?}, peer_addr: {}, rejection: {:?} }}",
+                client, peer_addr, rejection
+            ),
+            Self::MessageDelivered(ref id) => {
+                write!(formatter, "Event::MessageDelivered({:?})", id)
+            }
+            Self::MessageDeliveryFailed(ref id) => {
+                write!(formatter, "Event::MessageDeliveryFailed({:?})", id)
+            }
+            Self::ReadyToSend => write!(formatter, "Event::ReadyToSend"),
         }
     }
 }
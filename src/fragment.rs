@@ -0,0 +1,512 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Fragmentation of large user messages.
+//!
+//! The content of a user message larger than the maximum fragment size is split into fragments,
+//! each sent as a separate `Variant::UserMessageFragment`. The recipient collects the fragments
+//! and, once it has all of them and they match the hash of the whole content, raises a single
+//! `Event::MessageReceived`. The id of the message is the id of its first fragment. Sets of
+//! fragments which are not complete within `FRAGMENT_TIMEOUT` are dropped.
+//!
+//! To bound the memory a sender can make us hold, the recipient rejects the fragments larger than
+//! its own maximum fragment size, the messages which would be larger than `MAX_MESSAGE_SIZE` and
+//! the fragments of new messages from a source which already has
+//! `MAX_INCOMPLETE_MESSAGES_PER_SRC` incomplete ones. As the sources are not limited, it also
+//! drops the oldest incomplete messages whenever all of them together would exceed
+//! `MAX_INCOMPLETE_MESSAGES` or `MAX_INCOMPLETE_SIZE`.
+
+use crate::{
+    crypto::{sha3_256, Digest256},
+    error::{Result, RoutingError},
+    location::SrcLocation,
+    messages::MessageId,
+    time::Duration,
+    timer::Timer,
+};
+use hex_fmt::HexFmt;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::{self, Debug, Formatter},
+};
+
+/// Default maximum size of the content of a user message, in bytes, above which it is sent in
+/// fragments.
+pub const DEFAULT_MAX_FRAGMENT_SIZE: usize = 256 * 1024;
+/// Time the recipient waits for all the fragments of a message before dropping them.
+pub const FRAGMENT_TIMEOUT: Duration = Duration::from_secs(120);
+/// Maximum size of the content of a user message, in bytes.
+pub const MAX_MESSAGE_SIZE: usize = 32 * 1024 * 1024;
+/// Maximum number of fragmented messages from a single source being reassembled at the same time.
+pub const MAX_INCOMPLETE_MESSAGES_PER_SRC: usize = 4;
+/// Maximum number of fragmented messages from all sources being reassembled at the same time.
+pub const MAX_INCOMPLETE_MESSAGES: usize = 64;
+/// Maximum total size of the fragments received of the messages being reassembled, in bytes.
+pub const MAX_INCOMPLETE_SIZE: usize = 256 * 1024 * 1024;
+
+const VARIANT_NAME: &str = "UserMessageFragment";
+
+/// Part of the content of a user message.
+#[derive(Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct UserMessageFragment {
+    /// Hash of the whole content. Identifies the fragments of the same message and checks the
+    /// content once reassembled.
+    pub hash: Digest256,
+    /// Position of this fragment.
+    pub index: u32,
+    /// Number of fragments of the message.
+    pub count: u32,
+    /// Whether the recipient should acknowledge the delivery of the message.
+    pub ack: bool,
+    /// The part of the content.
+    pub data: Vec<u8>,
+}
+
+impl Debug for UserMessageFragment {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "UserMessageFragment {{ hash: {:10}, index: {}/{}, ack: {}, len: {} }}",
+            HexFmt(&self.hash),
+            self.index,
+            self.count,
+            self.ack,
+            self.data.len()
+        )
+    }
+}
+
+/// Splits the content into fragments of at most `max_fragment_size` bytes.
+pub fn split(content: &[u8], max_fragment_size: usize, ack: bool) -> Vec<UserMessageFragment> {
+    let hash = sha3_256(content);
+    let chunks: Vec<_> = content.chunks(max_fragment_size.max(1)).collect();
+    let count = chunks.len() as u32;
+
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(index, data)| UserMessageFragment {
+            hash,
+            index: index as u32,
+            count,
+            ack,
+            data: data.to_vec(),
+        })
+        .collect()
+}
+
+/// A user message put back together from its fragments.
+#[derive(Debug)]
+pub struct ReassembledMessage {
+    /// Id of the first fragment, which is the id of the whole message.
+    pub id: MessageId,
+    /// The whole content.
+    pub content: Vec<u8>,
+    /// Whether the sender asked for the delivery to be acknowledged.
+    pub ack: bool,
+}
+
+type FragmentSetKey = (SrcLocation, Digest256);
+
+struct FragmentSet {
+    count: u32,
+    ack: bool,
+    first_id: Option<MessageId>,
+    fragments: BTreeMap<u32, Vec<u8>>,
+    // Total size of the fragments.
+    size: usize,
+    // Order in which the sets were started, the oldest is dropped first.
+    seq: u64,
+    timer_token: u64,
+}
+
+/// Fragments received so far, grouped by the message they belong to.
+pub struct Reassembler {
+    max_fragment_size: usize,
+    max_incomplete_messages: usize,
+    max_incomplete_size: usize,
+    sets: HashMap<FragmentSetKey, FragmentSet>,
+    timers: HashMap<u64, FragmentSetKey>,
+    // Total size of the fragments of all the sets.
+    size: usize,
+    next_seq: u64,
+}
+
+impl Default for Reassembler {
+    fn default() -> Self {
+        Self {
+            max_fragment_size: DEFAULT_MAX_FRAGMENT_SIZE,
+            max_incomplete_messages: MAX_INCOMPLETE_MESSAGES,
+            max_incomplete_size: MAX_INCOMPLETE_SIZE,
+            sets: HashMap::new(),
+            timers: HashMap::new(),
+            size: 0,
+            next_seq: 0,
+        }
+    }
+}
+
+impl Reassembler {
+    /// Maximum size of the content of the user messages we send unfragmented.
+    pub fn max_fragment_size(&self) -> usize {
+        self.max_fragment_size
+    }
+
+    pub fn set_max_fragment_size(&mut self, max_fragment_size: usize) {
+        self.max_fragment_size = max_fragment_size.max(1);
+    }
+
+    /// Adds a fragment received from `src` in a message with the given `id`. Returns the whole
+    /// message once all its fragments have been received.
+    pub fn insert(
+        &mut self,
+        src: SrcLocation,
+        id: MessageId,
+        fragment: UserMessageFragment,
+        timer: &Timer,
+    ) -> Result<Option<ReassembledMessage>> {
+        let invalid = || RoutingError::InvalidMessage {
+            variant: VARIANT_NAME,
+            sender: src,
+        };
+
+        if fragment.index >= fragment.count
+            || fragment.count as usize > self.max_fragment_count()
+            || fragment.data.len() > self.max_fragment_size
+            || fragment.data.len() > self.max_incomplete_size
+        {
+            return Err(invalid());
+        }
+
+        let key = (src, fragment.hash);
+        if !self.sets.contains_key(&key)
+            && self
+                .sets
+                .keys()
+                .filter(|(other_src, _)| *other_src == src)
+                .count()
+                >= MAX_INCOMPLETE_MESSAGES_PER_SRC
+        {
+            return Err(invalid());
+        }
+
+        if let Some(set) = self.sets.get(&key) {
+            if set.count != fragment.count
+                || set.ack != fragment.ack
+                || set.fragments.contains_key(&fragment.index)
+            {
+                return Err(invalid());
+            }
+        } else {
+            if self.sets.len() >= self.max_incomplete_messages {
+                let _ = self.remove_oldest(&key);
+            }
+
+            let timer_token = timer.schedule(FRAGMENT_TIMEOUT);
+            let _ = self.timers.insert(timer_token, key);
+            let _ = self.sets.insert(
+                key,
+                FragmentSet {
+                    count: fragment.count,
+                    ack: fragment.ack,
+                    first_id: None,
+                    fragments: BTreeMap::new(),
+                    size: 0,
+                    seq: self.next_seq,
+                    timer_token,
+                },
+            );
+            self.next_seq += 1;
+        }
+
+        while self.size + fragment.data.len() > self.max_incomplete_size {
+            if !self.remove_oldest(&key) {
+                // The message alone is too large to keep.
+                let _ = self.remove(&key);
+                return Err(invalid());
+            }
+        }
+
+        let set = self.sets.get_mut(&key).ok_or_else(invalid)?;
+        if fragment.index == 0 {
+            set.first_id = Some(id);
+        }
+
+        set.size += fragment.data.len();
+        self.size += fragment.data.len();
+        let _ = set.fragments.insert(fragment.index, fragment.data);
+        if set.fragments.len() < set.count as usize {
+            return Ok(None);
+        }
+
+        let set = self.remove(&key).ok_or_else(invalid)?;
+        let id = set.first_id.ok_or_else(invalid)?;
+        let content: Vec<u8> = set
+            .fragments
+            .into_iter()
+            .flat_map(|(_, data)| data)
+            .collect();
+        if sha3_256(&content) != fragment.hash {
+            return Err(invalid());
+        }
+
+        Ok(Some(ReassembledMessage {
+            id,
+            content,
+            ack: set.ack,
+        }))
+    }
+
+    /// Handles a timeout. If it was the timeout of an incomplete set of fragments, drops it and
+    /// returns its source.
+    pub fn handle_timeout(&mut self, timer_token: u64) -> Option<SrcLocation> {
+        let key = *self.timers.get(&timer_token)?;
+        let _ = self.remove(&key);
+        Some(key.0)
+    }
+
+    // Drops the oldest set other than the one with the given key, to make room for it. Returns
+    // whether there was one.
+    fn remove_oldest(&mut self, keep: &FragmentSetKey) -> bool {
+        let oldest = self
+            .sets
+            .iter()
+            .filter(|(key, _)| *key != keep)
+            .min_by_key(|(_, set)| set.seq)
+            .map(|(key, _)| *key);
+
+        if let Some(key) = oldest {
+            debug!("Dropping incomplete message from {:?} to make room.", key.0);
+            let _ = self.remove(&key);
+            true
+        } else {
+            false
+        }
+    }
+
+    // Number of fragments of a message of `MAX_MESSAGE_SIZE` bytes.
+    fn max_fragment_count(&self) -> usize {
+        (MAX_MESSAGE_SIZE + self.max_fragment_size - 1) / self.max_fragment_size
+    }
+
+    fn remove(&mut self, key: &FragmentSetKey) -> Option<FragmentSet> {
+        let set = self.sets.remove(key)?;
+        let _ = self.timers.remove(&set.timer_token);
+        self.size -= set.size;
+        Some(set)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        id::FullId,
+        location::DstLocation,
+        messages::{Message, Variant},
+        rng::{self, MainRng},
+        unwrap,
+        xor_space::XorName,
+    };
+    use crossbeam_channel as mpmc;
+    use rand::{distributions::Standard, Rng};
+
+    #[test]
+    fn split_and_reassemble() {
+        let mut rng = rng::new();
+        let timer = Timer::new(mpmc::unbounded().0);
+        let mut reassembler = Reassembler::default();
+        let src = SrcLocation::Section(Default::default());
+
+        let content: Vec<u8> = (&mut rng).sample_iter(Standard).take(1000).collect();
+        let fragments = split(&content, 300, true);
+        assert_eq!(fragments.len(), 4);
+
+        let ids: Vec<_> = fragments
+            .iter()
+            .map(|fragment| message_id(&mut rng, fragment))
+            .collect();
+
+        // The fragments can arrive in any order.
+        let mut result = None;
+        for index in &[2, 0, 3, 1] {
+            assert!(result.is_none());
+            result =
+                unwrap!(reassembler.insert(src, ids[*index], fragments[*index].clone(), &timer));
+        }
+
+        let msg = unwrap!(result);
+        assert_eq!(msg.id, ids[0]);
+        assert_eq!(msg.content, content);
+        assert!(msg.ack);
+        assert!(reassembler.sets.is_empty());
+        assert!(reassembler.timers.is_empty());
+    }
+
+    #[test]
+    fn reject_invalid_fragments() {
+        let mut rng = rng::new();
+        let timer = Timer::new(mpmc::unbounded().0);
+        let mut reassembler = Reassembler::default();
+        let src = SrcLocation::Section(Default::default());
+
+        let content: Vec<u8> = (&mut rng).sample_iter(Standard).take(100).collect();
+        let mut fragments = split(&content, 60, false);
+        assert_eq!(fragments.len(), 2);
+
+        let mut out_of_range = fragments[0].clone();
+        out_of_range.index = out_of_range.count;
+        let id = message_id(&mut rng, &out_of_range);
+        assert!(reassembler.insert(src, id, out_of_range, &timer).is_err());
+
+        let mut too_many = fragments[0].clone();
+        too_many.count = reassembler.max_fragment_count() as u32 + 1;
+        let id = message_id(&mut rng, &too_many);
+        assert!(reassembler.insert(src, id, too_many, &timer).is_err());
+
+        reassembler.set_max_fragment_size(50);
+        let id = message_id(&mut rng, &fragments[0]);
+        assert!(reassembler
+            .insert(src, id, fragments[0].clone(), &timer)
+            .is_err());
+        reassembler.set_max_fragment_size(60);
+
+        // A fragment can't be replaced by another one with the same index.
+        let id = message_id(&mut rng, &fragments[0]);
+        assert!(unwrap!(reassembler.insert(src, id, fragments[0].clone(), &timer)).is_none());
+        assert!(reassembler
+            .insert(src, id, fragments[0].clone(), &timer)
+            .is_err());
+        let _ = reassembler.remove(&(src, fragments[0].hash));
+
+        // Tampered data doesn't match the hash of the content.
+        fragments[1].data[0] ^= 1;
+        for fragment in fragments {
+            let id = message_id(&mut rng, &fragment);
+            match reassembler.insert(src, id, fragment, &timer) {
+                Ok(None) | Err(RoutingError::InvalidMessage { .. }) => (),
+                result => panic!("Unexpected result: {:?}", result),
+            }
+        }
+        assert!(reassembler.sets.is_empty());
+    }
+
+    #[test]
+    fn limit_incomplete_sets_per_src() {
+        let mut rng = rng::new();
+        let timer = Timer::new(mpmc::unbounded().0);
+        let mut reassembler = Reassembler::default();
+        let src = SrcLocation::Section(Default::default());
+        let other_src = SrcLocation::Node(*FullId::gen(&mut rng).public_id());
+
+        let mut first_fragment = || {
+            let content: Vec<u8> = (&mut rng).sample_iter(Standard).take(100).collect();
+            let fragment = split(&content, 60, false).swap_remove(0);
+            let id = message_id(&mut rng, &fragment);
+            (id, fragment)
+        };
+
+        for _ in 0..MAX_INCOMPLETE_MESSAGES_PER_SRC {
+            let (id, fragment) = first_fragment();
+            assert!(unwrap!(reassembler.insert(src, id, fragment, &timer)).is_none());
+        }
+
+        let (id, fragment) = first_fragment();
+        assert!(reassembler
+            .insert(src, id, fragment.clone(), &timer)
+            .is_err());
+
+        // Other sources are not affected.
+        assert!(unwrap!(reassembler.insert(other_src, id, fragment, &timer)).is_none());
+    }
+
+    #[test]
+    fn drop_oldest_sets_over_global_limits() {
+        let mut rng = rng::new();
+        let timer = Timer::new(mpmc::unbounded().0);
+        let mut reassembler = Reassembler::default();
+        reassembler.max_incomplete_messages = 3;
+        reassembler.max_incomplete_size = 200;
+
+        let mut insert_first_fragment = |reassembler: &mut Reassembler| {
+            let src = SrcLocation::Node(*FullId::gen(&mut rng).public_id());
+            let content: Vec<u8> = (&mut rng).sample_iter(Standard).take(120).collect();
+            let fragment = split(&content, 60, false).swap_remove(0);
+            let id = message_id(&mut rng, &fragment);
+            let key = (src, fragment.hash);
+            assert!(unwrap!(reassembler.insert(src, id, fragment, &timer)).is_none());
+            key
+        };
+
+        // Each new source pushes out the oldest set once there are too many.
+        let keys: Vec<_> = (0..4)
+            .map(|_| insert_first_fragment(&mut reassembler))
+            .collect();
+        assert_eq!(reassembler.sets.len(), 3);
+        assert!(!reassembler.sets.contains_key(&keys[0]));
+        assert_eq!(reassembler.timers.len(), 3);
+        assert_eq!(reassembler.size, 180);
+
+        // Too many bytes push out the oldest sets as well.
+        reassembler.max_incomplete_size = 130;
+        let key = insert_first_fragment(&mut reassembler);
+        assert!(!reassembler.sets.contains_key(&keys[1]));
+        assert!(!reassembler.sets.contains_key(&keys[2]));
+        assert!(reassembler.sets.contains_key(&keys[3]));
+        assert!(reassembler.sets.contains_key(&key));
+        assert_eq!(reassembler.size, 120);
+
+        // A message too large on its own is rejected.
+        reassembler.max_incomplete_size = 50;
+        let src = SrcLocation::Section(Default::default());
+        let content: Vec<u8> = (&mut rng).sample_iter(Standard).take(80).collect();
+        let fragments = split(&content, 40, false);
+        let id = message_id(&mut rng, &fragments[0]);
+        assert!(unwrap!(reassembler.insert(src, id, fragments[0].clone(), &timer)).is_none());
+        let id = message_id(&mut rng, &fragments[1]);
+        assert!(reassembler
+            .insert(src, id, fragments[1].clone(), &timer)
+            .is_err());
+        assert!(!reassembler.sets.contains_key(&(src, fragments[0].hash)));
+        assert_eq!(reassembler.size, 0);
+    }
+
+    #[test]
+    fn drop_incomplete_set_on_timeout() {
+        let mut rng = rng::new();
+        let timer = Timer::new(mpmc::unbounded().0);
+        let mut reassembler = Reassembler::default();
+        let src = SrcLocation::Section(Default::default());
+
+        let content: Vec<u8> = (&mut rng).sample_iter(Standard).take(100).collect();
+        let fragments = split(&content, 60, false);
+        let id = message_id(&mut rng, &fragments[0]);
+        assert!(unwrap!(reassembler.insert(src, id, fragments[0].clone(), &timer)).is_none());
+
+        let timer_token = *unwrap!(reassembler.timers.keys().next());
+        assert_eq!(
+            reassembler.handle_timeout(timer_token.wrapping_add(1)),
+            None
+        );
+        assert_eq!(reassembler.handle_timeout(timer_token), Some(src));
+        assert!(reassembler.sets.is_empty());
+
+        // The remaining fragment starts a new set.
+        let id = message_id(&mut rng, &fragments[1]);
+        assert!(unwrap!(reassembler.insert(src, id, fragments[1].clone(), &timer)).is_none());
+    }
+
+    fn message_id(rng: &mut MainRng, fragment: &UserMessageFragment) -> MessageId {
+        let msg = unwrap!(Message::single_src(
+            &FullId::gen(rng),
+            DstLocation::Node(XorName::default()),
+            Variant::UserMessageFragment(fragment.clone()),
+        ));
+        unwrap!(msg.id())
+    }
+}
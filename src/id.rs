@@ -8,22 +8,37 @@
 
 use crate::{
     crypto::{encryption, signing},
+    error::{Result, RoutingError},
     parsec,
     rng::{self, MainRng, RngCompat},
     xor_space::XorName,
 };
 use bincode::{deserialize, serialize};
+use chacha20poly1305::{
+    aead::{Aead, NewAead},
+    ChaCha20Poly1305, Key, Nonce,
+};
 use rand_crypto::Rng as _;
+use scrypt::ScryptParams;
 use serde::{de::Deserialize, Deserializer, Serialize, Serializer};
 use std::{
     cmp::Ordering,
     fmt::{self, Debug, Display, Formatter},
+    fs::{self, OpenOptions},
     hash::{Hash, Hasher},
+    io::Write,
     net::{Ipv6Addr, SocketAddr},
     ops::RangeInclusive,
+    path::Path,
     rc::Rc,
 };
 
+/// Version of the key file format written by `FullId::to_bytes`. Bump it when the format changes
+/// and keep reading the old versions in `FullId::from_bytes`. Version 1 was never released.
+const KEY_FILE_FORMAT_VERSION: u32 = 2;
+/// Scrypt cost parameters (log2 of N, r, p) used to derive the encryption key from a passphrase.
+const KEY_DERIVATION_PARAMS: (u8, u32, u32) = (15, 8, 1);
+
 /// Network identity component containing name, and public and private keys.
 #[derive(Clone)]
 pub struct FullId {
@@ -97,6 +112,92 @@ impl FullId {
             &self.secret_keys.signing,
         )
     }
+
+    /// Serialises the secret keys so the identity can be restored with `from_bytes`. If
+    /// `passphrase` is given, the keys are encrypted with a key derived from it.
+    pub fn to_bytes(&self, passphrase: Option<&str>) -> Result<Vec<u8>> {
+        let keys = self.secret_keys_to_bytes()?;
+        let key_file = match passphrase {
+            None => KeyFile::Plain(keys),
+            Some(passphrase) => KeyFile::encrypt(&keys, passphrase)?,
+        };
+
+        Ok(serialize(&(KEY_FILE_FORMAT_VERSION, key_file))?)
+    }
+
+    /// Restores the identity from the bytes produced by `to_bytes`. Fails with
+    /// `RoutingError::InvalidKeyFile` if the passphrase is missing or wrong, or if one is given
+    /// but the keys are not encrypted.
+    pub fn from_bytes(bytes: &[u8], passphrase: Option<&str>) -> Result<Self> {
+        let version: u32 = deserialize(bytes)?;
+        let key_file = match version {
+            KEY_FILE_FORMAT_VERSION => deserialize::<(u32, KeyFile)>(bytes)?.1,
+            _ => return Err(RoutingError::UnsupportedKeyFileVersion(version)),
+        };
+
+        let keys = match (key_file, passphrase) {
+            (KeyFile::Plain(keys), None) => keys,
+            (key_file @ KeyFile::Encrypted { .. }, Some(passphrase)) => {
+                key_file.decrypt(passphrase)?
+            }
+            (KeyFile::Plain(_), Some(_)) | (KeyFile::Encrypted { .. }, None) => {
+                return Err(RoutingError::InvalidKeyFile)
+            }
+        };
+
+        Self::from_secret_keys_bytes(&keys)
+    }
+
+    /// Serialises the secret keys, unencrypted. Only for the state of a paused node and the key
+    /// files, which decide whether to encrypt them.
+    pub(crate) fn secret_keys_to_bytes(&self) -> Result<Vec<u8>> {
+        Ok(serialize(&(
+            &self.secret_keys.signing,
+            encryption::SerdeSecret(self.secret_keys.encryption.clone()),
+        ))?)
+    }
+
+    /// Restores the identity from the secret keys serialised by `secret_keys_to_bytes`.
+    pub(crate) fn from_secret_keys_bytes(bytes: &[u8]) -> Result<Self> {
+        let (secret_signing_key, secret_encryption_key): (
+            signing::SecretKey,
+            encryption::SerdeSecret<encryption::SecretKey>,
+        ) = deserialize(bytes)?;
+        let secret_encryption_key = secret_encryption_key.into_inner();
+
+        let public_signing_key = signing::PublicKey::from(&secret_signing_key);
+        let public_encryption_key = secret_encryption_key.public_key();
+
+        Ok(Self {
+            public_id: PublicId::new(public_signing_key, public_encryption_key),
+            secret_keys: Rc::new(SecretKeys {
+                signing: secret_signing_key,
+                encryption: secret_encryption_key,
+            }),
+        })
+    }
+
+    /// Writes the identity to the file at `path` (see `to_bytes`). On unix the file is only
+    /// readable by its owner.
+    pub fn save_to_file(&self, path: &Path, passphrase: Option<&str>) -> Result<()> {
+        let bytes = self.to_bytes(passphrase)?;
+
+        let mut options = OpenOptions::new();
+        let _ = options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            let _ = options.mode(0o600);
+        }
+
+        options.open(path)?.write_all(&bytes)?;
+        Ok(())
+    }
+
+    /// Reads the identity from the file at `path` written by `save_to_file`.
+    pub fn load_from_file(path: &Path, passphrase: Option<&str>) -> Result<Self> {
+        Self::from_bytes(&fs::read(path)?, passphrase)
+    }
 }
 
 impl parsec::SecretId for FullId {
@@ -129,6 +230,62 @@ struct SecretKeys {
     encryption: encryption::SecretKey,
 }
 
+// Serialised secret keys, possibly encrypted with a passphrase.
+//
+// The encryption key is derived from the passphrase with scrypt and a random salt, and the keys
+// are encrypted and authenticated with ChaCha20-Poly1305 under a random nonce.
+#[derive(Serialize, Deserialize)]
+enum KeyFile {
+    Plain(Vec<u8>),
+    Encrypted {
+        salt: [u8; 16],
+        nonce: [u8; 12],
+        ciphertext: Vec<u8>,
+    },
+}
+
+impl KeyFile {
+    fn encrypt(plaintext: &[u8], passphrase: &str) -> Result<Self> {
+        let mut rng = RngCompat(rng::new());
+        let salt: [u8; 16] = rng.gen();
+        let nonce: [u8; 12] = rng.gen();
+
+        let ciphertext = cipher(passphrase, &salt)?
+            .encrypt(&Nonce::from(nonce), plaintext)
+            .map_err(|_| RoutingError::InvalidKeyFile)?;
+
+        Ok(KeyFile::Encrypted {
+            salt,
+            nonce,
+            ciphertext,
+        })
+    }
+
+    fn decrypt(self, passphrase: &str) -> Result<Vec<u8>> {
+        match self {
+            KeyFile::Plain(plaintext) => Ok(plaintext),
+            KeyFile::Encrypted {
+                salt,
+                nonce,
+                ciphertext,
+            } => cipher(passphrase, &salt)?
+                .decrypt(&Nonce::from(nonce), &ciphertext[..])
+                .map_err(|_| RoutingError::InvalidKeyFile),
+        }
+    }
+}
+
+fn cipher(passphrase: &str, salt: &[u8]) -> Result<ChaCha20Poly1305> {
+    let (log_n, r, p) = KEY_DERIVATION_PARAMS;
+    let params = ScryptParams::new(log_n, r, p).map_err(|_| RoutingError::InvalidKeyFile)?;
+
+    let mut key = [0; 32];
+    scrypt::scrypt(passphrase.as_bytes(), salt, &params, &mut key)
+        .map_err(|_| RoutingError::InvalidKeyFile)?;
+
+    Ok(ChaCha20Poly1305::new(&Key::from(key)))
+}
+
 /// Network identity component containing name and public keys.
 ///
 /// Note that the `name` member is omitted when serialising `PublicId` and is calculated from the
@@ -310,4 +467,64 @@ mod tests {
         let parsed = unwrap!(deserialize(&serialised));
         assert_eq!(*full_id.public_id(), parsed);
     }
+
+    #[test]
+    fn full_id_serialisation() {
+        let full_id = FullId::gen(&mut rng::new());
+        let serialised = unwrap!(full_id.secret_keys_to_bytes());
+        let parsed = unwrap!(FullId::from_secret_keys_bytes(&serialised));
+        assert_eq!(full_id.public_id(), parsed.public_id());
+
+        let data = b"signed data";
+        assert!(full_id.public_id().verify(data, &parsed.sign(data)));
+    }
+
+    #[test]
+    fn key_file_round_trip() {
+        let full_id = FullId::gen(&mut rng::new());
+        let data = b"signed data";
+
+        for passphrase in &[None, Some("correct horse battery staple")] {
+            let bytes = unwrap!(full_id.to_bytes(*passphrase));
+            let parsed = unwrap!(FullId::from_bytes(&bytes, *passphrase));
+            assert_eq!(parsed.public_id().name(), full_id.public_id().name());
+            assert_eq!(parsed.public_id(), full_id.public_id());
+            assert!(full_id.public_id().verify(data, &parsed.sign(data)));
+        }
+    }
+
+    #[test]
+    fn key_file_wrong_passphrase() {
+        let full_id = FullId::gen(&mut rng::new());
+        let bytes = unwrap!(full_id.to_bytes(Some("right")));
+
+        match FullId::from_bytes(&bytes, Some("wrong")) {
+            Err(RoutingError::InvalidKeyFile) => (),
+            result => panic!("unexpected result: {:?}", result.map(|_| ())),
+        }
+        match FullId::from_bytes(&bytes, None) {
+            Err(RoutingError::InvalidKeyFile) => (),
+            result => panic!("unexpected result: {:?}", result.map(|_| ())),
+        }
+
+        // A passphrase doesn't go with keys which were not encrypted.
+        let bytes = unwrap!(full_id.to_bytes(None));
+        match FullId::from_bytes(&bytes, Some("right")) {
+            Err(RoutingError::InvalidKeyFile) => (),
+            result => panic!("unexpected result: {:?}", result.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn key_file_tampered() {
+        let full_id = FullId::gen(&mut rng::new());
+        let mut bytes = unwrap!(full_id.to_bytes(Some("right")));
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+
+        match FullId::from_bytes(&bytes, Some("right")) {
+            Err(RoutingError::InvalidKeyFile) => (),
+            result => panic!("unexpected result: {:?}", result.map(|_| ())),
+        }
+    }
 }
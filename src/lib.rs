@@ -78,17 +78,44 @@ mod macros;
 // Public API
 // ############################################################################
 pub use self::{
-    error::RoutingError,
+    chain::{
+        verify_section_history, AgeingPolicy, DefaultAgeingPolicy, ProofError, SectionElders,
+        SectionHistory, SectionHistoryEntry, SectionKeyInfo, SectionProofSlice,
+        MAX_SECTION_HISTORY_ENTRIES, SECTION_HISTORY_FORMAT_VERSION,
+    },
+    client::{ClientFullId, ClientId, ClientRateLimit, ClientRejection, ClientWireMessage},
+    delivery::DELIVERY_ACK_TIMEOUT,
+    error::{ErrorKind, RoutingError, UntrustedProof},
+    fragment::{
+        DEFAULT_MAX_FRAGMENT_SIZE, FRAGMENT_TIMEOUT, MAX_INCOMPLETE_MESSAGES,
+        MAX_INCOMPLETE_MESSAGES_PER_SRC, MAX_INCOMPLETE_SIZE, MAX_MESSAGE_SIZE,
+    },
     id::{FullId, P2pNode, PublicId},
     location::{DstLocation, SrcLocation},
+    messages::MessageId,
+    network_service::{
+        EventSink, NetworkConfig, NetworkEvent, Peer, ResendConfig, Token, Transport,
+        MAX_QUEUED_USER_MESSAGES,
+    },
     node::{Builder, Node},
     pause::PausedState,
-    quic_p2p::Config as NetworkConfig,
-    quic_p2p::Event as NetworkEvent,
+    relocation::{
+        DefaultRelocationPolicy, RelocationContext, RelocationPolicy, SectionSize,
+        UnderPopulatedRelocationPolicy,
+    },
+    reputation::{PENALTY_DECAY_PERIOD, PENALTY_THRESHOLD},
+    status::{
+        BacklogStatus, MemberStatus, NetworkSizeEstimate, NodeState, NodeStatus, SectionStatus,
+    },
     xor_space::{Prefix, XorName, XOR_NAME_LEN},
 };
 /// Routing events.
 pub mod event;
+/// Runtime metrics.
+pub mod metrics;
+
+#[cfg(feature = "async")]
+pub use self::async_node::{AsyncNode, Driver};
 
 // ############################################################################
 // Mock and test API
@@ -106,11 +133,12 @@ pub mod rng;
 pub use self::{
     chain::{
         delivery_group_size, elders_info_for_test, quorum_count, section_proof_slice_for_test,
-        NetworkParams, SectionKeyShare, MIN_AGE,
+        NetworkParams, ResourceProofParams, SectionKeyShare, DEFAULT_BAN_EXPIRY, MIN_AGE,
     },
     messages::{AccumulatingMessage, Message, PlainMessage, Variant},
     parsec::generate_bls_threshold_secret_key,
     relocation::Overrides as RelocationOverrides,
+    resource_proof::Overrides as ResourceProofOverrides,
     xor_space::Xorable,
 };
 
@@ -119,7 +147,9 @@ pub use self::{
 pub mod test_consts {
     pub use crate::{
         chain::{UNRESPONSIVE_THRESHOLD, UNRESPONSIVE_WINDOW},
-        network_service::{RESEND_DELAY, RESEND_MAX_ATTEMPTS},
+        network_service::{
+            MAX_IN_FLIGHT_PER_PEER, MAX_QUEUED_PER_PEER, RESEND_DELAY, RESEND_MAX_ATTEMPTS,
+        },
         parsec::GOSSIP_PERIOD,
         states::{BOOTSTRAP_TIMEOUT, JOIN_TIMEOUT},
     };
@@ -133,8 +163,13 @@ pub use self::mock::parsec::init_mock;
 // ############################################################################
 
 mod action;
+#[cfg(feature = "async")]
+mod async_node;
 mod chain;
+mod client;
+mod delivery;
 mod error;
+mod fragment;
 mod id;
 mod location;
 mod message_filter;
@@ -145,12 +180,15 @@ mod outbox;
 mod parsec;
 mod pause;
 mod relocation;
+mod reputation;
+mod resource_proof;
 #[cfg(not(feature = "mock_base"))]
 mod rng;
 mod routing_message_filter;
 mod signature_accumulator;
 mod state_machine;
 mod states;
+mod status;
 mod time;
 mod timer;
 mod utils;
@@ -181,12 +219,6 @@ const ELDER_SIZE: usize = 7;
 #[cfg(any(test, feature = "mock_base"))]
 use unwrap::unwrap;
 
-// Quic-p2p
-#[cfg(feature = "mock_base")]
-use mock_quic_p2p as quic_p2p;
-#[cfg(not(feature = "mock_base"))]
-use quic_p2p;
-
 #[cfg(test)]
 mod tests {
     use super::{QUORUM_DENOMINATOR, QUORUM_NUMERATOR};
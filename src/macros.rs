@@ -21,3 +21,56 @@ macro_rules! log_or_panic {
         }
     };
 }
+
+/// Defines the conversions between the events and peers of quic-p2p, or of its mock which has the
+/// same API, and our own.
+///
+/// Example usage:
+/// `quic_p2p_conversions!(mock_quic_p2p);`
+macro_rules! quic_p2p_conversions {
+    ($quic_p2p:ident) => {
+        fn from_quic_p2p_event(event: $quic_p2p::Event) -> NetworkEvent {
+            use $quic_p2p::Event;
+
+            match event {
+                Event::BootstrappedTo { node } => NetworkEvent::BootstrappedTo { node },
+                Event::BootstrapFailure => NetworkEvent::BootstrapFailure,
+                Event::ConnectedTo { peer } => NetworkEvent::ConnectedTo {
+                    peer: from_quic_p2p_peer(peer),
+                },
+                Event::ConnectionFailure { peer, .. } => NetworkEvent::ConnectionFailure {
+                    peer: from_quic_p2p_peer(peer),
+                },
+                Event::NewMessage { peer, msg } => NetworkEvent::NewMessage {
+                    peer: from_quic_p2p_peer(peer),
+                    msg,
+                },
+                Event::UnsentUserMessage { peer, msg, token } => NetworkEvent::UnsentUserMessage {
+                    peer: from_quic_p2p_peer(peer),
+                    msg,
+                    token,
+                },
+                Event::SentUserMessage { peer, msg, token } => NetworkEvent::SentUserMessage {
+                    peer: from_quic_p2p_peer(peer),
+                    msg,
+                    token,
+                },
+                Event::Finish => NetworkEvent::Finish,
+            }
+        }
+
+        fn from_quic_p2p_peer(peer: $quic_p2p::Peer) -> Peer {
+            match peer {
+                $quic_p2p::Peer::Node(addr) => Peer::Node(addr),
+                $quic_p2p::Peer::Client(addr) => Peer::Client(addr),
+            }
+        }
+
+        fn into_quic_p2p_peer(peer: Peer) -> $quic_p2p::Peer {
+            match peer {
+                Peer::Node(addr) => $quic_p2p::Peer::Node(addr),
+                Peer::Client(addr) => $quic_p2p::Peer::Client(addr),
+            }
+        }
+    };
+}
@@ -7,6 +7,7 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use crate::time::{Duration, Instant};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::{
     collections::{
         hash_map::{DefaultHasher, Entry},
@@ -104,6 +105,40 @@ impl<Message: Hash> MessageFilter<Message> {
     }
 }
 
+// Only the message hashes and their counts are serialised. The expiry timestamps can't be carried
+// over to another process, so every entry gets a fresh `time_to_live` on deserialisation.
+impl<Message> Serialize for MessageFilter<Message> {
+    fn serialize<S: Serializer>(&self, serialiser: S) -> Result<S::Ok, S::Error> {
+        let counts: Vec<_> = self
+            .count
+            .iter()
+            .map(|(hash_code, &(count, _))| (*hash_code, count))
+            .collect();
+        (self.time_to_live, counts).serialize(serialiser)
+    }
+}
+
+impl<'de, Message> Deserialize<'de> for MessageFilter<Message> {
+    fn deserialize<D: Deserializer<'de>>(deserialiser: D) -> Result<Self, D::Error> {
+        let (time_to_live, counts): (Duration, Vec<(u64, usize)>) =
+            Deserialize::deserialize(deserialiser)?;
+        let expiry = Instant::now() + time_to_live;
+
+        Ok(Self {
+            count: counts
+                .iter()
+                .map(|&(hash_code, count)| (hash_code, (count, expiry)))
+                .collect(),
+            timeout_queue: counts
+                .iter()
+                .map(|&(hash_code, _)| (hash_code, expiry))
+                .collect(),
+            time_to_live,
+            phantom: PhantomData,
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
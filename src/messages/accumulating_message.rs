@@ -6,7 +6,7 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::{DstLocation, Message, SrcAuthority, Variant};
+use super::{DstLocation, Message, MessageId, SrcAuthority, Variant};
 use crate::{
     chain::{SectionKeyShare, SectionProofSlice},
     crypto::{self, Digest256},
@@ -81,6 +81,20 @@ impl AccumulatingMessage {
         self.has_enough_signatures()
     }
 
+    /// Removes the signature shares which don't match the content. Returns `false` if there were
+    /// any.
+    pub fn verify_signature_shares(&mut self) -> bool {
+        let bytes = match self.content.serialize_for_signing() {
+            Ok(bytes) => bytes,
+            Err(error) => {
+                warn!("Failed to serialise {:?}: {:?}", self, error);
+                return false;
+            }
+        };
+
+        self.remove_invalid_signatures(&bytes).is_empty()
+    }
+
     /// Combines the signature shares into a single signature and convert this into full `Message`
     pub fn combine_signatures(self) -> Option<Message> {
         let signature = match self.public_key_set.combine_signatures(
@@ -122,6 +136,11 @@ impl AccumulatingMessage {
         Ok(crypto::sha3_256(&bytes))
     }
 
+    /// Returns the id the message will have once its signatures are combined.
+    pub(crate) fn id(&self) -> Result<MessageId> {
+        MessageId::of_section_message(&self.content.src, &self.content.dst, &self.content.variant)
+    }
+
     fn remove_invalid_signatures(&mut self, bytes: &[u8]) -> Vec<(usize, bls::SignatureShare)> {
         let mut invalid = Vec::new();
 
@@ -205,8 +224,10 @@ mod tests {
         msg_0.add_signature_shares(msg_1);
         assert!(msg_0.check_fully_signed());
 
+        let id = unwrap!(msg_0.id());
         let msg = unwrap!(msg_0.combine_signatures());
         assert_eq!(unwrap!(msg.verify(&their_key_infos)), VerifyStatus::Full);
+        assert_eq!(unwrap!(msg.id()), id);
     }
 
     #[test]
@@ -240,6 +261,12 @@ mod tests {
             signature_shares: iter::once((1, invalid_signature_share)).collect(),
         };
 
+        // The invalid share is detected on its own too.
+        let mut rejected_msg = msg_1.clone();
+        assert!(!rejected_msg.verify_signature_shares());
+        assert!(rejected_msg.signature_shares.is_empty());
+        assert!(msg_0.clone().verify_signature_shares());
+
         msg_0.add_signature_shares(msg_1);
 
         // There is enough signature shares in total, but not enough valid ones, so the message is
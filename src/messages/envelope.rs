@@ -0,0 +1,454 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! The envelope every serialised `Message` is wrapped in on the wire. It consists of a fixed size
+//! header (magic bytes, protocol version and flags, the numbers big-endian) followed by the
+//! message itself, so that nodes can tell which protocol version a peer speaks before trying to
+//! deserialise anything.
+//!
+//! Messages of at least `COMPRESSION_THRESHOLD` bytes are compressed with DEFLATE and marked with
+//! `FLAG_COMPRESSED`. The compressed payload starts with the uncompressed length (`u32`,
+//! big-endian).
+//!
+//! The flags also carry the priority class of the message, so that the network layer can queue
+//! it without deserialising it. They are set by the sender and can't be trusted, so a node
+//! relaying a message sets them again from the message itself before queueing it.
+//!
+//! The messages themselves are not re-encoded for older versions, so a node speaks only
+//! `PROTOCOL_VERSION`. A node which rejects a message because it doesn't speak its version
+//! replies with a message marked with `FLAG_VERSION_MISMATCH` whose payload is the range of
+//! versions it speaks, so the sender can tell it apart from a lost message.
+
+use super::Priority;
+use crate::error::{Result, RoutingError};
+use bytes::{BufMut, Bytes, BytesMut};
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use std::{
+    convert::TryInto,
+    io::{Read, Write},
+    ops::RangeInclusive,
+};
+
+/// Magic bytes identifying a routing message.
+const MAGIC: [u8; 4] = *b"SNRT";
+/// Length of the header: the magic bytes, the version (`u16`) and the flags (`u16`).
+pub const HEADER_LEN: usize = 8;
+
+/// Version of the wire protocol spoken by this version of routing. Bump it whenever the layout
+/// of the serialised messages changes.
+pub const PROTOCOL_VERSION: u16 = 3;
+/// The oldest version of the wire protocol this version of routing still understands. The
+/// messages are not re-encoded for peers speaking an older version, so it's our version.
+pub const MIN_PROTOCOL_VERSION: u16 = PROTOCOL_VERSION;
+
+/// Flag marking a compressed message.
+pub const FLAG_COMPRESSED: u16 = 0x0001;
+/// Flags holding the priority class of the message.
+pub const PRIORITY_MASK: u16 = 0x0006;
+const PRIORITY_SHIFT: u16 = 1;
+/// Flag marking a reply to a message whose protocol version we don't speak. The payload holds
+/// `MIN_PROTOCOL_VERSION` and `PROTOCOL_VERSION` (`u16`, big-endian). The reply keeps this
+/// layout in every version, so it can be read whatever version its header has.
+pub const FLAG_VERSION_MISMATCH: u16 = 0x0008;
+/// Size of the serialised message from which it's compressed. Smaller messages are not worth it.
+pub const COMPRESSION_THRESHOLD: usize = 4 * 1024;
+/// Largest uncompressed size we accept, so a small malicious message can't exhaust our memory.
+const MAX_UNCOMPRESSED_LEN: usize = 64 * 1024 * 1024;
+const LEN_PREFIX_SIZE: usize = 4;
+const VERSION_SIZE: usize = 2;
+
+/// Header of the envelope.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Header {
+    /// Version of the wire protocol the message is encoded with.
+    pub version: u16,
+    /// Flags describing how the message is encoded, see `FLAG_COMPRESSED`, `PRIORITY_MASK` and
+    /// `FLAG_VERSION_MISMATCH`.
+    pub flags: u16,
+}
+
+impl Header {
+    /// Creates a header for a message encoded with our protocol version.
+    pub fn new(flags: u16) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            flags,
+        }
+    }
+
+    /// Returns the header with the priority flags set to `priority`.
+    pub fn with_priority(self, priority: Priority) -> Self {
+        let bits = match priority {
+            Priority::Bulk => 0,
+            Priority::Consensus => 1,
+            Priority::Control => 2,
+        };
+
+        Self {
+            version: self.version,
+            flags: (self.flags & !PRIORITY_MASK) | (bits << PRIORITY_SHIFT),
+        }
+    }
+
+    /// Priority class of the message. Unknown values are treated as the lowest priority.
+    pub fn priority(&self) -> Priority {
+        match (self.flags & PRIORITY_MASK) >> PRIORITY_SHIFT {
+            1 => Priority::Consensus,
+            2 => Priority::Control,
+            _ => Priority::Bulk,
+        }
+    }
+
+    /// Reads the header at the start of `bytes` and checks the message is encoded with a
+    /// protocol version we understand.
+    pub fn read(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < HEADER_LEN || bytes[..MAGIC.len()] != MAGIC {
+            // Routing versions predating the envelope sent the plain serialised message.
+            return Err(RoutingError::IncompatibleProtocolVersion {
+                ours: PROTOCOL_VERSION,
+                theirs: 0,
+            });
+        }
+
+        let version = u16::from_be_bytes([bytes[4], bytes[5]]);
+        let flags = u16::from_be_bytes([bytes[6], bytes[7]]);
+
+        if version < MIN_PROTOCOL_VERSION || version > PROTOCOL_VERSION {
+            return Err(RoutingError::IncompatibleProtocolVersion {
+                ours: PROTOCOL_VERSION,
+                theirs: version,
+            });
+        }
+
+        Ok(Self { version, flags })
+    }
+}
+
+/// Wraps the serialised message in the envelope.
+pub fn seal(header: Header, payload: &[u8]) -> Bytes {
+    let mut bytes = BytesMut::with_capacity(HEADER_LEN + payload.len());
+    bytes.put_slice(&MAGIC);
+    bytes.put_slice(&header.version.to_be_bytes());
+    bytes.put_slice(&header.flags.to_be_bytes());
+    bytes.put_slice(payload);
+    bytes.freeze()
+}
+
+/// Wraps the serialised message in the envelope with the given priority, compressing it if it's
+/// large enough and the compression actually makes it smaller.
+pub fn seal_compressed(payload: &[u8], priority: Priority) -> Bytes {
+    if payload.len() >= COMPRESSION_THRESHOLD {
+        if let Some(compressed) = compress(payload) {
+            return seal(
+                Header::new(FLAG_COMPRESSED).with_priority(priority),
+                &compressed,
+            );
+        }
+    }
+
+    seal(Header::new(0).with_priority(priority), payload)
+}
+
+/// Returns the sealed message with the priority flags set to `priority`. The bytes are only copied
+/// if the flags change.
+#[cfg(test)]
+pub fn set_priority(bytes: &Bytes, priority: Priority) -> Result<Bytes> {
+    let header = Header::read(bytes)?;
+    let new_header = header.with_priority(priority);
+
+    if new_header == header {
+        Ok(bytes.clone())
+    } else {
+        Ok(seal(new_header, payload(bytes)))
+    }
+}
+
+/// Checks the envelope and returns its header together with the serialised message, decompressed
+/// if needed.
+pub fn open(bytes: &Bytes) -> Result<(Header, Bytes)> {
+    let header = Header::read(bytes)?;
+    let payload = bytes.slice_from(HEADER_LEN);
+
+    if header.flags & FLAG_COMPRESSED == 0 {
+        Ok((header, payload))
+    } else {
+        Ok((header, decompress(&payload)?))
+    }
+}
+
+/// Creates the reply telling a peer we rejected its message because we don't speak its protocol
+/// version.
+pub fn version_mismatch() -> Bytes {
+    let mut payload = Vec::with_capacity(2 * VERSION_SIZE);
+    payload.extend_from_slice(&MIN_PROTOCOL_VERSION.to_be_bytes());
+    payload.extend_from_slice(&PROTOCOL_VERSION.to_be_bytes());
+    seal(
+        Header::new(FLAG_VERSION_MISMATCH).with_priority(Priority::Control),
+        &payload,
+    )
+}
+
+/// If the sealed message is a version mismatch reply, returns the range of protocol versions its
+/// sender speaks. The version in the header is not checked, as we might not speak it.
+pub fn supported_versions(bytes: &[u8]) -> Option<RangeInclusive<u16>> {
+    if bytes.len() != HEADER_LEN + 2 * VERSION_SIZE || bytes[..MAGIC.len()] != MAGIC {
+        return None;
+    }
+
+    let flags = u16::from_be_bytes([bytes[6], bytes[7]]);
+    if flags & FLAG_VERSION_MISMATCH == 0 {
+        return None;
+    }
+
+    let payload = payload(bytes);
+    let min = u16::from_be_bytes([payload[0], payload[1]]);
+    let max = u16::from_be_bytes([payload[2], payload[3]]);
+    Some(min..=max)
+}
+
+/// Returns the serialised message inside the envelope as it is on the wire, without checking the
+/// header or decompressing it.
+///
+/// Precondition: `bytes` holds a complete header.
+pub fn payload(bytes: &[u8]) -> &[u8] {
+    &bytes[HEADER_LEN..]
+}
+
+/// Returns the priority class of the sealed message, the lowest one if the header is invalid.
+pub fn priority(bytes: &[u8]) -> Priority {
+    Header::read(bytes)
+        .map(|header| header.priority())
+        .unwrap_or(Priority::Bulk)
+}
+
+/// Returns how many bytes the compression saved on the sealed message, zero if it's not
+/// compressed.
+pub fn compression_saving(bytes: &[u8]) -> u64 {
+    match Header::read(bytes) {
+        Ok(header) if header.flags & FLAG_COMPRESSED != 0 => {
+            let payload = payload(bytes);
+            uncompressed_len(payload)
+                .map(|len| len.saturating_sub(payload.len()) as u64)
+                .unwrap_or(0)
+        }
+        _ => 0,
+    }
+}
+
+// Returns `None` if the compressed payload wouldn't be smaller.
+fn compress(payload: &[u8]) -> Option<Vec<u8>> {
+    let len: u32 = payload.len().try_into().ok()?;
+
+    let mut result = Vec::with_capacity(payload.len() / 2);
+    result.extend_from_slice(&len.to_be_bytes());
+
+    let mut encoder = DeflateEncoder::new(result, Compression::default());
+    encoder.write_all(payload).ok()?;
+    let result = encoder.finish().ok()?;
+
+    if result.len() < payload.len() {
+        Some(result)
+    } else {
+        None
+    }
+}
+
+fn decompress(payload: &[u8]) -> Result<Bytes> {
+    let len = uncompressed_len(payload).ok_or(RoutingError::MalformedCompression)?;
+    if len > MAX_UNCOMPRESSED_LEN {
+        return Err(RoutingError::MalformedCompression);
+    }
+
+    // Don't trust the announced length for the allocation, the buffer grows as the data is
+    // decoded. Read one byte more than expected, to detect a payload longer than announced.
+    let mut decompressed = Vec::new();
+    let _ = DeflateDecoder::new(&payload[LEN_PREFIX_SIZE..])
+        .take(len as u64 + 1)
+        .read_to_end(&mut decompressed)
+        .map_err(|_| RoutingError::MalformedCompression)?;
+
+    if decompressed.len() == len {
+        Ok(Bytes::from(decompressed))
+    } else {
+        Err(RoutingError::MalformedCompression)
+    }
+}
+
+fn uncompressed_len(payload: &[u8]) -> Option<usize> {
+    let prefix = payload.get(..LEN_PREFIX_SIZE)?;
+    Some(u32::from_be_bytes([prefix[0], prefix[1], prefix[2], prefix[3]]) as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{rng, unwrap};
+    use rand::{distributions::Standard, Rng};
+
+    #[test]
+    fn seal_and_open() {
+        let content = b"payload".to_vec();
+        let bytes = seal(Header::new(0), &content);
+
+        let (header, opened) = unwrap!(open(&bytes));
+        assert_eq!(header, Header::new(0));
+        assert_eq!(&opened[..], &content[..]);
+        assert_eq!(payload(&bytes), &content[..]);
+    }
+
+    #[test]
+    fn reject_missing_envelope() {
+        let bytes = Bytes::from(&b"plain bincode"[..]);
+
+        match open(&bytes) {
+            Err(RoutingError::IncompatibleProtocolVersion { ours, theirs }) => {
+                assert_eq!(ours, PROTOCOL_VERSION);
+                assert_eq!(theirs, 0);
+            }
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn reject_newer_version() {
+        let header = Header {
+            version: PROTOCOL_VERSION + 1,
+            flags: 0,
+        };
+        let bytes = seal(header, b"payload");
+
+        match open(&bytes) {
+            Err(RoutingError::IncompatibleProtocolVersion { ours, theirs }) => {
+                assert_eq!(ours, PROTOCOL_VERSION);
+                assert_eq!(theirs, PROTOCOL_VERSION + 1);
+            }
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn reject_older_version() {
+        let header = Header {
+            version: MIN_PROTOCOL_VERSION - 1,
+            flags: 0,
+        };
+        let bytes = seal(header, b"payload");
+
+        match open(&bytes) {
+            Err(RoutingError::IncompatibleProtocolVersion { ours, theirs }) => {
+                assert_eq!(ours, PROTOCOL_VERSION);
+                assert_eq!(theirs, MIN_PROTOCOL_VERSION - 1);
+            }
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn compress_large_messages_only() {
+        let small = vec![7; COMPRESSION_THRESHOLD - 1];
+        let bytes = seal_compressed(&small, Priority::Bulk);
+        let (header, opened) = unwrap!(open(&bytes));
+        assert_eq!(header.flags & FLAG_COMPRESSED, 0);
+        assert_eq!(&opened[..], &small[..]);
+        assert_eq!(compression_saving(&bytes), 0);
+
+        let large = vec![7; 10 * COMPRESSION_THRESHOLD];
+        let bytes = seal_compressed(&large, Priority::Bulk);
+        assert!(bytes.len() < large.len());
+        let (header, opened) = unwrap!(open(&bytes));
+        assert_eq!(header.flags & FLAG_COMPRESSED, FLAG_COMPRESSED);
+        assert_eq!(&opened[..], &large[..]);
+        assert_eq!(
+            compression_saving(&bytes),
+            (large.len() - payload(&bytes).len()) as u64
+        );
+    }
+
+    #[test]
+    fn skip_compression_if_not_smaller() {
+        let mut rng = rng::new();
+        let random: Vec<u8> = (&mut rng)
+            .sample_iter(Standard)
+            .take(2 * COMPRESSION_THRESHOLD)
+            .collect();
+        let bytes = seal_compressed(&random, Priority::Bulk);
+        assert_eq!(unwrap!(Header::read(&bytes)).flags & FLAG_COMPRESSED, 0);
+        assert_eq!(payload(&bytes), &random[..]);
+    }
+
+    #[test]
+    fn priority_flags() {
+        for priority in &[Priority::Bulk, Priority::Consensus, Priority::Control] {
+            let bytes = seal_compressed(b"payload", *priority);
+            assert_eq!(super::priority(&bytes), *priority);
+
+            let large = vec![7; 10 * COMPRESSION_THRESHOLD];
+            let bytes = seal_compressed(&large, *priority);
+            assert_eq!(super::priority(&bytes), *priority);
+            assert!(compression_saving(&bytes) > 0);
+        }
+
+        // Unknown priority bits.
+        let bytes = seal(Header::new(PRIORITY_MASK), b"payload");
+        assert_eq!(super::priority(&bytes), Priority::Bulk);
+
+        let bytes = seal_compressed(b"payload", Priority::Control);
+        let reset = unwrap!(set_priority(&bytes, Priority::Bulk));
+        assert_eq!(super::priority(&reset), Priority::Bulk);
+        assert_eq!(payload(&reset), payload(&bytes));
+        assert_eq!(unwrap!(set_priority(&reset, Priority::Bulk)), reset);
+    }
+
+    #[test]
+    fn version_mismatch_reply() {
+        let reply = version_mismatch();
+        assert_eq!(
+            supported_versions(&reply),
+            Some(MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION)
+        );
+        assert_eq!(super::priority(&reply), Priority::Control);
+
+        // Readable whatever version its header has.
+        let mut newer = reply.to_vec();
+        newer[4..6].copy_from_slice(&(PROTOCOL_VERSION + 1).to_be_bytes());
+        assert!(Header::read(&newer).is_err());
+        assert_eq!(
+            supported_versions(&newer),
+            Some(MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION)
+        );
+
+        // Regular messages are not replies.
+        assert_eq!(supported_versions(&seal(Header::new(0), b"payl")), None);
+        assert_eq!(
+            supported_versions(&seal_compressed(b"payload", Priority::Control)),
+            None
+        );
+    }
+
+    #[test]
+    fn reject_malformed_compression() {
+        let large = vec![7; 10 * COMPRESSION_THRESHOLD];
+        let bytes = seal_compressed(&large, Priority::Bulk);
+
+        // Announced length doesn't match the compressed data.
+        let mut wrong_len = bytes.to_vec();
+        wrong_len[HEADER_LEN + LEN_PREFIX_SIZE - 1] ^= 1;
+        match open(&Bytes::from(wrong_len)) {
+            Err(RoutingError::MalformedCompression) => (),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+
+        // Announced length over the limit.
+        let mut too_long = bytes.to_vec();
+        too_long[HEADER_LEN] = 0xff;
+        match open(&Bytes::from(too_long)) {
+            Err(RoutingError::MalformedCompression) => (),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+}
@@ -7,6 +7,7 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 mod accumulating_message;
+pub mod envelope;
 mod src_authority;
 mod variant;
 mod with_bytes;
@@ -14,17 +15,19 @@ mod with_bytes;
 pub use self::{
     accumulating_message::{AccumulatingMessage, PlainMessage},
     src_authority::SrcAuthority,
-    variant::{BootstrapResponse, JoinRequest, MemberKnowledge, Variant},
+    variant::{BootstrapResponse, JoinRequest, MemberKnowledge, Priority, Variant},
     with_bytes::MessageWithBytes,
 };
 use crate::{
     chain::SectionKeyInfo,
-    error::{Result, RoutingError},
+    crypto::{self, Digest256},
+    error::{Result, RoutingError, UntrustedProof},
     id::{FullId, PublicId},
     location::DstLocation,
     xor_space::{Prefix, XorName},
 };
 use bytes::Bytes;
+use hex_fmt::HexFmt;
 use std::{
     fmt::{self, Debug, Formatter},
     net::SocketAddr,
@@ -51,19 +54,23 @@ pub struct PartialMessage {
 impl PartialMessage {
     /// Deserialize the message.
     pub fn from_bytes(bytes: &Bytes) -> Result<Self> {
-        Ok(bincode::deserialize(&bytes[..])?)
+        let (_, payload) = envelope::open(bytes)?;
+        Ok(bincode::deserialize(&payload)?)
     }
 }
 
 impl Message {
     /// Deserialize the message.
+    #[cfg(test)]
     pub(crate) fn from_bytes(bytes: &Bytes) -> Result<Self> {
-        Ok(bincode::deserialize(&bytes[..])?)
+        let (_, payload) = envelope::open(bytes)?;
+        Ok(bincode::deserialize(&payload)?)
     }
 
-    /// Serialize the message.
+    /// Serialize the message, wrapped in the wire envelope and compressed if it's large.
     pub(crate) fn to_bytes(&self) -> Result<Bytes> {
-        Ok(bincode::serialize(self)?.into())
+        let payload = bincode::serialize(self)?;
+        Ok(envelope::seal_compressed(&payload, self.variant.priority()))
     }
 
     /// Creates a message from single node.
@@ -89,6 +96,18 @@ impl Message {
         self.src.verify(&self.dst, &self.variant, their_key_infos)
     }
 
+    /// Returns the id of the message.
+    pub(crate) fn id(&self) -> Result<MessageId> {
+        match &self.src {
+            SrcAuthority::Node { .. } => {
+                Ok(MessageId(crypto::sha3_256(&bincode::serialize(self)?)))
+            }
+            SrcAuthority::Section { prefix, .. } => {
+                MessageId::of_section_message(prefix, &self.dst, &self.variant)
+            }
+        }
+    }
+
     /// Returns the latest section key from the message proof.
     pub fn source_section_key_info(&self) -> Option<&SectionKeyInfo> {
         match &self.src {
@@ -120,24 +139,54 @@ impl Debug for Message {
     }
 }
 
+/// Identifier of a message, which lets the application correlate and deduplicate messages.
+///
+/// For a message sent by a single node, this is the hash of the full message, the same as its
+/// `MessageWithBytes::full_crypto_hash`. A message sent by a section is only complete once the
+/// signatures of its elders accumulate, so the elders can't know its full hash when they send it.
+/// Its id is instead the hash of the content the elders sign. Either way the sender and the
+/// recipients of a message agree on its id.
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub struct MessageId(Digest256);
+
+impl MessageId {
+    // Id of a message sent by a section, computed from what its elders sign.
+    fn of_section_message(
+        src: &Prefix<XorName>,
+        dst: &DstLocation,
+        variant: &Variant,
+    ) -> Result<Self> {
+        Ok(Self(crypto::sha3_256(&bincode::serialize(&(
+            src, dst, variant,
+        ))?)))
+    }
+}
+
+impl Debug for MessageId {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "MessageId({:10})", HexFmt(&self.0))
+    }
+}
+
 #[derive(Eq, PartialEq, Debug)]
 pub enum VerifyStatus {
     // The message has been fully verified.
     Full,
     // The message trust and integrity cannot be verified because it's proof is too new. It should
     // be relayed to other nodes who might be able to verify it.
-    ProofTooNew,
+    ProofTooNew(UntrustedProof),
 }
 
 impl VerifyStatus {
     pub fn require_full(self) -> Result<(), RoutingError> {
         match self {
             Self::Full => Ok(()),
-            Self::ProofTooNew => Err(RoutingError::UntrustedMessage),
+            Self::ProofTooNew(proof) => Err(RoutingError::UntrustedMessage(proof)),
         }
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct QueuedMessage {
     pub message: Message,
     pub sender: Option<SocketAddr>,
@@ -82,8 +82,12 @@ impl SrcAuthority {
             } => {
                 let public_key = match proof.check_trust(their_key_infos) {
                     TrustStatus::Trusted(key) => key,
-                    TrustStatus::ProofTooNew => return Ok(VerifyStatus::ProofTooNew),
-                    TrustStatus::ProofInvalid => return Err(RoutingError::UntrustedMessage),
+                    TrustStatus::ProofTooNew => {
+                        return Ok(VerifyStatus::ProofTooNew(proof.untrusted(true)))
+                    }
+                    TrustStatus::ProofInvalid => {
+                        return Err(RoutingError::UntrustedMessage(proof.untrusted(false)))
+                    }
                 };
 
                 let bytes = super::serialize_for_section_signing(dst, variant)?;
@@ -6,11 +6,13 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::AccumulatingMessage;
+use super::{AccumulatingMessage, MessageId};
 use crate::{
-    chain::{EldersInfo, GenesisPfxInfo},
+    chain::{EldersInfo, GenesisPfxInfo, MergeDetails},
+    fragment::UserMessageFragment,
     parsec,
     relocation::{RelocateDetails, RelocatePayload},
+    resource_proof::{ResourceChallenge, ResourceProof},
     xor_space::{Prefix, XorName},
 };
 use hex_fmt::HexFmt;
@@ -64,6 +66,98 @@ pub enum Variant {
     ParsecResponse(u64, parsec::Response),
     /// Message sent to a disconnected peer to trigger lost peer detection.
     Ping,
+    /// Sent from a section which became too small to its sibling, asking it to merge.
+    /// Section X -> Sibling of section X
+    Merge(Box<MergeDetails>),
+    /// Sent from an elder to a node which sent a `JoinRequest`. The node has to solve the challenge
+    /// before the elder votes for it to join.
+    ResourceChallenge(ResourceChallenge),
+    /// Sent from a joining node to the elder which sent it the `ResourceChallenge`.
+    ResourceProofResponse(ResourceProof),
+    /// User-facing message whose destination is asked to acknowledge its delivery.
+    UserMessageWithAck(Vec<u8>),
+    /// Acknowledgement of the delivery of a `UserMessageWithAck`.
+    /// Destination of the message -> its source
+    MessageDeliveryAck(MessageId),
+    /// Part of a user-facing message too large to be sent in one piece.
+    UserMessageFragment(UserMessageFragment),
+    /// Inform neighbours about the number of joined members of our section.
+    /// Section X -> Neighbours of section X
+    MemberCount(u64),
+    /// Sent from an elder which lost its parsec when it was resumed to the other elders of its
+    /// section, asking them to start a new parsec version it can take part in. Holds the version
+    /// it lost.
+    ParsecResync(u64),
+}
+
+impl Variant {
+    /// Name of the variant, without the payload.
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Self::NeighbourInfo(_) => "NeighbourInfo",
+            Self::UserMessage(_) => "UserMessage",
+            Self::NodeApproval(_) => "NodeApproval",
+            Self::AckMessage { .. } => "AckMessage",
+            Self::GenesisUpdate(_) => "GenesisUpdate",
+            Self::Relocate(_) => "Relocate",
+            Self::MessageSignature(_) => "MessageSignature",
+            Self::BootstrapRequest(_) => "BootstrapRequest",
+            Self::BootstrapResponse(_) => "BootstrapResponse",
+            Self::JoinRequest(_) => "JoinRequest",
+            Self::MemberKnowledge(_) => "MemberKnowledge",
+            Self::ParsecRequest(..) => "ParsecRequest",
+            Self::ParsecResponse(..) => "ParsecResponse",
+            Self::Ping => "Ping",
+            Self::Merge(_) => "Merge",
+            Self::ResourceChallenge(_) => "ResourceChallenge",
+            Self::ResourceProofResponse(_) => "ResourceProofResponse",
+            Self::UserMessageWithAck(_) => "UserMessageWithAck",
+            Self::MessageDeliveryAck(_) => "MessageDeliveryAck",
+            Self::UserMessageFragment(_) => "UserMessageFragment",
+            Self::MemberCount(_) => "MemberCount",
+            Self::ParsecResync(_) => "ParsecResync",
+        }
+    }
+
+    /// Priority class of the variant, which decides the order the queued messages are sent in.
+    pub(crate) fn priority(&self) -> Priority {
+        match self {
+            Self::ParsecRequest(..) | Self::ParsecResponse(..) => Priority::Consensus,
+            Self::UserMessage(_) | Self::UserMessageWithAck(_) | Self::UserMessageFragment(_) => {
+                Priority::Bulk
+            }
+            // The signature share is as urgent as the message it signs.
+            Self::MessageSignature(msg) => msg.content.variant.priority(),
+            Self::NeighbourInfo(_)
+            | Self::MemberCount(_)
+            | Self::Merge(_)
+            | Self::NodeApproval(_)
+            | Self::AckMessage { .. }
+            | Self::GenesisUpdate(_)
+            | Self::Relocate(_)
+            | Self::BootstrapRequest(_)
+            | Self::BootstrapResponse(_)
+            | Self::JoinRequest(_)
+            | Self::ResourceChallenge(_)
+            | Self::ResourceProofResponse(_)
+            | Self::MemberKnowledge(_)
+            | Self::ParsecResync(_)
+            | Self::Ping
+            | Self::MessageDeliveryAck(_) => Priority::Control,
+        }
+    }
+}
+
+/// Priority class of a message. When messages to a peer have to be queued, the queued messages of
+/// a higher class are sent before any of a lower class.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Priority {
+    /// User messages, which can be large and numerous.
+    Bulk,
+    /// Parsec gossip.
+    Consensus,
+    /// Messages managing the membership and the structure of the network.
+    Control,
 }
 
 impl Debug for Variant {
@@ -90,6 +184,18 @@ impl Debug for Variant {
             Self::ParsecRequest(version, _) => write!(f, "ParsecRequest({}, ..)", version),
             Self::ParsecResponse(version, _) => write!(f, "ParsecResponse({}, ..)", version),
             Self::Ping => write!(f, "Ping"),
+            Self::Merge(payload) => write!(f, "Merge({:?})", payload),
+            Self::ResourceChallenge(payload) => write!(f, "ResourceChallenge({:?})", payload),
+            Self::ResourceProofResponse(payload) => {
+                write!(f, "ResourceProofResponse({:?})", payload)
+            }
+            Self::UserMessageWithAck(payload) => {
+                write!(f, "UserMessageWithAck({})", HexFmt(payload))
+            }
+            Self::MessageDeliveryAck(id) => write!(f, "MessageDeliveryAck({:?})", id),
+            Self::UserMessageFragment(payload) => write!(f, "{:?}", payload),
+            Self::MemberCount(count) => write!(f, "MemberCount({})", count),
+            Self::ParsecResync(version) => write!(f, "ParsecResync({})", version),
         }
     }
 }
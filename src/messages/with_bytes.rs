@@ -6,10 +6,10 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::{DstLocation, Message, PartialMessage};
+use super::{envelope, DstLocation, Message, MessageId, PartialMessage, Priority};
 use crate::{
     crypto::{self, Digest256},
-    error::Result,
+    error::{Result, RoutingError},
     utils::LogIdent,
 };
 use bytes::Bytes;
@@ -22,18 +22,33 @@ pub struct MessageWithBytes {
     full_content: Option<Message>,
     /// Partial message (just the destination location)
     partial_content: PartialMessage,
-    /// Serialized full message as received or sent to quic_p2p.
+    /// Serialized full message as received or sent to quic_p2p, including the envelope. Possibly
+    /// compressed.
     full_bytes: Bytes,
-    /// Crypto hash of the full message.
+    /// Serialized full message, without the envelope and uncompressed.
+    payload: Bytes,
+    /// Priority class of the message, as set in its envelope. The sender of a received message
+    /// could lie about it, so it's checked once the message is deserialized.
+    priority: Priority,
+    /// Crypto hash of the full message, excluding the envelope so the hash doesn't depend on its
+    /// compression.
     full_crypto_hash: Digest256,
 }
 
 impl MessageWithBytes {
     /// Serialize message and keep both SignedRoutingMessage and Bytes.
     pub fn new(full_content: Message, log_ident: &LogIdent) -> Result<Self> {
-        let full_bytes = full_content.to_bytes()?;
+        let payload = Bytes::from(bincode::serialize(&full_content)?);
+        let full_bytes = envelope::seal_compressed(&payload, full_content.variant.priority());
         let partial_content = full_content.to_partial();
-        let result = Self::new_from_parts(Some(full_content), partial_content, full_bytes);
+        let priority = full_content.variant.priority();
+        let result = Self::new_from_parts(
+            Some(full_content),
+            partial_content,
+            full_bytes,
+            payload,
+            priority,
+        );
 
         trace!(
             "{} Creating message hash({}) {:?}",
@@ -49,22 +64,34 @@ impl MessageWithBytes {
     }
 
     pub fn partial_from_bytes(bytes: Bytes) -> Result<Self> {
-        let partial_content = PartialMessage::from_bytes(&bytes)?;
-        Ok(Self::new_from_parts(None, partial_content, bytes))
+        let (header, payload) = envelope::open(&bytes)?;
+        let partial_content = bincode::deserialize(&payload)?;
+        Ok(Self::new_from_parts(
+            None,
+            partial_content,
+            bytes,
+            payload,
+            header.priority(),
+        ))
     }
 
-    // Precondition: `full_bytes == serialize(&full_content)`
+    // Precondition: `payload == serialize(&full_content)` and `full_bytes` is `payload` sealed in
+    // a valid envelope with the priority flags set to `priority`.
     fn new_from_parts(
         full_content: Option<Message>,
         partial_content: PartialMessage,
         full_bytes: Bytes,
+        payload: Bytes,
+        priority: Priority,
     ) -> Self {
-        let full_crypto_hash = crypto::sha3_256(&full_bytes);
+        let full_crypto_hash = crypto::sha3_256(&payload);
 
         Self {
             full_content,
             partial_content,
             full_bytes,
+            payload,
+            priority,
             full_crypto_hash,
         }
     }
@@ -86,16 +113,34 @@ impl MessageWithBytes {
         &self.full_bytes
     }
 
+    pub fn priority(&self) -> Priority {
+        self.priority
+    }
+
     pub fn full_crypto_hash(&self) -> &Digest256 {
         &self.full_crypto_hash
     }
 
+    // Precondition: the message is sent by a single node. The id of a section message doesn't
+    // depend on its signature, so it isn't the hash of the full message.
+    pub fn single_src_message_id(&self) -> MessageId {
+        MessageId(self.full_crypto_hash)
+    }
+
     pub fn message_dst(&self) -> &DstLocation {
         &self.partial_content.dst
     }
 
+    // Fails with `RoutingError::WrongPriority` if the sender set the priority flags of the
+    // envelope to anything but the priority of the variant, so messages relayed with the wrong
+    // priority are not handled by their destination.
     fn deserialize_message(&self) -> Result<Message> {
-        Message::from_bytes(&self.full_bytes)
+        let msg: Message = bincode::deserialize(&self.payload)?;
+        if msg.variant.priority() == self.priority {
+            Ok(msg)
+        } else {
+            Err(RoutingError::WrongPriority)
+        }
     }
 }
 
@@ -119,12 +164,76 @@ mod tests {
 
         let full_msg = unwrap!(Message::from_bytes(bytes));
         let partial_msg = unwrap!(PartialMessage::from_bytes(bytes));
-        let partial_msg_head = unwrap!(PartialMessage::from_bytes(&bytes.slice(0, 40)));
+        let partial_msg_head = unwrap!(PartialMessage::from_bytes(
+            &bytes.slice(0, envelope::HEADER_LEN + 40)
+        ));
 
         let expected_partial = PartialMessage { dst: msg.dst };
 
         assert_eq!(partial_msg, expected_partial);
         assert_eq!(partial_msg_head, expected_partial);
         assert_eq!(full_msg, msg);
+        assert_eq!(
+            unwrap!(full_msg.id()),
+            msg_with_bytes.single_src_message_id()
+        );
+    }
+
+    #[test]
+    fn compressed_message() {
+        let mut rng = rng::new();
+        let full_id = FullId::gen(&mut rng);
+
+        let dst = DstLocation::Section(rng.gen());
+        let variant = Variant::UserMessage(vec![7; 2 * envelope::COMPRESSION_THRESHOLD]);
+        let msg = unwrap!(Message::single_src(&full_id, dst, variant));
+
+        let msg_with_bytes = unwrap!(MessageWithBytes::new(msg.clone(), &LogIdent::new("node")));
+        let bytes = msg_with_bytes.full_bytes().clone();
+        assert!(envelope::compression_saving(&bytes) > 0);
+
+        let mut received = unwrap!(MessageWithBytes::partial_from_bytes(bytes));
+        assert_eq!(received.message_dst(), &msg.dst);
+        assert_eq!(
+            received.full_crypto_hash(),
+            msg_with_bytes.full_crypto_hash()
+        );
+        assert_eq!(received.single_src_message_id(), unwrap!(msg.id()));
+        assert_eq!(unwrap!(received.take_or_deserialize_message()), msg);
+    }
+
+    #[test]
+    fn reject_wrong_priority() {
+        let mut rng = rng::new();
+        let full_id = FullId::gen(&mut rng);
+
+        let dst = DstLocation::Section(rng.gen());
+        let variant = Variant::UserMessage(rng.sample_iter(Standard).take(6).collect());
+        let msg = unwrap!(Message::single_src(&full_id, dst, variant));
+
+        let msg_with_bytes = unwrap!(MessageWithBytes::new(msg.clone(), &LogIdent::new("node")));
+        assert_eq!(msg_with_bytes.priority(), Priority::Bulk);
+
+        let mut received = unwrap!(MessageWithBytes::partial_from_bytes(
+            msg_with_bytes.full_bytes().clone()
+        ));
+        assert_eq!(received.priority(), Priority::Bulk);
+        assert_eq!(unwrap!(received.take_or_deserialize_message()), msg);
+
+        // The sender claims a user message is urgent. It's relayed as such, but not handled.
+        let forged = unwrap!(envelope::set_priority(
+            msg_with_bytes.full_bytes(),
+            Priority::Control
+        ));
+        let mut received = unwrap!(MessageWithBytes::partial_from_bytes(forged));
+        assert_eq!(received.priority(), Priority::Control);
+        assert_eq!(
+            received.full_crypto_hash(),
+            msg_with_bytes.full_crypto_hash()
+        );
+        match received.take_or_deserialize_message() {
+            Err(RoutingError::WrongPriority) => (),
+            result => panic!("unexpected result: {:?}", result),
+        }
     }
 }
@@ -0,0 +1,226 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! The node reports its metrics to a [`MetricsSink`](trait.MetricsSink.html) passed to
+//! [`Builder::metrics`](../struct.Builder.html#method.metrics). By default they are discarded.
+//! The sink can forward them to any monitoring system, for example Prometheus. The metric names
+//! are the constants in this module.
+
+use crate::{messages::Variant, time::Duration};
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Debug, Formatter},
+    sync::{Arc, Mutex},
+};
+
+/// Number of messages sent, labelled by the message variant.
+pub const MESSAGES_SENT: &str = "routing_messages_sent_total";
+/// Number of messages received, labelled by the message variant.
+pub const MESSAGES_RECEIVED: &str = "routing_messages_received_total";
+/// Number of messages resent after a failed send attempt.
+pub const MESSAGE_RESENDS: &str = "routing_message_resends_total";
+/// Number of messages dropped after all the send attempts failed.
+pub const MESSAGES_DROPPED: &str = "routing_messages_dropped_total";
+/// Time between receiving the first signature of a message and accumulating enough of them, in
+/// seconds.
+pub const SIGNATURE_ACCUMULATION_SECONDS: &str = "routing_signature_accumulation_seconds";
+/// Number of bytes of parsec gossip received for the current parsec instance.
+pub const PARSEC_GOSSIP_BYTES: &str = "routing_parsec_gossip_bytes_total";
+/// Number of messages filtered out as already seen, labelled by `incoming` or `outgoing`.
+pub const FILTER_HITS: &str = "routing_filter_hits_total";
+/// Number of churn events handled, labelled by the kind of the event.
+pub const CHURN_EVENTS: &str = "routing_churn_events_total";
+/// Number of client messages dropped for exceeding the client rate limit.
+pub const CLIENT_MESSAGES_REJECTED: &str = "routing_client_messages_rejected_total";
+/// Number of bytes saved by compressing the messages sent.
+pub const COMPRESSION_BYTES_SAVED: &str = "routing_compression_bytes_saved_total";
+
+/// Destination of the metrics reported by a node.
+///
+/// Counters have an optional label which further distinguishes the counted items, e.g. the message
+/// variant for `MESSAGES_SENT`.
+pub trait MetricsSink: Send + Sync {
+    /// Increases the counter `name` with the given `label` by `value`.
+    fn increment_counter(&self, name: &'static str, label: Option<&'static str>, value: u64);
+
+    /// Records a single observation of the histogram `name`.
+    fn observe_histogram(&self, name: &'static str, value: f64);
+}
+
+/// Sink which discards all the metrics. This is the default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoopMetricsSink;
+
+impl MetricsSink for NoopMetricsSink {
+    fn increment_counter(&self, _name: &'static str, _label: Option<&'static str>, _value: u64) {}
+
+    fn observe_histogram(&self, _name: &'static str, _value: f64) {}
+}
+
+/// Sink which keeps all the metrics in memory. Useful for tests, or to be periodically scraped by
+/// the application.
+#[derive(Default)]
+pub struct InMemoryMetricsSink {
+    counters: Mutex<BTreeMap<(&'static str, Option<&'static str>), u64>>,
+    histograms: Mutex<BTreeMap<&'static str, Vec<f64>>>,
+}
+
+impl InMemoryMetricsSink {
+    /// Creates a new, empty sink.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the value of the counter `name` with the given `label`.
+    pub fn counter(&self, name: &str, label: Option<&str>) -> u64 {
+        self.counters
+            .lock()
+            .ok()
+            .and_then(|counters| {
+                counters
+                    .iter()
+                    .find(|((key_name, key_label), _)| *key_name == name && *key_label == label)
+                    .map(|(_, value)| *value)
+            })
+            .unwrap_or(0)
+    }
+
+    /// Returns the sum of the counter `name` over all its labels.
+    pub fn counter_total(&self, name: &str) -> u64 {
+        self.counters
+            .lock()
+            .map(|counters| {
+                counters
+                    .iter()
+                    .filter(|((key_name, _), _)| *key_name == name)
+                    .map(|(_, value)| *value)
+                    .sum()
+            })
+            .unwrap_or(0)
+    }
+
+    /// Returns all the observations of the histogram `name`, in the order they were recorded.
+    pub fn histogram(&self, name: &str) -> Vec<f64> {
+        self.histograms
+            .lock()
+            .ok()
+            .and_then(|histograms| histograms.get(name).cloned())
+            .unwrap_or_default()
+    }
+}
+
+impl MetricsSink for InMemoryMetricsSink {
+    fn increment_counter(&self, name: &'static str, label: Option<&'static str>, value: u64) {
+        if let Ok(mut counters) = self.counters.lock() {
+            *counters.entry((name, label)).or_insert(0) += value;
+        }
+    }
+
+    fn observe_histogram(&self, name: &'static str, value: f64) {
+        if let Ok(mut histograms) = self.histograms.lock() {
+            histograms.entry(name).or_insert_with(Vec::new).push(value);
+        }
+    }
+}
+
+impl Debug for InMemoryMetricsSink {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        formatter
+            .debug_struct("InMemoryMetricsSink")
+            .field("counters", &self.counters)
+            .field("histograms", &self.histograms)
+            .finish()
+    }
+}
+
+/// Handle to the metrics sink of a node, with helpers for the individual metrics.
+#[derive(Clone)]
+pub(crate) struct Metrics(Arc<dyn MetricsSink>);
+
+impl Metrics {
+    pub fn new(sink: Arc<dyn MetricsSink>) -> Self {
+        Self(sink)
+    }
+
+    pub fn message_sent(&self, variant: &Variant) {
+        self.0
+            .increment_counter(MESSAGES_SENT, Some(variant.name()), 1)
+    }
+
+    pub fn message_received(&self, variant: &Variant) {
+        self.0
+            .increment_counter(MESSAGES_RECEIVED, Some(variant.name()), 1)
+    }
+
+    pub fn message_resent(&self) {
+        self.0.increment_counter(MESSAGE_RESENDS, None, 1)
+    }
+
+    pub fn message_dropped(&self) {
+        self.0.increment_counter(MESSAGES_DROPPED, None, 1)
+    }
+
+    pub fn signatures_accumulated(&self, latency: Duration) {
+        self.0
+            .observe_histogram(SIGNATURE_ACCUMULATION_SECONDS, latency.as_secs_f64())
+    }
+
+    pub fn parsec_gossip_received(&self, bytes: u64) {
+        self.0.increment_counter(PARSEC_GOSSIP_BYTES, None, bytes)
+    }
+
+    pub fn filter_hit(&self, direction: &'static str) {
+        self.0.increment_counter(FILTER_HITS, Some(direction), 1)
+    }
+
+    pub fn churn_event(&self, kind: &'static str) {
+        self.0.increment_counter(CHURN_EVENTS, Some(kind), 1)
+    }
+
+    pub fn client_message_rejected(&self) {
+        self.0.increment_counter(CLIENT_MESSAGES_REJECTED, None, 1)
+    }
+
+    pub fn compression_saved(&self, bytes: u64) {
+        self.0
+            .increment_counter(COMPRESSION_BYTES_SAVED, None, bytes)
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self(Arc::new(NoopMetricsSink))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn in_memory_sink() {
+        let sink = Arc::new(InMemoryMetricsSink::new());
+        let metrics = Metrics::new(sink.clone());
+
+        metrics.message_sent(&Variant::Ping);
+        metrics.message_sent(&Variant::Ping);
+        metrics.message_sent(&Variant::UserMessage(vec![]));
+        metrics.parsec_gossip_received(10);
+        metrics.parsec_gossip_received(5);
+        metrics.signatures_accumulated(Duration::from_millis(500));
+        metrics.compression_saved(100);
+
+        assert_eq!(sink.counter(MESSAGES_SENT, Some("Ping")), 2);
+        assert_eq!(sink.counter(MESSAGES_SENT, Some("UserMessage")), 1);
+        assert_eq!(sink.counter_total(MESSAGES_SENT), 3);
+        assert_eq!(sink.counter(MESSAGES_RECEIVED, Some("Ping")), 0);
+        assert_eq!(sink.counter(PARSEC_GOSSIP_BYTES, None), 15);
+        assert_eq!(sink.histogram(SIGNATURE_ACCUMULATION_SECONDS), vec![0.5]);
+        assert_eq!(sink.counter(COMPRESSION_BYTES_SAVED, None), 100);
+    }
+}
@@ -52,6 +52,7 @@ pub mod signing {
         }
     }
 
+    #[derive(Serialize, Deserialize)]
     pub struct SecretKey([u8; SECRET_KEY_LENGTH]);
 
     impl SecretKey {
@@ -165,6 +166,7 @@ pub mod encryption {
         }
     }
 
+    #[derive(Clone, Serialize, Deserialize)]
     pub struct SecretKey([u8; KEY_LENGTH]);
 
     impl SecretKey {
@@ -189,6 +191,16 @@ pub mod encryption {
         }
     }
 
+    // Wrapper to explicitly opt in to serialising a secret key, mirroring the `bls` API.
+    #[derive(Serialize, Deserialize)]
+    pub struct SerdeSecret<T>(pub T);
+
+    impl<T> SerdeSecret<T> {
+        pub fn into_inner(self) -> T {
+            self.0
+        }
+    }
+
     #[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Serialize, Deserialize, Debug)]
     pub struct Ciphertext(Vec<u8>);
 
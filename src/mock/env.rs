@@ -10,9 +10,9 @@
 use crate::mock::parsec;
 use crate::{
     chain::NetworkParams,
-    quic_p2p::Network,
     rng::{self, MainRng, Seed, SeedPrinter},
 };
+use mock_quic_p2p::Network;
 use rand::SeedableRng;
 use std::{
     cell::RefCell,
@@ -68,7 +68,7 @@ impl Environment {
 
     /// Get the chain network config.
     pub fn network_cfg(&self) -> NetworkParams {
-        self.network_cfg
+        self.network_cfg.clone()
     }
 
     /// Get the number of elders
@@ -105,7 +105,7 @@ impl Clone for Environment {
         Self {
             rng: RefCell::new(self.new_rng()),
             network: self.network.clone(),
-            network_cfg: self.network_cfg,
+            network_cfg: self.network_cfg.clone(),
             seed_printer: None,
         }
     }
@@ -14,4 +14,5 @@ pub(crate) mod parsec;
 pub(crate) mod crypto;
 
 mod env;
-pub use self::env::Environment;
+mod transport;
+pub use self::{env::Environment, transport::MockTransport};
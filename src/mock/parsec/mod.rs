@@ -174,8 +174,12 @@ where
     pub fn handle_request(
         &mut self,
         _src: &S::PublicId,
-        _req: Request<T, S::PublicId>,
+        req: Request<T, S::PublicId>,
     ) -> Result<Response<T, S::PublicId>, Error> {
+        if req.1 != 1 {
+            return Err(Error::InvalidMessage);
+        }
+
         state::with(self.section_hash, |state| self.compute_consensus(state));
         Ok(Response::new())
     }
@@ -348,6 +352,12 @@ impl<T: NetworkEvent, P: PublicId> Request<T, P> {
     pub fn new() -> Self {
         Self(PhantomData, 1)
     }
+
+    // Creates a request which the recipient rejects as malformed.
+    #[cfg(test)]
+    pub fn new_invalid() -> Self {
+        Self(PhantomData, 0)
+    }
 }
 
 #[derive(Clone, Eq, PartialEq, Hash, Serialize, Deserialize, Debug, Default)]
@@ -363,6 +373,7 @@ impl<T: NetworkEvent, P: PublicId> Response<T, P> {
 pub enum Error {
     InvalidPeerState,
     DuplicateVote,
+    InvalidMessage,
 }
 
 #[derive(Clone, Copy)]
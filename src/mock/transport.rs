@@ -0,0 +1,81 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::{
+    error::RoutingError,
+    network_service::{EventSink, NetworkConfig, NetworkEvent, Peer, Token, Transport},
+};
+use bytes::Bytes;
+use crossbeam_channel::{self as mpmc, Receiver};
+use mock_quic_p2p::{Builder, EventSenders, OurType, QuicP2p};
+use std::net::SocketAddr;
+
+/// Transport over the mock network.
+///
+/// The mock network delivers the events when it's polled by the test. They are reported to the
+/// node only when it polls the transport, so the node handles them on its own thread, in a
+/// deterministic order.
+pub struct MockTransport {
+    inner: QuicP2p,
+    event_rx: Receiver<mock_quic_p2p::Event>,
+    events: EventSink,
+}
+
+impl MockTransport {
+    /// Creates the transport over the mock network. If `config` is `None`, it uses the default
+    /// config.
+    pub fn new(events: EventSink, config: Option<NetworkConfig>) -> Result<Self, RoutingError> {
+        let (node_tx, event_rx) = mpmc::unbounded();
+        let client_tx = node_tx.clone();
+
+        let builder = Builder::new(EventSenders { node_tx, client_tx });
+        let builder = if let Some(mut config) = config {
+            config.our_type = OurType::Node;
+            builder.with_config(config)
+        } else {
+            builder
+        };
+        let inner = builder
+            .build()
+            .map_err(|error| RoutingError::Network(format!("{:?}", error)))?;
+
+        Ok(Self {
+            inner,
+            event_rx,
+            events,
+        })
+    }
+}
+
+impl Transport for MockTransport {
+    fn bootstrap(&mut self) {
+        self.inner.bootstrap()
+    }
+
+    fn send(&mut self, peer: Peer, msg: Bytes, token: Token) {
+        self.inner.send(into_quic_p2p_peer(peer), msg, token)
+    }
+
+    fn disconnect_from(&mut self, addr: SocketAddr) {
+        self.inner.disconnect_from(addr)
+    }
+
+    fn our_connection_info(&mut self) -> Result<SocketAddr, RoutingError> {
+        self.inner
+            .our_connection_info()
+            .map_err(|error| RoutingError::Network(format!("{:?}", error)))
+    }
+
+    fn poll(&mut self) {
+        for event in self.event_rx.try_iter() {
+            let _ = self.events.send(from_quic_p2p_event(event));
+        }
+    }
+}
+
+quic_p2p_conversions!(mock_quic_p2p);
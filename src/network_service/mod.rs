@@ -6,31 +6,62 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+mod scheduler;
 mod sending_targets_cache;
+mod transport;
 
-pub use sending_targets_cache::{Resend, RESEND_DELAY, RESEND_MAX_ATTEMPTS};
+pub use scheduler::{MAX_IN_FLIGHT_PER_PEER, MAX_QUEUED_PER_PEER, MAX_QUEUED_USER_MESSAGES};
+pub use sending_targets_cache::{Resend, ResendConfig, RESEND_DELAY, RESEND_MAX_ATTEMPTS};
+pub use transport::{
+    default_transport, EventSink, MakeTransport, NetworkConfig, NetworkEvent, Peer, Token,
+    Transport,
+};
 
 use crate::{
-    quic_p2p::{Builder, EventSenders, Peer, QuicP2p, QuicP2pError, Token},
-    NetworkConfig,
+    client::ClientSessions,
+    delivery::PendingAcks,
+    error::RoutingError,
+    fragment::Reassembler,
+    messages::{envelope, Priority},
+    metrics::Metrics,
 };
 use bytes::Bytes;
-use std::{collections::HashMap, net::SocketAddr};
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+};
 
+use scheduler::{Push, Scheduler};
 use sending_targets_cache::SendingTargetsCache;
 
 /// Struct that handles network operations: sending and receiving messages, as well as resending on
 /// failure.
 pub struct NetworkService {
-    quic_p2p: QuicP2p,
+    transport: Box<dyn Transport>,
     cache: SendingTargetsCache,
+    scheduler: Scheduler,
+    // Whether we rejected a user message because the outbound queues were full and haven't told
+    // the user they can send again yet.
+    backpressure: bool,
     next_msg_token: Token,
     scheduled_messages: HashMap<u64, ScheduledMessage>,
+    // Peers we already told we don't speak the version of their messages.
+    version_mismatch_sent: HashSet<SocketAddr>,
+    clients: ClientSessions,
+    pending_acks: PendingAcks,
+    reassembler: Reassembler,
+    metrics: Metrics,
 }
 
 impl NetworkService {
-    pub fn service_mut(&mut self) -> &mut QuicP2p {
-        &mut self.quic_p2p
+    pub fn bootstrap(&mut self) {
+        self.transport.bootstrap()
+    }
+
+    /// Lets the mock transport report the events which occurred since the last call.
+    #[cfg(feature = "mock_base")]
+    pub fn poll_transport(&mut self) {
+        self.transport.poll()
     }
 
     pub fn next_msg_token(&mut self) -> Token {
@@ -38,6 +69,34 @@ impl NetworkService {
         self.next_msg_token
     }
 
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    pub fn set_metrics(&mut self, metrics: Metrics) {
+        self.metrics = metrics;
+    }
+
+    pub fn clients(&self) -> &ClientSessions {
+        &self.clients
+    }
+
+    pub fn clients_mut(&mut self) -> &mut ClientSessions {
+        &mut self.clients
+    }
+
+    pub fn pending_acks_mut(&mut self) -> &mut PendingAcks {
+        &mut self.pending_acks
+    }
+
+    pub fn reassembler(&self) -> &Reassembler {
+        &self.reassembler
+    }
+
+    pub fn reassembler_mut(&mut self) -> &mut Reassembler {
+        &mut self.reassembler
+    }
+
     pub fn targets_cache_mut(&mut self) -> &mut SendingTargetsCache {
         &mut self.cache
     }
@@ -61,12 +120,84 @@ impl NetworkService {
         token
     }
 
-    pub fn target_failed(&mut self, msg_token: Token, failed_target: SocketAddr) -> Resend {
-        self.cache.target_failed(msg_token, failed_target)
+    /// Handles a failure to send the message to the target. `random` is uniformly distributed in
+    /// `[0, 1)` and determines the jitter of the resend delay.
+    pub fn target_failed(
+        &mut self,
+        msg_token: Token,
+        failed_target: SocketAddr,
+        random: f64,
+    ) -> Resend {
+        self.cache
+            .target_failed(msg_token, failed_target, random, &self.metrics)
     }
 
+    /// Sends the message to the node, or queues it if too many messages to the node are already
+    /// in flight.
     pub fn send_now(&mut self, target: SocketAddr, content: Bytes, token: Token) {
-        self.quic_p2p.send(Peer::Node(target), content, token)
+        let priority = envelope::priority(&content);
+        self.schedule(Peer::Node(target), priority, content, token)
+    }
+
+    /// Number of user messages waiting in the outbound queues.
+    pub fn queued_user_messages(&self) -> usize {
+        self.scheduler.queued_bulk()
+    }
+
+    /// Fails with `RoutingError::Backpressure` if the outbound queues have no room for more user
+    /// messages.
+    pub fn check_user_message_capacity(&mut self) -> Result<(), RoutingError> {
+        if self.scheduler.queued_bulk() < MAX_QUEUED_USER_MESSAGES {
+            Ok(())
+        } else {
+            self.backpressure = true;
+            Err(RoutingError::Backpressure)
+        }
+    }
+
+    /// Returns `true` once the outbound queues have drained to half their capacity after a user
+    /// message was rejected with `RoutingError::Backpressure`.
+    pub fn take_ready_to_send(&mut self) -> bool {
+        if self.backpressure && self.scheduler.queued_bulk() <= MAX_QUEUED_USER_MESSAGES / 2 {
+            self.backpressure = false;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Handles the transport reporting the result of sending a message to the peer, sending the
+    /// next queued message to it.
+    pub fn send_completed(&mut self, target: Peer) {
+        if let Some(msg) = self.scheduler.completed(target) {
+            self.transmit(target, msg.content, msg.token)
+        }
+    }
+
+    /// Handles losing the connection to the peer. The transport might not report the results of
+    /// the messages in flight, so we stop waiting for them. The transport reconnects to nodes, so
+    /// the queued messages to a node are sent, but the ones to a client are dropped.
+    pub fn connection_failed(&mut self, target: Peer) {
+        match target {
+            Peer::Node(_) => {
+                for msg in self.scheduler.reset(target) {
+                    self.transmit(target, msg.content, msg.token)
+                }
+            }
+            Peer::Client(_) => self.scheduler.remove(&target),
+        }
+    }
+
+    /// Sends the message to the client, or queues it if too many messages to the client are
+    /// already in flight.
+    pub fn send_to_client(
+        &mut self,
+        target: SocketAddr,
+        priority: Priority,
+        content: Bytes,
+        token: Token,
+    ) {
+        self.schedule(Peer::Client(target), priority, content, token)
     }
 
     pub fn send_later(
@@ -86,18 +217,52 @@ impl NetworkService {
         );
     }
 
-    pub fn our_connection_info(&mut self) -> Result<SocketAddr, QuicP2pError> {
-        self.quic_p2p.our_connection_info()
+    pub fn our_connection_info(&mut self) -> Result<SocketAddr, RoutingError> {
+        self.transport.our_connection_info()
     }
 
     pub fn disconnect(&mut self, addr: SocketAddr) {
-        self.quic_p2p.disconnect_from(addr)
+        let _ = self.version_mismatch_sent.remove(&addr);
+        self.scheduler.remove(&Peer::Node(addr));
+        self.scheduler.remove(&Peer::Client(addr));
+        self.cache.forget_target(&addr);
+        self.transport.disconnect_from(addr)
+    }
+
+    /// Tells the peer we rejected its message because we don't speak its protocol version. Only
+    /// the first rejected message on a connection is answered.
+    pub fn send_version_mismatch(&mut self, target: SocketAddr) {
+        if self.version_mismatch_sent.insert(target) {
+            let _ =
+                self.send_message_to_initial_targets(&[target], 1, envelope::version_mismatch());
+        }
+    }
+
+    fn schedule(&mut self, target: Peer, priority: Priority, content: Bytes, token: Token) {
+        match self.scheduler.push(target, priority, content, token) {
+            Push::Send(msg) => self.transmit(target, msg.content, msg.token),
+            Push::Queued => (),
+            Push::Dropped(msg) => {
+                debug!(
+                    "Too many queued messages to {:?}, dropping message ID {}",
+                    target, msg.token
+                );
+                self.metrics.message_dropped();
+            }
+        }
+    }
+
+    fn transmit(&mut self, target: Peer, content: Bytes, token: Token) {
+        let saved = envelope::compression_saving(&content);
+        if saved > 0 {
+            self.metrics.compression_saved(saved);
+        }
+        self.transport.send(target, content, token)
     }
 
     pub fn handle_timeout(&mut self, timer_token: u64) -> bool {
         if let Some(msg) = self.scheduled_messages.remove(&timer_token) {
-            self.quic_p2p
-                .send(Peer::Node(msg.target), msg.content, msg.token);
+            self.send_now(msg.target, msg.content, msg.token);
             true
         } else {
             false
@@ -105,29 +270,53 @@ impl NetworkService {
     }
 }
 
+#[derive(Default)]
 pub struct NetworkBuilder {
-    quic_p2p: Builder,
+    config: Option<NetworkConfig>,
+    transport: Option<MakeTransport>,
 }
 
 impl NetworkBuilder {
-    pub fn new(event_tx: EventSenders) -> Self {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Configures the default transport. Ignored if `with_transport` is used.
+    pub fn with_config(self, config: NetworkConfig) -> Self {
         Self {
-            quic_p2p: Builder::new(event_tx),
+            config: Some(config),
+            ..self
         }
     }
 
-    pub fn with_config(self, config: NetworkConfig) -> Self {
+    /// Uses the transport created by `make_transport` instead of the default one.
+    pub fn with_transport(self, make_transport: MakeTransport) -> Self {
         Self {
-            quic_p2p: self.quic_p2p.with_config(config),
+            transport: Some(make_transport),
+            ..self
         }
     }
 
-    pub fn build(self) -> Result<NetworkService, QuicP2pError> {
+    /// Creates the network service, with the transport reporting its events into `events`.
+    pub fn build(self, events: EventSink) -> Result<NetworkService, RoutingError> {
+        let transport = if let Some(make_transport) = self.transport {
+            make_transport(events)?
+        } else {
+            default_transport(events, self.config)?
+        };
+
         Ok(NetworkService {
-            quic_p2p: self.quic_p2p.build()?,
+            transport,
             cache: Default::default(),
+            scheduler: Default::default(),
+            backpressure: false,
             next_msg_token: 0,
             scheduled_messages: Default::default(),
+            version_mismatch_sent: Default::default(),
+            clients: Default::default(),
+            pending_acks: Default::default(),
+            reassembler: Default::default(),
+            metrics: Default::default(),
         })
     }
 }
@@ -137,3 +326,86 @@ struct ScheduledMessage {
     token: Token,
     target: SocketAddr,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unwrap;
+    use crossbeam_channel as mpmc;
+    use std::{cell::RefCell, rc::Rc};
+
+    type Sent = Rc<RefCell<Vec<(Peer, Bytes, Token)>>>;
+
+    // Transport which only records what it's asked to send.
+    struct RecordingTransport {
+        sent: Sent,
+    }
+
+    impl Transport for RecordingTransport {
+        fn bootstrap(&mut self) {}
+
+        fn send(&mut self, peer: Peer, msg: Bytes, token: Token) {
+            self.sent.borrow_mut().push((peer, msg, token))
+        }
+
+        fn disconnect_from(&mut self, _addr: SocketAddr) {}
+
+        fn our_connection_info(&mut self) -> Result<SocketAddr, RoutingError> {
+            Err(RoutingError::InvalidState)
+        }
+    }
+
+    fn recording_network_service() -> (NetworkService, Sent) {
+        let sent = Sent::default();
+        let transport = RecordingTransport { sent: sent.clone() };
+        let (event_tx, _) = mpmc::unbounded();
+        let network_service = unwrap!(NetworkBuilder::new()
+            .with_transport(Box::new(move |_| Ok(Box::new(transport))))
+            .build(EventSink::new(event_tx)));
+        (network_service, sent)
+    }
+
+    #[test]
+    fn send_through_custom_transport() {
+        let (mut network_service, sent) = recording_network_service();
+
+        let targets: Vec<SocketAddr> = vec![
+            unwrap!("127.0.0.1:5000".parse()),
+            unwrap!("127.0.0.1:5001".parse()),
+            unwrap!("127.0.0.1:5002".parse()),
+        ];
+        let client: SocketAddr = unwrap!("127.0.0.1:6000".parse());
+        let msg = Bytes::from_static(b"message");
+
+        let token = network_service.send_message_to_initial_targets(&targets, 2, msg.clone());
+        network_service.send_to_client(client, Priority::Bulk, msg.clone(), token);
+
+        assert_eq!(
+            *sent.borrow(),
+            vec![
+                (Peer::Node(targets[0]), msg.clone(), token),
+                (Peer::Node(targets[1]), msg.clone(), token),
+                (Peer::Client(client), msg, token),
+            ]
+        );
+    }
+
+    #[test]
+    fn send_version_mismatch_once_per_connection() {
+        let (mut network_service, sent) = recording_network_service();
+        let peer: SocketAddr = unwrap!("127.0.0.1:5000".parse());
+
+        network_service.send_version_mismatch(peer);
+        network_service.send_completed(Peer::Node(peer));
+        network_service.send_version_mismatch(peer);
+        assert_eq!(sent.borrow().len(), 1);
+        assert_eq!(
+            envelope::supported_versions(&sent.borrow()[0].1),
+            Some(envelope::MIN_PROTOCOL_VERSION..=envelope::PROTOCOL_VERSION)
+        );
+
+        network_service.disconnect(peer);
+        network_service.send_version_mismatch(peer);
+        assert_eq!(sent.borrow().len(), 2);
+    }
+}
@@ -0,0 +1,289 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::{
+    messages::Priority,
+    network_service::{Peer, Token},
+};
+use bytes::Bytes;
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    iter,
+};
+
+/// Maximal number of messages handed to the transport for the same peer whose send result hasn't
+/// been reported yet. Further messages to the peer wait in its queues.
+pub const MAX_IN_FLIGHT_PER_PEER: usize = 16;
+/// Maximal number of user messages waiting in the outbound queues of all the peers. Once reached,
+/// sending further user messages fails with `RoutingError::Backpressure`.
+pub const MAX_QUEUED_USER_MESSAGES: usize = 1024;
+/// Maximal number of messages of each priority class waiting in the outbound queues of the same
+/// peer. Once reached, the oldest queued message of the class is dropped to make room for a new
+/// one. This bounds the messages we relay or send to clients, which are not subject to
+/// `MAX_QUEUED_USER_MESSAGES`.
+pub const MAX_QUEUED_PER_PEER: usize = 512;
+
+/// Message waiting to be handed to the transport.
+pub struct QueuedMessage {
+    pub content: Bytes,
+    pub token: Token,
+}
+
+/// Result of adding a message to the queues of a peer.
+pub enum Push {
+    /// The message can be sent right away.
+    Send(QueuedMessage),
+    /// The message was queued.
+    Queued,
+    /// The message was queued, and the oldest queued message of its class dropped to make room.
+    Dropped(QueuedMessage),
+}
+
+/// Outbound queues of every peer. A message is handed to the transport only once the number of
+/// messages in flight to its peer drops below `MAX_IN_FLIGHT_PER_PEER`, the queued messages of
+/// the highest priority class first.
+#[derive(Default)]
+pub struct Scheduler {
+    peers: HashMap<Peer, PeerQueues>,
+    // Number of queued messages of the `Bulk` priority class, over all the peers.
+    queued_bulk: usize,
+}
+
+#[derive(Default)]
+struct PeerQueues {
+    in_flight: usize,
+    queues: BTreeMap<Priority, VecDeque<QueuedMessage>>,
+}
+
+impl PeerQueues {
+    fn pop(&mut self) -> Option<(Priority, QueuedMessage)> {
+        let msg = self
+            .queues
+            .iter_mut()
+            .rev()
+            .find_map(|(priority, queue)| Some((*priority, queue.pop_front()?)))?;
+        self.in_flight += 1;
+        Some(msg)
+    }
+
+    fn queued(&self, priority: Priority) -> usize {
+        self.queues.get(&priority).map(VecDeque::len).unwrap_or(0)
+    }
+
+    fn is_idle(&self) -> bool {
+        self.in_flight == 0 && self.queues.values().all(VecDeque::is_empty)
+    }
+}
+
+impl Scheduler {
+    /// Adds a message for the peer.
+    pub fn push(&mut self, peer: Peer, priority: Priority, content: Bytes, token: Token) -> Push {
+        let peer = self.peers.entry(peer).or_default();
+        let msg = QueuedMessage { content, token };
+
+        if peer.in_flight < MAX_IN_FLIGHT_PER_PEER {
+            peer.in_flight += 1;
+            return Push::Send(msg);
+        }
+
+        let queue = peer.queues.entry(priority).or_default();
+        let dropped = if queue.len() < MAX_QUEUED_PER_PEER {
+            if priority == Priority::Bulk {
+                self.queued_bulk += 1;
+            }
+            None
+        } else {
+            queue.pop_front()
+        };
+        queue.push_back(msg);
+
+        dropped.map_or(Push::Queued, Push::Dropped)
+    }
+
+    /// Number of queued user messages, over all the peers.
+    pub fn queued_bulk(&self) -> usize {
+        self.queued_bulk
+    }
+
+    /// Handles the result of sending a message to the peer being reported. Returns the next
+    /// message to send to it, if any.
+    pub fn completed(&mut self, peer: Peer) -> Option<QueuedMessage> {
+        let queues = self.peers.get_mut(&peer)?;
+        queues.in_flight = queues.in_flight.saturating_sub(1);
+
+        let msg = queues.pop();
+        if queues.is_idle() {
+            let _ = self.peers.remove(&peer);
+        }
+
+        let (priority, msg) = msg?;
+        if priority == Priority::Bulk {
+            self.queued_bulk -= 1;
+        }
+        Some(msg)
+    }
+
+    /// Handles losing the connection to the peer. The results of the messages in flight might
+    /// never be reported, so stops waiting for them and returns the messages to send instead.
+    pub fn reset(&mut self, peer: Peer) -> Vec<QueuedMessage> {
+        let queues = match self.peers.get_mut(&peer) {
+            Some(queues) => queues,
+            None => return Vec::new(),
+        };
+
+        queues.in_flight = 0;
+        let msgs: Vec<_> = iter::from_fn(|| queues.pop())
+            .take(MAX_IN_FLIGHT_PER_PEER)
+            .collect();
+        if queues.is_idle() {
+            let _ = self.peers.remove(&peer);
+        }
+
+        self.queued_bulk -= msgs
+            .iter()
+            .filter(|(priority, _)| *priority == Priority::Bulk)
+            .count();
+        msgs.into_iter().map(|(_, msg)| msg).collect()
+    }
+
+    /// Drops the queued messages to the peer.
+    pub fn remove(&mut self, peer: &Peer) {
+        if let Some(queues) = self.peers.remove(peer) {
+            self.queued_bulk -= queues.queued(Priority::Bulk);
+        }
+    }
+}
+
+#[cfg(test)]
+impl Push {
+    fn is_send(&self) -> bool {
+        match self {
+            Self::Send(_) => true,
+            Self::Queued | Self::Dropped(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unwrap;
+
+    #[test]
+    fn queue_when_saturated() {
+        let mut scheduler = Scheduler::default();
+        let peer = Peer::Node(unwrap!("127.0.0.1:5000".parse()));
+        let other_peer = Peer::Node(unwrap!("127.0.0.1:5001".parse()));
+
+        for token in 0..MAX_IN_FLIGHT_PER_PEER as Token {
+            assert!(scheduler
+                .push(peer, Priority::Bulk, Bytes::new(), token)
+                .is_send());
+        }
+
+        let token = MAX_IN_FLIGHT_PER_PEER as Token;
+        assert!(!scheduler
+            .push(peer, Priority::Bulk, Bytes::new(), token)
+            .is_send());
+        // Other peers are not affected.
+        assert!(scheduler
+            .push(other_peer, Priority::Bulk, Bytes::new(), token)
+            .is_send());
+
+        assert_eq!(unwrap!(scheduler.completed(peer)).token, token);
+        assert!(scheduler.completed(peer).is_none());
+    }
+
+    #[test]
+    fn send_higher_priority_first() {
+        let mut scheduler = Scheduler::default();
+        let peer = Peer::Node(unwrap!("127.0.0.1:5000".parse()));
+
+        for token in 0..MAX_IN_FLIGHT_PER_PEER as Token {
+            let _ = scheduler.push(peer, Priority::Bulk, Bytes::new(), token);
+        }
+
+        let _ = scheduler.push(peer, Priority::Bulk, Bytes::new(), 100);
+        let _ = scheduler.push(peer, Priority::Consensus, Bytes::new(), 101);
+        let _ = scheduler.push(peer, Priority::Bulk, Bytes::new(), 102);
+        let _ = scheduler.push(peer, Priority::Control, Bytes::new(), 103);
+
+        assert_eq!(scheduler.queued_bulk(), 2);
+
+        let tokens: Vec<_> = (0..4)
+            .filter_map(|_| scheduler.completed(peer))
+            .map(|msg| msg.token)
+            .collect();
+        assert_eq!(tokens, vec![103, 101, 100, 102]);
+        assert_eq!(scheduler.queued_bulk(), 0);
+    }
+
+    #[test]
+    fn release_queued_messages_on_reset() {
+        let mut scheduler = Scheduler::default();
+        let peer = Peer::Node(unwrap!("127.0.0.1:5000".parse()));
+
+        for token in 0..2 * MAX_IN_FLIGHT_PER_PEER as Token {
+            let _ = scheduler.push(peer, Priority::Bulk, Bytes::new(), token);
+        }
+
+        // Only the queued messages are released. The ones in flight are not sent again.
+        let msgs = scheduler.reset(peer);
+        assert_eq!(msgs.len(), MAX_IN_FLIGHT_PER_PEER);
+        assert_eq!(msgs[0].token, MAX_IN_FLIGHT_PER_PEER as Token);
+        assert!(scheduler.reset(peer).is_empty());
+        assert_eq!(scheduler.queued_bulk(), 0);
+
+        for token in 0..2 * MAX_IN_FLIGHT_PER_PEER as Token {
+            let _ = scheduler.push(peer, Priority::Bulk, Bytes::new(), token);
+        }
+        assert_eq!(scheduler.queued_bulk(), MAX_IN_FLIGHT_PER_PEER);
+        scheduler.remove(&peer);
+        assert_eq!(scheduler.queued_bulk(), 0);
+        assert!(scheduler.reset(peer).is_empty());
+        assert!(scheduler.completed(peer).is_none());
+    }
+
+    #[test]
+    fn drop_oldest_over_peer_limit() {
+        let mut scheduler = Scheduler::default();
+        let peer = Peer::Node(unwrap!("127.0.0.1:5000".parse()));
+        let client = Peer::Client(unwrap!("127.0.0.1:5000".parse()));
+
+        for token in 0..MAX_IN_FLIGHT_PER_PEER as Token {
+            let _ = scheduler.push(peer, Priority::Control, Bytes::new(), token);
+        }
+        for token in 0..MAX_QUEUED_PER_PEER as Token {
+            let _ = scheduler.push(peer, Priority::Control, Bytes::new(), token);
+            let _ = scheduler.push(peer, Priority::Bulk, Bytes::new(), token);
+        }
+        assert_eq!(scheduler.queued_bulk(), MAX_QUEUED_PER_PEER);
+
+        let token = MAX_QUEUED_PER_PEER as Token;
+        match scheduler.push(peer, Priority::Control, Bytes::new(), token) {
+            Push::Dropped(msg) => assert_eq!(msg.token, 0),
+            Push::Send(_) | Push::Queued => panic!("message not dropped"),
+        }
+        match scheduler.push(peer, Priority::Bulk, Bytes::new(), token) {
+            Push::Dropped(msg) => assert_eq!(msg.token, 0),
+            Push::Send(_) | Push::Queued => panic!("message not dropped"),
+        }
+        assert_eq!(scheduler.queued_bulk(), MAX_QUEUED_PER_PEER);
+
+        // The queues of the other classes and peers are not affected.
+        match scheduler.push(peer, Priority::Consensus, Bytes::new(), token) {
+            Push::Queued => (),
+            Push::Send(_) | Push::Dropped(_) => panic!("message not queued"),
+        }
+        assert!(scheduler
+            .push(client, Priority::Bulk, Bytes::new(), token)
+            .is_send());
+
+        assert_eq!(unwrap!(scheduler.completed(peer)).token, 1);
+    }
+}
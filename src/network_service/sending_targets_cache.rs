@@ -6,18 +6,69 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::{quic_p2p::Token, time::Duration};
-use std::{collections::HashMap, net::SocketAddr};
+use crate::{
+    metrics::Metrics,
+    network_service::Token,
+    time::{Duration, Instant},
+};
+use std::{cmp::Ordering, collections::HashMap, net::SocketAddr};
 
-/// Maximal number of resend attempts to the same target.
+/// Default maximal number of resend attempts to the same target.
 pub const RESEND_MAX_ATTEMPTS: u8 = 3;
-/// Delay before attempting to resend a previously failed message.
+/// Default delay before attempting to resend a previously failed message for the first time.
 pub const RESEND_DELAY: Duration = Duration::from_secs(10);
+/// Default upper bound of the delay before attempting to resend a previously failed message.
+pub const RESEND_MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// Parameters of resending a message to other targets after sending it failed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ResendConfig {
+    /// Maximal number of send attempts to the same target.
+    pub max_attempts: u8,
+    /// Delay before resending to a target which failed once. It doubles with every further
+    /// failure of the target.
+    pub base_delay: Duration,
+    /// Upper bound of the delay before resending.
+    pub max_delay: Duration,
+    /// Fraction, between 0 and 1, of the delay which is randomly taken off it, so the resends of
+    /// messages which failed together don't all happen at the same time.
+    pub jitter: f64,
+}
+
+impl Default for ResendConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: RESEND_MAX_ATTEMPTS,
+            base_delay: RESEND_DELAY,
+            max_delay: RESEND_MAX_DELAY,
+            jitter: 0.25,
+        }
+    }
+}
+
+impl ResendConfig {
+    /// Delay before resending to a target which failed `failed_attempts` times, before the jitter
+    /// is taken off.
+    pub fn backoff(&self, failed_attempts: u8) -> Duration {
+        let exponent = u32::from(failed_attempts.saturating_sub(1)).min(31);
+        self.base_delay
+            .checked_mul(1 << exponent)
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay)
+    }
+
+    // `random` is uniformly distributed in `[0, 1)`.
+    fn delay(&self, failed_attempts: u8, random: f64) -> Duration {
+        let jitter = self.jitter.max(0.0).min(1.0) * random.max(0.0).min(1.0);
+        self.backoff(failed_attempts).mul_f64(1.0 - jitter)
+    }
+}
 
 enum TargetState {
     /// we don't know whether the last send attempt succeeded or failed
-    /// the stored number of attempts already failed before
-    Sending(u8),
+    /// the stored number of attempts already failed before, and when the attempt was made unless
+    /// it was delayed
+    Sending(u8, Option<Instant>),
     /// the last sending attempt (if any) failed; in total, the stored number of attempts failed
     Failed(u8),
     /// sending to this target succeeded
@@ -28,22 +79,54 @@ impl TargetState {
     pub fn is_sending(&self) -> bool {
         match *self {
             Self::Failed(_) | Self::Sent => false,
-            Self::Sending(_) => true,
+            Self::Sending(..) => true,
         }
     }
 }
 
+// Results of sending to a target, over all the messages.
+#[derive(Default)]
+struct TargetStats {
+    successes: u32,
+    failures: u32,
+    // Moving average of the time between sending a message and the report of its success.
+    latency: Option<Duration>,
+}
+
+impl TargetStats {
+    // Estimated probability that sending succeeds. It is 1/2 for targets we know nothing about.
+    fn success_rate(&self) -> f64 {
+        let successes = f64::from(self.successes);
+        let failures = f64::from(self.failures);
+        (successes + 1.0) / (successes + failures + 2.0)
+    }
+
+    fn record_latency(&mut self, sample: Duration) {
+        self.latency = Some(match self.latency {
+            Some(latency) => (latency * 7 + sample) / 8,
+            None => sample,
+        });
+    }
+}
+
 #[derive(Default)]
 pub struct SendingTargetsCache {
     cache: HashMap<Token, Vec<(SocketAddr, TargetState)>>,
+    stats: HashMap<SocketAddr, TargetStats>,
+    config: ResendConfig,
 }
 
 impl SendingTargetsCache {
+    pub fn set_config(&mut self, config: ResendConfig) {
+        self.config = config;
+    }
+
     pub fn insert_message(&mut self, token: Token, initial_targets: &[SocketAddr], dg_size: usize) {
         // When a message is inserted into the cache initially, we are only sending it to `dg_size`
         // targets with the highest priority - thus, we will set the first `dg_size` targets'
         // states to Sending(0), and the rest to Failed(0) (indicating that we haven't sent to
         // them, and so they haven't failed yet)
+        let now = Instant::now();
         let targets = initial_targets
             .iter()
             .enumerate()
@@ -51,7 +134,7 @@ impl SendingTargetsCache {
                 (
                     *tgt_info,
                     if idx < dg_size {
-                        TargetState::Sending(0)
+                        TargetState::Sending(0, Some(now))
                     } else {
                         TargetState::Failed(0)
                     },
@@ -61,28 +144,32 @@ impl SendingTargetsCache {
         let _ = self.cache.insert(token, targets);
     }
 
-    fn target_states(&self, token: Token) -> impl Iterator<Item = &(SocketAddr, TargetState)> {
-        self.cache.get(&token).into_iter().flatten()
+    /// Forgets the results of sending to the target.
+    pub fn forget_target(&mut self, target: &SocketAddr) {
+        let _ = self.stats.remove(target);
     }
 
-    fn target_states_mut(
-        &mut self,
-        token: Token,
-    ) -> impl Iterator<Item = &mut (SocketAddr, TargetState)> {
-        self.cache.get_mut(&token).into_iter().flatten()
+    fn target_states(&self, token: Token) -> impl Iterator<Item = &(SocketAddr, TargetState)> {
+        self.cache.get(&token).into_iter().flatten()
     }
 
     fn fail_target(&mut self, token: Token, target: SocketAddr) {
         if let Some((_addr, state)) = self
-            .target_states_mut(token)
+            .cache
+            .get_mut(&token)
+            .into_iter()
+            .flatten()
             .find(|(addr, _state)| *addr == target)
         {
             match *state {
                 TargetState::Failed(_) => {
                     log_or_panic!(log::Level::Error, "Got a failure from a failed target!");
                 }
-                TargetState::Sending(x) => {
+                TargetState::Sending(x, _) => {
                     *state = TargetState::Failed(x + 1);
+
+                    let stats = self.stats.entry(target).or_default();
+                    stats.failures = stats.failures.saturating_add(1);
                 }
                 TargetState::Sent => {
                     log_or_panic!(
@@ -95,29 +182,70 @@ impl SendingTargetsCache {
     }
 
     /// Finds a Failed target with the lowest number of failed attempts so far. If there are
-    /// multiple possibilities, the one with the highest priority (earliest in the list) is taken.
-    /// Returns `Never` if no such targets exist.
-    fn take_next_target(&mut self, token: Token) -> Resend {
-        if let Some((addr, failed_attempts, state)) = self
-            .target_states_mut(token)
-            .filter_map(|(addr, state)| match state {
-                TargetState::Failed(x) if *x < RESEND_MAX_ATTEMPTS => Some((*addr, *x, state)),
-                TargetState::Failed(_) | TargetState::Sending(_) | TargetState::Sent => None,
+    /// multiple possibilities, the most promising one according to the past results of sending to
+    /// them is taken, and if that doesn't decide, the one with the highest priority (earliest in
+    /// the list). Returns `Never` if no such targets exist.
+    fn take_next_target(&mut self, token: Token, random: f64) -> Resend {
+        let max_attempts = self.config.max_attempts;
+        let next = self
+            .target_states(token)
+            .enumerate()
+            .filter_map(|(index, (addr, state))| match state {
+                TargetState::Failed(x) if *x < max_attempts => Some((index, *addr, *x)),
+                TargetState::Failed(_) | TargetState::Sending(..) | TargetState::Sent => None,
             })
-            .min_by_key(|(_addr, failed_attempts, _state)| *failed_attempts)
+            .min_by(|(_, addr0, attempts0), (_, addr1, attempts1)| {
+                attempts0
+                    .cmp(attempts1)
+                    .then_with(|| self.compare_targets(addr0, addr1))
+            });
+
+        let (index, addr, failed_attempts) = if let Some(next) = next {
+            next
+        } else {
+            return Resend::Never;
+        };
+
+        // The latency is measured only for the attempts made right away.
+        let sent_at = if failed_attempts == 0 {
+            Some(Instant::now())
+        } else {
+            None
+        };
+        if let Some((_addr, state)) = self
+            .cache
+            .get_mut(&token)
+            .and_then(|targets| targets.get_mut(index))
         {
-            *state = TargetState::Sending(failed_attempts);
+            *state = TargetState::Sending(failed_attempts, sent_at);
+        }
 
-            if failed_attempts == 0 {
-                Resend::Now(addr)
-            } else {
-                Resend::Later(addr, RESEND_DELAY)
-            }
+        if failed_attempts == 0 {
+            Resend::Now(addr)
         } else {
-            Resend::Never
+            Resend::Later(addr, self.config.delay(failed_attempts, random))
         }
     }
 
+    // Orders the targets from the most to the least promising: the higher estimated success rate
+    // first, then the lower latency, with the targets of unknown latency last.
+    fn compare_targets(&self, addr0: &SocketAddr, addr1: &SocketAddr) -> Ordering {
+        let default = TargetStats::default();
+        let stats0 = self.stats.get(addr0).unwrap_or(&default);
+        let stats1 = self.stats.get(addr1).unwrap_or(&default);
+
+        stats1
+            .success_rate()
+            .partial_cmp(&stats0.success_rate())
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| match (stats0.latency, stats1.latency) {
+                (Some(latency0), Some(latency1)) => latency0.cmp(&latency1),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            })
+    }
+
     fn should_drop(&self, token: Token) -> bool {
         // Other methods maintain the invariant that exactly one of these is true:
         // - some target is in the Sending state
@@ -128,25 +256,58 @@ impl SendingTargetsCache {
             .all(|(_info, state)| !state.is_sending())
     }
 
-    pub fn target_failed(&mut self, token: Token, target: SocketAddr) -> Resend {
+    /// Handles a failure to send the message to the target. `random` is uniformly distributed in
+    /// `[0, 1)` and determines the jitter of the resend delay.
+    pub fn target_failed(
+        &mut self,
+        token: Token,
+        target: SocketAddr,
+        random: f64,
+        metrics: &Metrics,
+    ) -> Resend {
         self.fail_target(token, target);
 
-        let next_target = self.take_next_target(token);
+        let next_target = self.take_next_target(token, random);
 
         if self.should_drop(token) {
-            let _ = self.cache.remove(&token);
+            if let Some(targets) = self.cache.remove(&token) {
+                let sent = targets.iter().any(|(_addr, state)| match state {
+                    TargetState::Sent => true,
+                    TargetState::Sending(..) | TargetState::Failed(_) => false,
+                });
+                if !sent {
+                    metrics.message_dropped();
+                }
+            }
+        }
+
+        if let Resend::Now(_) | Resend::Later(..) = next_target {
+            metrics.message_resent();
         }
 
         next_target
     }
 
     pub fn target_succeeded(&mut self, token: Token, target: SocketAddr) {
-        let _ = self
-            .target_states_mut(token)
+        if let Some((_addr, state)) = self
+            .cache
+            .get_mut(&token)
+            .into_iter()
+            .flatten()
             .find(|(addr, _state)| *addr == target)
-            .map(|(_addr, state)| {
-                *state = TargetState::Sent;
-            });
+        {
+            let latency = match state {
+                TargetState::Sending(_, Some(sent_at)) => Some(sent_at.elapsed()),
+                TargetState::Sending(_, None) | TargetState::Failed(_) | TargetState::Sent => None,
+            };
+            *state = TargetState::Sent;
+
+            let stats = self.stats.entry(target).or_default();
+            stats.successes = stats.successes.saturating_add(1);
+            if let Some(latency) = latency {
+                stats.record_latency(latency);
+            }
+        }
         if self.should_drop(token) {
             let _ = self.cache.remove(&token);
         }
@@ -162,3 +323,181 @@ pub enum Resend {
     // Don't resend - all attempts failed.
     Never,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unwrap;
+
+    #[test]
+    fn backoff() {
+        let config = ResendConfig::default();
+
+        assert_eq!(config.backoff(1), RESEND_DELAY);
+        assert_eq!(config.backoff(2), RESEND_DELAY * 2);
+        assert_eq!(config.backoff(3), RESEND_DELAY * 4);
+        assert_eq!(config.backoff(4), RESEND_MAX_DELAY);
+        assert_eq!(config.backoff(u8::max_value()), RESEND_MAX_DELAY);
+
+        // The jitter only ever shortens the delay, by at most the configured fraction.
+        assert_eq!(config.delay(2, 0.0), config.backoff(2));
+        let delay = config.delay(2, 0.999);
+        assert!(delay < config.backoff(2));
+        assert!(delay >= config.backoff(2).mul_f64(1.0 - config.jitter));
+
+        let config = ResendConfig {
+            jitter: 0.0,
+            ..config
+        };
+        assert_eq!(config.delay(2, 0.999), config.backoff(2));
+    }
+
+    #[test]
+    fn resend_later_with_backoff() {
+        let metrics = Metrics::default();
+        let config = ResendConfig {
+            jitter: 0.0,
+            ..Default::default()
+        };
+        let mut cache = SendingTargetsCache::default();
+        cache.set_config(config);
+
+        let targets = addrs(2);
+        cache.insert_message(0, &targets, 1);
+
+        assert_resend_now(
+            cache.target_failed(0, targets[0], 0.0, &metrics),
+            targets[1],
+        );
+        assert_resend_later(
+            cache.target_failed(0, targets[1], 0.0, &metrics),
+            targets[0],
+            config.backoff(1),
+        );
+        assert_resend_later(
+            cache.target_failed(0, targets[0], 0.0, &metrics),
+            targets[1],
+            config.backoff(1),
+        );
+        assert_resend_later(
+            cache.target_failed(0, targets[1], 0.0, &metrics),
+            targets[0],
+            config.backoff(2),
+        );
+        assert_resend_later(
+            cache.target_failed(0, targets[0], 0.0, &metrics),
+            targets[1],
+            config.backoff(2),
+        );
+        assert_resend_never(cache.target_failed(0, targets[1], 0.0, &metrics));
+        assert!(cache.cache.is_empty());
+    }
+
+    #[test]
+    fn rank_targets_by_success_rate() {
+        let metrics = Metrics::default();
+        let mut cache = SendingTargetsCache::default();
+        let targets = addrs(4);
+
+        // The third target succeeded before and the second failed, so they are ranked above and
+        // below the fourth we know nothing about.
+        cache.insert_message(0, &targets[2..3], 1);
+        cache.target_succeeded(0, targets[2]);
+        cache.stats.entry(targets[1]).or_default().failures = 1;
+
+        cache.insert_message(1, &targets, 1);
+        assert_resend_now(
+            cache.target_failed(1, targets[0], 0.0, &metrics),
+            targets[2],
+        );
+        assert_resend_now(
+            cache.target_failed(1, targets[2], 0.0, &metrics),
+            targets[3],
+        );
+        assert_resend_now(
+            cache.target_failed(1, targets[3], 0.0, &metrics),
+            targets[1],
+        );
+
+        // Forgotten targets are back to their original priority.
+        for target in &targets {
+            cache.forget_target(target);
+        }
+        cache.insert_message(2, &targets, 1);
+        assert_resend_now(
+            cache.target_failed(2, targets[0], 0.0, &metrics),
+            targets[1],
+        );
+    }
+
+    #[test]
+    fn rank_targets_by_latency() {
+        let metrics = Metrics::default();
+        let mut cache = SendingTargetsCache::default();
+        let targets = addrs(4);
+
+        for (target, latency) in targets[1..3].iter().zip(&[500, 100]) {
+            let stats = cache.stats.entry(*target).or_default();
+            stats.successes = 1;
+            stats.record_latency(Duration::from_millis(*latency));
+        }
+        // Same success rate as the two above, but unknown latency.
+        cache.stats.entry(targets[3]).or_default().successes = 1;
+
+        cache.insert_message(0, &targets, 1);
+        assert_resend_now(
+            cache.target_failed(0, targets[0], 0.0, &metrics),
+            targets[2],
+        );
+        assert_resend_now(
+            cache.target_failed(0, targets[2], 0.0, &metrics),
+            targets[1],
+        );
+        assert_resend_now(
+            cache.target_failed(0, targets[1], 0.0, &metrics),
+            targets[3],
+        );
+    }
+
+    #[test]
+    fn moving_average_latency() {
+        let mut stats = TargetStats::default();
+        stats.record_latency(Duration::from_millis(800));
+        assert_eq!(stats.latency, Some(Duration::from_millis(800)));
+        stats.record_latency(Duration::from_millis(0));
+        assert_eq!(stats.latency, Some(Duration::from_millis(700)));
+    }
+
+    fn addrs(count: u16) -> Vec<SocketAddr> {
+        (0..count)
+            .map(|index| unwrap!(format!("127.0.0.1:{}", 5000 + index).parse()))
+            .collect()
+    }
+
+    fn assert_resend_now(resend: Resend, expected_target: SocketAddr) {
+        match resend {
+            Resend::Now(target) => assert_eq!(target, expected_target),
+            Resend::Later(..) => panic!("Unexpected Resend::Later"),
+            Resend::Never => panic!("Unexpected Resend::Never"),
+        }
+    }
+
+    fn assert_resend_later(resend: Resend, expected_target: SocketAddr, expected_delay: Duration) {
+        match resend {
+            Resend::Later(target, delay) => {
+                assert_eq!(target, expected_target);
+                assert_eq!(delay, expected_delay);
+            }
+            Resend::Now(_) => panic!("Unexpected Resend::Now"),
+            Resend::Never => panic!("Unexpected Resend::Never"),
+        }
+    }
+
+    fn assert_resend_never(resend: Resend) {
+        match resend {
+            Resend::Never => (),
+            Resend::Now(_) => panic!("Unexpected Resend::Now"),
+            Resend::Later(..) => panic!("Unexpected Resend::Later"),
+        }
+    }
+}
@@ -0,0 +1,205 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::error::RoutingError;
+use bytes::Bytes;
+use crossbeam_channel as mpmc;
+#[cfg(not(feature = "mock_base"))]
+use quic_p2p::{Builder, EventSenders, OurType, QuicP2p};
+use std::net::SocketAddr;
+#[cfg(not(feature = "mock_base"))]
+use std::thread;
+
+/// Config of the default transport: quic-p2p, or its mock with the `mock_base` feature.
+#[cfg(not(feature = "mock_base"))]
+pub type NetworkConfig = quic_p2p::Config;
+/// Config of the default transport: quic-p2p, or its mock with the `mock_base` feature.
+#[cfg(feature = "mock_base")]
+pub type NetworkConfig = mock_quic_p2p::Config;
+
+/// Creates the transport of a node, reporting its events into the given sink.
+pub type MakeTransport = Box<dyn FnOnce(EventSink) -> Result<Box<dyn Transport>, RoutingError>>;
+
+/// Identifies a message handed to the transport. It's reported back with the result of sending
+/// it.
+pub type Token = u64;
+
+/// The peer on the other end of a connection.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum Peer {
+    /// Another node.
+    Node(SocketAddr),
+    /// A client.
+    Client(SocketAddr),
+}
+
+impl Peer {
+    /// Address of the peer.
+    pub fn peer_addr(&self) -> SocketAddr {
+        match *self {
+            Self::Node(addr) | Self::Client(addr) => addr,
+        }
+    }
+}
+
+/// Event reported by a transport.
+#[derive(Clone, Debug)]
+pub enum NetworkEvent {
+    /// Bootstrapped to a node of the network.
+    BootstrappedTo {
+        /// Address of the node.
+        node: SocketAddr,
+    },
+    /// Failed to bootstrap to any node.
+    BootstrapFailure,
+    /// Connected to the peer.
+    ConnectedTo {
+        /// The peer.
+        peer: Peer,
+    },
+    /// Failed to connect to the peer, or lost the connection to it.
+    ConnectionFailure {
+        /// The peer.
+        peer: Peer,
+    },
+    /// Received a message from the peer.
+    NewMessage {
+        /// The sender.
+        peer: Peer,
+        /// The message.
+        msg: Bytes,
+    },
+    /// Failed to send the message to the peer.
+    UnsentUserMessage {
+        /// The recipient.
+        peer: Peer,
+        /// The message.
+        msg: Bytes,
+        /// The token the message was handed to the transport with.
+        token: Token,
+    },
+    /// Sent the message to the peer.
+    SentUserMessage {
+        /// The recipient.
+        peer: Peer,
+        /// The message.
+        msg: Bytes,
+        /// The token the message was handed to the transport with.
+        token: Token,
+    },
+    /// The transport stopped.
+    Finish,
+}
+
+/// Where a transport reports its events.
+#[derive(Clone)]
+pub struct EventSink(mpmc::Sender<NetworkEvent>);
+
+impl EventSink {
+    /// Creates a sink reporting the events into the channel.
+    pub fn new(tx: mpmc::Sender<NetworkEvent>) -> Self {
+        Self(tx)
+    }
+
+    /// Reports the event. Returns `false` if nobody receives the events anymore.
+    pub fn send(&self, event: NetworkEvent) -> bool {
+        self.0.send(event).is_ok()
+    }
+}
+
+/// The network layer used by `NetworkService` to talk to other peers.
+///
+/// The events (connections, received messages, send successes and failures) are reported into
+/// the `EventSink` the transport was created with, as they occur. The node doesn't poll the
+/// transport, so a transport has to report them from its own thread if necessary.
+pub trait Transport {
+    /// Starts bootstrapping to the network.
+    fn bootstrap(&mut self);
+
+    /// Sends the message to the given peer. The `token` is reported back with the send result.
+    fn send(&mut self, peer: Peer, msg: Bytes, token: Token);
+
+    /// Disconnects from the peer at the given address.
+    fn disconnect_from(&mut self, addr: SocketAddr);
+
+    /// Returns our own connection info.
+    fn our_connection_info(&mut self) -> Result<SocketAddr, RoutingError>;
+
+    /// Reports the events which occurred since the last call. The mock network delivers them only
+    /// when the test polls it, so the node polls its transport before waiting for its next input.
+    #[cfg(feature = "mock_base")]
+    #[doc(hidden)]
+    fn poll(&mut self) {}
+}
+
+/// Creates the default transport: quic-p2p, or its mock with the `mock_base` feature. If `config`
+/// is `None`, the transport uses its default config.
+pub fn default_transport(
+    events: EventSink,
+    config: Option<NetworkConfig>,
+) -> Result<Box<dyn Transport>, RoutingError> {
+    #[cfg(not(feature = "mock_base"))]
+    let transport = new_quic_p2p(events, config)?;
+    #[cfg(feature = "mock_base")]
+    let transport = crate::mock::MockTransport::new(events, config)?;
+
+    Ok(Box::new(transport))
+}
+
+#[cfg(not(feature = "mock_base"))]
+fn new_quic_p2p(events: EventSink, config: Option<NetworkConfig>) -> Result<QuicP2p, RoutingError> {
+    let (node_tx, node_rx) = mpmc::unbounded();
+    let client_tx = node_tx.clone();
+
+    let builder = Builder::new(EventSenders { node_tx, client_tx });
+    let builder = if let Some(mut config) = config {
+        config.our_type = OurType::Node;
+        builder.with_config(config)
+    } else {
+        builder
+    };
+    let quic_p2p = builder
+        .build()
+        .map_err(|error| RoutingError::Network(format!("{:?}", error)))?;
+
+    // quic-p2p reports its events from its own thread, translate them as they arrive.
+    let _ = thread::Builder::new()
+        .name("routing-transport".to_string())
+        .spawn(move || {
+            for event in node_rx.iter() {
+                if !events.send(from_quic_p2p_event(event)) {
+                    break;
+                }
+            }
+        })?;
+
+    Ok(quic_p2p)
+}
+
+#[cfg(not(feature = "mock_base"))]
+impl Transport for QuicP2p {
+    fn bootstrap(&mut self) {
+        QuicP2p::bootstrap(self)
+    }
+
+    fn send(&mut self, peer: Peer, msg: Bytes, token: Token) {
+        QuicP2p::send(self, into_quic_p2p_peer(peer), msg, token)
+    }
+
+    fn disconnect_from(&mut self, addr: SocketAddr) {
+        QuicP2p::disconnect_from(self, addr)
+    }
+
+    fn our_connection_info(&mut self) -> Result<SocketAddr, RoutingError> {
+        QuicP2p::our_connection_info(self)
+            .map_err(|error| RoutingError::Network(format!("{:?}", error)))
+    }
+}
+
+#[cfg(not(feature = "mock_base"))]
+quic_p2p_conversions!(quic_p2p);
@@ -8,33 +8,40 @@
 
 use crate::{
     action::Action,
-    chain::NetworkParams,
+    chain::{NetworkParams, SectionProofSlice},
+    client::{ClientId, ClientRateLimit},
     error::RoutingError,
     event::Event,
+    fragment::DEFAULT_MAX_FRAGMENT_SIZE,
     id::{FullId, P2pNode, PublicId},
     location::{DstLocation, SrcLocation},
+    messages::MessageId,
+    metrics::{Metrics, MetricsSink},
+    network_service::{
+        EventSink, MakeTransport, NetworkBuilder, NetworkService, ResendConfig, Transport,
+    },
     outbox::EventBox,
     pause::PausedState,
-    quic_p2p::{OurType, Token},
     rng::{self, MainRng},
     state_machine::{State, StateMachine},
     states::{self, BootstrappingPeer, BootstrappingPeerDetails},
+    status::NodeStatus,
     xor_space::XorName,
-    NetworkConfig, NetworkEvent,
+    NetworkConfig,
 };
-use bytes::Bytes;
 use crossbeam_channel as mpmc;
 use rand::RngCore;
-use std::{net::SocketAddr, sync::mpsc};
+use std::{
+    collections::BTreeMap,
+    net::SocketAddr,
+    sync::{mpsc, Arc},
+};
 
 #[cfg(feature = "mock_base")]
 use {
-    crate::{
-        chain::{Chain, SectionProofSlice},
-        Prefix,
-    },
+    crate::{chain::Chain, Prefix},
     std::{
-        collections::{BTreeMap, BTreeSet},
+        collections::BTreeSet,
         fmt::{self, Display, Formatter},
     },
     unwrap::unwrap,
@@ -45,8 +52,13 @@ pub struct Builder {
     first: bool,
     rng: Option<MainRng>,
     network_config: Option<NetworkConfig>,
+    transport: Option<MakeTransport>,
     full_id: Option<FullId>,
-    network_cfg: NetworkParams,
+    network_cfg: Option<NetworkParams>,
+    metrics: Metrics,
+    client_rate_limit: Option<ClientRateLimit>,
+    max_fragment_size: usize,
+    resend_config: ResendConfig,
 }
 
 impl Builder {
@@ -63,6 +75,19 @@ impl Builder {
         }
     }
 
+    /// The node will talk to other peers over the transport created by `make_transport` rather
+    /// than over quic-p2p, which makes `network_config` irrelevant. The transport has to report
+    /// its events into the given sink.
+    pub fn transport<F>(self, make_transport: F) -> Self
+    where
+        F: FnOnce(EventSink) -> Result<Box<dyn Transport>, RoutingError> + 'static,
+    {
+        Self {
+            transport: Some(Box::new(make_transport)),
+            ..self
+        }
+    }
+
     /// The node will use the given full id rather than default, randomly generated one.
     pub fn full_id(self, full_id: FullId) -> Self {
         Self {
@@ -71,10 +96,49 @@ impl Builder {
         }
     }
 
-    /// Override the default network config.
+    /// Override the default network config. A resumed node keeps the one it was paused with,
+    /// unless this is called.
     pub fn network_cfg(self, network_cfg: NetworkParams) -> Self {
         Self {
-            network_cfg,
+            network_cfg: Some(network_cfg),
+            ..self
+        }
+    }
+
+    /// Report the runtime metrics to the given sink. If this is not called, they are discarded.
+    pub fn metrics(self, sink: Arc<dyn MetricsSink>) -> Self {
+        Self {
+            metrics: Metrics::new(sink),
+            ..self
+        }
+    }
+
+    /// Limit the rate of messages each client can send to the node. Messages over the limit are
+    /// dropped and the client is told when to retry. `None` disables the limit. If this is not
+    /// called, `ClientRateLimit::default()` applies.
+    pub fn client_rate_limit(self, client_rate_limit: Option<ClientRateLimit>) -> Self {
+        Self {
+            client_rate_limit,
+            ..self
+        }
+    }
+
+    /// Send the user messages larger than `max_fragment_size` bytes in fragments, which the
+    /// recipients put back together. If this is not called, `DEFAULT_MAX_FRAGMENT_SIZE` applies.
+    /// The fragments larger than this are also rejected on receipt, so all the nodes of the
+    /// network should use the same value.
+    pub fn max_fragment_size(self, max_fragment_size: usize) -> Self {
+        Self {
+            max_fragment_size,
+            ..self
+        }
+    }
+
+    /// Resend the messages which failed to send according to the given config. If this is not
+    /// called, `ResendConfig::default()` applies.
+    pub fn resend_config(self, resend_config: ResendConfig) -> Self {
+        Self {
+            resend_config,
             ..self
         }
     }
@@ -88,13 +152,14 @@ impl Builder {
     }
 
     /// Creates new `Node`.
-    pub fn create(self) -> (Node, mpmc::Receiver<Event>, mpmc::Receiver<NetworkEvent>) {
+    ///
+    /// Returns the node and the receiver of its events, including the messages from clients.
+    pub fn create(self) -> (Node, mpmc::Receiver<Event>) {
         // start the handler for routing without a restriction to become a full node
         let (interface_result_tx, interface_result_rx) = mpsc::channel();
         let (mut user_event_tx, user_event_rx) = mpmc::unbounded();
-        let (client_tx, client_rx) = mpmc::unbounded();
 
-        let (_, machine) = self.make_state_machine(client_tx, &mut user_event_tx);
+        let (_, machine) = self.make_state_machine(&mut user_event_tx);
 
         let node = Node {
             user_event_tx,
@@ -103,25 +168,74 @@ impl Builder {
             machine,
         };
 
-        (node, user_event_rx, client_rx)
+        (node, user_event_rx)
     }
 
-    fn make_state_machine(
+    /// Resumes a node from the serialised state obtained by `PausedState::to_bytes`, possibly
+    /// written by an older version of routing, instead of creating a new one.
+    ///
+    /// The node is configured by this builder the same way a new one would be, except for its
+    /// full id, which is part of the state, and the network params, which are restored from the
+    /// state unless set with `network_cfg`. Their ageing and relocation policies are not part of
+    /// the state though, so they must be the ones the node was paused with, otherwise this fails
+    /// with `RoutingError::PolicyMismatch`.
+    ///
+    /// Returns the node and the receiver of its events, including the messages from clients.
+    pub fn resume_from_bytes(
         self,
-        client_tx: mpmc::Sender<NetworkEvent>,
-        outbox: &mut dyn EventBox,
-    ) -> (mpmc::Sender<Action>, StateMachine) {
+        bytes: &[u8],
+    ) -> Result<(Node, mpmc::Receiver<Event>), RoutingError> {
+        let state = PausedState::from_bytes(bytes, self.network_cfg)?;
+        let settings = NetworkSettings {
+            metrics: self.metrics,
+            client_rate_limit: self.client_rate_limit,
+            max_fragment_size: self.max_fragment_size,
+            resend_config: self.resend_config,
+        };
+
+        let (interface_result_tx, interface_result_rx) = mpsc::channel();
+        let (mut user_event_tx, user_event_rx) = mpmc::unbounded();
+
+        let (_, machine) = StateMachine::new(
+            move |mut network_service, timer, _| {
+                settings.apply(&mut network_service);
+                State::resume(state, network_service, timer)
+            },
+            network_builder(self.network_config, self.transport),
+            &mut user_event_tx,
+        );
+
+        info!("{} - Resume", machine.current());
+
+        let node = Node {
+            interface_result_tx,
+            interface_result_rx,
+            user_event_tx,
+            machine,
+        };
+
+        Ok((node, user_event_rx))
+    }
+
+    fn make_state_machine(self, outbox: &mut dyn EventBox) -> (mpmc::Sender<Action>, StateMachine) {
         let mut rng = self.rng.unwrap_or_else(rng::new);
 
         let full_id = self.full_id.unwrap_or_else(|| FullId::gen(&mut rng));
-        let network_cfg = self.network_cfg;
+        let network_cfg = self.network_cfg.unwrap_or_default();
         let first = self.first;
+        let settings = NetworkSettings {
+            metrics: self.metrics,
+            client_rate_limit: self.client_rate_limit,
+            max_fragment_size: self.max_fragment_size,
+            resend_config: self.resend_config,
+        };
 
-        let mut network_config = self.network_config.unwrap_or_default();
-        network_config.our_type = OurType::Node;
+        let network = network_builder(self.network_config, self.transport);
 
         StateMachine::new(
-            move |network_service, timer, outbox| {
+            move |mut network_service, timer, outbox| {
+                settings.apply(&mut network_service);
+
                 if first {
                     debug!("Creating a first node in the Elder state");
 
@@ -140,13 +254,47 @@ impl Builder {
                     }))
                 }
             },
-            network_config,
-            client_tx,
+            network,
             outbox,
         )
     }
 }
 
+fn network_builder(
+    network_config: Option<NetworkConfig>,
+    transport: Option<MakeTransport>,
+) -> NetworkBuilder {
+    let network = NetworkBuilder::new().with_config(network_config.unwrap_or_default());
+    if let Some(make_transport) = transport {
+        network.with_transport(make_transport)
+    } else {
+        network
+    }
+}
+
+// Settings of the network service configured by the builder.
+struct NetworkSettings {
+    metrics: Metrics,
+    client_rate_limit: Option<ClientRateLimit>,
+    max_fragment_size: usize,
+    resend_config: ResendConfig,
+}
+
+impl NetworkSettings {
+    fn apply(self, network_service: &mut NetworkService) {
+        network_service.set_metrics(self.metrics);
+        network_service
+            .clients_mut()
+            .set_rate_limit(self.client_rate_limit);
+        network_service
+            .reassembler_mut()
+            .set_max_fragment_size(self.max_fragment_size);
+        network_service
+            .targets_cache_mut()
+            .set_config(self.resend_config);
+    }
+}
+
 /// Interface for sending and receiving messages to and from other nodes, in the role of a full
 /// routing node.
 ///
@@ -168,8 +316,13 @@ impl Node {
             first: false,
             rng: None,
             network_config: None,
+            transport: None,
             full_id: None,
-            network_cfg: Default::default(),
+            network_cfg: None,
+            metrics: Default::default(),
+            client_rate_limit: Some(Default::default()),
+            max_fragment_size: DEFAULT_MAX_FRAGMENT_SIZE,
+            resend_config: Default::default(),
         }
     }
 
@@ -178,7 +331,8 @@ impl Node {
         self.machine.pause()
     }
 
-    /// Resume previously paused node.
+    /// Resume previously paused node. It keeps reporting the runtime metrics to the sink it was
+    /// created with.
     pub fn resume(state: PausedState) -> (Self, mpmc::Receiver<Event>) {
         let (interface_result_tx, interface_result_rx) = mpsc::channel();
         let (user_event_tx, user_event_rx) = mpmc::unbounded();
@@ -194,6 +348,18 @@ impl Node {
         (node, user_event_rx)
     }
 
+    /// Resume a node from the serialised state obtained by `PausedState::to_bytes`, possibly
+    /// written by an older version of routing, with the default settings and the given network
+    /// config. Use `Builder::resume_from_bytes` to configure it.
+    pub fn resume_from_bytes(
+        bytes: &[u8],
+        network_config: NetworkConfig,
+    ) -> Result<(Self, mpmc::Receiver<Event>), RoutingError> {
+        Self::builder()
+            .network_config(network_config)
+            .resume_from_bytes(bytes)
+    }
+
     /// Returns the first `count` names of the nodes in the routing table which are closest
     /// to the given one.
     pub fn close_group(&self, name: XorName, count: usize) -> Option<Vec<XorName>> {
@@ -205,6 +371,13 @@ impl Node {
         self.machine.current().our_elders()
     }
 
+    /// Returns the penalty scores of the nodes which misbehaved recently, e.g. by sending us
+    /// invalid signatures or gossip. The members of our section whose score reaches
+    /// `PENALTY_THRESHOLD` are voted offline. Empty unless we are an elder.
+    pub fn peer_penalties(&self) -> BTreeMap<PublicId, u32> {
+        self.machine.current().peer_penalties()
+    }
+
     /// Find out if the given XorName matches our prefix.
     pub fn matches_our_prefix(&self, name: &XorName) -> Result<bool, RoutingError> {
         self.machine.current().matches_our_prefix(name)
@@ -229,6 +402,27 @@ impl Node {
             .ok_or(RoutingError::InvalidState)
     }
 
+    /// Returns a snapshot of the current state of this node, e.g. to show on a status page.
+    pub fn status(&self) -> NodeStatus {
+        self.machine.current().status()
+    }
+
+    /// Provide a SectionProofSlice that proves the latest key of our section to the given
+    /// destination. It can be checked with `SectionProofSlice::verify`. Returns `None` if we are
+    /// not a member of a section yet.
+    pub fn prove(&self, target: &DstLocation) -> Option<SectionProofSlice> {
+        self.machine.current().prove(target)
+    }
+
+    /// Exports the history of our section since we joined it: every version of its elders with
+    /// the BLS proof of its key, and all the members that joined, left or were relocated. Only
+    /// the latest entries are kept, see `chain::section_history`. The log is signed with our id
+    /// and can be checked with `verify_section_history`.
+    /// Fails with `RoutingError::InvalidState` if we are not a member of a section yet.
+    pub fn export_section_history(&self) -> Result<Vec<u8>, RoutingError> {
+        self.machine.current().export_section_history()
+    }
+
     /// Vote for a custom event.
     pub fn vote_for(&mut self, event: Vec<u8>) {
         // TODO: Return interface error here
@@ -239,44 +433,80 @@ impl Node {
             .map(|elder| elder.vote_for_user_event(event));
     }
 
-    /// Send a message.
+    /// Send a message. Returns its id, which the recipients get in `Event::MessageReceived`.
+    ///
+    /// Fails with `RoutingError::Backpressure` if the outbound queues are full. The message is not
+    /// sent then, and `Event::ReadyToSend` is raised once there is room again. Fails with
+    /// `RoutingError::MessageTooLarge` if the content is larger than `MAX_MESSAGE_SIZE`.
     pub fn send_message(
         &mut self,
         src: SrcLocation,
         dst: DstLocation,
         content: Vec<u8>,
-    ) -> Result<(), RoutingError> {
+    ) -> Result<MessageId, RoutingError> {
+        self.send_user_message(src, dst, content, false)
+    }
+
+    /// Send a message and ask its destination to acknowledge its delivery. Returns the id of the
+    /// message. Once the ack arrives, `Event::MessageDelivered` is raised with this id, or
+    /// `Event::MessageDeliveryFailed` if it doesn't arrive within `DELIVERY_ACK_TIMEOUT`.
+    pub fn send_message_with_ack(
+        &mut self,
+        src: SrcLocation,
+        dst: DstLocation,
+        content: Vec<u8>,
+    ) -> Result<MessageId, RoutingError> {
+        self.send_user_message(src, dst, content, true)
+    }
+
+    fn send_user_message(
+        &mut self,
+        src: SrcLocation,
+        dst: DstLocation,
+        content: Vec<u8>,
+        ack: bool,
+    ) -> Result<MessageId, RoutingError> {
+        let (result_tx, result_rx) = mpsc::channel();
         let action = Action::SendMessage {
             src,
             dst,
             content,
-            result_tx: self.interface_result_tx.clone(),
+            ack,
+            result_tx,
         };
 
-        self.perform_action(action)
+        self.run_action(action);
+        result_rx.recv()?
+    }
+
+    /// Number of user messages waiting in the outbound queues. Sending fails with
+    /// `RoutingError::Backpressure` once it reaches `MAX_QUEUED_USER_MESSAGES`.
+    pub fn outbound_queue_depth(&self) -> usize {
+        self.machine.current().outbound_queue_depth()
     }
 
-    /// Send a message to a client peer.
-    pub fn send_message_to_client(
+    /// Send a message to a client. The message goes to the connection the client last
+    /// authenticated on, so it reaches the client even after it reconnected from another address.
+    /// Fails with `RoutingError::ClientNotConnected` if the client is currently not connected,
+    /// or with `RoutingError::Backpressure` if the outbound queues are full.
+    pub fn send_to_client(
         &mut self,
-        peer_addr: SocketAddr,
-        msg: Bytes,
-        token: Token,
+        client: &ClientId,
+        content: Vec<u8>,
     ) -> Result<(), RoutingError> {
-        let action = Action::SendMessageToClient {
-            peer_addr,
-            msg,
-            token,
+        let action = Action::SendToClient {
+            client: *client,
+            content,
             result_tx: self.interface_result_tx.clone(),
         };
 
         self.perform_action(action)
     }
 
-    /// Disconnect form a client peer.
-    pub fn disconnect_from_client(&mut self, peer_addr: SocketAddr) -> Result<(), RoutingError> {
+    /// Disconnect from a client.
+    pub fn disconnect_from_client(&mut self, client: &ClientId) -> Result<(), RoutingError> {
         let action = Action::DisconnectClient {
-            peer_addr,
+            client: *client,
             result_tx: self.interface_result_tx.clone(),
         };
 
@@ -284,13 +514,17 @@ impl Node {
     }
 
     fn perform_action(&mut self, action: Action) -> Result<(), RoutingError> {
+        self.run_action(action);
+        self.interface_result_rx.recv()?
+    }
+
+    fn run_action(&mut self, action: Action) {
         let transition = self
             .machine
             .current_mut()
             .handle_action(action, &mut self.user_event_tx);
         self.machine
             .apply_transition(transition, &mut self.user_event_tx);
-        self.interface_result_rx.recv()?
     }
 
     /// Register the node event channels with the provided
@@ -321,6 +555,13 @@ impl Node {
     pub fn our_connection_info(&mut self) -> Result<SocketAddr, RoutingError> {
         self.machine.current_mut().our_connection_info()
     }
+
+    #[cfg(feature = "async")]
+    pub(crate) fn input_channels(
+        &self,
+    ) -> (mpmc::Receiver<crate::NetworkEvent>, mpmc::Receiver<Action>) {
+        self.machine.input_channels()
+    }
 }
 
 #[cfg(feature = "mock_base")]
@@ -459,11 +700,6 @@ impl Node {
         self.machine.current().unpolled_observations_string()
     }
 
-    /// Provide a SectionProofSlice that proves the given signature to the given destination.
-    pub fn prove(&self, target: &DstLocation) -> Option<SectionProofSlice> {
-        self.chain().map(|chain| chain.prove(target, None))
-    }
-
     /// Checks whether the given location represents self.
     pub fn in_src_location(&self, src: &SrcLocation) -> bool {
         self.machine.current().in_src_location(src)
@@ -480,6 +716,13 @@ impl Node {
         self.chain()
             .and_then(|chain| chain.member_age_counter(name))
     }
+
+    /// Returns the number of joined members of the given neighbour section as reported by it, or
+    /// `None` if it didn't report it yet or is not our neighbour.
+    pub fn neighbour_member_count(&self, prefix: &Prefix<XorName>) -> Option<u64> {
+        self.chain()
+            .and_then(|chain| chain.neighbour_member_count(prefix))
+    }
 }
 
 #[cfg(feature = "mock_base")]
@@ -16,6 +16,7 @@ use crate::{
     chain::{self, GenesisPfxInfo},
     id::{self, FullId},
     messages::Variant,
+    metrics::Metrics,
     rng::{self, MainRng, RngCompat},
     time::Duration,
     utils::LogIdent,
@@ -94,6 +95,9 @@ impl Display for ParsecSizeCounter {
 
 pub struct ParsecMap {
     map: BTreeMap<u64, Parsec>,
+    // Version of the parsec instance we lost when resumed from a serialised state. We don't take
+    // part in it, as restarting it from the genesis would fork our previous events.
+    resync_version: Option<u64>,
     size_counter: ParsecSizeCounter,
     send_gossip: bool,
     // Number of gossip messages we sent within this gossip period.
@@ -112,6 +116,7 @@ impl Default for ParsecMap {
 
         Self {
             map: Default::default(),
+            resync_version: None,
             size_counter: Default::default(),
             send_gossip: false,
             gossip_count: 0,
@@ -133,6 +138,13 @@ impl ParsecMap {
         self
     }
 
+    /// Creates a map without any parsec instance, waiting for the genesis of a version newer
+    /// than `version` to be initialised with.
+    pub fn with_resync(mut self, version: u64) -> Self {
+        self.resync_version = Some(version);
+        self
+    }
+
     pub fn init(
         &mut self,
         rng: &mut MainRng,
@@ -140,29 +152,37 @@ impl ParsecMap {
         gen_pfx_info: &GenesisPfxInfo,
         log_ident: &LogIdent,
     ) {
+        self.resync_version = None;
         self.add_new(rng, full_id, gen_pfx_info, log_ident);
         self.remove_old();
     }
 
+    /// Returns whether we're waiting for the genesis of a new parsec version after losing the
+    /// current one.
+    pub fn is_resyncing(&self) -> bool {
+        self.resync_version.is_some()
+    }
+
     pub fn handle_request(
         &mut self,
         msg_version: u64,
         request: Request,
         pub_id: id::PublicId,
         log_ident: &LogIdent,
-    ) -> Option<Variant> {
+        metrics: &Metrics,
+    ) -> Result<Option<Variant>, Error> {
         // Increase the size before fetching the parsec to satisfy the borrow checker
         let ser_size = if let Ok(size) = bincode::serialized_size(&request) {
             size
         } else {
-            return None;
+            return Ok(None);
         };
-        self.count_size(ser_size, msg_version, log_ident);
+        self.count_size(ser_size, msg_version, log_ident, metrics);
 
         let parsec = if let Some(parsec) = self.map.get_mut(&msg_version) {
             parsec
         } else {
-            return None;
+            return Ok(None);
         };
 
         match parsec.handle_request(&pub_id, request) {
@@ -173,11 +193,11 @@ impl ParsecMap {
                     self.send_gossip = true;
                 }
 
-                Some(Variant::ParsecResponse(msg_version, response))
+                Ok(Some(Variant::ParsecResponse(msg_version, response)))
             }
             Err(err) => {
                 debug!("{} - Error handling parsec request: {:?}", log_ident, err);
-                None
+                Err(err)
             }
         }
     }
@@ -188,24 +208,26 @@ impl ParsecMap {
         response: Response,
         pub_id: id::PublicId,
         log_ident: &LogIdent,
-    ) {
+        metrics: &Metrics,
+    ) -> Result<(), Error> {
         // Increase the size before fetching the parsec to satisfy the borrow checker
         let ser_size = if let Ok(size) = bincode::serialized_size(&response) {
             size
         } else {
-            return;
+            return Ok(());
         };
-        self.count_size(ser_size, msg_version, log_ident);
+        self.count_size(ser_size, msg_version, log_ident, metrics);
 
         let parsec = if let Some(parsec) = self.map.get_mut(&msg_version) {
             parsec
         } else {
-            return;
+            return Ok(());
         };
 
-        if let Err(err) = parsec.handle_response(&pub_id, response) {
+        parsec.handle_response(&pub_id, response).map_err(|err| {
             debug!("{} - Error handling parsec response: {:?}", log_ident, err);
-        }
+            err
+        })
     }
 
     pub fn create_gossip(
@@ -265,8 +287,8 @@ impl ParsecMap {
     }
 
     pub fn last_version(&self) -> u64 {
-        if let Some(version) = self.map.keys().last() {
-            *version
+        if let Some(version) = self.map.keys().last().copied().or(self.resync_version) {
+            version
         } else {
             log_or_panic!(log::Level::Error, "ParsecMap is empty.");
             0
@@ -335,6 +357,11 @@ impl ParsecMap {
         )
     }
 
+    // Estimated size of the current parsec instance, in bytes.
+    pub fn get_size(&self) -> u64 {
+        self.size_counter.size_counter
+    }
+
     pub fn needs_pruning(&self) -> bool {
         self.size_counter.needs_pruning()
     }
@@ -343,6 +370,10 @@ impl ParsecMap {
         self.size_counter.set_pruning_voted_for();
     }
 
+    pub fn pruning_voted_for(&self) -> bool {
+        self.size_counter.pruning_voted_for
+    }
+
     // Returns whether we should send parsec gossip now.
     pub fn should_send_gossip(&mut self, log_ident: &LogIdent) -> bool {
         let send_gossip = self.send_gossip;
@@ -372,9 +403,10 @@ impl ParsecMap {
         }
     }
 
-    fn count_size(&mut self, size: u64, msg_version: u64, log_ident: &LogIdent) {
+    fn count_size(&mut self, size: u64, msg_version: u64, log_ident: &LogIdent, metrics: &Metrics) {
         if self.last_version() == msg_version && self.map.contains_key(&msg_version) {
             self.size_counter.increase_size(size);
+            metrics.parsec_gossip_received(size);
             trace!(
                 "{} - Parsec size is now estimated to: {} / {}.",
                 log_ident,
@@ -412,13 +444,6 @@ impl ParsecMap {
     }
 }
 
-#[cfg(feature = "mock_base")]
-impl ParsecMap {
-    pub fn get_size(&self) -> u64 {
-        self.size_counter.size_counter
-    }
-}
-
 // Generate a DkgResult that can be used for the first node
 pub fn generate_first_dkg_result(rng: &mut MainRng) -> DkgResult {
     let participants = 1;
@@ -473,6 +498,30 @@ fn create(rng: &mut MainRng, full_id: FullId, gen_pfx_info: &GenesisPfxInfo) ->
     }
 }
 
+/// Returns whether the error means the peer sent us invalid gossip, as opposed to one of us lagging
+/// behind the other.
+#[cfg(not(feature = "mock"))]
+pub fn is_invalid_gossip(error: &Error) -> bool {
+    match error {
+        Error::SignatureFailure { .. }
+        | Error::InvalidEvent { .. }
+        | Error::InvalidMessage { .. } => true,
+        // Any other error might be caused by one of us lagging behind the other or by our own
+        // state, so it's no proof the peer misbehaved.
+        _ => false,
+    }
+}
+
+/// Returns whether the error means the peer sent us invalid gossip, as opposed to one of us lagging
+/// behind the other.
+#[cfg(feature = "mock")]
+pub fn is_invalid_gossip(error: &Error) -> bool {
+    match error {
+        Error::InvalidMessage => true,
+        Error::InvalidPeerState | Error::DuplicateVote => false,
+    }
+}
+
 #[derive(Debug)]
 pub enum CreateGossipError {
     MissingVersion,
@@ -553,6 +602,7 @@ mod tests {
             first_ages,
             latest_info: EldersInfo::default(),
             parsec_version: version,
+            ban_list: Default::default(),
         }
     }
 
@@ -597,7 +647,13 @@ mod tests {
             pub_id: &id::PublicId,
             log_ident: &LogIdent,
         ) {
-            let _ = parsec_map.handle_request(msg_version, self.clone(), *pub_id, log_ident);
+            let _ = parsec_map.handle_request(
+                msg_version,
+                self.clone(),
+                *pub_id,
+                log_ident,
+                &Metrics::default(),
+            );
         }
     }
 
@@ -609,7 +665,13 @@ mod tests {
             pub_id: &id::PublicId,
             log_ident: &LogIdent,
         ) {
-            parsec_map.handle_response(msg_version, self.clone(), *pub_id, log_ident);
+            let _ = parsec_map.handle_response(
+                msg_version,
+                self.clone(),
+                *pub_id,
+                log_ident,
+                &Metrics::default(),
+            );
         }
     }
 
@@ -7,11 +7,13 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use crate::{
-    chain::{Chain, GenesisPfxInfo},
+    chain::{Chain, GenesisPfxInfo, NetworkParams},
+    error::{Result, RoutingError},
     id::FullId,
     messages::QueuedMessage,
     network_service::NetworkService,
     parsec::ParsecMap,
+    rng,
     routing_message_filter::RoutingMessageFilter,
     signature_accumulator::SignatureAccumulator,
     NetworkEvent,
@@ -19,14 +21,32 @@ use crate::{
 use crossbeam_channel as mpmc;
 use std::collections::VecDeque;
 
+/// Version of the serialised `PausedState` format written by this version of routing. Bump it
+/// whenever the layout of `SerialisedState` changes and keep the ability to read all the older
+/// versions in `PausedState::from_bytes`.
+const PAUSED_STATE_FORMAT_VERSION: u32 = 2;
+
 /// A type that wraps the internal state of a node while it is paused in order to be upgraded and/or
 /// restarted. A value of this type is obtained by pausing a node and can be then used to resume
 /// it.
-// TODO: this is just a placeholder for now which allows us to have the pause/resume API in place
-// and have tests passing. To make it actually work for all its intended purposes, we need to make
-// sure this type is serialisable/deserialisable in a forward compatible way - that is, we must be
-// able to create a value of this type in routing version X and use it to resume the node in any
-// version >= X.
+///
+/// The state can be written out with [`to_bytes`](#method.to_bytes) and the resulting blob passed
+/// to [`Builder::resume_from_bytes`](struct.Builder.html#method.resume_from_bytes), possibly in a
+/// newer version of routing. The blob starts with a format version so that any version >= X can
+/// read the state written by version X.
+///
+/// The network connections are not part of the blob - they are recreated from the network config
+/// on resume. Neither are the other settings of the node, they are taken from the builder it's
+/// resumed with. Neither are the ageing and relocation policies, only their ids, so the node has
+/// to be resumed with the same ones. Neither are the parsec graphs, which parsec can't serialise:
+/// an adult initialises its parsec instance again from the genesis prefix info and catches up
+/// with the rest of the section through gossip. An elder can't do that without forking its own
+/// previous votes, so it asks the other elders to prune their parsec and doesn't vote until they
+/// send it the genesis of the new version. It keeps its place among the elders in the meantime,
+/// unless the section key changed while it was paused: it then has no share of the new key and
+/// continues as an adult.
+///
+/// The blob holds the secret keys of the node unencrypted, so it must be kept as safe as they are.
 pub struct PausedState {
     pub(super) chain: Chain,
     pub(super) full_id: FullId,
@@ -34,15 +54,124 @@ pub struct PausedState {
     pub(super) msg_filter: RoutingMessageFilter,
     pub(super) msg_queue: VecDeque<QueuedMessage>,
     pub(super) msg_backlog: Vec<QueuedMessage>,
-    // TODO: instead of storing both network_service and network_rx, store only the network config.
-    pub(super) network_service: NetworkService,
+    // The live network service and receiver. Only present when the state was obtained by pausing
+    // a node in this process, `None` when it was deserialised.
+    pub(super) network_service: Option<NetworkService>,
     pub(super) network_rx: Option<mpmc::Receiver<NetworkEvent>>,
     pub(super) sig_accumulator: SignatureAccumulator,
     pub(super) parsec_map: ParsecMap,
 }
 
 impl PausedState {
+    /// Serialises the state into a versioned byte blob which can be passed to
+    /// `Builder::resume_from_bytes`.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let network_cfg = self.chain.network_cfg();
+        let state = SerialisedStateRef {
+            chain: &self.chain,
+            full_id: self.full_id.secret_keys_to_bytes()?,
+            gen_pfx_info: &self.gen_pfx_info,
+            msg_filter: &self.msg_filter,
+            msg_queue: &self.msg_queue,
+            msg_backlog: &self.msg_backlog,
+            sig_accumulator: &self.sig_accumulator,
+            ageing_policy: network_cfg.ageing_policy.id(),
+            relocation_policy: network_cfg.relocation_policy.id(),
+        };
+
+        Ok(bincode::serialize(&(
+            PAUSED_STATE_FORMAT_VERSION,
+            bincode::serialize(&state)?,
+        ))?)
+    }
+
+    /// Deserialises the state from the blob produced by `to_bytes` and continues with the given
+    /// network params, or the serialised ones with the default policies if `None`. Either way the
+    /// policies must be the ones the node was paused with. The returned state has no network
+    /// service yet.
+    pub(crate) fn from_bytes(bytes: &[u8], network_cfg: Option<NetworkParams>) -> Result<Self> {
+        let (version, payload): (u32, Vec<u8>) = bincode::deserialize(bytes)?;
+        let mut state: SerialisedState = match version {
+            // Version 1 was never released: its chain lacked the resource proof params, the ban
+            // list and the section history, so there are no blobs of it to read.
+            2 => bincode::deserialize(&payload)?,
+            _ => return Err(RoutingError::UnsupportedPausedStateVersion(version)),
+        };
+
+        let network_cfg = network_cfg.unwrap_or_else(|| state.chain.network_cfg());
+        check_policy(
+            "ageing",
+            state.ageing_policy,
+            network_cfg.ageing_policy.id(),
+        )?;
+        check_policy(
+            "relocation",
+            state.relocation_policy,
+            network_cfg.relocation_policy.id(),
+        )?;
+        state.chain.set_network_cfg(network_cfg);
+        let full_id = FullId::from_secret_keys_bytes(&state.full_id)?;
+
+        let parsec_map = if state.chain.is_self_elder() {
+            ParsecMap::default().with_resync(state.gen_pfx_info.parsec_version)
+        } else {
+            ParsecMap::default().with_init(&mut rng::new(), full_id.clone(), &state.gen_pfx_info)
+        };
+
+        Ok(Self {
+            chain: state.chain,
+            full_id,
+            gen_pfx_info: state.gen_pfx_info,
+            msg_filter: state.msg_filter,
+            msg_queue: state.msg_queue,
+            msg_backlog: state.msg_backlog,
+            network_service: None,
+            network_rx: None,
+            sig_accumulator: state.sig_accumulator,
+            parsec_map,
+        })
+    }
+
     pub(super) fn is_elder(&self) -> bool {
         self.chain.is_self_elder()
     }
 }
+
+fn check_policy(kind: &'static str, paused: String, resumed: &str) -> Result<()> {
+    if paused == resumed {
+        Ok(())
+    } else {
+        Err(RoutingError::PolicyMismatch {
+            kind,
+            paused,
+            resumed: resumed.to_string(),
+        })
+    }
+}
+
+// Layout of the serialised state, version 2.
+#[derive(Serialize)]
+struct SerialisedStateRef<'a> {
+    chain: &'a Chain,
+    full_id: Vec<u8>,
+    gen_pfx_info: &'a GenesisPfxInfo,
+    msg_filter: &'a RoutingMessageFilter,
+    msg_queue: &'a VecDeque<QueuedMessage>,
+    msg_backlog: &'a Vec<QueuedMessage>,
+    sig_accumulator: &'a SignatureAccumulator,
+    ageing_policy: &'a str,
+    relocation_policy: &'a str,
+}
+
+#[derive(Deserialize)]
+struct SerialisedState {
+    chain: Chain,
+    full_id: Vec<u8>,
+    gen_pfx_info: GenesisPfxInfo,
+    msg_filter: RoutingMessageFilter,
+    msg_queue: VecDeque<QueuedMessage>,
+    msg_backlog: Vec<QueuedMessage>,
+    sig_accumulator: SignatureAccumulator,
+    ageing_policy: String,
+    relocation_policy: String,
+}
@@ -18,6 +18,7 @@ use crate::{
 };
 use bincode::serialize;
 use serde::{de::Error as SerdeDeError, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::Debug;
 
 #[cfg(feature = "mock_base")]
 pub use self::overrides::Overrides;
@@ -56,7 +57,10 @@ impl SignedRelocateDetails {
         if let Variant::Relocate(_) = &signed_msg.variant {
             Ok(Self { signed_msg })
         } else {
-            Err(RoutingError::InvalidMessage)
+            Err(RoutingError::InvalidMessage {
+                variant: signed_msg.variant.name(),
+                sender: signed_msg.src.location(),
+            })
         }
     }
 
@@ -131,31 +135,109 @@ impl RelocatePayload {
     }
 }
 
+/// Known size of a section, as seen by the elders computing a relocation destination.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SectionSize {
+    /// Prefix of the section.
+    pub prefix: Prefix<XorName>,
+    /// Number of joined members of the section. Our neighbours tell us theirs whenever it changes,
+    /// so it may lag behind. Until a neighbour told us, it is the number of its elders.
+    pub size: usize,
+}
+
+/// Inputs to the relocation destination computation.
+#[derive(Clone, Copy, Debug)]
+pub struct RelocationContext<'a> {
+    /// Prefix of the section the node is relocated from.
+    pub src_prefix: &'a Prefix<XorName>,
+    /// Name of the relocated node.
+    pub relocated_name: &'a XorName,
+    /// Name of the node whose churn triggered the relocation.
+    pub trigger_name: &'a XorName,
+    /// Our section and all the other sections we know, ordered by prefix.
+    pub sections: &'a [SectionSize],
+    /// Minimum number of nodes we consider safe in a section.
+    pub safe_section_size: usize,
+}
+
+/// Rules by which the destination of a relocated node is chosen.
+///
+/// The destination is a name - the node is relocated to the section whose prefix matches it. If it
+/// matches the source section, the relocation is skipped.
+///
+/// The destination must be a deterministic function of the context, and all the nodes of a network
+/// must use the same policy, otherwise the elders won't agree on where to relocate a node.
+pub trait RelocationPolicy: Debug + Send + Sync {
+    /// Name identifying the policy. It is stored when a node is paused, so that it can't be
+    /// resumed with a different policy.
+    fn id(&self) -> &str;
+
+    /// Returns the relocation destination.
+    fn destination(&self, context: &RelocationContext) -> XorName;
+}
+
+/// The default relocation policy: the destination is the hash of the relocated name and the trigger
+/// name, so it is uniformly random regardless of the section sizes.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultRelocationPolicy;
+
+impl RelocationPolicy for DefaultRelocationPolicy {
+    fn id(&self) -> &str {
+        "default"
+    }
+
+    fn destination(&self, context: &RelocationContext) -> XorName {
+        hash_destination(context.relocated_name, context.trigger_name)
+    }
+}
+
+/// Relocation policy which prefers the section furthest below the safe section size, other than the
+/// source section.
+///
+/// Ties are broken in favour of the section closest to the destination the default policy would
+/// pick, which is then moved into the chosen section. If no other section is below the safe size,
+/// this behaves like `DefaultRelocationPolicy`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UnderPopulatedRelocationPolicy;
+
+impl RelocationPolicy for UnderPopulatedRelocationPolicy {
+    fn id(&self) -> &str {
+        "under-populated"
+    }
+
+    fn destination(&self, context: &RelocationContext) -> XorName {
+        let hashed = hash_destination(context.relocated_name, context.trigger_name);
+        let deficit =
+            |section: &SectionSize| context.safe_section_size.saturating_sub(section.size);
+
+        let target = context
+            .sections
+            .iter()
+            .filter(|section| section.prefix != *context.src_prefix && deficit(section) > 0)
+            .max_by(|lhs, rhs| {
+                deficit(lhs)
+                    .cmp(&deficit(rhs))
+                    .then_with(|| rhs.prefix.cmp_distance(&lhs.prefix, &hashed))
+            });
+
+        match target {
+            Some(section) => section.prefix.substituted_in(hashed),
+            None => hashed,
+        }
+    }
+}
+
 #[cfg(not(feature = "mock_base"))]
-pub fn compute_destination(
-    _src_prefix: &Prefix<XorName>,
-    relocated_name: &XorName,
-    trigger_name: &XorName,
-) -> XorName {
-    compute_destination_without_override(relocated_name, trigger_name)
+pub fn compute_destination(policy: &dyn RelocationPolicy, context: &RelocationContext) -> XorName {
+    policy.destination(context)
 }
 
 #[cfg(feature = "mock_base")]
-pub fn compute_destination(
-    src_prefix: &Prefix<XorName>,
-    relocated_name: &XorName,
-    trigger_name: &XorName,
-) -> XorName {
-    self::overrides::get(
-        src_prefix,
-        compute_destination_without_override(relocated_name, trigger_name),
-    )
+pub fn compute_destination(policy: &dyn RelocationPolicy, context: &RelocationContext) -> XorName {
+    self::overrides::get(context.src_prefix, policy.destination(context))
 }
 
-fn compute_destination_without_override(
-    relocated_name: &XorName,
-    trigger_name: &XorName,
-) -> XorName {
+fn hash_destination(relocated_name: &XorName, trigger_name: &XorName) -> XorName {
     let mut buffer = [0; 2 * XOR_NAME_LEN];
     buffer[..XOR_NAME_LEN].copy_from_slice(&relocated_name.0);
     buffer[XOR_NAME_LEN..].copy_from_slice(&trigger_name.0);
@@ -333,3 +415,99 @@ mod overrides {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng;
+    use rand::Rng;
+
+    fn section(prefix: Prefix<XorName>, size: usize) -> SectionSize {
+        SectionSize { prefix, size }
+    }
+
+    #[test]
+    fn under_populated_policy_picks_smallest_section() {
+        let mut rng = rng::new();
+        let relocated_name: XorName = rng.gen();
+        let trigger_name: XorName = rng.gen();
+
+        let p00 = Prefix::default().pushed(false).pushed(false);
+        let p01 = Prefix::default().pushed(false).pushed(true);
+        let p1 = Prefix::default().pushed(true);
+        let sections = [section(p00, 5), section(p01, 2), section(p1, 4)];
+
+        let context = RelocationContext {
+            src_prefix: &p00,
+            relocated_name: &relocated_name,
+            trigger_name: &trigger_name,
+            sections: &sections,
+            safe_section_size: 6,
+        };
+        let dst = UnderPopulatedRelocationPolicy.destination(&context);
+        assert!(p01.matches(&dst));
+
+        // The source section is never picked, even when it is the smallest.
+        let context = RelocationContext {
+            src_prefix: &p01,
+            ..context
+        };
+        let dst = UnderPopulatedRelocationPolicy.destination(&context);
+        assert!(p1.matches(&dst));
+    }
+
+    #[test]
+    fn under_populated_policy_breaks_ties_deterministically() {
+        let mut rng = rng::new();
+        let p0 = Prefix::default().pushed(false);
+        let p10 = Prefix::default().pushed(true).pushed(false);
+        let p11 = Prefix::default().pushed(true).pushed(true);
+        let sections = [section(p0, 3), section(p10, 3), section(p11, 3)];
+
+        for _ in 0..20 {
+            let relocated_name: XorName = rng.gen();
+            let trigger_name: XorName = rng.gen();
+            let context = RelocationContext {
+                src_prefix: &p0,
+                relocated_name: &relocated_name,
+                trigger_name: &trigger_name,
+                sections: &sections,
+                safe_section_size: 6,
+            };
+
+            let hashed = DefaultRelocationPolicy.destination(&context);
+            let dst = UnderPopulatedRelocationPolicy.destination(&context);
+            assert_eq!(dst, UnderPopulatedRelocationPolicy.destination(&context));
+
+            // Among equally sized sections, the one the default policy picks is preferred.
+            if p0.matches(&hashed) {
+                assert!(p10.matches(&dst) || p11.matches(&dst));
+            } else {
+                assert_eq!(dst, hashed);
+            }
+        }
+    }
+
+    #[test]
+    fn under_populated_policy_falls_back_to_default() {
+        let mut rng = rng::new();
+        let relocated_name: XorName = rng.gen();
+        let trigger_name: XorName = rng.gen();
+
+        let p0 = Prefix::default().pushed(false);
+        let p1 = Prefix::default().pushed(true);
+        let sections = [section(p0, 2), section(p1, 6)];
+
+        let context = RelocationContext {
+            src_prefix: &p0,
+            relocated_name: &relocated_name,
+            trigger_name: &trigger_name,
+            sections: &sections,
+            safe_section_size: 6,
+        };
+        assert_eq!(
+            UnderPopulatedRelocationPolicy.destination(&context),
+            DefaultRelocationPolicy.destination(&context)
+        );
+    }
+}
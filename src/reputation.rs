@@ -0,0 +1,166 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Reputation of other nodes, tracked by the elders.
+//!
+//! Every fault of a node we detect adds a penalty to its score, weighted by how severe the fault
+//! is. The score drops by one point every `PENALTY_DECAY_PERIOD`, so occasional faults which can
+//! also be caused by lagging nodes are forgiven. Once the score of a member of our section reaches
+//! `PENALTY_THRESHOLD`, we vote it offline. The scores are not shared: the node only goes offline
+//! once a quorum of the elders detected enough of its faults to vote so.
+
+use crate::{
+    id::PublicId,
+    time::{Duration, Instant},
+};
+use std::collections::{BTreeMap, HashMap};
+
+/// Penalty score at which the elders vote the node offline.
+pub const PENALTY_THRESHOLD: u32 = 100;
+/// Time it takes for one point of penalty to be forgiven.
+pub const PENALTY_DECAY_PERIOD: Duration = Duration::from_secs(6);
+
+/// Misbehaviour of another node.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Fault {
+    /// It sent us a signature share which doesn't match the message.
+    InvalidSignature,
+    /// It sent us a message whose proof we couldn't trust.
+    UntrustedMessage,
+    /// It sent us a relocation payload with an invalid signature.
+    InvalidRelocation,
+    /// It sent us parsec gossip which parsec rejected.
+    InvalidGossip,
+}
+
+impl Fault {
+    fn penalty(self) -> u32 {
+        match self {
+            Self::InvalidSignature | Self::InvalidRelocation => 25,
+            Self::UntrustedMessage => 10,
+            Self::InvalidGossip => 5,
+        }
+    }
+}
+
+struct Score {
+    penalty: u32,
+    // When the penalty last decayed.
+    updated: Instant,
+}
+
+impl Score {
+    // Number of decay periods elapsed since the last update.
+    fn elapsed_periods(&self, now: Instant) -> u32 {
+        let periods = (now - self.updated).as_millis() / PENALTY_DECAY_PERIOD.as_millis();
+        periods.min(u128::from(u32::max_value())) as u32
+    }
+
+    fn penalty_at(&self, now: Instant) -> u32 {
+        self.penalty.saturating_sub(self.elapsed_periods(now))
+    }
+
+    fn decay(&mut self, now: Instant) {
+        let periods = self.elapsed_periods(now);
+        if periods == 0 {
+            return;
+        }
+
+        self.penalty = self.penalty.saturating_sub(periods);
+        self.updated = if self.penalty > 0 {
+            self.updated + PENALTY_DECAY_PERIOD * periods
+        } else {
+            now
+        };
+    }
+}
+
+/// Penalty scores of the nodes which misbehaved recently.
+#[derive(Default)]
+pub struct PeerReputation {
+    scores: HashMap<PublicId, Score>,
+}
+
+impl PeerReputation {
+    /// Adds the penalty of the fault to the score of the node. Returns `true` if that made the
+    /// score reach `PENALTY_THRESHOLD`.
+    pub fn record(&mut self, pub_id: PublicId, fault: Fault) -> bool {
+        self.record_at(pub_id, fault, Instant::now())
+    }
+
+    /// Current penalty scores of the nodes which misbehaved recently.
+    pub fn scores(&self) -> BTreeMap<PublicId, u32> {
+        let now = Instant::now();
+        self.scores
+            .iter()
+            .map(|(pub_id, score)| (*pub_id, score.penalty_at(now)))
+            .filter(|(_, penalty)| *penalty > 0)
+            .collect()
+    }
+
+    fn record_at(&mut self, pub_id: PublicId, fault: Fault, now: Instant) -> bool {
+        for score in self.scores.values_mut() {
+            score.decay(now);
+        }
+        self.scores
+            .retain(|other_id, score| score.penalty > 0 || *other_id == pub_id);
+
+        let score = self.scores.entry(pub_id).or_insert_with(|| Score {
+            penalty: 0,
+            updated: now,
+        });
+        let below_threshold = score.penalty < PENALTY_THRESHOLD;
+        score.penalty = score.penalty.saturating_add(fault.penalty());
+        below_threshold && score.penalty >= PENALTY_THRESHOLD
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{id::FullId, rng};
+
+    #[test]
+    fn reach_threshold_once() {
+        let mut rng = rng::new();
+        let pub_id = *FullId::gen(&mut rng).public_id();
+        let mut reputation = PeerReputation::default();
+        let now = Instant::now();
+
+        let faults = (PENALTY_THRESHOLD / Fault::InvalidSignature.penalty()) as usize;
+        let results: Vec<_> = (0..faults + 1)
+            .map(|_| reputation.record_at(pub_id, Fault::InvalidSignature, now))
+            .collect();
+
+        let mut expected = vec![false; faults + 1];
+        expected[faults - 1] = true;
+        assert_eq!(results, expected);
+    }
+
+    #[test]
+    fn forgive_over_time() {
+        let mut rng = rng::new();
+        let pub_id = *FullId::gen(&mut rng).public_id();
+        let other_id = *FullId::gen(&mut rng).public_id();
+        let mut reputation = PeerReputation::default();
+        let now = Instant::now();
+
+        assert!(!reputation.record_at(pub_id, Fault::UntrustedMessage, now));
+        assert!(!reputation.record_at(other_id, Fault::InvalidGossip, now));
+
+        let later = now + PENALTY_DECAY_PERIOD * 3 + PENALTY_DECAY_PERIOD / 2;
+        assert_eq!(reputation.scores[&pub_id].penalty_at(later), 7);
+        assert_eq!(reputation.scores[&other_id].penalty_at(later), 2);
+
+        // The forgiven scores are dropped.
+        let later = now + PENALTY_DECAY_PERIOD * 5;
+        assert!(!reputation.record_at(pub_id, Fault::InvalidGossip, later));
+        assert_eq!(reputation.scores.len(), 1);
+        assert_eq!(reputation.scores[&pub_id].penalty_at(later), 10);
+    }
+}
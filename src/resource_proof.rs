@@ -0,0 +1,332 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Proof of resources required from a node before it is allowed to join a section.
+//!
+//! The elders send the joining node a random seed. The node has to expand the seed into `size`
+//! bytes of data and find a nonce such that the hash of the seed, the data and the nonce starts
+//! with `difficulty` zero bits. It then sends the data and the nonce back. This costs the node both
+//! bandwidth and CPU time, which makes flooding a section with new nodes expensive.
+//!
+//! As the difficulty and size are chosen by the elders, the node refuses to solve the challenges
+//! exceeding `MAX_DIFFICULTY` or `MAX_SIZE`, which it couldn't answer in time anyway.
+
+use crate::{
+    chain::ResourceProofParams,
+    crypto::{sha3_256, Digest256},
+    rng::MainRng,
+};
+use hex_fmt::HexFmt;
+use rand::Rng;
+use std::fmt::{self, Debug, Formatter};
+
+#[cfg(feature = "mock_base")]
+pub use self::overrides::Overrides;
+
+/// Maximum difficulty of a challenge the joining node is willing to solve.
+pub const MAX_DIFFICULTY: u8 = 20;
+/// Maximum size of the data the joining node is willing to send back, in bytes.
+pub const MAX_SIZE: usize = 1024 * 1024;
+
+/// Challenge sent by an elder to a node which wants to join its section.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct ResourceChallenge {
+    /// Number of leading zero bits required in the proof hash.
+    pub difficulty: u8,
+    /// Size of the data to send back, in bytes.
+    pub size: usize,
+    /// Random seed identifying this challenge.
+    pub seed: Digest256,
+}
+
+impl ResourceChallenge {
+    /// Creates a new challenge with a random seed.
+    pub fn new(rng: &mut MainRng, params: &ResourceProofParams) -> Self {
+        Self {
+            difficulty: params.difficulty,
+            size: params.size,
+            seed: rng.gen(),
+        }
+    }
+
+    /// Returns whether the difficulty and size of the challenge are within the limits the joining
+    /// node accepts.
+    pub fn is_within_limits(&self) -> bool {
+        self.difficulty <= MAX_DIFFICULTY && self.size <= MAX_SIZE
+    }
+
+    /// Solves the challenge. Takes on average `2^difficulty` hash computations, so it should only
+    /// be called for challenges within the limits.
+    pub fn solve(&self) -> ResourceProof {
+        let data = expand_seed(&self.seed, self.size);
+        let data_hash = sha3_256(&data);
+        let nonce = (0..)
+            .find(|nonce| {
+                leading_zero_bits(&proof_hash(&self.seed, &data_hash, *nonce))
+                    >= u32::from(self.difficulty)
+            })
+            .unwrap_or(0);
+
+        let proof = ResourceProof {
+            seed: self.seed,
+            data,
+            nonce,
+        };
+
+        #[cfg(feature = "mock_base")]
+        let proof = overrides::apply(proof);
+
+        proof
+    }
+
+    /// Checks that `proof` is a valid solution of this challenge.
+    pub fn verify(&self, proof: &ResourceProof) -> bool {
+        if proof.seed != self.seed || proof.data.len() != self.size {
+            return false;
+        }
+
+        if proof.data != expand_seed(&self.seed, self.size) {
+            return false;
+        }
+
+        let hash = proof_hash(&self.seed, &sha3_256(&proof.data), proof.nonce);
+        leading_zero_bits(&hash) >= u32::from(self.difficulty)
+    }
+}
+
+impl Debug for ResourceChallenge {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        formatter
+            .debug_struct("ResourceChallenge")
+            .field("difficulty", &self.difficulty)
+            .field("size", &self.size)
+            .field("seed", &format_args!("{:10}", HexFmt(&self.seed)))
+            .finish()
+    }
+}
+
+/// Answer of the joining node to a `ResourceChallenge`.
+#[derive(Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct ResourceProof {
+    /// Seed of the challenge this answers.
+    pub seed: Digest256,
+    /// The data expanded from the seed.
+    pub data: Vec<u8>,
+    /// The nonce which makes the proof hash start with enough zero bits.
+    pub nonce: u64,
+}
+
+impl Debug for ResourceProof {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        formatter
+            .debug_struct("ResourceProof")
+            .field("seed", &format_args!("{:10}", HexFmt(&self.seed)))
+            .field("data_len", &self.data.len())
+            .field("nonce", &self.nonce)
+            .finish()
+    }
+}
+
+// Deterministically expands the seed into `size` bytes.
+fn expand_seed(seed: &Digest256, size: usize) -> Vec<u8> {
+    let mut data = Vec::with_capacity(size);
+    let mut input = [0; 40];
+    input[..32].copy_from_slice(seed);
+
+    let mut counter = 0u64;
+    while data.len() < size {
+        input[32..].copy_from_slice(&counter.to_le_bytes());
+        let block = sha3_256(&input);
+        let len = block.len().min(size - data.len());
+        data.extend_from_slice(&block[..len]);
+        counter += 1;
+    }
+
+    data
+}
+
+fn proof_hash(seed: &Digest256, data_hash: &Digest256, nonce: u64) -> Digest256 {
+    let mut input = [0; 72];
+    input[..32].copy_from_slice(seed);
+    input[32..64].copy_from_slice(data_hash);
+    input[64..].copy_from_slice(&nonce.to_le_bytes());
+    sha3_256(&input)
+}
+
+fn leading_zero_bits(hash: &Digest256) -> u32 {
+    let mut bits = 0;
+    for byte in hash {
+        bits += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+#[cfg(feature = "mock_base")]
+mod overrides {
+    use super::ResourceProof;
+    use crate::time::Duration;
+    use fake_clock::FakeClock;
+    use std::cell::RefCell;
+
+    /// Mechanism for making the answers to resource challenges slow or invalid. Useful for tests.
+    /// The overrides are automatically `clear`ed when this instance goes out of scope.
+    pub struct Overrides {
+        _private: (),
+    }
+
+    impl Overrides {
+        /// Create new instance of resource proof overrides.
+        pub fn new() -> Self {
+            Self { _private: () }
+        }
+
+        /// Make solving any challenge advance the clock by `duration`, as if the node was slow.
+        pub fn set_solve_duration(&mut self, duration: Duration) {
+            OVERRIDES.with(|overrides| overrides.borrow_mut().solve_duration = duration)
+        }
+
+        /// Make the answers to all the challenges invalid.
+        pub fn corrupt_answers(&mut self) {
+            OVERRIDES.with(|overrides| overrides.borrow_mut().corrupt = true)
+        }
+
+        /// Clear all the overrides.
+        pub fn clear(&mut self) {
+            OVERRIDES.with(|overrides| *overrides.borrow_mut() = Default::default())
+        }
+    }
+
+    impl Default for Overrides {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl Drop for Overrides {
+        fn drop(&mut self) {
+            self.clear();
+        }
+    }
+
+    #[derive(Default)]
+    struct OverrideInfo {
+        solve_duration: Duration,
+        corrupt: bool,
+    }
+
+    pub(super) fn apply(mut proof: ResourceProof) -> ResourceProof {
+        OVERRIDES.with(|overrides| {
+            let overrides = overrides.borrow();
+
+            FakeClock::advance_time(overrides.solve_duration.as_millis() as u64);
+
+            if overrides.corrupt {
+                if let Some(byte) = proof.data.first_mut() {
+                    *byte ^= 1;
+                } else {
+                    proof.data.push(0);
+                }
+            }
+        });
+
+        proof
+    }
+
+    thread_local! {
+        static OVERRIDES: RefCell<OverrideInfo> = RefCell::new(Default::default());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{rng, time::Duration};
+
+    fn params() -> ResourceProofParams {
+        ResourceProofParams {
+            difficulty: 8,
+            size: 1000,
+            timeout: Duration::from_secs(1),
+        }
+    }
+
+    #[test]
+    fn solve_and_verify() {
+        let mut rng = rng::new();
+        let challenge = ResourceChallenge::new(&mut rng, &params());
+        let proof = challenge.solve();
+
+        assert_eq!(proof.data.len(), 1000);
+        assert!(challenge.verify(&proof));
+    }
+
+    #[test]
+    fn reject_invalid_proof() {
+        let mut rng = rng::new();
+        let challenge = ResourceChallenge::new(&mut rng, &params());
+        let proof = challenge.solve();
+
+        let mut wrong_data = proof.clone();
+        wrong_data.data[0] ^= 1;
+        assert!(!challenge.verify(&wrong_data));
+
+        let mut short_data = proof.clone();
+        let _ = short_data.data.pop();
+        assert!(!challenge.verify(&short_data));
+
+        let other_challenge = ResourceChallenge::new(&mut rng, &params());
+        assert!(!other_challenge.verify(&proof));
+
+        // Find a nonce which doesn't solve the challenge.
+        let data_hash = sha3_256(&proof.data);
+        let wrong_nonce = (0..)
+            .find(|nonce| {
+                leading_zero_bits(&proof_hash(&challenge.seed, &data_hash, *nonce))
+                    < u32::from(challenge.difficulty)
+            })
+            .unwrap_or(0);
+        let wrong_nonce = ResourceProof {
+            nonce: wrong_nonce,
+            ..proof
+        };
+        assert!(!challenge.verify(&wrong_nonce));
+    }
+
+    #[test]
+    fn check_limits() {
+        let mut rng = rng::new();
+        let challenge = ResourceChallenge::new(&mut rng, &params());
+        assert!(challenge.is_within_limits());
+
+        let too_hard = ResourceChallenge {
+            difficulty: MAX_DIFFICULTY + 1,
+            ..challenge
+        };
+        assert!(!too_hard.is_within_limits());
+
+        let too_large = ResourceChallenge {
+            size: MAX_SIZE + 1,
+            ..challenge
+        };
+        assert!(!too_large.is_within_limits());
+    }
+
+    #[test]
+    fn count_leading_zero_bits() {
+        let mut hash = [0; 32];
+        assert_eq!(leading_zero_bits(&hash), 256);
+        hash[0] = 0b1000_0000;
+        assert_eq!(leading_zero_bits(&hash), 0);
+        hash[0] = 0;
+        hash[1] = 0b0001_0000;
+        assert_eq!(leading_zero_bits(&hash), 11);
+    }
+}
@@ -8,9 +8,10 @@
 
 use crate::{
     crypto::Digest256, id::PublicId, location::DstLocation, message_filter::MessageFilter,
-    messages::MessageWithBytes,
+    messages::MessageWithBytes, metrics::Metrics,
 };
 use lru_time_cache::LruCache;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::time::Duration;
 
 const INCOMING_EXPIRY_DURATION_SECS: u64 = 60 * 20;
@@ -52,7 +53,11 @@ impl RoutingMessageFilter {
     }
 
     // Filter incoming `RoutingMessage`. Return whether this specific message has already been seen.
-    pub fn filter_incoming(&mut self, msg: &MessageWithBytes) -> FilteringResult {
+    pub fn filter_incoming(
+        &mut self,
+        msg: &MessageWithBytes,
+        metrics: &Metrics,
+    ) -> FilteringResult {
         // Not filtering direct messages.
         if let DstLocation::Direct = msg.message_dst() {
             return FilteringResult::NewMessage;
@@ -61,12 +66,23 @@ impl RoutingMessageFilter {
         let hash = msg.full_crypto_hash();
 
         if self.incoming.insert(hash) > 1 {
+            metrics.filter_hit("incoming");
             FilteringResult::KnownMessage
         } else {
             FilteringResult::NewMessage
         }
     }
 
+    // Record the incoming `RoutingMessage` as seen without filtering it, e.g. because we are
+    // sending it ourselves.
+    pub fn mark_incoming(&mut self, msg: &MessageWithBytes) {
+        if let DstLocation::Direct = msg.message_dst() {
+            return;
+        }
+
+        let _ = self.incoming.insert(msg.full_crypto_hash());
+    }
+
     // Filter outgoing `RoutingMessage`. Return whether this specific message has been seen recently
     // (and thus should not be sent, due to deduplication).
     //
@@ -75,6 +91,7 @@ impl RoutingMessageFilter {
         &mut self,
         msg: &MessageWithBytes,
         pub_id: &PublicId,
+        metrics: &Metrics,
     ) -> FilteringResult {
         // Not filtering direct messages.
         if let DstLocation::Direct = msg.message_dst() {
@@ -84,6 +101,7 @@ impl RoutingMessageFilter {
         let hash = msg.full_crypto_hash();
 
         if self.outgoing.insert((*hash, *pub_id), ()).is_some() {
+            metrics.filter_hit("outgoing");
             FilteringResult::KnownMessage
         } else {
             FilteringResult::NewMessage
@@ -91,6 +109,26 @@ impl RoutingMessageFilter {
     }
 }
 
+// Only the incoming filter is persisted. The outgoing one merely prevents sending duplicates and
+// starts empty after deserialisation.
+impl Serialize for RoutingMessageFilter {
+    fn serialize<S: Serializer>(&self, serialiser: S) -> Result<S::Ok, S::Error> {
+        self.incoming.serialize(serialiser)
+    }
+}
+
+impl<'de> Deserialize<'de> for RoutingMessageFilter {
+    fn deserialize<D: Deserializer<'de>>(deserialiser: D) -> Result<Self, D::Error> {
+        let incoming = Deserialize::deserialize(deserialiser)?;
+        let outgoing_duration = Duration::from_secs(OUTGOING_EXPIRY_DURATION_SECS);
+
+        Ok(Self {
+            incoming,
+            outgoing: LruCache::with_expiry_duration(outgoing_duration),
+        })
+    }
+}
+
 impl Default for RoutingMessageFilter {
     fn default() -> Self {
         Self::new()
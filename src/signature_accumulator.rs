@@ -9,10 +9,12 @@
 use crate::{
     crypto::Digest256,
     messages::{AccumulatingMessage, Message, MessageWithBytes},
+    metrics::Metrics,
     time::{Duration, Instant},
     utils::LogIdent,
 };
 use itertools::Itertools;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::collections::HashMap;
 
 /// Time (in seconds) within which a message and a quorum of signatures need to arrive to
@@ -31,6 +33,7 @@ impl SignatureAccumulator {
         &mut self,
         msg: AccumulatingMessage,
         log_ident: &LogIdent,
+        metrics: &Metrics,
     ) -> Option<MessageWithBytes> {
         self.remove_expired();
         let hash = msg.crypto_hash().ok()?;
@@ -42,7 +45,9 @@ impl SignatureAccumulator {
             let _ = self.msgs.insert(hash, (Some(msg), Instant::now()));
         }
 
-        let msg = self.remove_if_complete(&hash)?;
+        let (msg, latency) = self.remove_if_complete(&hash)?;
+        metrics.signatures_accumulated(latency);
+
         match MessageWithBytes::new(msg, log_ident) {
             Ok(msg) => Some(msg),
             Err(error) => {
@@ -69,10 +74,14 @@ impl SignatureAccumulator {
         }
     }
 
-    fn remove_if_complete(&mut self, hash: &Digest256) -> Option<Message> {
-        self.msgs.get_mut(hash).and_then(|(msg, _)| {
+    // Returns the message if it's fully signed now, together with the time elapsed since its first
+    // signature was added.
+    fn remove_if_complete(&mut self, hash: &Digest256) -> Option<(Message, Duration)> {
+        self.msgs.get_mut(hash).and_then(|(msg, first_seen)| {
             if msg.as_mut().map_or(false, |msg| msg.check_fully_signed()) {
-                msg.take().and_then(|msg| msg.combine_signatures())
+                msg.take()
+                    .and_then(|msg| msg.combine_signatures())
+                    .map(|msg| (msg, first_seen.elapsed()))
             } else {
                 None
             }
@@ -80,6 +89,34 @@ impl SignatureAccumulator {
     }
 }
 
+// The accumulation timestamps are not serialised: pending messages get a full
+// `ACCUMULATION_TIMEOUT` again after deserialisation.
+impl Serialize for SignatureAccumulator {
+    fn serialize<S: Serializer>(&self, serialiser: S) -> Result<S::Ok, S::Error> {
+        let msgs: Vec<_> = self
+            .msgs
+            .iter()
+            .map(|(hash, (msg, _))| (hash, msg))
+            .collect();
+        msgs.serialize(serialiser)
+    }
+}
+
+impl<'de> Deserialize<'de> for SignatureAccumulator {
+    fn deserialize<D: Deserializer<'de>>(deserialiser: D) -> Result<Self, D::Error> {
+        let msgs: Vec<(Digest256, Option<AccumulatingMessage>)> =
+            Deserialize::deserialize(deserialiser)?;
+        let now = Instant::now();
+
+        Ok(Self {
+            msgs: msgs
+                .into_iter()
+                .map(|(hash, msg)| (hash, (msg, now)))
+                .collect(),
+        })
+    }
+}
+
 #[cfg(test)]
 #[cfg(feature = "mock_base")]
 mod tests {
@@ -203,7 +240,7 @@ mod tests {
         // Add each message with the section list added - none should accumulate.
         env.msgs_and_sigs.iter().foreach(|msg_and_sigs| {
             let signed_msg = msg_and_sigs.signed_msg.clone();
-            let result = sig_accumulator.add_proof(signed_msg, &log_ident);
+            let result = sig_accumulator.add_proof(signed_msg, &log_ident, &Metrics::default());
             assert!(result.is_none());
         });
         let expected_msgs_count = env.msgs_and_sigs.len();
@@ -216,7 +253,9 @@ mod tests {
                 let old_num_msgs = sig_accumulator.msgs.len();
 
                 let result = match signature_msg.variant {
-                    Variant::MessageSignature(msg) => sig_accumulator.add_proof(*msg, &log_ident),
+                    Variant::MessageSignature(msg) => {
+                        sig_accumulator.add_proof(*msg, &log_ident, &Metrics::default())
+                    }
                     unexpected_msg => panic!("Unexpected message: {:?}", unexpected_msg),
                 };
 
@@ -8,27 +8,27 @@
 
 use crate::{
     action::Action,
-    chain::{EldersInfo, GenesisPfxInfo},
+    chain::{EldersInfo, GenesisPfxInfo, SectionProofSlice},
     error::RoutingError,
     id::{P2pNode, PublicId},
-    network_service::{NetworkBuilder, NetworkService},
+    location::DstLocation,
+    network_service::{EventSink, NetworkBuilder, NetworkEvent, NetworkService},
     outbox::EventBox,
     pause::PausedState,
-    quic_p2p::EventSenders,
     relocation::{RelocatePayload, SignedRelocateDetails},
-    states::{common::Base, Adult, BootstrappingPeer, Elder, JoiningPeer},
+    states::{
+        common::{Approved, Base},
+        Adult, BootstrappingPeer, Elder, JoiningPeer,
+    },
+    status::{NodeState, NodeStatus},
     timer::Timer,
     xor_space::{Prefix, XorName},
-    NetworkConfig, NetworkEvent,
 };
 #[cfg(feature = "mock_base")]
-use crate::{
-    chain::Chain,
-    location::{DstLocation, SrcLocation},
-    rng::MainRng,
-};
+use crate::{chain::Chain, location::SrcLocation, rng::MainRng};
 use crossbeam_channel as mpmc;
 use std::{
+    collections::BTreeMap,
     fmt::{self, Debug, Display, Formatter},
     mem,
     net::SocketAddr,
@@ -69,6 +69,15 @@ pub enum State {
 }
 
 impl State {
+    // Recreate the state of a previously paused node.
+    pub fn resume(state: PausedState, network_service: NetworkService, timer: Timer) -> Self {
+        if state.is_elder() {
+            State::Elder(Elder::resume(state, network_service, timer))
+        } else {
+            State::Adult(Adult::resume(state, network_service, timer))
+        }
+    }
+
     pub fn handle_action(&mut self, action: Action, outbox: &mut dyn EventBox) -> Transition {
         state_dispatch!(
             *self,
@@ -105,6 +114,14 @@ impl State {
         )
     }
 
+    pub fn outbound_queue_depth(&self) -> usize {
+        state_dispatch!(
+            *self,
+            ref state => state.network_service().queued_user_messages(),
+            Terminated => 0
+        )
+    }
+
     pub fn close_group(&self, name: XorName, count: usize) -> Option<Vec<XorName>> {
         state_dispatch!(
             *self,
@@ -123,6 +140,16 @@ impl State {
         }
     }
 
+    pub fn peer_penalties(&self) -> BTreeMap<PublicId, u32> {
+        match *self {
+            Self::Elder(ref state) => state.peer_penalties(),
+            Self::BootstrappingPeer(_)
+            | Self::JoiningPeer(_)
+            | Self::Adult(_)
+            | Self::Terminated => BTreeMap::new(),
+        }
+    }
+
     pub fn matches_our_prefix(&self, name: &XorName) -> Result<bool, RoutingError> {
         match *self {
             Self::Elder(ref state) => Ok(state.our_prefix().matches(name)),
@@ -149,11 +176,37 @@ impl State {
     pub fn our_connection_info(&mut self) -> Result<SocketAddr, RoutingError> {
         state_dispatch!(
             self,
-            state => state.network_service_mut().our_connection_info().map_err(RoutingError::from),
+            state => state.network_service_mut().our_connection_info(),
             Terminated => Err(RoutingError::InvalidState)
         )
     }
 
+    pub fn prove(&self, target: &DstLocation) -> Option<SectionProofSlice> {
+        match *self {
+            Self::Elder(ref state) => Some(state.chain().prove(target, None)),
+            Self::Adult(ref state) => Some(state.chain().prove(target, None)),
+            Self::BootstrappingPeer(_) | Self::JoiningPeer(_) | Self::Terminated => None,
+        }
+    }
+
+    pub fn export_section_history(&self) -> Result<Vec<u8>, RoutingError> {
+        match *self {
+            Self::Elder(ref state) => state.export_section_history(),
+            Self::Adult(ref state) => state.export_section_history(),
+            Self::BootstrappingPeer(_) | Self::JoiningPeer(_) | Self::Terminated => {
+                Err(RoutingError::InvalidState)
+            }
+        }
+    }
+
+    pub fn status(&self) -> NodeStatus {
+        state_dispatch!(
+            *self,
+            ref state => state.status(),
+            Terminated => NodeStatus::new(NodeState::Terminated, None)
+        )
+    }
+
     /// Returns this elder mut state.
     pub fn elder_state_mut(&mut self) -> Option<&mut Elder> {
         match *self {
@@ -226,6 +279,15 @@ impl State {
         )
     }
 
+    #[cfg(feature = "mock_base")]
+    pub fn poll_transport(&mut self) {
+        state_dispatch!(
+            self,
+            state => state.network_service_mut().poll_transport(),
+            Terminated => ()
+        )
+    }
+
     pub fn has_unpolled_observations(&self) -> bool {
         match *self {
             Self::Terminated | Self::BootstrappingPeer(_) | Self::JoiningPeer(_) => false,
@@ -318,23 +380,17 @@ impl StateMachine {
     // Construct a new StateMachine by passing a function returning the initial state.
     pub fn new<F>(
         init_state: F,
-        network_config: NetworkConfig,
-        client_tx: mpmc::Sender<NetworkEvent>,
+        network: NetworkBuilder,
         outbox: &mut dyn EventBox,
     ) -> (mpmc::Sender<Action>, Self)
     where
         F: FnOnce(NetworkService, Timer, &mut dyn EventBox) -> State,
     {
         let (action_tx, action_rx) = mpmc::unbounded();
-        let (network_tx, network_rx) = {
-            let (node_tx, node_rx) = mpmc::unbounded();
-            (EventSenders { node_tx, client_tx }, node_rx)
-        };
+        // The events concerning both nodes and clients are handled by the state machine.
+        let (network_tx, network_rx) = mpmc::unbounded();
 
-        let network_service = match NetworkBuilder::new(network_tx)
-            .with_config(network_config)
-            .build()
-        {
+        let network_service = match network.build(EventSink::new(network_tx)) {
             Ok(network_service) => network_service,
             Err(err) => panic!("Unable to start network service: {:?}", err),
         };
@@ -373,14 +429,14 @@ impl StateMachine {
 
     pub fn resume(mut state: PausedState) -> (mpmc::Sender<Action>, Self) {
         let (action_tx, action_rx) = mpmc::unbounded();
+        let network_service = state
+            .network_service
+            .take()
+            .expect("PausedState is incomplete");
         let network_rx = state.network_rx.take().expect("PausedState is incomplete");
 
         let timer = Timer::new(action_tx.clone());
-        let state = if state.is_elder() {
-            State::Elder(Elder::resume(state, timer))
-        } else {
-            State::Adult(Adult::resume(state, timer))
-        };
+        let state = State::resume(state, network_service, timer);
 
         let machine = Self {
             state,
@@ -461,6 +517,9 @@ impl StateMachine {
         // Populate action_rx timeouts
         #[cfg(feature = "mock_base")]
         self.state.process_timers();
+        // Collect the events the mock network delivered to our transport.
+        #[cfg(feature = "mock_base")]
+        self.state.poll_transport();
 
         let network_rx_idx = select.recv(&self.network_rx);
         let action_rx_idx = select.recv(&self.action_rx);
@@ -509,6 +568,13 @@ impl StateMachine {
         }
     }
 
+    /// Returns clones of the channels the state machine receives its inputs from, so their
+    /// readiness can be watched from another thread.
+    #[cfg(feature = "async")]
+    pub fn input_channels(&self) -> (mpmc::Receiver<NetworkEvent>, mpmc::Receiver<Action>) {
+        (self.network_rx.clone(), self.action_rx.clone())
+    }
+
     /// Get reference to the current state.
     pub fn current(&self) -> &State {
         &self.state
@@ -16,8 +16,8 @@ use super::{
 };
 use crate::{
     chain::{
-        Chain, EldersChange, EldersInfo, GenesisPfxInfo, NetworkParams, OnlinePayload,
-        SectionKeyInfo, SendAckMessagePayload,
+        Chain, EldersChange, EldersInfo, GenesisPfxInfo, MergeDetails, NetworkParams,
+        OnlinePayload, SectionKeyInfo, SendAckMessagePayload,
     },
     error::{Result, RoutingError},
     event::Event,
@@ -36,6 +36,7 @@ use crate::{
     routing_message_filter::RoutingMessageFilter,
     signature_accumulator::SignatureAccumulator,
     state_machine::{State, Transition},
+    status::{NodeState, NodeStatus},
     time::Duration,
     timer::Timer,
     utils::LogIdent,
@@ -79,6 +80,9 @@ pub struct Adult {
     msg_filter: RoutingMessageFilter,
     timer: Timer,
     rng: MainRng,
+    // Genesis our sibling sent us after merging our section into its own, kept until our parsec
+    // agrees on the merge as well.
+    merged_genesis: Option<GenesisPfxInfo>,
 }
 
 impl Adult {
@@ -116,6 +120,7 @@ impl Adult {
             timer: details.timer,
             knowledge_timer_token,
             rng: details.rng,
+            merged_genesis: None,
         };
 
         Ok(node)
@@ -195,19 +200,19 @@ impl Adult {
             msg_filter: self.msg_filter,
             msg_queue: VecDeque::new(),
             msg_backlog: self.msg_backlog,
-            network_service: self.network_service,
+            network_service: Some(self.network_service),
             network_rx: None,
             sig_accumulator: self.sig_accumulator,
             parsec_map: self.parsec_map,
         }
     }
 
-    pub fn resume(state: PausedState, timer: Timer) -> Self {
+    pub fn resume(state: PausedState, network_service: NetworkService, timer: Timer) -> Self {
         let knowledge_timer_token = timer.schedule(KNOWLEDGE_TIMEOUT);
 
         Self {
             chain: state.chain,
-            network_service: state.network_service,
+            network_service,
             event_backlog: Vec::new(),
             full_id: state.full_id,
             gen_pfx_info: state.gen_pfx_info,
@@ -218,6 +223,7 @@ impl Adult {
             msg_filter: state.msg_filter,
             timer,
             rng: rng::new(),
+            merged_genesis: None,
         }
     }
 
@@ -225,6 +231,13 @@ impl Adult {
         self.chain.our_prefix()
     }
 
+    pub fn status(&self) -> NodeStatus {
+        let mut status = NodeStatus::new(NodeState::Adult, Some(self.id()))
+            .with_chain(&self.chain, &self.parsec_map);
+        status.backlog.messages = self.msg_backlog.len();
+        status
+    }
+
     fn handle_relocate(
         &mut self,
         signed_msg: SignedRelocateDetails,
@@ -294,10 +307,30 @@ impl Adult {
 
     fn handle_genesis_update(
         &mut self,
+        src_prefix: Prefix<XorName>,
         gen_pfx_info: GenesisPfxInfo,
     ) -> Result<Transition, RoutingError> {
         info!("{} - Received GenesisUpdate: {:?}", self, gen_pfx_info);
 
+        if src_prefix == self.our_prefix().sibling() && self.our_prefix().bit_count() > 0 {
+            // Our sibling merged our section into its own. Switch to its parsec only once ours
+            // agreed on the merge too, see `handle_merge_polled`.
+            if self.chain.merging_into() != Some(src_prefix) {
+                self.merged_genesis = Some(gen_pfx_info);
+                return Ok(Transition::Stay);
+            }
+        } else if !src_prefix.is_compatible(self.our_prefix()) {
+            debug!(
+                "{} - Ignoring GenesisUpdate from {:?} which is not our section.",
+                self, src_prefix
+            );
+            return Ok(Transition::Stay);
+        }
+
+        self.apply_genesis(gen_pfx_info)
+    }
+
+    fn apply_genesis(&mut self, gen_pfx_info: GenesisPfxInfo) -> Result<Transition, RoutingError> {
         // An Adult can receive the same message from multiple Elders - bail early if we are
         // already up to date
         if gen_pfx_info.parsec_version <= self.gen_pfx_info.parsec_version {
@@ -310,7 +343,9 @@ impl Adult {
             &self.gen_pfx_info,
             &LogIdent::new(self.full_id.public_id()),
         );
+        let section_history = self.chain.section_history().clone();
         self.chain = Chain::new(self.chain.network_cfg(), *self.id(), gen_pfx_info, None);
+        self.chain.continue_section_history(section_history);
 
         // We were not promoted during the last section change, so we are not going to need these
         // messages anymore. This also prevents the messages from becoming stale (fail the trust
@@ -335,7 +370,10 @@ impl Adult {
             );
         }
 
-        if let Some(msg) = self.sig_accumulator.add_proof(msg, &self.log_ident()) {
+        if let Some(msg) =
+            self.sig_accumulator
+                .add_proof(msg, &self.log_ident(), self.network_service.metrics())
+        {
             self.try_handle_message(None, msg, outbox)
         } else {
             Ok(Transition::Stay)
@@ -437,8 +475,8 @@ impl Base for Adult {
 
         match msg.variant {
             Variant::GenesisUpdate(info) => {
-                let _: &Prefix<_> = msg.src.as_section()?;
-                self.handle_genesis_update(*info)
+                let src_prefix = *msg.src.as_section()?;
+                self.handle_genesis_update(src_prefix, *info)
             }
             Variant::Relocate(_) => {
                 let _: &Prefix<_> = msg.src.as_section()?;
@@ -467,7 +505,7 @@ impl Base for Adult {
 
     fn unhandled_message(&mut self, sender: Option<SocketAddr>, msg: Message) {
         match msg.variant {
-            Variant::Ping | Variant::BootstrapResponse(_) => {
+            Variant::Ping | Variant::BootstrapResponse(_) | Variant::ResourceChallenge(_) => {
                 debug!("{} Unhandled message, discarding: {:?}", self, msg);
             }
             _ => {
@@ -478,7 +516,9 @@ impl Base for Adult {
     }
 
     fn filter_incoming_message(&mut self, message: &MessageWithBytes) -> bool {
-        self.msg_filter.filter_incoming(message).is_new()
+        self.msg_filter
+            .filter_incoming(message, self.network_service.metrics())
+            .is_new()
     }
 
     fn should_handle_message(&self, msg: &Message) -> bool {
@@ -491,11 +531,19 @@ impl Base for Adult {
             | Variant::BootstrapRequest(_) => true,
 
             Variant::NeighbourInfo(_)
+            | Variant::MemberCount(_)
+            | Variant::Merge(_)
             | Variant::UserMessage(_)
+            | Variant::UserMessageFragment(_)
+            | Variant::UserMessageWithAck(_)
+            | Variant::MessageDeliveryAck(_)
             | Variant::NodeApproval(_)
             | Variant::AckMessage { .. }
             | Variant::JoinRequest(_)
+            | Variant::ResourceChallenge(_)
+            | Variant::ResourceProofResponse(_)
             | Variant::MemberKnowledge(_)
+            | Variant::ParsecResync(_)
             | Variant::BootstrapResponse(_)
             | Variant::Ping => false,
         }
@@ -522,22 +570,23 @@ impl Base for Adult {
         );
 
         let msg_filter = &mut self.msg_filter;
+        let metrics = self.network_service.metrics();
         let targets: Vec<_> = self
             .chain
             .our_elders()
             .filter(|p2p_node| {
                 msg_filter
-                    .filter_outgoing(msg, p2p_node.public_id())
+                    .filter_outgoing(msg, p2p_node.public_id(), metrics)
                     .is_new()
             })
             .map(|node| *node.peer_addr())
             .collect();
 
-        let cheap_bytes_clone = msg.full_bytes().clone();
-        self.send_message_to_targets(&targets, targets.len(), cheap_bytes_clone);
+        let bytes = msg.full_bytes().clone();
+        self.send_message_to_targets(&targets, targets.len(), bytes);
 
         // we've seen this message - don't handle it again if someone else sends it to us
-        let _ = self.msg_filter.filter_incoming(msg);
+        self.msg_filter.mark_incoming(msg);
 
         Ok(())
     }
@@ -583,6 +632,29 @@ impl Approved for Adult {
         Ok(())
     }
 
+    fn handle_merge_polled(&mut self, details: MergeDetails) -> Result<Transition, RoutingError> {
+        if self.chain.is_merge_lead() {
+            info!(
+                "{} - Our section merges with our sibling {:?}.",
+                self,
+                details.elders_info.prefix()
+            );
+            return Ok(Transition::Stay);
+        }
+
+        info!(
+            "{} - Our section merges into our sibling {:?}.",
+            self,
+            details.elders_info.prefix()
+        );
+
+        // Our sibling might have sent us its genesis before our parsec got here.
+        match self.merged_genesis.take() {
+            Some(gen_pfx_info) => self.apply_genesis(gen_pfx_info),
+            None => Ok(Transition::Stay),
+        }
+    }
+
     fn handle_member_added(
         &mut self,
         _payload: OnlinePayload,
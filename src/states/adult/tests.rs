@@ -12,10 +12,14 @@ use mock_quic_p2p::Network;
 use std::collections::BTreeMap;
 
 const ELDER_SIZE: usize = 3;
-const NETWORK_PARAMS: NetworkParams = NetworkParams {
-    elder_size: ELDER_SIZE,
-    safe_section_size: ELDER_SIZE + 1,
-};
+
+fn network_params() -> NetworkParams {
+    NetworkParams {
+        elder_size: ELDER_SIZE,
+        safe_section_size: ELDER_SIZE + 1,
+        ..NetworkParams::default()
+    }
+}
 
 struct AdultUnderTest {
     rng: MainRng,
@@ -119,7 +123,7 @@ fn create_elders(
             let gen_pfx_info =
                 test_utils::create_gen_pfx_info(elders_info.clone(), public_key_set.clone(), 0);
             let chain = Chain::new(
-                NETWORK_PARAMS,
+                network_params(),
                 *id,
                 gen_pfx_info,
                 Some(secret_key_set.secret_key_share(index)),
@@ -144,6 +148,7 @@ fn new_adult_state(
         first_ages,
         latest_info: EldersInfo::default(),
         parsec_version: 0,
+        ban_list: Default::default(),
     };
 
     let full_id = FullId::gen(rng);
@@ -157,7 +162,7 @@ fn new_adult_state(
         sig_accumulator: Default::default(),
         msg_filter: Default::default(),
         timer: test_utils::create_timer(),
-        network_cfg: NETWORK_PARAMS,
+        network_cfg: network_params(),
         rng: rng::new_from(rng),
     };
 
@@ -13,13 +13,14 @@ use crate::{
     event::Event,
     id::FullId,
     location::{DstLocation, SrcLocation},
-    messages::{BootstrapResponse, Message, MessageWithBytes, Variant, VerifyStatus},
+    messages::{BootstrapResponse, Message, MessageId, MessageWithBytes, Variant, VerifyStatus},
     network_service::NetworkService,
     outbox::EventBox,
     relocation::{RelocatePayload, SignedRelocateDetails},
     rng::MainRng,
     state_machine::{State, Transition},
     states::JoiningPeer,
+    status::{NodeState, NodeStatus},
     timer::Timer,
     xor_space::{Prefix, XorName},
 };
@@ -58,7 +59,7 @@ pub struct BootstrappingPeer {
 
 impl BootstrappingPeer {
     pub fn new(mut details: BootstrappingPeerDetails) -> Self {
-        details.network_service.service_mut().bootstrap();
+        details.network_service.bootstrap();
         Self {
             network_service: details.network_service,
             full_id: details.full_id,
@@ -191,9 +192,13 @@ impl BootstrappingPeer {
 
     fn request_failed(&mut self) {
         if self.pending_requests.is_empty() {
-            self.network_service.service_mut().bootstrap();
+            self.network_service.bootstrap();
         }
     }
+
+    pub fn status(&self) -> NodeStatus {
+        NodeStatus::new(NodeState::BootstrappingPeer, Some(self.id()))
+    }
 }
 
 impl Base for BootstrappingPeer {
@@ -229,11 +234,11 @@ impl Base for BootstrappingPeer {
         _: SrcLocation,
         _: DstLocation,
         _: Vec<u8>,
-    ) -> Result<(), RoutingError> {
+        _: bool,
+    ) -> Result<MessageId, RoutingError> {
         warn!("{} - Cannot handle SendMessage - not bootstrapped.", self);
-        // TODO: return Err here eventually. Returning Ok for now to
-        // preserve the pre-refactor behaviour.
-        Ok(())
+        // There is no message, so there is no id to return.
+        Err(RoutingError::InvalidState)
     }
 
     fn handle_timeout(&mut self, token: u64, _: &mut dyn EventBox) -> Transition {
@@ -315,7 +320,12 @@ impl Base for BootstrappingPeer {
         match msg.variant {
             Variant::BootstrapResponse(_) => true,
             Variant::NeighbourInfo(_)
+            | Variant::MemberCount(_)
+            | Variant::Merge(_)
             | Variant::UserMessage(_)
+            | Variant::UserMessageFragment(_)
+            | Variant::UserMessageWithAck(_)
+            | Variant::MessageDeliveryAck(_)
             | Variant::NodeApproval(_)
             | Variant::AckMessage { .. }
             | Variant::GenesisUpdate(_)
@@ -323,7 +333,10 @@ impl Base for BootstrappingPeer {
             | Variant::MessageSignature(_)
             | Variant::BootstrapRequest(_)
             | Variant::JoinRequest(_)
+            | Variant::ResourceChallenge(_)
+            | Variant::ResourceProofResponse(_)
             | Variant::MemberKnowledge { .. }
+            | Variant::ParsecResync(_)
             | Variant::ParsecRequest(..)
             | Variant::ParsecResponse(..)
             | Variant::Ping => false,
@@ -351,16 +364,12 @@ impl Display for BootstrappingPeer {
 mod tests {
     use super::*;
     use crate::{
-        chain::NetworkParams,
-        id::FullId,
-        messages::Message,
-        mock::Environment,
-        quic_p2p::{Builder, EventSenders, Peer},
-        state_machine::StateMachine,
-        unwrap, NetworkConfig, NetworkEvent,
+        chain::NetworkParams, id::FullId, messages::Message, mock::Environment,
+        network_service::NetworkBuilder, state_machine::StateMachine, unwrap, NetworkConfig,
     };
     use crossbeam_channel as mpmc;
     use fake_clock::FakeClock;
+    use mock_quic_p2p::{Builder, Event as NetworkEvent, EventSenders, Peer};
 
     #[test]
     // Check that losing our proxy connection while in the `BootstrappingPeer` state doesn't stall
@@ -395,7 +404,6 @@ mod tests {
         let node_b_full_id = FullId::gen(&mut rng);
 
         let mut node_b_outbox = Vec::new();
-        let (node_b_client_tx, _) = mpmc::unbounded();
 
         let (_node_b_action_tx, mut node_b_state_machine) = StateMachine::new(
             move |network_service, timer, _outbox2| {
@@ -407,8 +415,7 @@ mod tests {
                     rng,
                 }))
             },
-            config,
-            node_b_client_tx,
+            NetworkBuilder::new().with_config(config),
             &mut node_b_outbox,
         );
 
@@ -10,7 +10,8 @@ use super::Base;
 use crate::{
     chain::{
         AccumulatedEvent, AccumulatingEvent, Chain, EldersChange, EldersInfo, MemberState,
-        OnlinePayload, PollAccumulated, Proof, ProofSet, SectionKeyInfo, SendAckMessagePayload,
+        MergeDetails, OnlinePayload, PollAccumulated, Proof, ProofSet, SectionKeyInfo,
+        SendAckMessagePayload,
     },
     error::{Result, RoutingError},
     event::Event,
@@ -19,6 +20,7 @@ use crate::{
     outbox::EventBox,
     parsec::{self, Block, DkgResultWrapper, Observation, ParsecMap},
     relocation::{RelocateDetails, SignedRelocateDetails},
+    reputation::Fault,
     state_machine::Transition,
     xor_space::{Prefix, XorName},
 };
@@ -36,6 +38,9 @@ pub trait Approved: Base {
     fn set_pfx_successfully_polled(&mut self, val: bool);
     fn is_pfx_successfully_polled(&self) -> bool;
 
+    /// Handles detecting a fault of the node. Only elders keep track of them.
+    fn handle_fault(&mut self, _pub_id: PublicId, _fault: Fault) {}
+
     /// Handles an accumulated relocation trigger
     fn handle_relocate_polled(&mut self, details: RelocateDetails) -> Result<(), RoutingError>;
 
@@ -45,6 +50,9 @@ pub trait Approved: Base {
         new_infos: Vec<EldersInfo>,
     ) -> Result<(), RoutingError>;
 
+    /// Handles an accumulated agreement to merge with our sibling
+    fn handle_merge_polled(&mut self, details: MergeDetails) -> Result<Transition, RoutingError>;
+
     /// Handles a member added.
     fn handle_member_added(
         &mut self,
@@ -120,6 +128,14 @@ pub trait Approved: Base {
     /// Handles an accumulated `ParsecPrune` event.
     fn handle_prune_event(&mut self) -> Result<(), RoutingError>;
 
+    /// Returns the history of our section, signed with our id, in the export format described in
+    /// `chain::section_history`.
+    fn export_section_history(&self) -> Result<Vec<u8>, RoutingError> {
+        self.chain()
+            .section_history()
+            .to_signed_bytes(self.full_id())
+    }
+
     fn handle_parsec_request(
         &mut self,
         msg_version: u64,
@@ -136,12 +152,22 @@ pub trait Approved: Base {
         );
 
         let log_ident = self.log_ident();
-        let response = self.parsec_map_mut().handle_request(
+        let metrics = self.network_service().metrics().clone();
+        let response = match self.parsec_map_mut().handle_request(
             msg_version,
             par_request,
             *p2p_node.public_id(),
             &log_ident,
-        );
+            &metrics,
+        ) {
+            Ok(response) => response,
+            Err(error) => {
+                if parsec::is_invalid_gossip(&error) {
+                    self.handle_fault(*p2p_node.public_id(), Fault::InvalidGossip);
+                }
+                None
+            }
+        };
 
         if let Some(response) = response {
             trace!(
@@ -175,8 +201,18 @@ pub trait Approved: Base {
         );
 
         let log_ident = self.log_ident();
-        self.parsec_map_mut()
-            .handle_response(msg_version, par_response, pub_id, &log_ident);
+        let metrics = self.network_service().metrics().clone();
+        if let Err(error) = self.parsec_map_mut().handle_response(
+            msg_version,
+            par_response,
+            pub_id,
+            &log_ident,
+            &metrics,
+        ) {
+            if parsec::is_invalid_gossip(&error) {
+                self.handle_fault(pub_id, Fault::InvalidGossip);
+            }
+        }
 
         if msg_version == self.parsec_map().last_version() {
             self.parsec_poll(outbox)
@@ -360,6 +396,12 @@ pub trait Approved: Base {
                 PollAccumulated::PromoteDemoteElders(new_infos) => {
                     self.handle_promote_and_demote_elders(new_infos)?;
                 }
+                PollAccumulated::MergeWithSibling(details) => {
+                    match self.handle_merge_polled(details)? {
+                        Transition::Stay => (),
+                        transition => return Ok(transition),
+                    }
+                }
             }
 
             old_pfx = *self.chain_mut().our_prefix();
@@ -384,12 +426,15 @@ pub trait Approved: Base {
                 );
             }
             AccumulatingEvent::Online(payload) => {
+                self.network_service().metrics().churn_event("online");
                 self.handle_online_event(payload, outbox)?;
             }
             AccumulatingEvent::Offline(pub_id) => {
+                self.network_service().metrics().churn_event("offline");
                 self.handle_offline_event(pub_id, outbox)?;
             }
             AccumulatingEvent::SectionInfo(_, _) => {
+                self.network_service().metrics().churn_event("section_info");
                 return self.handle_section_info_event(old_pfx, event.elders_change, outbox);
             }
             AccumulatingEvent::NeighbourInfo(elders_info) => {
@@ -405,10 +450,27 @@ pub trait Approved: Base {
                 self.handle_send_ack_message_event(payload)?
             }
             AccumulatingEvent::ParsecPrune => self.handle_prune_event()?,
-            AccumulatingEvent::Relocate(payload) => self.handle_relocate_event(payload, outbox)?,
+            AccumulatingEvent::Relocate(payload) => {
+                self.network_service().metrics().churn_event("relocate");
+                self.handle_relocate_event(payload, outbox)?
+            }
             AccumulatingEvent::RelocatePrepare(pub_id, count) => {
                 self.handle_relocate_prepare_event(pub_id, count, outbox);
             }
+            AccumulatingEvent::Merge(_) => {
+                // Caching the merge details is handled within the chain.
+                self.network_service().metrics().churn_event("merge");
+            }
+            AccumulatingEvent::Ban(target) => {
+                // Updating the ban list is handled within the chain.
+                info!("{} - Banned {:?}.", self, target);
+            }
+            AccumulatingEvent::AbortMerge => {
+                // Dropping the merge in progress is handled within the chain.
+            }
+            AccumulatingEvent::NeighbourMemberCount(..) => {
+                // Updating the member counts is handled within the chain.
+            }
             AccumulatingEvent::User(payload) => self.handle_user_event(payload, outbox)?,
         }
 
@@ -491,7 +553,7 @@ pub trait Approved: Base {
         } else {
             info!("{} - handle Relocate: {:?}.", self, details);
 
-            match self.chain_mut().remove_member(&details.pub_id) {
+            match self.chain_mut().relocate_member(&details) {
                 MemberState::Relocating { node_knowledge } => {
                     self.handle_member_relocated(details, node_knowledge, outbox)?;
                 }
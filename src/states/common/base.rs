@@ -9,13 +9,19 @@
 use crate::{
     action::Action,
     chain::SectionKeyInfo,
+    client::{ClientId, ClientWireMessage, RateCheck},
+    crypto::Digest256,
     error::{Result, RoutingError},
-    id::{FullId, PublicId},
+    event::Event,
+    id::{FullId, P2pNode, PublicId},
     location::{DstLocation, SrcLocation},
-    messages::{Message, MessageWithBytes, Variant},
-    network_service::{NetworkService, Resend},
+    messages::{
+        envelope::{self, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION},
+        Message, MessageId, MessageWithBytes, Priority, Variant,
+    },
+    network_service::{NetworkService, Peer, Resend, Token},
     outbox::EventBox,
-    quic_p2p::{Peer, Token},
+    resource_proof::ResourceProof,
     rng::MainRng,
     state_machine::Transition,
     timer::Timer,
@@ -26,9 +32,11 @@ use crate::{
 use bytes::Bytes;
 use hex_fmt::HexFmt;
 use itertools::Itertools;
+use rand::Rng;
 use std::{
     fmt::{Debug, Display},
     net::SocketAddr,
+    ops::RangeInclusive,
     slice,
 };
 
@@ -67,15 +75,25 @@ pub trait Base: Display {
 
     fn unhandled_message(&mut self, sender: Option<SocketAddr>, message: Message);
 
+    /// Handles failing to handle a message with the given destination the peer sent us.
+    fn handle_message_failure(
+        &mut self,
+        _sender: SocketAddr,
+        _dst: DstLocation,
+        _error: &RoutingError,
+    ) {
+    }
+
     fn handle_action(&mut self, action: Action, outbox: &mut dyn EventBox) -> Transition {
         match action {
             Action::SendMessage {
                 src,
                 dst,
                 content,
+                ack,
                 result_tx,
             } => {
-                let result = self.handle_send_message(src, dst, content);
+                let result = self.handle_send_message(src, dst, content, ack);
                 let _ = result_tx.send(result);
             }
             Action::HandleTimeout(token) => match self.invoke_handle_timeout(token, outbox) {
@@ -84,40 +102,87 @@ pub trait Base: Display {
                     return transition;
                 }
             },
-            Action::DisconnectClient {
-                peer_addr,
-                result_tx,
-            } => {
-                self.network_service_mut().disconnect(peer_addr);
-                let _ = result_tx.send(Ok(()));
+            Action::DisconnectClient { client, result_tx } => {
+                let result = self.handle_disconnect_client(&client);
+                let _ = result_tx.send(result);
             }
-            Action::SendMessageToClient {
-                peer_addr,
-                msg,
-                token,
+            Action::SendToClient {
+                client,
+                content,
                 result_tx,
             } => {
-                self.send_message_to_client(peer_addr, msg, token);
-                let _ = result_tx.send(Ok(()));
+                let result = self.handle_send_to_client(&client, content);
+                let _ = result_tx.send(result);
+            }
+            Action::ResourceProofSolved { elder, proof } => {
+                self.handle_resource_proof_solved(elder, proof)
             }
         }
 
         self.finish_handle_input(outbox)
     }
 
+    // Handles the proof of the resource challenge `elder` sent us, solved in the background.
+    fn handle_resource_proof_solved(&mut self, _elder: P2pNode, _proof: ResourceProof) {}
+
     fn handle_send_message(
         &mut self,
         _src: SrcLocation,
         _dst: DstLocation,
         _content: Vec<u8>,
-    ) -> Result<(), RoutingError> {
+        _ack: bool,
+    ) -> Result<MessageId, RoutingError> {
         warn!("{} - Cannot handle SendMessage - invalid state.", self);
         Err(RoutingError::InvalidState)
     }
 
+    fn handle_send_to_client(&mut self, client: &ClientId, content: Vec<u8>) -> Result<()> {
+        let addr = self
+            .network_service()
+            .clients()
+            .addr_of(client)
+            .ok_or(RoutingError::ClientNotConnected)?;
+        self.network_service_mut().check_user_message_capacity()?;
+
+        let msg = ClientWireMessage::Message(content);
+        let bytes = msg.to_bytes()?;
+        let token = self.network_service_mut().next_msg_token();
+        self.send_message_to_client(addr, msg.priority(), bytes, token);
+        Ok(())
+    }
+
+    fn handle_disconnect_client(&mut self, client: &ClientId) -> Result<()> {
+        let addr = self
+            .network_service()
+            .clients()
+            .addr_of(client)
+            .ok_or(RoutingError::ClientNotConnected)?;
+        self.network_service_mut().clients_mut().disconnected(&addr);
+        self.network_service_mut().disconnect(addr);
+        Ok(())
+    }
+
     fn invoke_handle_timeout(&mut self, token: u64, outbox: &mut dyn EventBox) -> Transition {
         if self.network_service_mut().handle_timeout(token) {
             Transition::Stay
+        } else if let Some(id) = self
+            .network_service_mut()
+            .pending_acks_mut()
+            .timed_out(token)
+        {
+            debug!("{} - Delivery of {:?} not acknowledged in time.", self, id);
+            outbox.send_event(Event::MessageDeliveryFailed(id));
+            Transition::Stay
+        } else if let Some(src) = self
+            .network_service_mut()
+            .reassembler_mut()
+            .handle_timeout(token)
+        {
+            debug!(
+                "{} - Dropping incomplete fragmented message from {:?}.",
+                self, src
+            );
+            Transition::Stay
         } else {
             self.handle_timeout(token, outbox)
         }
@@ -146,28 +211,45 @@ pub trait Base: Display {
             BootstrappedTo { node } => self.handle_bootstrapped_to(node),
             BootstrapFailure => self.handle_bootstrap_failure(outbox),
             ConnectedTo { peer } => match peer {
-                Peer::Client(_) => Transition::Stay,
+                Peer::Client(peer_addr) => self.handle_client_connected(peer_addr),
                 Peer::Node(peer_addr) => self.handle_connected_to(peer_addr, outbox),
             },
-            ConnectionFailure { peer, .. } => match peer {
-                Peer::Client(_) => Transition::Stay,
-                Peer::Node(peer_addr) => self.handle_connection_failure(peer_addr, outbox),
-            },
+            ConnectionFailure { peer, .. } => {
+                self.network_service_mut().connection_failed(peer);
+                match peer {
+                    Peer::Client(peer_addr) => self.handle_client_lost(peer_addr),
+                    Peer::Node(peer_addr) => self.handle_connection_failure(peer_addr, outbox),
+                }
+            }
             NewMessage { peer, msg } => match peer {
-                Peer::Client(_) => Transition::Stay,
+                Peer::Client(peer_addr) => self.handle_client_message(peer_addr, msg, outbox),
                 Peer::Node(peer_addr) => self.handle_new_message(peer_addr, msg, outbox),
             },
-            UnsentUserMessage { peer, msg, token } => match peer {
-                Peer::Client(_) => Transition::Stay,
-                Peer::Node(peer_addr) => self.handle_unsent_message(peer_addr, msg, token, outbox),
-            },
-            SentUserMessage { peer, msg, token } => match peer {
-                Peer::Client(_) => Transition::Stay,
-                Peer::Node(peer_addr) => self.handle_sent_message(peer_addr, msg, token, outbox),
-            },
+            UnsentUserMessage { peer, msg, token } => {
+                self.network_service_mut().send_completed(peer);
+                match peer {
+                    Peer::Client(_) => Transition::Stay,
+                    Peer::Node(peer_addr) => {
+                        self.handle_unsent_message(peer_addr, msg, token, outbox)
+                    }
+                }
+            }
+            SentUserMessage { peer, msg, token } => {
+                self.network_service_mut().send_completed(peer);
+                match peer {
+                    Peer::Client(_) => Transition::Stay,
+                    Peer::Node(peer_addr) => {
+                        self.handle_sent_message(peer_addr, msg, token, outbox)
+                    }
+                }
+            }
             Finish => Transition::Terminate,
         };
 
+        if self.network_service_mut().take_ready_to_send() {
+            outbox.send_event(Event::ReadyToSend);
+        }
+
         if let Transition::Stay = transition {
             self.finish_handle_input(outbox)
         } else {
@@ -198,29 +280,156 @@ pub trait Base: Display {
         Transition::Stay
     }
 
+    fn handle_client_connected(&mut self, addr: SocketAddr) -> Transition {
+        // Challenge the client to prove its identity before accepting any messages from it.
+        let challenge: Digest256 = self.rng().gen();
+        self.network_service_mut()
+            .clients_mut()
+            .insert_challenge(addr, challenge);
+        self.send_client_wire_message(addr, &ClientWireMessage::Challenge(challenge));
+        Transition::Stay
+    }
+
+    fn handle_client_lost(&mut self, addr: SocketAddr) -> Transition {
+        trace!("{} - Client on {} disconnected", self, addr);
+        self.network_service_mut().clients_mut().disconnected(&addr);
+        Transition::Stay
+    }
+
+    fn handle_client_message(
+        &mut self,
+        addr: SocketAddr,
+        bytes: Bytes,
+        outbox: &mut dyn EventBox,
+    ) -> Transition {
+        match self
+            .network_service_mut()
+            .clients_mut()
+            .check_rate_limit(addr)
+        {
+            RateCheck::Accept => (),
+            RateCheck::Reject(rejection) => {
+                let client = self.network_service().clients().client_at(&addr).copied();
+                debug!(
+                    "{} - Client {:?} on {} rejected: {:?}",
+                    self, client, addr, rejection
+                );
+
+                self.network_service().metrics().client_message_rejected();
+                self.send_client_wire_message(addr, &ClientWireMessage::Rejected(rejection));
+                outbox.send_event(Event::ClientRejected {
+                    client,
+                    peer_addr: addr,
+                    rejection,
+                });
+                return Transition::Stay;
+            }
+            RateCheck::Drop => {
+                self.network_service().metrics().client_message_rejected();
+                return Transition::Stay;
+            }
+        }
+
+        let msg = match ClientWireMessage::from_bytes(&bytes) {
+            Ok(msg) => msg,
+            Err(error) => {
+                debug!(
+                    "{} - Failed to deserialize client message from {}: {:?}",
+                    self, addr, error
+                );
+                return Transition::Stay;
+            }
+        };
+
+        match msg {
+            ClientWireMessage::HandshakeResponse {
+                client_id,
+                signature,
+            } => {
+                if self
+                    .network_service_mut()
+                    .clients_mut()
+                    .authenticate(addr, client_id, &signature)
+                {
+                    debug!(
+                        "{} - Client {:?} authenticated on {}",
+                        self, client_id, addr
+                    );
+                } else {
+                    debug!(
+                        "{} - Invalid handshake from client on {} - disconnecting",
+                        self, addr
+                    );
+                    self.network_service_mut().clients_mut().disconnected(&addr);
+                    self.network_service_mut().disconnect(addr);
+                }
+            }
+            ClientWireMessage::Message(content) => {
+                let client = self.network_service().clients().client_at(&addr).copied();
+                if let Some(client) = client {
+                    outbox.send_event(Event::ClientMessage { client, content });
+                } else {
+                    debug!(
+                        "{} - Ignoring message from unauthenticated client on {}",
+                        self, addr
+                    );
+                }
+            }
+            ClientWireMessage::Challenge(_) | ClientWireMessage::Rejected(_) => {
+                debug!("{} - Unexpected message from client on {}", self, addr);
+            }
+        }
+
+        Transition::Stay
+    }
+
     fn handle_new_message(
         &mut self,
         sender: SocketAddr,
         bytes: Bytes,
         outbox: &mut dyn EventBox,
     ) -> Transition {
+        if let Some(versions) = envelope::supported_versions(&bytes) {
+            self.handle_version_mismatch(sender, versions);
+            return Transition::Stay;
+        }
+
         let msg = match MessageWithBytes::partial_from_bytes(bytes) {
             Ok(msg) => msg,
+            Err(error @ RoutingError::IncompatibleProtocolVersion { .. }) => {
+                warn!("{} - Rejecting message from {}: {}", self, sender, error);
+                self.network_service_mut().send_version_mismatch(sender);
+                return Transition::Stay;
+            }
             Err(error) => {
                 debug!("{} - Failed to deserialize message: {:?}", self, error);
                 return Transition::Stay;
             }
         };
 
+        let dst = *msg.message_dst();
         match self.try_handle_message(Some(sender), msg, outbox) {
             Ok(transition) => transition,
             Err(error) => {
                 debug!("{} - Failed to handle message: {:?}", self, error);
+                self.handle_message_failure(sender, dst, &error);
                 Transition::Stay
             }
         }
     }
 
+    // The peer rejected our message because it doesn't speak its version. We don't speak any
+    // other, so there's nothing to do but tell the user. The rejected message is not resent.
+    fn handle_version_mismatch(&self, sender: SocketAddr, versions: RangeInclusive<u16>) {
+        warn!(
+            "{} - {} speaks protocol versions {:?}, we speak {:?}",
+            self,
+            sender,
+            versions,
+            MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION
+        );
+    }
+
     fn try_handle_message(
         &mut self,
         sender: Option<SocketAddr>,
@@ -259,6 +468,10 @@ pub trait Base: Display {
         }
 
         let msg = msg.take_or_deserialize_message()?;
+        self.network_service()
+            .metrics()
+            .message_received(&msg.variant);
+
         if self.should_handle_message(&msg) && self.verify_message(&msg)? {
             self.handle_message(sender, msg, outbox)
         } else {
@@ -283,7 +496,11 @@ pub trait Base: Display {
         msg_token: Token,
         outbox: &mut dyn EventBox,
     ) -> Transition {
-        match self.network_service_mut().target_failed(msg_token, addr) {
+        let random: f64 = self.rng().gen();
+        match self
+            .network_service_mut()
+            .target_failed(msg_token, addr, random)
+        {
             Resend::Now(next_target) => {
                 trace!(
                     "{} - Sending message ID {} to {} failed - resending to {} now",
@@ -354,14 +571,13 @@ pub trait Base: Display {
 
     fn our_connection_info(&mut self) -> Result<SocketAddr> {
         self.network_service_mut()
-            .service_mut()
             .our_connection_info()
             .map_err(|err| {
                 debug!(
                     "{} - Failed to retrieve our connection info: {:?}",
                     self, err
                 );
-                err.into()
+                err
             })
     }
 
@@ -370,6 +586,8 @@ pub trait Base: Display {
     }
 
     fn send_direct_message(&mut self, recipient: &SocketAddr, variant: Variant) {
+        self.network_service().metrics().message_sent(&variant);
+
         let message = match Message::single_src(self.full_id(), DstLocation::Direct, variant) {
             Ok(message) => message,
             Err(error) => {
@@ -429,11 +647,31 @@ pub trait Base: Display {
         );
     }
 
-    fn send_message_to_client(&mut self, peer_addr: SocketAddr, msg: Bytes, token: Token) {
-        let client = Peer::Client(peer_addr);
+    fn send_message_to_client(
+        &mut self,
+        peer_addr: SocketAddr,
+        priority: Priority,
+        msg: Bytes,
+        token: Token,
+    ) {
         self.network_service_mut()
-            .service_mut()
-            .send(client, msg, token);
+            .send_to_client(peer_addr, priority, msg, token);
+    }
+
+    fn send_client_wire_message(&mut self, peer_addr: SocketAddr, msg: &ClientWireMessage) {
+        let bytes = match msg.to_bytes() {
+            Ok(bytes) => bytes,
+            Err(error) => {
+                error!(
+                    "{} - Failed to serialize client message {:?}: {:?}",
+                    self, msg, error
+                );
+                return;
+            }
+        };
+
+        let token = self.network_service_mut().next_msg_token();
+        self.send_message_to_client(peer_addr, msg.priority(), bytes, token)
     }
 
     fn log_verify_failure<'a, T, I>(&self, msg: &T, error: &RoutingError, their_key_infos: I)
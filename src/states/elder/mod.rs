@@ -16,17 +16,19 @@ use super::{
 };
 use crate::{
     chain::{
-        delivery_group_size, AccumulatingEvent, AckMessagePayload, Chain, EldersChange, EldersInfo,
-        EventSigPayload, GenesisPfxInfo, IntoAccumulatingEvent, NetworkEvent, NetworkParams,
-        OnlinePayload, ParsecResetData, SectionKeyInfo, SendAckMessagePayload, MIN_AGE,
-        MIN_AGE_COUNTER,
+        delivery_group_size, AccumulatingEvent, AckMessagePayload, AgeCounter, Chain, EldersChange,
+        EldersInfo, EventSigPayload, GenesisPfxInfo, IntoAccumulatingEvent, MergeDetails,
+        NetworkEvent, NetworkParams, OnlinePayload, ParsecResetData, ResourceProofParams,
+        SectionKeyInfo, SendAckMessagePayload,
     },
+    delivery::DELIVERY_ACK_TIMEOUT,
     error::{Result, RoutingError},
     event::{Connected, Event},
+    fragment::{self, MAX_MESSAGE_SIZE},
     id::{FullId, P2pNode, PublicId},
     location::{DstLocation, SrcLocation},
     messages::{
-        AccumulatingMessage, BootstrapResponse, JoinRequest, MemberKnowledge, Message,
+        AccumulatingMessage, BootstrapResponse, JoinRequest, MemberKnowledge, Message, MessageId,
         MessageWithBytes, PlainMessage, QueuedMessage, SrcAuthority, Variant, VerifyStatus,
     },
     network_service::NetworkService,
@@ -34,10 +36,14 @@ use crate::{
     parsec::{self, generate_first_dkg_result, DkgResultWrapper, ParsecMap},
     pause::PausedState,
     relocation::RelocateDetails,
+    reputation::{Fault, PeerReputation},
+    resource_proof::{ResourceChallenge, ResourceProof},
     rng::{self, MainRng},
     routing_message_filter::RoutingMessageFilter,
     signature_accumulator::SignatureAccumulator,
     state_machine::{State, Transition},
+    status::{NodeState, NodeStatus},
+    time::Instant,
     timer::Timer,
     xor_space::{Prefix, XorName, Xorable},
 };
@@ -49,12 +55,17 @@ use std::{
     fmt::{self, Display, Formatter},
     iter, mem,
     net::SocketAddr,
+    time::Duration,
 };
 
 /// Number of RelocatePrepare to consensus before actually relocating a node.
 /// This helps avoid relocated node receiving message they need to process from previous section.
 const INITIAL_RELOCATE_COOL_DOWN_COUNT_DOWN: i32 = 10;
 
+/// Time to wait for the other side of a merge: for the sibling we committed to merging into to
+/// send us its genesis, or for the nodes of the sibling merging into us to join our parsec.
+const MERGE_TIMEOUT: Duration = Duration::from_secs(90);
+
 struct CompleteParsecReset {
     /// The new genesis prefix info.
     pub gen_pfx_info: GenesisPfxInfo,
@@ -76,6 +87,14 @@ enum PendingMessageKey {
     },
 }
 
+// Resource challenge sent to a joining node which hasn't answered it yet.
+struct PendingResourceChallenge {
+    p2p_node: P2pNode,
+    challenge: ResourceChallenge,
+    deadline: Instant,
+    timer_token: u64,
+}
+
 pub struct ElderDetails {
     pub chain: Chain,
     pub network_service: NetworkService,
@@ -112,6 +131,20 @@ pub struct Elder {
     pending_voted_msgs: BTreeMap<PendingMessageKey, Message>,
     /// The knowledge of the non-elder members about our section.
     members_knowledge: BTreeMap<XorName, MemberKnowledge>,
+    // The latest merge details we sent to our sibling, so we don't keep resending them.
+    sent_merge_details: Option<MergeDetails>,
+    // The merged section info whose DKG waits for the elders of our sibling to join our parsec.
+    pending_merge_info: Option<EldersInfo>,
+    // Timer for the other side of the merge in progress to follow up, see `MERGE_TIMEOUT`.
+    merge_timer_token: Option<u64>,
+    // The latest number of our members we told each of our neighbours.
+    sent_member_counts: BTreeMap<Prefix<XorName>, u64>,
+    // Resource challenges sent to joining nodes, keyed by their ids.
+    resource_challenges: BTreeMap<PublicId, PendingResourceChallenge>,
+    // Ids of the nodes whose resource challenge expires, keyed by the timer token.
+    resource_challenge_timers: BTreeMap<u64, PublicId>,
+    // Penalty scores of the nodes which misbehaved recently.
+    reputation: PeerReputation,
     rng: MainRng,
 }
 
@@ -128,7 +161,10 @@ impl Elder {
         let connection_info = network_service.our_connection_info()?;
         let p2p_node = P2pNode::new(public_id, connection_info);
         let mut first_ages = BTreeMap::new();
-        let _ = first_ages.insert(public_id, MIN_AGE_COUNTER);
+        let _ = first_ages.insert(
+            public_id,
+            AgeCounter::from_age(network_cfg.ageing_policy.start_age()),
+        );
         let first_dkg_result = generate_first_dkg_result(&mut rng);
         let gen_pfx_info = GenesisPfxInfo {
             first_info: create_first_elders_info(p2p_node)?,
@@ -137,6 +173,7 @@ impl Elder {
             first_ages,
             latest_info: EldersInfo::default(),
             parsec_version: 0,
+            ban_list: Default::default(),
         };
         let parsec_map = ParsecMap::default().with_init(&mut rng, full_id.clone(), &gen_pfx_info);
         let chain = Chain::new(
@@ -216,17 +253,17 @@ impl Elder {
             msg_filter: self.msg_filter,
             msg_queue: self.msg_queue,
             msg_backlog: self.msg_backlog,
-            network_service: self.network_service,
+            network_service: Some(self.network_service),
             network_rx: None,
             sig_accumulator: self.sig_accumulator,
             parsec_map: self.parsec_map,
         }
     }
 
-    pub fn resume(state: PausedState, timer: Timer) -> Self {
+    pub fn resume(state: PausedState, network_service: NetworkService, timer: Timer) -> Self {
         Self::new(ElderDetails {
             chain: state.chain,
-            network_service: state.network_service,
+            network_service,
             event_backlog: Vec::new(),
             full_id: state.full_id,
             gen_pfx_info: state.gen_pfx_info,
@@ -244,10 +281,23 @@ impl Elder {
         self.chain.our_elders()
     }
 
+    pub fn status(&self) -> NodeStatus {
+        let mut status = NodeStatus::new(NodeState::Elder, Some(self.id()))
+            .with_chain(&self.chain, &self.parsec_map);
+        status.backlog.messages = self.msg_backlog.len();
+        status.backlog.queued_messages = self.msg_queue.len();
+        status
+    }
+
     pub fn our_prefix(&self) -> &Prefix<XorName> {
         self.chain.our_prefix()
     }
 
+    /// Penalty scores of the nodes which misbehaved recently.
+    pub fn peer_penalties(&self) -> BTreeMap<PublicId, u32> {
+        self.reputation.scores()
+    }
+
     pub fn closest_known_elders_to(&self, name: &XorName) -> impl Iterator<Item = &P2pNode> {
         self.chain.closest_section_info(*name).1.member_nodes()
     }
@@ -274,6 +324,13 @@ impl Elder {
             dkg_cache: Default::default(),
             pending_voted_msgs: Default::default(),
             members_knowledge: Default::default(),
+            sent_merge_details: None,
+            pending_merge_info: None,
+            merge_timer_token: None,
+            sent_member_counts: Default::default(),
+            resource_challenges: Default::default(),
+            resource_challenge_timers: Default::default(),
+            reputation: Default::default(),
             rng: details.rng,
         }
     }
@@ -353,11 +410,64 @@ impl Elder {
                 .entry(*p2p_node.name())
                 .or_default()
                 .update(payload);
+
+            if let Err(error) = self.start_merge_dkg_if_ready(false) {
+                error!(
+                    "{} - Failed to start the merged section DKG: {:?}",
+                    self, error
+                );
+            }
+        }
+
+        // An elder behind our parsec version lost its parsec when it was resumed. Send it our
+        // genesis so it can take part in the current parsec again.
+        if !self.parsec_map.is_resyncing()
+            && self.chain.is_peer_our_elder(p2p_node.public_id())
+            && payload.parsec_version < self.parsec_map.last_version()
+        {
+            if let Some(msg) = self.create_genesis_update(&p2p_node, payload.elders_version) {
+                self.send_direct_message(
+                    p2p_node.peer_addr(),
+                    Variant::MessageSignature(Box::new(msg)),
+                );
+            }
         }
 
         self.send_parsec_gossip(Some((payload.parsec_version, p2p_node)))
     }
 
+    // An elder lost its parsec when it was resumed and can't vote until our section starts a new
+    // parsec version. Don't wait for the next churn to do so, as the section might need its vote
+    // before that. The genesis of the new version is sent to it in reply to its member knowledge.
+    fn handle_parsec_resync(&mut self, p2p_node: P2pNode, version: u64) {
+        if !self.parsec_map.is_resyncing()
+            && !self.parsec_map.pruning_voted_for()
+            && self.chain.is_peer_our_elder(p2p_node.public_id())
+            && version == self.parsec_map.last_version()
+        {
+            debug!(
+                "{} - {} lost parsec v{}, voting to prune it.",
+                self, p2p_node, version
+            );
+            self.vote_for_event(AccumulatingEvent::ParsecPrune);
+            self.parsec_map.set_pruning_voted_for();
+        }
+    }
+
+    fn send_parsec_resync(&mut self) {
+        let version = self.parsec_map.last_version();
+        let recipients = self
+            .chain
+            .our_elders()
+            .filter(|p2p_node| p2p_node.public_id() != self.id())
+            .map(|p2p_node| *p2p_node.peer_addr())
+            .collect_vec();
+
+        for recipient in recipients {
+            self.send_direct_message(&recipient, Variant::ParsecResync(version));
+        }
+    }
+
     // Connect to all elders from our section or neighbour sections that we are not yet connected
     // to and disconnect from peers that are no longer elders of neighbour sections.
     fn update_peer_connections(&mut self, change: &EldersChange) {
@@ -426,6 +536,10 @@ impl Elder {
                 | AccumulatingEvent::NeighbourInfo(_)
                 | AccumulatingEvent::TheirKeyInfo(_)
                 | AccumulatingEvent::SendAckMessage(_)
+                | AccumulatingEvent::Merge(_)
+                | AccumulatingEvent::Ban(_)
+                | AccumulatingEvent::AbortMerge
+                | AccumulatingEvent::NeighbourMemberCount(..)
                 | AccumulatingEvent::User(_) => false,
             })
             .cloned()
@@ -448,17 +562,24 @@ impl Elder {
                         our_pfx.matches(details.pub_id.name())
                     }
                     // Drop: no longer relevant after prefix change.
-                    AccumulatingEvent::StartDkg(_) | AccumulatingEvent::ParsecPrune => false,
+                    AccumulatingEvent::StartDkg(_)
+                    | AccumulatingEvent::ParsecPrune
+                    | AccumulatingEvent::Merge(_)
+                    | AccumulatingEvent::AbortMerge => false,
 
                     // Keep: Additional signatures for neighbours for sec-msg-relay.
                     AccumulatingEvent::SectionInfo(ref elders_info, _)
                     | AccumulatingEvent::NeighbourInfo(ref elders_info) => {
                         our_pfx.is_neighbour(elders_info.prefix())
                     }
+                    AccumulatingEvent::NeighbourMemberCount(ref pfx, _) => {
+                        our_pfx.is_neighbour(pfx)
+                    }
 
                     // Keep: Still relevant after prefix change.
                     AccumulatingEvent::TheirKeyInfo(_)
                     | AccumulatingEvent::SendAckMessage(_)
+                    | AccumulatingEvent::Ban(_)
                     | AccumulatingEvent::User(_) => true,
                 }
             })
@@ -488,6 +609,10 @@ impl Elder {
             | evt @ AccumulatingEvent::NeighbourInfo(_)
             | evt @ AccumulatingEvent::TheirKeyInfo(_)
             | evt @ AccumulatingEvent::SendAckMessage(_)
+            | evt @ AccumulatingEvent::Merge(_)
+            | evt @ AccumulatingEvent::Ban(_)
+            | evt @ AccumulatingEvent::AbortMerge
+            | evt @ AccumulatingEvent::NeighbourMemberCount(..)
             | evt @ AccumulatingEvent::User(_) => {
                 log_or_panic!(log::Level::Error, "unexpected event {:?}", evt);
             }
@@ -578,6 +703,75 @@ impl Elder {
         Ok(complete_data)
     }
 
+    fn prepare_finalise_merge(&mut self) -> Result<CompleteParsecReset, RoutingError> {
+        let reset_data = self
+            .chain
+            .finalise_prefix_change(self.parsec_map.last_version().saturating_add(1))?;
+        let mut complete_data = self.complete_parsec_reset_data(reset_data);
+        complete_data.event_to_send = Some(Event::SectionMerged(*self.our_prefix()));
+        Ok(complete_data)
+    }
+
+    // Ask our sibling to merge with us, if our section became too small and leads the merge. Our
+    // sibling commits to the merge once it became too small as well.
+    fn send_merge_request_if_needed(&mut self) {
+        if !self.chain.should_merge() || !self.chain.is_merge_lead() {
+            self.sent_merge_details = None;
+            return;
+        }
+
+        let details = self
+            .chain
+            .merge_details(self.parsec_map.last_version(), false);
+        if self.sent_merge_details.as_ref() == Some(&details) {
+            return;
+        }
+
+        self.send_merge_details(details);
+    }
+
+    fn send_merge_details(&mut self, details: MergeDetails) {
+        let src = SrcLocation::Section(*self.our_prefix());
+        let dst = DstLocation::Prefix(self.our_prefix().sibling());
+        let variant = Variant::Merge(Box::new(details.clone()));
+
+        if let Err(err) = self.send_routing_message(src, dst, variant, None) {
+            debug!("{} Failed to send Merge: {:?}.", self, err);
+        } else {
+            self.sent_merge_details = Some(details);
+        }
+    }
+
+    // Starts the DKG of the merged section once all its elders from our sibling run our parsec,
+    // as they couldn't take part in the DKG otherwise. If `timed_out`, starts it without waiting
+    // for the rest of them any longer.
+    fn start_merge_dkg_if_ready(&mut self, timed_out: bool) -> Result<()> {
+        let parsec_version = self.parsec_map.last_version();
+        let ready = if let Some(info) = &self.pending_merge_info {
+            info.member_nodes()
+                .filter(|p2p_node| !self.chain.is_peer_our_elder(p2p_node.public_id()))
+                .all(|p2p_node| {
+                    self.members_knowledge
+                        .get(p2p_node.name())
+                        .map(|knowledge| knowledge.parsec_version >= parsec_version)
+                        .unwrap_or(false)
+                })
+        } else {
+            return Ok(());
+        };
+
+        if !ready && !timed_out {
+            return Ok(());
+        }
+
+        self.merge_timer_token = None;
+        if let Some(info) = self.pending_merge_info.take() {
+            self.handle_promote_and_demote_elders(vec![info])?;
+        }
+
+        Ok(())
+    }
+
     fn send_neighbour_infos(&mut self) {
         self.chain.other_prefixes().iter().for_each(|pfx| {
             let src = SrcLocation::Section(*self.our_prefix());
@@ -590,6 +784,31 @@ impl Elder {
         });
     }
 
+    // Tell our neighbours how many members we have, if it changed since we last told them. They
+    // pass it to their relocation policy.
+    fn send_member_counts_if_changed(&mut self) {
+        let count = self.chain.our_member_count();
+        let neighbours = self.chain.other_prefixes();
+        self.sent_member_counts
+            .retain(|pfx, _| neighbours.contains(pfx));
+
+        for pfx in neighbours {
+            if self.sent_member_counts.get(&pfx) == Some(&count) {
+                continue;
+            }
+
+            let src = SrcLocation::Section(*self.our_prefix());
+            let dst = DstLocation::Prefix(pfx);
+
+            if let Err(err) = self.send_routing_message(src, dst, Variant::MemberCount(count), None)
+            {
+                debug!("{} Failed to send MemberCount: {:?}.", self, err);
+            } else {
+                let _ = self.sent_member_counts.insert(pfx, count);
+            }
+        }
+    }
+
     // Send `GenesisUpdate` message to all non-elders.
     fn send_genesis_updates(&mut self) {
         for (recipient, msg) in self.create_genesis_updates() {
@@ -612,33 +831,118 @@ impl Elder {
             .adults_and_infants_p2p_nodes()
             .cloned()
             .filter_map(|recipient| {
-                let variant = Variant::GenesisUpdate(Box::new(self.gen_pfx_info.clone()));
-                let dst = DstLocation::Node(*recipient.name());
                 let version = self
                     .members_knowledge
                     .get(recipient.name())
                     .map(|knowledge| knowledge.elders_version)
                     .unwrap_or(0);
-
-                match self.to_accumulating_message(dst, variant, Some(version)) {
-                    Ok(msg) => Some((recipient, msg)),
-                    Err(error) => {
-                        error!("{} - Failed to create signed message: {:?}", self, error);
-                        None
-                    }
-                }
+                let msg = self.create_genesis_update(&recipient, version)?;
+                Some((recipient, msg))
             })
             .collect()
     }
 
+    fn create_genesis_update(
+        &self,
+        recipient: &P2pNode,
+        known_elders_version: u64,
+    ) -> Option<AccumulatingMessage> {
+        let variant = Variant::GenesisUpdate(Box::new(self.gen_pfx_info.clone()));
+        let dst = DstLocation::Node(*recipient.name());
+
+        match self.to_accumulating_message(dst, variant, Some(known_elders_version)) {
+            Ok(msg) => Some(msg),
+            Err(error) => {
+                error!("{} - Failed to create signed message: {:?}", self, error);
+                None
+            }
+        }
+    }
+
+    // Handles the genesis of our sibling's parsec it sends us after merging our section into its
+    // own. We continue in its section as an adult and get promoted back if we are among the elders
+    // of the merged section.
+    fn handle_merged_genesis(&mut self, gen_pfx_info: GenesisPfxInfo) -> Transition {
+        if gen_pfx_info.first_info.prefix() != &self.our_prefix().sibling() {
+            debug!(
+                "{} - Ignoring merged genesis for {:?}.",
+                self,
+                gen_pfx_info.first_info.prefix()
+            );
+            return Transition::Stay;
+        }
+
+        info!("{} - Merged into our sibling: {:?}", self, gen_pfx_info);
+        self.merge_timer_token = None;
+        Transition::Demote { gen_pfx_info }
+    }
+
+    // Handles the genesis of a new parsec version sent by our section while we don't take part in
+    // its parsec, because we lost it when resumed.
+    fn handle_resync(&mut self, gen_pfx_info: GenesisPfxInfo) -> Result<Transition> {
+        if gen_pfx_info.parsec_version <= self.parsec_map.last_version() {
+            return Ok(Transition::Stay);
+        }
+
+        info!("{} - Resync with {:?}", self, gen_pfx_info);
+
+        if !gen_pfx_info.first_info.is_member(self.id()) {
+            return Ok(Transition::Demote { gen_pfx_info });
+        }
+
+        // Our key share is still valid if the section key didn't change in the meantime.
+        let secret_key_share = self
+            .chain
+            .our_section_bls_secret_key_share()
+            .ok()
+            .filter(|_| {
+                self.chain.our_section_bls_keys().public_key()
+                    == gen_pfx_info.first_bls_keys.public_key()
+            })
+            .map(|share| share.key.clone());
+
+        // The section key changed while we were paused, so we missed the key generation and
+        // can't sign section messages. Continue as an adult until the section promotes us again.
+        if secret_key_share.is_none() {
+            info!(
+                "{} - No share of the section key {:?}, demoting",
+                self,
+                gen_pfx_info.first_bls_keys.public_key()
+            );
+            return Ok(Transition::Demote { gen_pfx_info });
+        }
+
+        let section_history = self.chain.section_history().clone();
+        self.chain = Chain::new(
+            self.chain.network_cfg(),
+            *self.id(),
+            gen_pfx_info.clone(),
+            secret_key_share,
+        );
+        self.chain.continue_section_history(section_history);
+        self.gen_pfx_info = gen_pfx_info;
+        self.init_parsec();
+
+        Ok(Transition::Stay)
+    }
+
     /// Handles a signature of a `SignedMessage`, and if we have enough to verify the signed
     /// message, handles it.
     fn handle_message_signature(
         &mut self,
-        msg: AccumulatingMessage,
+        mut msg: AccumulatingMessage,
         src: PublicId,
         _outbox: &mut dyn EventBox,
     ) -> Result<Transition> {
+        if !msg.verify_signature_shares() {
+            debug!(
+                "{} - Received invalid message signature from {}: {:?}",
+                self, src, msg
+            );
+            self.handle_fault(src, Fault::InvalidSignature);
+            return Ok(Transition::Stay);
+        }
+
         if !self.chain.is_peer_elder(&src) {
             debug!(
                 "{} - Received message signature from not known elder (still use it) {}, {:?}",
@@ -648,7 +952,10 @@ impl Elder {
             // Need to verify whether there are any security implications with doing this.
         }
 
-        if let Some(msg) = self.sig_accumulator.add_proof(msg, &self.log_ident()) {
+        if let Some(msg) =
+            self.sig_accumulator
+                .add_proof(msg, &self.log_ident(), self.network_service.metrics())
+        {
             self.handle_accumulated_message(msg)?
         }
 
@@ -710,7 +1017,9 @@ impl Elder {
         outbox: &mut dyn EventBox,
     ) -> Result<Transition, RoutingError> {
         match msg.variant {
-            Variant::UserMessage { .. } => (),
+            Variant::UserMessage(_)
+            | Variant::UserMessageWithAck(_)
+            | Variant::UserMessageFragment(_) => (),
             _ => trace!("{} Got {:?}.", self, msg),
         }
 
@@ -722,12 +1031,38 @@ impl Elder {
 
                 self.handle_neighbour_info(elders_info, msg.src, msg.dst)?;
             }
-            Variant::UserMessage(content) => {
-                outbox.send_event(Event::MessageReceived {
-                    content,
-                    src: msg.src.location(),
-                    dst: msg.dst,
-                });
+            Variant::Merge(details) => {
+                // Ensure the src and dst are what we expect.
+                let _: &Prefix<_> = msg.src.as_section()?;
+                let _: &Prefix<_> = msg.dst.as_prefix()?;
+
+                self.handle_merge(*details);
+            }
+            Variant::MemberCount(count) => {
+                // Ensure the src and dst are what we expect.
+                let src_prefix = *msg.src.as_section()?;
+                let _: &Prefix<_> = msg.dst.as_prefix()?;
+
+                self.handle_member_count(src_prefix, count);
+            }
+            Variant::UserMessage(_)
+            | Variant::UserMessageWithAck(_)
+            | Variant::UserMessageFragment(_) => {
+                self.handle_user_message(msg, outbox)?;
+            }
+            Variant::MessageDeliveryAck(id) => {
+                if self
+                    .network_service
+                    .pending_acks_mut()
+                    .acknowledged(&id, &msg.src.location())
+                {
+                    outbox.send_event(Event::MessageDelivered(id));
+                } else {
+                    debug!(
+                        "{} - Ignoring unexpected {:?} from {:?}.",
+                        self, id, msg.src
+                    );
+                }
             }
             Variant::AckMessage {
                 src_prefix,
@@ -753,9 +1088,15 @@ impl Elder {
             Variant::JoinRequest(join_request) => {
                 self.handle_join_request(msg.src.to_sender_node(sender)?, *join_request)
             }
+            Variant::ResourceProofResponse(proof) => {
+                self.handle_resource_proof_response(msg.src.to_sender_node(sender)?, proof)
+            }
             Variant::MemberKnowledge(payload) => {
                 self.handle_member_knowledge(msg.src.to_sender_node(sender)?, payload)
             }
+            Variant::ParsecResync(version) => {
+                self.handle_parsec_resync(msg.src.to_sender_node(sender)?, version)
+            }
             Variant::ParsecRequest(version, request) => {
                 return self.handle_parsec_request(
                     version,
@@ -767,11 +1108,25 @@ impl Elder {
             Variant::ParsecResponse(version, response) => {
                 return self.handle_parsec_response(version, response, *msg.src.as_node()?, outbox);
             }
+            Variant::GenesisUpdate(gen_pfx_info) if self.parsec_map.is_resyncing() => {
+                return self.handle_resync(*gen_pfx_info);
+            }
+            Variant::GenesisUpdate(gen_pfx_info)
+                if self
+                    .chain
+                    .merging_into()
+                    .map_or(false, |sibling| msg.src.as_section().ok() == Some(&sibling)) =>
+            {
+                return Ok(self.handle_merged_genesis(*gen_pfx_info));
+            }
             Variant::GenesisUpdate(_) | Variant::Relocate(_) => {
                 debug!("{} Unhandled message, adding to backlog: {:?}", self, msg);
                 self.msg_backlog.push(msg.into_queued(sender));
             }
-            Variant::BootstrapResponse(_) | Variant::NodeApproval(_) | Variant::Ping => {
+            Variant::BootstrapResponse(_)
+            | Variant::NodeApproval(_)
+            | Variant::ResourceChallenge(_)
+            | Variant::Ping => {
                 debug!("{} Unhandled message, ignoring: {:?}", self, msg);
             }
         }
@@ -779,6 +1134,87 @@ impl Elder {
         Ok(Transition::Stay)
     }
 
+    fn handle_user_message(&mut self, msg: Message, outbox: &mut dyn EventBox) -> Result<()> {
+        // The id is computed from the whole message, so before it's taken apart.
+        let id = msg.id()?;
+        let src = msg.src.location();
+        let dst = msg.dst;
+        let (id, content, ack) = match msg.variant {
+            Variant::UserMessage(content) => (id, content, false),
+            Variant::UserMessageWithAck(content) => (id, content, true),
+            Variant::UserMessageFragment(fragment) => {
+                match self.network_service.reassembler_mut().insert(
+                    src,
+                    id,
+                    fragment,
+                    &self.timer,
+                )? {
+                    Some(msg) => (msg.id, msg.content, msg.ack),
+                    None => return Ok(()),
+                }
+            }
+            _ => unreachable!(),
+        };
+
+        outbox.send_event(Event::MessageReceived {
+            id,
+            content,
+            src,
+            dst,
+        });
+
+        if ack {
+            self.send_delivery_ack(id, src, dst)
+        } else {
+            Ok(())
+        }
+    }
+
+    // Sends the ack of a `UserMessageWithAck` back to its source. If the message was sent to a
+    // section, the ack is sent by the section so the source can trust it once accumulated.
+    fn send_delivery_ack(
+        &mut self,
+        id: MessageId,
+        msg_src: SrcLocation,
+        msg_dst: DstLocation,
+    ) -> Result<()> {
+        let src = match msg_dst {
+            DstLocation::Node(_) => SrcLocation::Node(*self.id()),
+            _ => SrcLocation::Section(*self.our_prefix()),
+        };
+        let dst = match msg_src {
+            SrcLocation::Node(pub_id) => DstLocation::Node(*pub_id.name()),
+            SrcLocation::Section(prefix) => DstLocation::Prefix(prefix),
+        };
+
+        self.send_routing_message(src, dst, Variant::MessageDeliveryAck(id), None)
+    }
+
+    fn handle_member_count(&mut self, src_prefix: Prefix<XorName>, count: u64) {
+        if !self.our_prefix().is_neighbour(&src_prefix) {
+            debug!(
+                "{} - Ignoring MemberCount from non-neighbour {:?}.",
+                self, src_prefix
+            );
+            return;
+        }
+
+        self.vote_for_event(AccumulatingEvent::NeighbourMemberCount(src_prefix, count));
+    }
+
+    fn handle_merge(&mut self, details: MergeDetails) {
+        if details.elders_info.prefix() != &self.our_prefix().sibling() {
+            debug!(
+                "{} - Ignoring Merge from non-sibling {:?}.",
+                self,
+                details.elders_info.prefix()
+            );
+            return;
+        }
+
+        self.vote_for_event(AccumulatingEvent::Merge(details));
+    }
+
     fn handle_ack_message(
         &mut self,
         src_prefix: Prefix<XorName>,
@@ -832,6 +1268,7 @@ impl Elder {
             first_ages: self.gen_pfx_info.first_ages.clone(),
             latest_info: self.chain.our_info().clone(),
             parsec_version: self.gen_pfx_info.parsec_version,
+            ban_list: self.gen_pfx_info.ban_list.clone(),
         };
 
         let src = SrcLocation::Section(*trimmed_info.first_info.prefix());
@@ -863,6 +1300,15 @@ impl Elder {
             self, name, p2p_node
         );
 
+        if self.chain.is_banned(&p2p_node) {
+            debug!(
+                "{} - Ignoring BootstrapRequest from {} - banned.",
+                self, p2p_node
+            );
+            self.network_service.disconnect(*p2p_node.peer_addr());
+            return;
+        }
+
         self.respond_to_bootstrap_request(&p2p_node, &name);
     }
 
@@ -894,6 +1340,15 @@ impl Elder {
             self, p2p_node, join_request.elders_version
         );
 
+        if self.chain.is_banned(&p2p_node) {
+            debug!(
+                "{} - Ignoring JoinRequest from {} - banned.",
+                self, p2p_node
+            );
+            self.network_service.disconnect(*p2p_node.peer_addr());
+            return;
+        }
+
         if join_request.elders_version < self.chain.our_info().version() {
             self.resend_bootstrap_response_join(&p2p_node);
         }
@@ -932,6 +1387,7 @@ impl Elder {
                     "{} - Ignoring relocation JoinRequest from {} - invalid signature.",
                     self, pub_id
                 );
+                self.handle_fault(pub_id, Fault::InvalidRelocation);
                 return;
             }
 
@@ -950,12 +1406,17 @@ impl Elder {
             }
 
             if !self.check_signed_relocation_details(&payload.details) {
+                self.handle_fault(pub_id, Fault::InvalidRelocation);
                 return;
             }
 
             (details.age, Some(details.destination_key_info.version()))
+        } else if let Some(params) = self.chain.network_cfg().resource_proof {
+            // The node has to prove its resources before we vote for it.
+            self.send_resource_challenge(p2p_node, &params);
+            return;
         } else {
-            (MIN_AGE, None)
+            (self.chain.network_cfg().ageing_policy.start_age(), None)
         };
 
         self.vote_for_event(AccumulatingEvent::Online(OnlinePayload {
@@ -965,6 +1426,88 @@ impl Elder {
         }))
     }
 
+    fn send_resource_challenge(&mut self, p2p_node: P2pNode, params: &ResourceProofParams) {
+        // Resend the pending challenge instead of creating a new one, so the node can't extend
+        // its deadline by sending more `JoinRequest`s.
+        let pending_challenge = self
+            .resource_challenges
+            .get(p2p_node.public_id())
+            .map(|pending| pending.challenge);
+        let challenge = if let Some(challenge) = pending_challenge {
+            challenge
+        } else {
+            let challenge = ResourceChallenge::new(&mut self.rng, params);
+            let timer_token = self.timer.schedule(params.timeout);
+            let _ = self
+                .resource_challenge_timers
+                .insert(timer_token, *p2p_node.public_id());
+            let _ = self.resource_challenges.insert(
+                *p2p_node.public_id(),
+                PendingResourceChallenge {
+                    p2p_node: p2p_node.clone(),
+                    challenge,
+                    deadline: Instant::now() + params.timeout,
+                    timer_token,
+                },
+            );
+            challenge
+        };
+
+        debug!(
+            "{} - Sending ResourceChallenge to {}: {:?}",
+            self, p2p_node, challenge
+        );
+        self.send_direct_message(p2p_node.peer_addr(), Variant::ResourceChallenge(challenge));
+    }
+
+    fn handle_resource_proof_response(&mut self, p2p_node: P2pNode, proof: ResourceProof) {
+        let pub_id = *p2p_node.public_id();
+        let pending = if let Some(pending) = self.resource_challenges.remove(&pub_id) {
+            let _ = self.resource_challenge_timers.remove(&pending.timer_token);
+            pending
+        } else {
+            debug!(
+                "{} - Ignoring ResourceProofResponse from {} - no pending challenge.",
+                self, pub_id
+            );
+            return;
+        };
+
+        if Instant::now() > pending.deadline {
+            debug!(
+                "{} - Ignoring ResourceProofResponse from {} - answered too late.",
+                self, pub_id
+            );
+            return;
+        }
+
+        if !pending.challenge.verify(&proof) {
+            debug!(
+                "{} - Ignoring ResourceProofResponse from {} - invalid proof.",
+                self, pub_id
+            );
+            return;
+        }
+
+        // Our section might have changed while the node was solving the challenge.
+        if !self.our_prefix().matches(pub_id.name())
+            || self.chain.is_peer_our_member(&pub_id)
+            || self.chain.is_in_online_backlog(&pub_id)
+        {
+            debug!(
+                "{} - Ignoring ResourceProofResponse from {} - no longer eligible to join.",
+                self, pub_id
+            );
+            return;
+        }
+
+        self.vote_for_event(AccumulatingEvent::Online(OnlinePayload {
+            p2p_node: pending.p2p_node,
+            age: self.chain.network_cfg().ageing_policy.start_age(),
+            their_knowledge: None,
+        }))
+    }
+
     fn update_our_knowledge(&mut self, msg: &Message) {
         let key_info = if let Some(key_info) = msg.source_section_key_info() {
             key_info
@@ -1082,24 +1625,42 @@ impl Elder {
             return Ok(());
         }
 
+        let _ = self.send_message_from_our_location(src, dst, variant, node_knowledge_override)?;
+        Ok(())
+    }
+
+    // Sends the message and returns its id. The caller must check we are part of `src`.
+    fn send_message_from_our_location(
+        &mut self,
+        src: SrcLocation,
+        dst: DstLocation,
+        variant: Variant,
+        node_knowledge_override: Option<u64>,
+    ) -> Result<MessageId> {
+        self.network_service.metrics().message_sent(&variant);
+
         let log_ident = self.log_ident();
 
         // If the source is single, we don't even need to send signatures, so let's cut this short
         if src.is_single() {
             let msg = Message::single_src(&self.full_id, dst, variant)?;
             let msg = MessageWithBytes::new(msg, &log_ident)?;
-            return self.handle_accumulated_message(msg);
+            let id = msg.single_src_message_id();
+            self.handle_accumulated_message(msg)?;
+            return Ok(id);
         }
 
         let accumulating_msg =
             self.to_accumulating_message(dst, variant, node_knowledge_override)?;
+        let id = accumulating_msg.id()?;
 
         for target in self.get_signature_targets(&dst) {
             if target.name() == self.name() {
-                if let Some(msg) = self
-                    .sig_accumulator
-                    .add_proof(accumulating_msg.clone(), &log_ident)
-                {
+                if let Some(msg) = self.sig_accumulator.add_proof(
+                    accumulating_msg.clone(),
+                    &log_ident,
+                    self.network_service.metrics(),
+                ) {
                     self.handle_accumulated_message(msg)?;
                 }
             } else {
@@ -1116,7 +1677,7 @@ impl Elder {
             }
         }
 
-        Ok(())
+        Ok(id)
     }
 
     // Send message over the network.
@@ -1148,17 +1709,17 @@ impl Elder {
             .into_iter()
             .filter(|p2p_node| {
                 self.msg_filter
-                    .filter_outgoing(msg, p2p_node.public_id())
+                    .filter_outgoing(msg, p2p_node.public_id(), self.network_service.metrics())
                     .is_new()
             })
             .map(|node| *node.peer_addr())
             .collect();
 
-        let cheap_bytes_clone = msg.full_bytes().clone();
-        self.send_message_to_targets(&targets, dg_size, cheap_bytes_clone);
+        let bytes = msg.full_bytes().clone();
+        self.send_message_to_targets(&targets, dg_size, bytes);
 
         // we've seen this message - don't handle it again if someone else sends it to us
-        let _ = self.msg_filter.filter_incoming(msg);
+        self.msg_filter.mark_incoming(msg);
 
         Ok(())
     }
@@ -1230,13 +1791,13 @@ impl Elder {
     fn verify_message_quiet(&self, msg: &Message) -> Result<bool, RoutingError> {
         match msg.verify(self.chain.get_their_key_infos()) {
             Ok(VerifyStatus::Full) => Ok(true),
-            Ok(VerifyStatus::ProofTooNew) if msg.dst.is_multiple() => {
+            Ok(VerifyStatus::ProofTooNew(_)) if msg.dst.is_multiple() => {
                 // Proof is too new which can only happen if we've been already demoted but are
                 // lagging behind (or the sender is faulty/malicious). We can't handle the
                 // message ourselves but the other elders likely can.
                 Ok(false)
             }
-            Ok(VerifyStatus::ProofTooNew) => Err(RoutingError::UntrustedMessage),
+            Ok(VerifyStatus::ProofTooNew(proof)) => Err(RoutingError::UntrustedMessage(proof)),
             Err(error) => Err(error),
         }
     }
@@ -1292,18 +1853,78 @@ impl Base for Elder {
         src: SrcLocation,
         dst: DstLocation,
         content: Vec<u8>,
-    ) -> Result<(), RoutingError> {
+        ack: bool,
+    ) -> Result<MessageId, RoutingError> {
         if let DstLocation::Direct = dst {
             return Err(RoutingError::BadLocation);
         }
 
-        self.send_routing_message(src, dst, Variant::UserMessage(content), None)
+        // `send_routing_message` treats this as a bug, but here it's just a wrong user input.
+        if !self.in_src_location(&src) {
+            return Err(RoutingError::BadLocation);
+        }
+
+        if content.len() > MAX_MESSAGE_SIZE {
+            return Err(RoutingError::MessageTooLarge {
+                size: content.len(),
+                max: MAX_MESSAGE_SIZE,
+            });
+        }
+
+        self.network_service.check_user_message_capacity()?;
+
+        let max_fragment_size = self.network_service.reassembler().max_fragment_size();
+        let id = if content.len() > max_fragment_size {
+            // The id of a fragmented message is the id of its first fragment.
+            let ids = fragment::split(&content, max_fragment_size, ack)
+                .into_iter()
+                .map(|fragment| {
+                    let variant = Variant::UserMessageFragment(fragment);
+                    self.send_message_from_our_location(src, dst, variant, None)
+                })
+                .collect::<Result<Vec<_>>>()?;
+            ids[0]
+        } else {
+            let variant = if ack {
+                Variant::UserMessageWithAck(content)
+            } else {
+                Variant::UserMessage(content)
+            };
+            self.send_message_from_our_location(src, dst, variant, None)?
+        };
+
+        if ack {
+            let token = self.timer.schedule(DELIVERY_ACK_TIMEOUT);
+            if !self
+                .network_service
+                .pending_acks_mut()
+                .insert(id, dst, token)
+            {
+                debug!("{} - Already waiting for the ack of {:?}.", self, id);
+            }
+        }
+
+        Ok(id)
     }
 
     fn handle_timeout(&mut self, token: u64, _outbox: &mut dyn EventBox) -> Transition {
         if self.gossip_timer_token == token {
             self.gossip_timer_token = self.timer.schedule(self.parsec_map.gossip_period());
             self.parsec_map.reset_gossip_period();
+
+            // Keep telling the section we lost our parsec until it sends us a new genesis.
+            if self.parsec_map.is_resyncing() {
+                self.send_member_knowledge();
+                self.send_parsec_resync();
+            }
+        } else if self.merge_timer_token == Some(token) {
+            self.handle_merge_timeout();
+        } else if let Some(pub_id) = self.resource_challenge_timers.remove(&token) {
+            debug!(
+                "{} - {} didn't answer its ResourceChallenge in time.",
+                self, pub_id
+            );
+            let _ = self.resource_challenges.remove(&pub_id);
         }
 
         Transition::Stay
@@ -1330,13 +1951,15 @@ impl Base for Elder {
 
         self.maintain_parsec();
         self.send_parsec_gossip(None);
+        self.send_merge_request_if_needed();
+        self.send_member_counts_if_changed();
 
         transition
     }
 
     fn handle_bootstrapped_to(&mut self, addr: SocketAddr) -> Transition {
         // A mature node doesn't need a bootstrap connection
-        self.network_service.service_mut().disconnect_from(addr);
+        self.network_service.disconnect(addr);
         Transition::Stay
     }
 
@@ -1383,6 +2006,33 @@ impl Base for Elder {
         Transition::Stay
     }
 
+    fn handle_message_failure(
+        &mut self,
+        sender: SocketAddr,
+        dst: DstLocation,
+        error: &RoutingError,
+    ) {
+        // A proof too new for us means we are lagging behind, not that the sender misbehaved.
+        match error {
+            RoutingError::UntrustedMessage(_) if !error.is_transient() => (),
+            _ => return,
+        }
+
+        // Any other message might have been relayed by an honest node which can't check it. Only
+        // a direct message is sure to come from the node which created it.
+        if dst != DstLocation::Direct {
+            return;
+        }
+
+        if let Some(pub_id) = self
+            .chain
+            .find_p2p_node_from_addr(&sender)
+            .map(|node| *node.public_id())
+        {
+            self.handle_fault(pub_id, Fault::UntrustedMessage);
+        }
+    }
+
     fn handle_message(
         &mut self,
         sender: Option<SocketAddr>,
@@ -1399,7 +2049,9 @@ impl Base for Elder {
     }
 
     fn filter_incoming_message(&mut self, message: &MessageWithBytes) -> bool {
-        self.msg_filter.filter_incoming(message).is_new()
+        self.msg_filter
+            .filter_incoming(message, self.network_service.metrics())
+            .is_new()
     }
 
     fn relay_message(&mut self, message: &MessageWithBytes) -> Result<()> {
@@ -1460,6 +2112,24 @@ impl Approved for Elder {
         outbox.send_event(event);
     }
 
+    // Each elder keeps its own scores and votes from its own view. The section still agrees on
+    // the penalty: `Offline` only accumulates once a quorum of elders voted for it, i.e. once a
+    // quorum saw the node misbehave. A dedicated vote to agree on the penalty itself would need
+    // the same quorum and then lead to the same `Offline`. Banning the node is left to an explicit
+    // `Ban` vote.
+    fn handle_fault(&mut self, pub_id: PublicId, fault: Fault) {
+        debug!("{} - Detected {:?} from {}.", self, fault, pub_id);
+
+        if !self.reputation.record(pub_id, fault) {
+            return;
+        }
+
+        if self.chain.is_peer_our_member(&pub_id) {
+            info!("{} - Voting misbehaving node {} offline.", self, pub_id);
+            self.vote_for_event(AccumulatingEvent::Offline(pub_id));
+        }
+    }
+
     fn parsec_map(&self) -> &ParsecMap {
         &self.parsec_map
     }
@@ -1502,6 +2172,69 @@ impl Approved for Elder {
         Ok(())
     }
 
+    fn handle_merge_polled(&mut self, details: MergeDetails) -> Result<Transition, RoutingError> {
+        self.merge_timer_token = Some(self.timer.schedule(MERGE_TIMEOUT));
+
+        if !self.chain.is_merge_lead() {
+            // Our sibling creates the merged section once it learns we committed to the merge,
+            // and then sends us the genesis of its parsec, see `handle_merged_genesis`.
+            info!(
+                "{} - Merging into our sibling {:?}.",
+                self,
+                details.elders_info.prefix()
+            );
+            let details = self
+                .chain
+                .merge_details(self.parsec_map.last_version(), true);
+            self.send_merge_details(details);
+            return Ok(Transition::Stay);
+        }
+
+        info!(
+            "{} - Merging with our sibling {:?}.",
+            self,
+            details.elders_info.prefix()
+        );
+        let merged_info = self.chain.merge_self(&details.elders_info)?;
+
+        // Restart parsec with a version newer than both our and our sibling's, so the nodes of our
+        // sibling accept the genesis update and join our parsec as adults.
+        let parsec_version =
+            cmp::max(self.parsec_map.last_version(), details.parsec_version).saturating_add(1);
+        let reset_data = self.chain.prepare_parsec_reset(parsec_version)?;
+        let complete_data = self.complete_parsec_reset_data(reset_data);
+        self.reset_parsec_with_data(complete_data.gen_pfx_info, complete_data.to_vote_again)?;
+        self.send_genesis_updates();
+        self.send_member_knowledge();
+
+        self.pending_merge_info = Some(merged_info);
+        self.start_merge_dkg_if_ready(false)?;
+        Ok(Transition::Stay)
+    }
+
+    fn handle_merge_timeout(&mut self) {
+        self.merge_timer_token = None;
+
+        if self.chain.merging_into().is_some() {
+            info!(
+                "{} - Our sibling didn't follow up on the merge, aborting.",
+                self
+            );
+            self.vote_for_event(AccumulatingEvent::AbortMerge);
+        } else if self.pending_merge_info.is_some() {
+            info!(
+                "{} - Not all the elders of our sibling joined our parsec, starting the DKG anyway.",
+                self
+            );
+            if let Err(error) = self.start_merge_dkg_if_ready(true) {
+                error!(
+                    "{} - Failed to start the merged section DKG: {:?}",
+                    self, error
+                );
+            }
+        }
+    }
+
     fn handle_member_added(
         &mut self,
         payload: OnlinePayload,
@@ -1573,7 +2306,7 @@ impl Approved for Elder {
     }
 
     fn handle_prune_event(&mut self) -> Result<(), RoutingError> {
-        if self.chain.split_in_progress() {
+        if self.chain.split_in_progress() || self.chain.merge_in_progress() {
             log_or_panic!(
                 log::Level::Warn,
                 "{} Tring to prune parsec during prefix change.",
@@ -1610,10 +2343,7 @@ impl Approved for Elder {
         let complete_data = if info_prefix.is_extension_of(&old_pfx) {
             self.prepare_finalise_split()?
         } else if old_pfx.is_extension_of(&info_prefix) {
-            panic!(
-                "{} - Merge not supported: {:?} -> {:?}",
-                self, old_pfx, info_prefix,
-            );
+            self.prepare_finalise_merge()?
         } else {
             self.prepare_reset_parsec()?
         };
@@ -15,22 +15,35 @@
 
 use super::{super::test_utils, *};
 use crate::{
-    chain::{SectionKeyInfo, SectionProofSlice},
+    action::Action,
+    chain::{BanTarget, SectionKeyInfo, SectionProofSlice, MIN_AGE},
+    client::{ClientFullId, ClientId, ClientRateLimit, ClientRejection, ClientWireMessage},
+    error::UntrustedProof,
     generate_bls_threshold_secret_key,
     messages::Variant,
-    quic_p2p,
+    network_service::{NetworkEvent, Peer},
+    reputation::PENALTY_THRESHOLD,
+    resource_proof::ResourceChallenge,
     rng::{self, MainRng},
     unwrap, utils, ELDER_SIZE,
 };
 use crossbeam_channel::Receiver;
-use mock_quic_p2p::Network;
-use std::{iter, net::SocketAddr};
+use fake_clock::FakeClock;
+use mock_quic_p2p::{self as quic_p2p, Network};
+use std::{iter, net::SocketAddr, sync::mpsc};
 
 // Minimal number of votes to reach accumulation.
 const ACCUMULATE_VOTE_COUNT: usize = 5;
 // Only one vote missing to reach accumulation.
 const NOT_ACCUMULATE_ALONE_VOTE_COUNT: usize = 4;
 
+fn network_params() -> NetworkParams {
+    NetworkParams {
+        resource_proof: Some(ResourceProofParams::default()),
+        ..NetworkParams::default()
+    }
+}
+
 struct DkgToSectionInfo {
     participants: BTreeSet<PublicId>,
     new_pk_set: bls::PublicKeySet,
@@ -217,6 +230,13 @@ impl ElderUnderTest {
         );
     }
 
+    fn accumulate_ban(&mut self, target: BanTarget) {
+        let _ = self.n_vote_for_gossipped(
+            ACCUMULATE_VOTE_COUNT,
+            iter::once(AccumulatingEvent::Ban(target)),
+        );
+    }
+
     fn accumulate_start_dkg(&mut self, info: &DkgToSectionInfo) {
         let _ = self.n_vote_for_gossipped(
             ACCUMULATE_VOTE_COUNT,
@@ -320,6 +340,64 @@ impl ElderUnderTest {
         gen_p2p_node(&mut self.rng, &self.network)
     }
 
+    // Sends a `JoinRequest` from `new_node` and returns the challenge it received in response.
+    fn send_join_request(&mut self, new_node: &mut JoiningPeer) -> (P2pNode, ResourceChallenge) {
+        let p2p_node = P2pNode::new(*new_node.public_id(), new_node.our_connection_info());
+
+        self.elder.handle_join_request(
+            p2p_node.clone(),
+            JoinRequest {
+                elders_version: self.elders_info.version(),
+                relocate_payload: None,
+            },
+        );
+        self.network.poll(&mut self.rng);
+
+        (p2p_node, new_node.expect_resource_challenge())
+    }
+
+    // Connects `client` to the elder and completes the handshake with the given identity.
+    fn connect_client(&mut self, client_id: &ClientFullId, client: &mut FakeClient) {
+        let addr = client.our_connection_info();
+        let _ = self.elder.handle_network_event(
+            NetworkEvent::ConnectedTo {
+                peer: Peer::Client(addr),
+            },
+            &mut (),
+        );
+        self.network.poll(&mut self.rng);
+
+        let challenge = client.expect_challenge();
+        let _ = self.send_client_message(addr, &client_id.handshake_response(&challenge));
+    }
+
+    // Delivers `msg` to the elder as if sent by the client on `addr` and returns the raised events.
+    fn send_client_message(&mut self, addr: SocketAddr, msg: &ClientWireMessage) -> Vec<Event> {
+        let mut events = Vec::new();
+        let _ = self.elder.handle_network_event(
+            NetworkEvent::NewMessage {
+                peer: Peer::Client(addr),
+                msg: unwrap!(msg.to_bytes()),
+            },
+            &mut events,
+        );
+        events
+    }
+
+    fn send_to_client(&mut self, client_id: &ClientId, content: Vec<u8>) -> Result<()> {
+        let (result_tx, result_rx) = mpsc::channel();
+        let _ = self.elder.handle_action(
+            Action::SendToClient {
+                client: *client_id,
+                content,
+                result_tx,
+            },
+            &mut (),
+        );
+        self.network.poll(&mut self.rng);
+        unwrap!(result_rx.recv())
+    }
+
     // Drop an existing elder and promote an adult to take its place. Drive the whole process to
     // completion by casting all necessary votes and letting them accumulate.
     fn perform_offline_and_promote(
@@ -349,7 +427,7 @@ fn new_elder_state(
 ) -> Elder {
     let parsec_map = ParsecMap::default().with_init(rng, full_id.clone(), &gen_pfx_info);
     let chain = Chain::new(
-        Default::default(),
+        network_params(),
         *full_id.public_id(),
         gen_pfx_info.clone(),
         Some(secret_key_share),
@@ -473,6 +551,29 @@ fn handle_bootstrap() {
     }
 }
 
+#[test]
+fn ignore_bootstrap_from_banned_node() {
+    let mut elder_test = ElderUnderTest::new(ELDER_SIZE);
+    let mut new_node = JoiningPeer::new(&mut elder_test.rng);
+
+    let p2p_node = P2pNode::new(*new_node.public_id(), new_node.our_connection_info());
+    let dst_name = *new_node.public_id().name();
+
+    // Banned by its address, so it can't come back under a new name either.
+    elder_test.accumulate_ban(BanTarget::Addr(*p2p_node.peer_addr()));
+    assert!(elder_test.elder.chain().is_banned(&p2p_node));
+
+    elder_test
+        .elder
+        .handle_bootstrap_request(p2p_node, dst_name);
+    elder_test.network.poll(&mut elder_test.rng);
+
+    assert!(new_node.recv_messages().all(|msg| match msg.variant {
+        Variant::BootstrapResponse(_) => false,
+        _ => true,
+    }));
+}
+
 #[test]
 fn send_genesis_update() {
     let mut elder_test = ElderUnderTest::new(ELDER_SIZE);
@@ -514,6 +615,325 @@ fn send_genesis_update() {
     verify_proof_chain_does_not_contain(proof, orig_elders_version);
 }
 
+#[test]
+fn join_request_requires_resource_proof() {
+    let mut elder_test = ElderUnderTest::new(ELDER_SIZE);
+    let mut new_node = JoiningPeer::new(&mut elder_test.rng);
+    let (_, challenge) = elder_test.send_join_request(&mut new_node);
+
+    let params = ResourceProofParams::default();
+    assert_eq!(challenge.difficulty, params.difficulty);
+    assert_eq!(challenge.size, params.size);
+    assert!(!elder_test.has_unpolled_observations());
+}
+
+#[test]
+fn accept_valid_resource_proof() {
+    let mut elder_test = ElderUnderTest::new(ELDER_SIZE);
+    let mut new_node = JoiningPeer::new(&mut elder_test.rng);
+    let (p2p_node, challenge) = elder_test.send_join_request(&mut new_node);
+
+    elder_test
+        .elder
+        .handle_resource_proof_response(p2p_node, challenge.solve());
+    assert!(elder_test.has_unpolled_observations());
+}
+
+#[test]
+fn reject_invalid_resource_proof() {
+    let mut elder_test = ElderUnderTest::new(ELDER_SIZE);
+    let mut new_node = JoiningPeer::new(&mut elder_test.rng);
+    let (p2p_node, challenge) = elder_test.send_join_request(&mut new_node);
+
+    let mut proof = challenge.solve();
+    proof.data[0] ^= 1;
+
+    elder_test
+        .elder
+        .handle_resource_proof_response(p2p_node.clone(), proof);
+    assert!(!elder_test.has_unpolled_observations());
+
+    // The challenge is discarded after a failed attempt.
+    elder_test
+        .elder
+        .handle_resource_proof_response(p2p_node, challenge.solve());
+    assert!(!elder_test.has_unpolled_observations());
+}
+
+#[test]
+fn reject_slow_resource_proof() {
+    let mut elder_test = ElderUnderTest::new(ELDER_SIZE);
+    let mut new_node = JoiningPeer::new(&mut elder_test.rng);
+    let (p2p_node, challenge) = elder_test.send_join_request(&mut new_node);
+    let proof = challenge.solve();
+
+    let timeout = ResourceProofParams::default().timeout;
+    FakeClock::advance_time(timeout.as_millis() as u64 + 1);
+
+    elder_test
+        .elder
+        .handle_resource_proof_response(p2p_node, proof);
+    assert!(!elder_test.has_unpolled_observations());
+}
+
+#[test]
+fn drop_unanswered_resource_challenge() {
+    let mut elder_test = ElderUnderTest::new(ELDER_SIZE);
+    let mut new_node = JoiningPeer::new(&mut elder_test.rng);
+    let (p2p_node, challenge) = elder_test.send_join_request(&mut new_node);
+
+    let timer_token = *unwrap!(elder_test.elder.resource_challenge_timers.keys().next());
+    let _ = elder_test.elder.handle_timeout(timer_token, &mut ());
+    assert!(elder_test.elder.resource_challenges.is_empty());
+    assert!(elder_test.elder.resource_challenge_timers.is_empty());
+
+    elder_test
+        .elder
+        .handle_resource_proof_response(p2p_node, challenge.solve());
+    assert!(!elder_test.has_unpolled_observations());
+}
+
+#[test]
+fn resend_pending_resource_challenge() {
+    let mut elder_test = ElderUnderTest::new(ELDER_SIZE);
+    let mut new_node = JoiningPeer::new(&mut elder_test.rng);
+    let (p2p_node, challenge) = elder_test.send_join_request(&mut new_node);
+
+    // Another `JoinRequest` from the same node gets the same challenge, so the node can't extend
+    // its deadline.
+    let (_, resent_challenge) = elder_test.send_join_request(&mut new_node);
+    assert_eq!(resent_challenge, challenge);
+
+    elder_test
+        .elder
+        .handle_resource_proof_response(p2p_node, challenge.solve());
+    assert!(elder_test.has_unpolled_observations());
+}
+
+#[test]
+fn charge_untrusted_message_to_direct_sender_only() {
+    let mut elder_test = ElderUnderTest::new(ELDER_SIZE);
+    let pub_id = *elder_test.other_ids[0].0.public_id();
+    let addr = *unwrap!(elder_test.elder.chain().get_p2p_node(pub_id.name())).peer_addr();
+    let error = RoutingError::UntrustedMessage(UntrustedProof {
+        prefix: *elder_test.elders_info.prefix(),
+        first_version: 0,
+        last_version: 0,
+        too_new: false,
+    });
+
+    // The sender might have just relayed it.
+    let dst = DstLocation::Section(*elder_test.candidate.public_id().name());
+    elder_test.elder.handle_message_failure(addr, dst, &error);
+    assert_eq!(elder_test.elder.peer_penalties().get(&pub_id), None);
+
+    elder_test
+        .elder
+        .handle_message_failure(addr, DstLocation::Direct, &error);
+    assert!(elder_test.elder.peer_penalties().get(&pub_id).is_some());
+}
+
+#[test]
+fn charge_invalid_gossip_only() {
+    let mut elder_test = ElderUnderTest::new(ELDER_SIZE);
+    let full_id = elder_test.other_ids[0].0.clone();
+    let pub_id = *full_id.public_id();
+    let addr = *unwrap!(elder_test.elder.chain().get_p2p_node(pub_id.name())).peer_addr();
+    let version = elder_test.elder.parsec_map().last_version();
+
+    let send_request = |elder_test: &mut ElderUnderTest, request| {
+        let msg = unwrap!(Message::single_src(
+            &full_id,
+            DstLocation::Direct,
+            Variant::ParsecRequest(version, request)
+        ));
+        let _ = elder_test.elder.dispatch_message(Some(addr), msg, &mut ());
+    };
+
+    send_request(&mut elder_test, parsec::Request::new());
+    assert_eq!(elder_test.elder.peer_penalties().get(&pub_id), None);
+
+    send_request(&mut elder_test, parsec::Request::new_invalid());
+    assert!(elder_test.elder.peer_penalties().get(&pub_id).is_some());
+}
+
+#[test]
+fn vote_offline_elder_sending_invalid_signatures() {
+    let mut elder_test = ElderUnderTest::new(ELDER_SIZE);
+    let (full_id, secret_key_share) = elder_test.other_ids[0].clone();
+    let pub_id = *full_id.public_id();
+
+    let content = PlainMessage {
+        src: *elder_test.elders_info.prefix(),
+        dst: DstLocation::Section(*elder_test.candidate.public_id().name()),
+        variant: Variant::UserMessage(b"hello".to_vec()),
+    };
+    let proof = elder_test.elder.chain().prove(&content.dst, None);
+    let public_key_set = elder_test.elder.our_section_bls_keys().clone();
+    let invalid_signature_share = secret_key_share.sign(b"bad message");
+
+    let mut attempts = 0;
+    while elder_test
+        .elder
+        .peer_penalties()
+        .get(&pub_id)
+        .map_or(true, |penalty| *penalty < PENALTY_THRESHOLD)
+    {
+        assert!(!elder_test.has_unpolled_observations());
+        assert!(attempts < PENALTY_THRESHOLD);
+        attempts += 1;
+
+        let msg = AccumulatingMessage {
+            content: content.clone(),
+            proof: proof.clone(),
+            public_key_set: public_key_set.clone(),
+            signature_shares: iter::once((1, invalid_signature_share.clone())).collect(),
+        };
+        let _ = unwrap!(elder_test
+            .elder
+            .handle_message_signature(msg, pub_id, &mut ()));
+    }
+
+    // The elder voted the misbehaving node offline.
+    assert!(elder_test.has_unpolled_observations());
+}
+
+#[test]
+fn client_handshake_and_messages() {
+    let mut elder_test = ElderUnderTest::new(ELDER_SIZE);
+    let client_id = ClientFullId::gen(&mut elder_test.rng);
+    let mut client = FakeClient::new();
+    elder_test.connect_client(&client_id, &mut client);
+
+    let content = b"request".to_vec();
+    let events = elder_test.send_client_message(
+        client.our_connection_info(),
+        &ClientWireMessage::Message(content.clone()),
+    );
+    assert!(events.contains(&Event::ClientMessage {
+        client: *client_id.public_id(),
+        content,
+    }));
+
+    let content = b"response".to_vec();
+    unwrap!(elder_test.send_to_client(client_id.public_id(), content.clone()));
+    assert_eq!(client.expect_message(), content);
+}
+
+#[test]
+fn ignore_unauthenticated_client() {
+    let mut elder_test = ElderUnderTest::new(ELDER_SIZE);
+    let client_id = ClientFullId::gen(&mut elder_test.rng);
+    let impostor_id = ClientFullId::gen(&mut elder_test.rng);
+    let mut client = FakeClient::new();
+    let addr = client.our_connection_info();
+
+    let _ = elder_test.elder.handle_network_event(
+        NetworkEvent::ConnectedTo {
+            peer: Peer::Client(addr),
+        },
+        &mut (),
+    );
+    elder_test.network.poll(&mut elder_test.rng);
+    let challenge = client.expect_challenge();
+
+    // The impostor answers the challenge on behalf of the client.
+    let response = match impostor_id.handshake_response(&challenge) {
+        ClientWireMessage::HandshakeResponse { signature, .. } => {
+            ClientWireMessage::HandshakeResponse {
+                client_id: *client_id.public_id(),
+                signature,
+            }
+        }
+        msg => panic!("Unexpected message {:?}", msg),
+    };
+    let _ = elder_test.send_client_message(addr, &response);
+
+    let events = elder_test.send_client_message(addr, &ClientWireMessage::Message(vec![1]));
+    assert!(events.is_empty());
+
+    match elder_test.send_to_client(client_id.public_id(), vec![2]) {
+        Err(RoutingError::ClientNotConnected) => (),
+        result => panic!("Unexpected result {:?}", result),
+    }
+}
+
+#[test]
+fn send_to_reconnected_client() {
+    let mut elder_test = ElderUnderTest::new(ELDER_SIZE);
+    let client_id = ClientFullId::gen(&mut elder_test.rng);
+    let mut old_client = FakeClient::new();
+    let mut new_client = FakeClient::new();
+
+    elder_test.connect_client(&client_id, &mut old_client);
+    elder_test.connect_client(&client_id, &mut new_client);
+
+    let content = b"response".to_vec();
+    unwrap!(elder_test.send_to_client(client_id.public_id(), content.clone()));
+    assert_eq!(new_client.expect_message(), content);
+    assert_eq!(old_client.recv_wire_messages().count(), 0);
+}
+
+#[test]
+fn rate_limit_flooding_client() {
+    let mut elder_test = ElderUnderTest::new(ELDER_SIZE);
+    elder_test
+        .elder
+        .network_service_mut()
+        .clients_mut()
+        .set_rate_limit(Some(ClientRateLimit {
+            burst: 5,
+            messages_per_second: 1,
+        }));
+
+    // The handshake takes one token.
+    let client_id = ClientFullId::gen(&mut elder_test.rng);
+    let mut client = FakeClient::new();
+    elder_test.connect_client(&client_id, &mut client);
+    let addr = client.our_connection_info();
+
+    let events = (0..20)
+        .flat_map(|index| {
+            elder_test.send_client_message(addr, &ClientWireMessage::Message(vec![index]))
+        })
+        .collect_vec();
+    elder_test.network.poll(&mut elder_test.rng);
+
+    let received = events
+        .iter()
+        .filter(|event| match event {
+            Event::ClientMessage { .. } => true,
+            _ => false,
+        })
+        .count();
+    assert_eq!(received, 4);
+
+    let rejections = events
+        .iter()
+        .filter_map(|event| match event {
+            Event::ClientRejected {
+                client, rejection, ..
+            } => Some((*client, *rejection)),
+            _ => None,
+        })
+        .collect_vec();
+    assert_eq!(rejections.len(), 1);
+    let (rejected_client, rejection) = rejections[0];
+    assert_eq!(rejected_client, Some(*client_id.public_id()));
+    assert_eq!(client.expect_rejection(), rejection);
+
+    // Once the bucket refills, messages are accepted again.
+    let ClientRejection::RateLimited { retry_after } = rejection;
+    FakeClock::advance_time(retry_after.as_millis() as u64 + 1);
+
+    let content = vec![20];
+    let events = elder_test.send_client_message(addr, &ClientWireMessage::Message(content.clone()));
+    assert!(events.contains(&Event::ClientMessage {
+        client: *client_id.public_id(),
+        content,
+    }));
+}
+
 fn verify_proof_chain_contains(proof_chain: &SectionProofSlice, expected_version: u64) {
     assert!(
         proof_chain
@@ -566,6 +986,14 @@ impl JoiningPeer {
         self.full_id.public_id()
     }
 
+    fn expect_resource_challenge(&self) -> ResourceChallenge {
+        let challenge = self.recv_messages().find_map(|msg| match msg.variant {
+            Variant::ResourceChallenge(challenge) => Some(challenge),
+            _ => None,
+        });
+        unwrap!(challenge, "ResourceChallenge not received")
+    }
+
     fn expect_bootstrap_response(&self) -> BootstrapResponse {
         let response = self.recv_messages().find_map(|msg| match msg.variant {
             Variant::BootstrapResponse(response) => Some(response),
@@ -583,3 +1011,65 @@ impl JoiningPeer {
             })
     }
 }
+
+// Client endpoint which only receives messages. The messages it sends are injected into the elder
+// directly.
+struct FakeClient {
+    network_service: quic_p2p::QuicP2p,
+    network_event_rx: Receiver<quic_p2p::Event>,
+}
+
+impl FakeClient {
+    fn new() -> Self {
+        let (network_event_tx, network_event_rx) = {
+            let (node_tx, node_rx) = crossbeam_channel::unbounded();
+            let (client_tx, _) = crossbeam_channel::unbounded();
+            (quic_p2p::EventSenders { node_tx, client_tx }, node_rx)
+        };
+        let network_service = unwrap!(quic_p2p::Builder::new(network_event_tx).build());
+
+        Self {
+            network_service,
+            network_event_rx,
+        }
+    }
+
+    fn our_connection_info(&mut self) -> SocketAddr {
+        unwrap!(self.network_service.our_connection_info())
+    }
+
+    fn expect_challenge(&self) -> [u8; 32] {
+        let challenge = self.recv_wire_messages().find_map(|msg| match msg {
+            ClientWireMessage::Challenge(challenge) => Some(challenge),
+            _ => None,
+        });
+        unwrap!(challenge, "Challenge not received")
+    }
+
+    fn expect_rejection(&self) -> ClientRejection {
+        let rejection = self.recv_wire_messages().find_map(|msg| match msg {
+            ClientWireMessage::Rejected(rejection) => Some(rejection),
+            _ => None,
+        });
+        unwrap!(rejection, "Rejection not received")
+    }
+
+    fn expect_message(&self) -> Vec<u8> {
+        let content = self.recv_wire_messages().find_map(|msg| match msg {
+            ClientWireMessage::Message(content) => Some(content),
+            _ => None,
+        });
+        unwrap!(content, "Message not received")
+    }
+
+    fn recv_wire_messages<'a>(&'a self) -> impl Iterator<Item = ClientWireMessage> + 'a {
+        self.network_event_rx
+            .try_iter()
+            .filter_map(|event| match event {
+                quic_p2p::Event::NewMessage { msg, .. } => {
+                    Some(unwrap!(ClientWireMessage::from_bytes(&msg)))
+                }
+                _ => None,
+            })
+    }
+}
@@ -12,21 +12,24 @@ use super::{
     common::Base,
 };
 use crate::{
+    action::Action,
     chain::{EldersInfo, GenesisPfxInfo, NetworkParams, SectionKeyInfo},
     error::{Result, RoutingError},
     event::{Connected, Event},
-    id::FullId,
+    id::{FullId, P2pNode},
     location::{DstLocation, SrcLocation},
     messages::{
-        BootstrapResponse, JoinRequest, Message, MessageWithBytes, QueuedMessage, Variant,
-        VerifyStatus,
+        BootstrapResponse, JoinRequest, Message, MessageId, MessageWithBytes, QueuedMessage,
+        Variant, VerifyStatus,
     },
     network_service::NetworkService,
     outbox::EventBox,
     relocation::RelocatePayload,
+    resource_proof::{ResourceChallenge, ResourceProof},
     rng::MainRng,
     routing_message_filter::RoutingMessageFilter,
     state_machine::{State, Transition},
+    status::{NodeState, NodeStatus},
     timer::Timer,
     xor_space::{Prefix, XorName},
 };
@@ -129,6 +132,12 @@ impl JoiningPeer {
         )))
     }
 
+    pub fn status(&self) -> NodeStatus {
+        let mut status = NodeStatus::new(NodeState::JoiningPeer, Some(self.id()));
+        status.backlog.messages = self.msg_backlog.len();
+        status
+    }
+
     fn send_join_requests(&mut self) {
         let elders_version = self.elders_info.version();
         for dst in self.elders_info.clone().member_nodes() {
@@ -150,6 +159,34 @@ impl JoiningPeer {
         }
     }
 
+    fn handle_resource_challenge(&mut self, p2p_node: P2pNode, challenge: ResourceChallenge) {
+        if !self.elders_info.is_member(p2p_node.public_id()) {
+            debug!(
+                "{} - Ignoring ResourceChallenge from {} - not an elder of the section we are \
+                 joining.",
+                self, p2p_node
+            );
+            return;
+        }
+
+        if !challenge.is_within_limits() {
+            warn!(
+                "{} - Ignoring ResourceChallenge from {} - too hard: {:?}",
+                self, p2p_node, challenge
+            );
+            return;
+        }
+
+        debug!(
+            "{} - Solving ResourceChallenge from {}: {:?}",
+            self, p2p_node, challenge
+        );
+        self.timer.spawn(move || Action::ResourceProofSolved {
+            elder: p2p_node,
+            proof: challenge.solve(),
+        });
+    }
+
     fn handle_node_approval(&mut self, gen_pfx_info: GenesisPfxInfo) -> Transition {
         info!(
             "{} - This node has been approved to join the network at {:?}!",
@@ -204,16 +241,28 @@ impl Base for JoiningPeer {
         &mut self.rng
     }
 
+    fn handle_resource_proof_solved(&mut self, elder: P2pNode, proof: ResourceProof) {
+        if !self.elders_info.is_member(elder.public_id()) {
+            debug!(
+                "{} - Dropping ResourceProof for {} - no longer joining its section.",
+                self, elder
+            );
+            return;
+        }
+
+        self.send_direct_message(elder.peer_addr(), Variant::ResourceProofResponse(proof));
+    }
+
     fn handle_send_message(
         &mut self,
         _: SrcLocation,
         _: DstLocation,
         _: Vec<u8>,
-    ) -> Result<(), RoutingError> {
+        _: bool,
+    ) -> Result<MessageId, RoutingError> {
         warn!("{} - Cannot handle SendMessage - not joined.", self);
-        // TODO: return Err here eventually. Returning Ok for now to
-        // preserve the pre-refactor behaviour.
-        Ok(())
+        // There is no message, so there is no id to return.
+        Err(RoutingError::InvalidState)
     }
 
     fn handle_timeout(&mut self, token: u64, _: &mut dyn EventBox) -> Transition {
@@ -268,6 +317,9 @@ impl Base for JoiningPeer {
                     }
                 }
             }
+            Variant::ResourceChallenge(challenge) => {
+                self.handle_resource_challenge(msg.src.to_sender_node(sender)?, challenge);
+            }
             Variant::NodeApproval(gen_info) => {
                 // Ensure src and dst are what we expect.
                 let _: &Prefix<_> = msg.src.as_section()?;
@@ -293,11 +345,16 @@ impl Base for JoiningPeer {
 
     fn should_handle_message(&self, msg: &Message) -> bool {
         match msg.variant {
-            Variant::BootstrapResponse(BootstrapResponse::Join(_)) | Variant::NodeApproval(_) => {
-                true
-            }
+            Variant::BootstrapResponse(BootstrapResponse::Join(_))
+            | Variant::ResourceChallenge(_)
+            | Variant::NodeApproval(_) => true,
             Variant::NeighbourInfo(_)
+            | Variant::MemberCount(_)
+            | Variant::Merge(_)
             | Variant::UserMessage(_)
+            | Variant::UserMessageFragment(_)
+            | Variant::UserMessageWithAck(_)
+            | Variant::MessageDeliveryAck(_)
             | Variant::AckMessage { .. }
             | Variant::GenesisUpdate(_)
             | Variant::Relocate(_)
@@ -305,7 +362,9 @@ impl Base for JoiningPeer {
             | Variant::BootstrapRequest(_)
             | Variant::BootstrapResponse(_)
             | Variant::JoinRequest(_)
+            | Variant::ResourceProofResponse(_)
             | Variant::MemberKnowledge { .. }
+            | Variant::ParsecResync(_)
             | Variant::ParsecRequest(..)
             | Variant::ParsecResponse(..)
             | Variant::Ping => false,
@@ -313,7 +372,9 @@ impl Base for JoiningPeer {
     }
 
     fn filter_incoming_message(&mut self, msg: &MessageWithBytes) -> bool {
-        self.msg_filter.filter_incoming(msg).is_new()
+        self.msg_filter
+            .filter_incoming(msg, self.network_service.metrics())
+            .is_new()
     }
 
     fn relay_message(&mut self, msg: &MessageWithBytes) -> Result<()> {
@@ -334,9 +395,8 @@ impl Base for JoiningPeer {
                 // handle it.
                 Ok(true)
             }
-            (Variant::BootstrapResponse(BootstrapResponse::Join(_)), _) => {
-                self.verify_message_full(msg, None)
-            }
+            (Variant::BootstrapResponse(BootstrapResponse::Join(_)), _)
+            | (Variant::ResourceChallenge(_), _) => self.verify_message_full(msg, None),
             _ => unreachable!(),
         }
     }
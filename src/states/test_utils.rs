@@ -16,8 +16,7 @@
 use crate::{
     chain::{AgeCounter, EldersInfo, GenesisPfxInfo, MIN_AGE_COUNTER},
     id::{FullId, P2pNode, PublicId},
-    network_service::{NetworkBuilder, NetworkService},
-    quic_p2p,
+    network_service::{EventSink, NetworkBuilder, NetworkService},
     rng::MainRng,
     timer::Timer,
     unwrap,
@@ -31,14 +30,10 @@ use std::collections::BTreeMap;
 pub fn create_network_service(network: &Network) -> NetworkService {
     let endpoint = network.gen_addr();
     let network_config = NetworkConfig::node().with_hard_coded_contact(endpoint);
-    let network_tx = {
-        let (node_tx, _) = crossbeam_channel::unbounded();
-        let (client_tx, _) = crossbeam_channel::unbounded();
-        quic_p2p::EventSenders { node_tx, client_tx }
-    };
-    unwrap!(NetworkBuilder::new(network_tx)
+    let (network_tx, _) = mpmc::unbounded();
+    unwrap!(NetworkBuilder::new()
         .with_config(network_config)
-        .build())
+        .build(EventSink::new(network_tx)))
 }
 
 pub fn create_timer() -> Timer {
@@ -86,6 +81,7 @@ pub fn create_gen_pfx_info(
         first_ages,
         latest_info: EldersInfo::default(),
         parsec_version,
+        ban_list: Default::default(),
     }
 }
 
@@ -0,0 +1,165 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::{
+    chain::Chain,
+    id::PublicId,
+    parsec::ParsecMap,
+    xor_space::{Prefix, XorName},
+};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Snapshot of the state of a node, as returned by `Node::status`.
+///
+/// The section related fields are only filled in once the node is a member of a section (that is,
+/// in the `Adult` or `Elder` state).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodeStatus {
+    /// The current state of the node.
+    pub state: NodeState,
+    /// Our name. `None` if the node terminated.
+    pub name: Option<XorName>,
+    /// Prefix of our section.
+    pub prefix: Option<Prefix<XorName>>,
+    /// Version of our section's elders info.
+    pub section_version: Option<u64>,
+    /// Elders of our section.
+    pub elders: Vec<MemberStatus>,
+    /// Adults of our section which are not elders.
+    pub adults: Vec<MemberStatus>,
+    /// Infants of our section.
+    pub infants: Vec<MemberStatus>,
+    /// Our neighbour sections.
+    pub neighbours: Vec<SectionStatus>,
+    /// The latest version of our section each other section is known to have.
+    pub their_knowledge: BTreeMap<Prefix<XorName>, u64>,
+    /// Version of the current parsec instance.
+    pub parsec_version: Option<u64>,
+    /// Estimated size of the current parsec instance, in bytes.
+    pub parsec_size: Option<u64>,
+    /// Numbers of the messages and events waiting to be handled.
+    pub backlog: BacklogStatus,
+    /// Estimate of the number of nodes in the network.
+    pub network_size_estimate: Option<NetworkSizeEstimate>,
+}
+
+impl NodeStatus {
+    pub(crate) fn new(state: NodeState, our_id: Option<&PublicId>) -> Self {
+        Self {
+            state,
+            name: our_id.map(|id| *id.name()),
+            prefix: None,
+            section_version: None,
+            elders: Vec::new(),
+            adults: Vec::new(),
+            infants: Vec::new(),
+            neighbours: Vec::new(),
+            their_knowledge: BTreeMap::new(),
+            parsec_version: None,
+            parsec_size: None,
+            backlog: BacklogStatus::default(),
+            network_size_estimate: None,
+        }
+    }
+
+    // Fills in the section related fields.
+    pub(crate) fn with_chain(mut self, chain: &Chain, parsec_map: &ParsecMap) -> Self {
+        let our_info = chain.our_info();
+        let ageing_policy = chain.network_cfg().ageing_policy;
+        let elder_names: BTreeSet<_> = our_info.member_names().collect();
+
+        for member in chain.our_joined_members() {
+            let status = MemberStatus {
+                name: *member.p2p_node.name(),
+                age: member.age(),
+            };
+
+            if elder_names.contains(&status.name) {
+                self.elders.push(status)
+            } else if ageing_policy.is_mature(status.age) {
+                self.adults.push(status)
+            } else {
+                self.infants.push(status)
+            }
+        }
+
+        let (size, is_exact) = chain.network_size_estimate();
+
+        self.prefix = Some(*our_info.prefix());
+        self.section_version = Some(our_info.version());
+        self.neighbours = chain
+            .neighbour_infos()
+            .map(|info| SectionStatus {
+                prefix: *info.prefix(),
+                version: info.version(),
+                elders: info.member_names().copied().collect(),
+            })
+            .collect();
+        self.their_knowledge = chain.get_their_knowledge().clone();
+        self.parsec_version = Some(parsec_map.last_version());
+        self.parsec_size = Some(parsec_map.get_size());
+        self.backlog.churn_events = chain.churn_event_backlog_len();
+        self.network_size_estimate = Some(NetworkSizeEstimate { size, is_exact });
+        self
+    }
+}
+
+/// The state a node is in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NodeState {
+    /// Connecting to the network.
+    BootstrappingPeer,
+    /// Waiting to be approved by a section.
+    JoiningPeer,
+    /// Member of a section, but not an elder.
+    Adult,
+    /// Elder of a section.
+    Elder,
+    /// The node terminated.
+    Terminated,
+}
+
+/// Member of our section.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemberStatus {
+    /// Name of the member.
+    pub name: XorName,
+    /// Age of the member.
+    pub age: u8,
+}
+
+/// One of our neighbour sections.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SectionStatus {
+    /// Prefix of the section.
+    pub prefix: Prefix<XorName>,
+    /// Version of the latest elders info of the section we know.
+    pub version: u64,
+    /// Elders of the section.
+    pub elders: Vec<XorName>,
+}
+
+/// Numbers of the messages and events waiting to be handled.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BacklogStatus {
+    /// Messages received before we were able to handle them.
+    pub messages: usize,
+    /// Verified messages waiting to be handled.
+    pub queued_messages: usize,
+    /// Accumulated events waiting for the current churn to complete.
+    pub churn_events: usize,
+}
+
+/// Estimate of the number of nodes in the network.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkSizeEstimate {
+    /// The estimated number of nodes.
+    pub size: u64,
+    /// Whether the estimate is exact, that is, whether we know all sections of the network.
+    pub is_exact: bool,
+}
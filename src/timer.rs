@@ -29,6 +29,8 @@ pub struct Timer {
 
 struct Inner {
     next_token: u64,
+    // Sends the actions computed by `spawn` back to the event loop.
+    action_tx: mpmc::Sender<Action>,
 
     #[cfg(not(feature = "mock_base"))]
     tx: mpsc::SyncSender<Detail>,
@@ -44,6 +46,8 @@ struct Inner {
 impl Timer {
     /// Creates a new timer, passing a channel sender used to send `Timeout` events.
     pub fn new(sender: mpmc::Sender<Action>) -> Self {
+        let action_tx = sender.clone();
+
         #[cfg(not(feature = "mock_base"))]
         let (tx, worker) = {
             let (tx, rx) = mpsc::sync_channel(1);
@@ -66,6 +70,7 @@ impl Timer {
         Self {
             inner: Rc::new(RefCell::new(Inner {
                 next_token: 0,
+                action_tx,
                 tx,
                 worker,
             })),
@@ -92,6 +97,33 @@ impl Timer {
         })
     }
 
+    /// Runs `work` on a background thread, so it doesn't block the event loop, and handles the
+    /// action it returns like the timeouts. With `mock_base` the work is done straight away, to
+    /// keep the tests deterministic.
+    pub fn spawn<F>(&self, work: F)
+    where
+        F: FnOnce() -> Action + Send + 'static,
+    {
+        let action_tx = self.inner.borrow().action_tx.clone();
+
+        #[cfg(not(feature = "mock_base"))]
+        {
+            let result = thread::Builder::new()
+                .name("Worker".to_string())
+                .spawn(move || {
+                    let _ = action_tx.send(work());
+                });
+            if let Err(error) = result {
+                error!("Worker thread could not be spawned: {:?}", error);
+            }
+        }
+
+        #[cfg(feature = "mock_base")]
+        {
+            let _ = action_tx.send(work());
+        }
+    }
+
     #[cfg(not(feature = "mock_base"))]
     fn run(sender: mpmc::Sender<Action>, rx: mpsc::Receiver<Detail>) {
         let mut deadlines: BTreeMap<Instant, Vec<u64>> = Default::default();
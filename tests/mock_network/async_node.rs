@@ -0,0 +1,53 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{gen_vec, LOWERED_ELDER_SIZE};
+use futures::{executor::LocalPool, task::LocalSpawnExt, StreamExt};
+use rand::Rng;
+use routing::{
+    event::{Connected, Event},
+    mock::Environment,
+    DstLocation, FullId, NetworkParams, Node, SrcLocation,
+};
+
+#[test]
+fn send_message_and_receive_events() {
+    let env = Environment::new(NetworkParams {
+        elder_size: LOWERED_ELDER_SIZE,
+        safe_section_size: LOWERED_ELDER_SIZE,
+        ..NetworkParams::default()
+    });
+    let mut rng = env.new_rng();
+    let full_id = FullId::gen(&mut rng);
+    let our_id = *full_id.public_id();
+
+    let (node, driver) = Node::builder()
+        .first(true)
+        .full_id(full_id)
+        .network_cfg(env.network_cfg())
+        .rng(&mut rng)
+        .create_async();
+
+    let mut pool = LocalPool::new();
+    unwrap!(pool.spawner().spawn_local(driver));
+
+    let mut events = node.events();
+    match pool.run_until(events.next()) {
+        Some(Event::Connected(Connected::First)) => (),
+        event => panic!("Unexpected event: {:?}", event),
+    }
+
+    let src = SrcLocation::Node(our_id);
+    let dst = DstLocation::Section(rng.gen());
+    let content = gen_vec(&mut rng, 1024);
+    let _ = unwrap!(pool.run_until(node.send_message(src, dst, content)));
+
+    // Once the driver is gone, the requests fail instead of hanging.
+    drop(pool);
+    assert!(futures::executor::block_on(node.send_message(src, dst, vec![])).is_err());
+}
@@ -69,6 +69,7 @@ fn remove_unresponsive_node() {
     let env = Environment::new(NetworkParams {
         elder_size,
         safe_section_size,
+        ..NetworkParams::default()
     });
 
     let mut nodes = create_connected_nodes(&env, safe_section_size);
@@ -123,6 +124,41 @@ fn remove_unresponsive_node() {
     assert_eq!(still_has_unresponsibe_elder, Vec::<String>::new());
 }
 
+#[test]
+fn merge() {
+    let env = Environment::new(NetworkParams {
+        elder_size: 4,
+        safe_section_size: 5,
+        ..NetworkParams::default()
+    });
+    let mut nodes = create_connected_nodes_until_split(&env, vec![1, 1]);
+
+    // Drop one adult from each section, so that both become smaller than `safe_section_size`.
+    for prefix in current_sections(&nodes).collect_vec() {
+        let index = unwrap!(nodes
+            .iter()
+            .position(|node| !node.inner.is_elder() && node.our_prefix() == &prefix));
+        let _ = nodes.remove(index);
+    }
+
+    poll_and_resend(&mut nodes);
+
+    assert_eq!(count_sections(&nodes), 1);
+
+    for node in nodes.iter().filter(|node| node.inner.is_elder()) {
+        let mut merged = false;
+        while let Some(event) = node.try_recv_event() {
+            if let Event::SectionMerged(prefix) = event {
+                assert_eq!(prefix, Prefix::default());
+                merged = true;
+            }
+        }
+        assert!(merged, "{} didn't receive SectionMerged", node.inner);
+    }
+
+    verify_invariant_for_all_nodes(&env, &mut nodes);
+}
+
 // Parameters for the churn tests.
 //
 // The test run in three phases:
@@ -169,6 +205,7 @@ impl Default for Params {
             network: NetworkParams {
                 elder_size: 4,
                 safe_section_size: 5,
+                ..NetworkParams::default()
             },
             initial_prefix_lens: vec![],
             message_schedule: MessageSchedule::AfterChurn,
@@ -589,7 +626,7 @@ impl Expectations {
             .iter_mut()
             .filter(|node| node.inner.is_elder() && node.in_src_location(&src))
         {
-            unwrap!(node.inner.send_message(src, dst, content.to_vec()));
+            let _ = unwrap!(node.inner.send_message(src, dst, content.to_vec()));
             sent_count += 1;
         }
         if src.is_multiple() {
@@ -660,7 +697,10 @@ impl Expectations {
             let orig_name = new_to_old_map.get(&curr_name).copied().unwrap_or(curr_name);
 
             while let Some(event) = node.try_recv_event() {
-                if let Event::MessageReceived { content, src, dst } = event {
+                if let Event::MessageReceived {
+                    content, src, dst, ..
+                } = event
+                {
                     let key = MessageKey { content, src, dst };
 
                     if dst.is_multiple() {
@@ -27,6 +27,7 @@ fn node_drops() {
     let env = Environment::new(NetworkParams {
         elder_size,
         safe_section_size,
+        ..NetworkParams::default()
     });
     let mut nodes = create_connected_nodes(&env, elder_size + 2);
     drop_node(&mut nodes, 0);
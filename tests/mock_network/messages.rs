@@ -6,11 +6,15 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::{create_connected_nodes, gen_elder_index, gen_vec, poll_all};
+use super::{create_connected_nodes, gen_elder_index, gen_vec, poll_all, TestNode};
+use fake_clock::FakeClock;
 use rand::Rng;
 use routing::{
-    event::Event, mock::Environment, quorum_count, DstLocation, NetworkParams, SrcLocation,
+    event::Event, mock::Environment, quorum_count, test_consts, DstLocation, MessageId,
+    NetworkConfig, NetworkParams, RoutingError, SrcLocation, DEFAULT_MAX_FRAGMENT_SIZE,
+    DELIVERY_ACK_TIMEOUT, MAX_MESSAGE_SIZE, MAX_QUEUED_USER_MESSAGES,
 };
+use std::{collections::HashSet, convert::TryInto};
 
 #[test]
 fn send() {
@@ -20,6 +24,7 @@ fn send() {
     let env = Environment::new(NetworkParams {
         elder_size,
         safe_section_size,
+        ..NetworkParams::default()
     });
     let mut rng = env.new_rng();
     let mut nodes = create_connected_nodes(&env, elder_size + 1);
@@ -28,10 +33,9 @@ fn send() {
     let src = SrcLocation::Node(nodes[sender_index].id());
     let dst = DstLocation::Section(rng.gen());
     let content = gen_vec(&mut rng, 1024);
-    assert!(nodes[sender_index]
+    let id = unwrap!(nodes[sender_index]
         .inner
-        .send_message(src, dst, content.clone())
-        .is_ok());
+        .send_message(src, dst, content.clone()));
 
     let _ = poll_all(&mut nodes);
 
@@ -43,11 +47,13 @@ fn send() {
         loop {
             match node.try_recv_event() {
                 Some(Event::MessageReceived {
+                    id: req_id,
                     content: ref req_content,
                     ..
                 }) => {
                     message_received_count += 1;
                     if content == *req_content {
+                        assert_eq!(req_id, id);
                         break;
                     }
                 }
@@ -68,6 +74,7 @@ fn send_and_receive() {
     let env = Environment::new(NetworkParams {
         elder_size,
         safe_section_size,
+        ..NetworkParams::default()
     });
     let mut rng = env.new_rng();
     let mut nodes = create_connected_nodes(&env, elder_size + 1);
@@ -79,14 +86,15 @@ fn send_and_receive() {
     let req_content = gen_vec(&mut rng, 10);
     let res_content = gen_vec(&mut rng, 11);
 
-    assert!(nodes[sender_index]
+    let req_id = unwrap!(nodes[sender_index]
         .inner
-        .send_message(src, dst, req_content.clone())
-        .is_ok());
+        .send_message(src, dst, req_content.clone()));
 
     let _ = poll_all(&mut nodes);
 
     let mut request_received_count = 0;
+    // All the elders of the responding section agree on the id of the response.
+    let mut res_ids = HashSet::new();
 
     for node in nodes
         .iter_mut()
@@ -94,20 +102,26 @@ fn send_and_receive() {
     {
         loop {
             match node.try_recv_event() {
-                Some(Event::MessageReceived { content, src, .. }) => {
+                Some(Event::MessageReceived {
+                    id, content, src, ..
+                }) => {
                     request_received_count += 1;
                     if req_content == content {
+                        assert_eq!(id, req_id);
                         let res_src = SrcLocation::Section(*node.our_prefix());
                         let res_dst = match src {
                             SrcLocation::Node(id) => DstLocation::Node(*id.name()),
                             _ => panic!("Unexpected src location: {:?}", src),
                         };
 
-                        if let Err(err) =
-                            node.inner
-                                .send_message(res_src, res_dst, res_content.clone())
+                        match node
+                            .inner
+                            .send_message(res_src, res_dst, res_content.clone())
                         {
-                            trace!("Failed to send message: {:?}", err);
+                            Ok(res_id) => {
+                                let _ = res_ids.insert(res_id);
+                            }
+                            Err(err) => trace!("Failed to send message: {:?}", err),
                         }
                         break;
                     }
@@ -119,6 +133,7 @@ fn send_and_receive() {
     }
 
     assert!(request_received_count >= quorum);
+    assert_eq!(res_ids.len(), 1);
 
     let _ = poll_all(&mut nodes);
 
@@ -126,9 +141,10 @@ fn send_and_receive() {
 
     loop {
         match nodes[sender_index].try_recv_event() {
-            Some(Event::MessageReceived { content, .. }) => {
+            Some(Event::MessageReceived { id, content, .. }) => {
                 response_received_count += 1;
                 if res_content == content {
+                    assert!(res_ids.contains(&id));
                     break;
                 }
             }
@@ -139,3 +155,282 @@ fn send_and_receive() {
 
     assert_eq!(response_received_count, 1);
 }
+
+#[test]
+fn send_fragmented_to_section() {
+    let elder_size = 8;
+    let safe_section_size = 8;
+    let quorum = quorum_count(elder_size);
+    let env = Environment::new(NetworkParams {
+        elder_size,
+        safe_section_size,
+        ..NetworkParams::default()
+    });
+    let mut rng = env.new_rng();
+    let mut nodes = create_connected_nodes(&env, elder_size + 1);
+
+    let sender_index = gen_elder_index(&mut rng, &nodes);
+    let src = SrcLocation::Node(nodes[sender_index].id());
+    let dst = DstLocation::Section(rng.gen());
+    match nodes[sender_index]
+        .inner
+        .send_message(src, dst, vec![0; MAX_MESSAGE_SIZE + 1])
+    {
+        Err(RoutingError::MessageTooLarge { .. }) => (),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+
+    let content = gen_vec(&mut rng, 4 * 1024 * 1024 + 1000);
+    assert!(content.len() > 16 * DEFAULT_MAX_FRAGMENT_SIZE);
+    let id = unwrap!(nodes[sender_index]
+        .inner
+        .send_message(src, dst, content.clone()));
+
+    let _ = poll_all(&mut nodes);
+
+    let mut message_received_count = 0;
+    for node in nodes
+        .iter_mut()
+        .filter(|n| n.inner.is_elder() && n.in_dst_location(&dst))
+    {
+        let events = messages_received(node);
+        assert_eq!(events, vec![(id, content.clone())]);
+        message_received_count += 1;
+    }
+
+    assert!(message_received_count >= quorum);
+}
+
+#[test]
+fn send_fragmented_to_node() {
+    let elder_size = 8;
+    let safe_section_size = 8;
+    let env = Environment::new(NetworkParams {
+        elder_size,
+        safe_section_size,
+        ..NetworkParams::default()
+    });
+    let mut rng = env.new_rng();
+    let mut nodes = create_connected_nodes(&env, elder_size + 1);
+
+    let receiver_index = gen_elder_index(&mut rng, &nodes);
+    let src = SrcLocation::Section(*nodes[receiver_index].our_prefix());
+    let dst = DstLocation::Node(nodes[receiver_index].name());
+    let content = gen_vec(&mut rng, 3 * 1024 * 1024 + 1000);
+    assert!(content.len() > 12 * DEFAULT_MAX_FRAGMENT_SIZE);
+
+    // All the elders of the sending section agree on the id of the message.
+    let mut ids = HashSet::new();
+    for node in nodes
+        .iter_mut()
+        .filter(|node| node.inner.is_elder() && node.in_src_location(&src))
+    {
+        let _ = ids.insert(unwrap!(node.inner.send_message(src, dst, content.clone())));
+    }
+    assert_eq!(ids.len(), 1);
+
+    let _ = poll_all(&mut nodes);
+
+    let events = messages_received(&mut nodes[receiver_index]);
+    assert_eq!(events.len(), 1);
+    assert!(ids.contains(&events[0].0));
+    assert_eq!(events[0].1, content);
+}
+
+#[test]
+fn send_with_ack() {
+    let elder_size = 8;
+    let safe_section_size = 8;
+    let env = Environment::new(NetworkParams {
+        elder_size,
+        safe_section_size,
+        ..NetworkParams::default()
+    });
+    let mut rng = env.new_rng();
+    let mut nodes = create_connected_nodes(&env, elder_size + 1);
+
+    let sender_index = gen_elder_index(&mut rng, &nodes);
+    let receiver_index = loop {
+        let index = gen_elder_index(&mut rng, &nodes);
+        if index != sender_index {
+            break index;
+        }
+    };
+    let src = SrcLocation::Node(nodes[sender_index].id());
+
+    // The receiver acknowledges the message.
+    let dst = DstLocation::Node(nodes[receiver_index].name());
+    let content = gen_vec(&mut rng, 1024);
+    let id = unwrap!(nodes[sender_index]
+        .inner
+        .send_message_with_ack(src, dst, content.clone()));
+
+    let _ = poll_all(&mut nodes);
+
+    assert_eq!(
+        messages_received(&mut nodes[receiver_index]),
+        vec![(id, content)]
+    );
+    assert_eq!(delivery_results(&mut nodes[sender_index]), vec![(id, true)]);
+
+    // Nobody acknowledges a message to a node which doesn't exist.
+    let dst = DstLocation::Node(rng.gen());
+    let id =
+        unwrap!(nodes[sender_index]
+            .inner
+            .send_message_with_ack(src, dst, gen_vec(&mut rng, 1024)));
+
+    let _ = poll_all(&mut nodes);
+    assert!(delivery_results(&mut nodes[sender_index]).is_empty());
+
+    FakeClock::advance_time(
+        (DELIVERY_ACK_TIMEOUT.as_millis() + 1)
+            .try_into()
+            .expect("time step too long"),
+    );
+    let _ = poll_all(&mut nodes);
+    assert_eq!(
+        delivery_results(&mut nodes[sender_index]),
+        vec![(id, false)]
+    );
+}
+
+#[test]
+fn parsec_progresses_while_user_traffic_saturates_links() {
+    let elder_size = 4;
+    let safe_section_size = 4;
+    let env = Environment::new(NetworkParams {
+        elder_size,
+        safe_section_size,
+        ..NetworkParams::default()
+    });
+    let mut nodes = create_connected_nodes(&env, elder_size);
+
+    // Adding a node needs the elders to reach consensus on it.
+    let config = NetworkConfig::node().with_hard_coded_contact(nodes[0].endpoint());
+    nodes.push(TestNode::builder(&env).network_config(config).create());
+
+    // Every round, each elder sends every other elder more user messages than can be in flight at
+    // once, so the links stay saturated until the end of the test.
+    let messages_per_round = 2 * test_consts::MAX_IN_FLIGHT_PER_PEER;
+    let max_rounds = 100;
+    let mut content_index = 0u64;
+    let mut sent_count = 0u64;
+    let mut received_count = 0;
+
+    for _ in 0..max_rounds {
+        for sender_index in 0..elder_size {
+            let src = SrcLocation::Node(nodes[sender_index].id());
+            for receiver_index in (0..elder_size).filter(|index| *index != sender_index) {
+                let dst = DstLocation::Node(nodes[receiver_index].name());
+                for _ in 0..messages_per_round {
+                    // Distinct contents, so the messages are not filtered as duplicates.
+                    content_index += 1;
+                    let content = content_index.to_be_bytes().to_vec();
+                    match nodes[sender_index].inner.send_message(src, dst, content) {
+                        Ok(_) => sent_count += 1,
+                        // The outbound queues are full, which is the point.
+                        Err(RoutingError::Backpressure) => (),
+                        Err(error) => panic!("Unexpected error: {:?}", error),
+                    }
+                }
+            }
+        }
+
+        env.poll();
+        for node in &mut nodes {
+            let _ = node.poll();
+            received_count += messages_received(node).len();
+        }
+
+        if nodes[elder_size].inner.is_approved() {
+            break;
+        }
+
+        FakeClock::advance_time(
+            (test_consts::GOSSIP_PERIOD.as_millis() + 1)
+                .try_into()
+                .expect("time step too long"),
+        );
+    }
+
+    assert!(
+        nodes[elder_size].inner.is_approved(),
+        "New node not approved in {} rounds",
+        max_rounds
+    );
+    // The user messages were still being queued when the node got approved.
+    assert!((received_count as u64) < sent_count);
+}
+
+#[test]
+fn backpressure() {
+    let elder_size = 4;
+    let safe_section_size = 4;
+    let env = Environment::new(NetworkParams {
+        elder_size,
+        safe_section_size,
+        ..NetworkParams::default()
+    });
+    let mut nodes = create_connected_nodes(&env, elder_size);
+
+    let src = SrcLocation::Node(nodes[0].id());
+    let dst = DstLocation::Node(nodes[1].name());
+    let capacity = (test_consts::MAX_IN_FLIGHT_PER_PEER + MAX_QUEUED_USER_MESSAGES) as u64;
+
+    // Without polling, the messages pile up in the outbound queue.
+    let mut sent_count = 0u64;
+    loop {
+        let content = (sent_count + 1).to_be_bytes().to_vec();
+        match nodes[0].inner.send_message(src, dst, content) {
+            Ok(_) => sent_count += 1,
+            Err(RoutingError::Backpressure) => break,
+            Err(error) => panic!("Unexpected error: {:?}", error),
+        }
+        assert!(sent_count <= capacity);
+    }
+    assert_eq!(sent_count, capacity);
+    assert_eq!(
+        nodes[0].inner.outbound_queue_depth(),
+        MAX_QUEUED_USER_MESSAGES
+    );
+
+    let _ = poll_all(&mut nodes);
+    assert_eq!(nodes[0].inner.outbound_queue_depth(), 0);
+    assert_eq!(messages_received(&mut nodes[1]).len() as u64, sent_count);
+
+    let mut ready_to_send_count = 0;
+    while let Some(event) = nodes[0].try_recv_event() {
+        if let Event::ReadyToSend = event {
+            ready_to_send_count += 1;
+        }
+    }
+    assert_eq!(ready_to_send_count, 1);
+
+    let _ = unwrap!(nodes[0].inner.send_message(src, dst, vec![]));
+}
+
+// Returns the ids and contents of all the messages the node received.
+fn messages_received(node: &mut TestNode) -> Vec<(MessageId, Vec<u8>)> {
+    let mut received = Vec::new();
+    while let Some(event) = node.try_recv_event() {
+        if let Event::MessageReceived { id, content, .. } = event {
+            received.push((id, content));
+        }
+    }
+    received
+}
+
+// Returns the ids of the messages sent with ack whose delivery the node was notified about, and
+// whether they were delivered.
+fn delivery_results(node: &mut TestNode) -> Vec<(MessageId, bool)> {
+    let mut results = Vec::new();
+    while let Some(event) = node.try_recv_event() {
+        match event {
+            Event::MessageDelivered(id) => results.push((id, true)),
+            Event::MessageDeliveryFailed(id) => results.push((id, false)),
+            _ => (),
+        }
+    }
+    results
+}
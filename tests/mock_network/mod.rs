@@ -7,6 +7,8 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 mod accumulate;
+#[cfg(feature = "async")]
+mod async_node;
 mod churn;
 mod drop;
 mod messages;
@@ -15,16 +17,23 @@ mod secure_message_delivery;
 mod utils;
 
 pub use self::utils::*;
+use bytes::Bytes;
 use itertools::Itertools;
 use rand::{seq::SliceRandom, Rng};
 use routing::{
-    event::Event, mock::Environment, FullId, NetworkConfig, NetworkParams, Prefix,
-    RelocationOverrides, XorName,
+    event::{Connected, Event},
+    metrics::{self, InMemoryMetricsSink},
+    mock::Environment,
+    EventSink, FullId, NetworkConfig, NetworkEvent, NetworkParams, Node, NodeState, Peer, Prefix,
+    RelocationOverrides, ResourceProofOverrides, ResourceProofParams, RoutingError, Token,
+    Transport, UnderPopulatedRelocationPolicy, XorName,
 };
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, net::SocketAddr, sync::Arc, time::Duration};
 
 pub const LOWERED_ELDER_SIZE: usize = 3;
 
+const RESOURCE_PROOF_TIMEOUT: Duration = Duration::from_secs(5);
+
 // -----  Miscellaneous tests below  -----
 
 // fn nodes_with_candidate(nodes: &[TestNode]) -> Vec<XorName> {
@@ -40,6 +49,7 @@ fn test_nodes(percentage_size: usize) {
     let env = Environment::new(NetworkParams {
         elder_size: LOWERED_ELDER_SIZE,
         safe_section_size: LOWERED_ELDER_SIZE,
+        ..NetworkParams::default()
     });
     let mut nodes = create_connected_nodes(&env, size);
     verify_invariant_for_all_nodes(&env, &mut nodes);
@@ -102,6 +112,7 @@ fn disconnect_on_rebootstrap() {
     let env = Environment::new(NetworkParams {
         elder_size: LOWERED_ELDER_SIZE,
         safe_section_size: LOWERED_ELDER_SIZE,
+        ..NetworkParams::default()
     });
     let mut nodes = create_connected_nodes(&env, 2);
 
@@ -116,6 +127,41 @@ fn disconnect_on_rebootstrap() {
     expect_next_event!(unwrap!(nodes.last_mut()), Event::Terminated);
 }
 
+// Transport which can't reach any peer.
+struct UnreachableTransport {
+    events: EventSink,
+}
+
+impl Transport for UnreachableTransport {
+    fn bootstrap(&mut self) {
+        let _ = self.events.send(NetworkEvent::BootstrapFailure);
+    }
+
+    fn send(&mut self, peer: Peer, msg: Bytes, token: Token) {
+        let _ = self
+            .events
+            .send(NetworkEvent::UnsentUserMessage { peer, msg, token });
+    }
+
+    fn disconnect_from(&mut self, _: SocketAddr) {}
+
+    fn our_connection_info(&mut self) -> Result<SocketAddr, RoutingError> {
+        Err(RoutingError::InvalidState)
+    }
+}
+
+#[test]
+fn run_over_custom_transport() {
+    let env = Environment::new(Default::default());
+    let mut node = TestNode::builder(&env)
+        .transport(|events| Ok(Box::new(UnreachableTransport { events }) as Box<dyn Transport>))
+        .create();
+
+    // The events of the transport reach the node without the mock network.
+    let _ = node.poll();
+    expect_next_event!(node, Event::Terminated);
+}
+
 // TODO: either modify this test or remove it
 // #[test]
 // fn candidate_expiration() {
@@ -174,6 +220,7 @@ fn single_section() {
     let env = Environment::new(NetworkParams {
         elder_size: sec_size,
         safe_section_size: sec_size,
+        ..NetworkParams::default()
     });
     let mut nodes = create_connected_nodes(&env, sec_size);
     verify_invariant_for_all_nodes(&env, &mut nodes);
@@ -199,6 +246,7 @@ fn node_joins_in_front() {
     let env = Environment::new(NetworkParams {
         elder_size: LOWERED_ELDER_SIZE,
         safe_section_size: LOWERED_ELDER_SIZE,
+        ..NetworkParams::default()
     });
     let mut nodes = create_connected_nodes(&env, 2 * LOWERED_ELDER_SIZE);
     let network_config = NetworkConfig::node().with_hard_coded_contact(nodes[0].endpoint());
@@ -218,6 +266,7 @@ fn multiple_joining_nodes() {
     let env = Environment::new(NetworkParams {
         elder_size: LOWERED_ELDER_SIZE,
         safe_section_size: LOWERED_ELDER_SIZE,
+        ..NetworkParams::default()
     });
     let mut nodes = create_connected_nodes(&env, LOWERED_ELDER_SIZE);
 
@@ -275,6 +324,7 @@ fn multi_split() {
     let env = Environment::new(NetworkParams {
         elder_size: LOWERED_ELDER_SIZE,
         safe_section_size: LOWERED_ELDER_SIZE,
+        ..NetworkParams::default()
     });
     let mut nodes = create_connected_nodes_until_split(&env, vec![2, 2, 2, 2]);
     verify_invariant_for_all_nodes(&env, &mut nodes);
@@ -380,6 +430,7 @@ fn simultaneous_joining_nodes_two_sections() {
     let env = Environment::new(NetworkParams {
         elder_size: LOWERED_ELDER_SIZE,
         safe_section_size: LOWERED_ELDER_SIZE,
+        ..NetworkParams::default()
     });
     let nodes = create_connected_nodes_until_split(&env, vec![1, 1]);
 
@@ -408,6 +459,7 @@ fn simultaneous_joining_nodes_two_sections_switch_section() {
     let env = Environment::new(NetworkParams {
         elder_size: LOWERED_ELDER_SIZE,
         safe_section_size: LOWERED_ELDER_SIZE,
+        ..NetworkParams::default()
     });
     let nodes = create_connected_nodes_until_split(&env, vec![1, 1]);
 
@@ -441,6 +493,7 @@ fn simultaneous_joining_nodes_three_section_with_one_ready_to_split() {
     let env = Environment::new(NetworkParams {
         elder_size,
         safe_section_size,
+        ..NetworkParams::default()
     });
     let mut nodes = create_connected_nodes_until_split(&env, vec![1, 2, 2]);
 
@@ -486,6 +539,7 @@ fn check_close_names_for_elder_size_nodes() {
         &Environment::new(NetworkParams {
             elder_size: LOWERED_ELDER_SIZE,
             safe_section_size: LOWERED_ELDER_SIZE,
+            ..NetworkParams::default()
         }),
         LOWERED_ELDER_SIZE,
     );
@@ -495,6 +549,144 @@ fn check_close_names_for_elder_size_nodes() {
     assert!(close_sections_complete);
 }
 
+#[test]
+fn node_status() {
+    let env = Environment::new(NetworkParams {
+        elder_size: LOWERED_ELDER_SIZE,
+        safe_section_size: LOWERED_ELDER_SIZE + 2,
+        ..NetworkParams::default()
+    });
+    let nodes = create_connected_nodes(&env, LOWERED_ELDER_SIZE + 1);
+
+    for node in &nodes {
+        let status = node.inner.status();
+        let expected_state = if node.inner.is_elder() {
+            NodeState::Elder
+        } else {
+            NodeState::Adult
+        };
+
+        assert_eq!(status.state, expected_state);
+        assert_eq!(status.name, Some(node.name()));
+        assert_eq!(status.prefix, Some(Prefix::default()));
+        assert_eq!(status.elders.len(), LOWERED_ELDER_SIZE);
+        assert_eq!(
+            status.elders.len() + status.adults.len() + status.infants.len(),
+            nodes.len()
+        );
+        assert!(status.neighbours.is_empty());
+        assert!(unwrap!(status.network_size_estimate).is_exact);
+    }
+}
+
+#[test]
+fn report_metrics() {
+    let env = Environment::new(NetworkParams {
+        elder_size: LOWERED_ELDER_SIZE,
+        safe_section_size: LOWERED_ELDER_SIZE,
+        ..NetworkParams::default()
+    });
+    let sink = Arc::new(InMemoryMetricsSink::new());
+
+    let mut nodes = vec![TestNode::builder(&env)
+        .first()
+        .metrics(sink.clone())
+        .create()];
+    let _ = nodes[0].poll();
+    let endpoint = nodes[0].endpoint();
+
+    for _ in 1..LOWERED_ELDER_SIZE {
+        let config = NetworkConfig::node().with_hard_coded_contact(endpoint);
+        nodes.push(TestNode::builder(&env).network_config(config).create());
+        poll_and_resend(&mut nodes);
+    }
+
+    let joined = LOWERED_ELDER_SIZE as u64 - 1;
+    assert!(sink.counter(metrics::MESSAGES_RECEIVED, Some("JoinRequest")) >= joined);
+    assert!(sink.counter(metrics::MESSAGES_SENT, Some("NodeApproval")) > 0);
+    assert_eq!(sink.counter(metrics::CHURN_EVENTS, Some("online")), joined);
+    assert!(sink.counter(metrics::PARSEC_GOSSIP_BYTES, None) > 0);
+}
+
+fn resource_proof_env() -> Environment {
+    Environment::new(NetworkParams {
+        elder_size: LOWERED_ELDER_SIZE,
+        safe_section_size: LOWERED_ELDER_SIZE,
+        resource_proof: Some(ResourceProofParams {
+            difficulty: 4,
+            size: 1024,
+            timeout: RESOURCE_PROOF_TIMEOUT,
+        }),
+        ..NetworkParams::default()
+    })
+}
+
+#[test]
+fn join_with_resource_proof() {
+    let env = resource_proof_env();
+    let mut nodes = create_connected_nodes(&env, LOWERED_ELDER_SIZE);
+    verify_invariant_for_all_nodes(&env, &mut nodes);
+
+    // Every elder challenges the new node, which answers each of them before being approved.
+    let sink = Arc::new(InMemoryMetricsSink::new());
+    let config = NetworkConfig::node().with_hard_coded_contact(nodes[0].endpoint());
+    nodes.push(
+        TestNode::builder(&env)
+            .network_config(config)
+            .metrics(sink.clone())
+            .create(),
+    );
+    poll_and_resend(&mut nodes);
+
+    expect_any_event!(
+        unwrap!(nodes.last_mut()),
+        Event::Connected(Connected::First)
+    );
+    assert!(
+        sink.counter(metrics::MESSAGES_RECEIVED, Some("ResourceChallenge"))
+            >= LOWERED_ELDER_SIZE as u64
+    );
+    assert!(
+        sink.counter(metrics::MESSAGES_SENT, Some("ResourceProofResponse"))
+            >= LOWERED_ELDER_SIZE as u64
+    );
+    verify_invariant_for_all_nodes(&env, &mut nodes);
+}
+
+// Adds a node whose answers to the resource challenges are tampered with by `overrides` and checks
+// the section doesn't accept it.
+fn join_with_rejected_resource_proof(configure: impl FnOnce(&mut ResourceProofOverrides)) {
+    let env = resource_proof_env();
+    let mut nodes = create_connected_nodes(&env, LOWERED_ELDER_SIZE);
+
+    let mut overrides = ResourceProofOverrides::new();
+    configure(&mut overrides);
+
+    let config = NetworkConfig::node().with_hard_coded_contact(nodes[0].endpoint());
+    nodes.push(TestNode::builder(&env).network_config(config).create());
+    let new_id = unwrap!(nodes.last()).id();
+    poll_and_resend_with_options(&mut nodes, PollOptions::default().fire_join_timeout(false));
+
+    let new_node = unwrap!(nodes.pop());
+    expect_no_event!(new_node, Event::Connected(_));
+    assert!(!new_node.inner.is_approved());
+    assert!(nodes
+        .iter()
+        .all(|node| !node.inner.is_peer_our_member(&new_id)));
+}
+
+#[test]
+fn reject_slow_resource_proof() {
+    join_with_rejected_resource_proof(|overrides| {
+        overrides.set_solve_duration(RESOURCE_PROOF_TIMEOUT + Duration::from_secs(1))
+    })
+}
+
+#[test]
+fn reject_invalid_resource_proof() {
+    join_with_rejected_resource_proof(|overrides| overrides.corrupt_answers())
+}
+
 #[test]
 fn check_section_info_ack() {
     // Arrange
@@ -504,6 +696,7 @@ fn check_section_info_ack() {
     let env = Environment::new(NetworkParams {
         elder_size,
         safe_section_size,
+        ..NetworkParams::default()
     });
 
     // Act
@@ -529,6 +722,37 @@ fn check_section_info_ack() {
     assert_eq!(node_with_sibling_knowledge, expected_all_elder);
 }
 
+#[test]
+fn neighbours_know_member_counts() {
+    let env = Environment::new(NetworkParams {
+        elder_size: LOWERED_ELDER_SIZE,
+        safe_section_size: LOWERED_ELDER_SIZE,
+        ..NetworkParams::default()
+    });
+    let mut nodes = create_connected_nodes_until_split(&env, vec![1, 1]);
+    poll_and_resend(&mut nodes);
+
+    for prefix in current_sections(&nodes).collect_vec() {
+        let member_count = nodes
+            .iter()
+            .filter(|node| prefix.matches(&node.name()))
+            .count() as u64;
+
+        for node in nodes
+            .iter()
+            .filter(|node| node.inner.is_elder() && !prefix.matches(&node.name()))
+        {
+            assert_eq!(
+                node.inner.neighbour_member_count(&prefix),
+                Some(member_count),
+                "{} doesn't know the member count of {:?}",
+                node.inner,
+                prefix
+            );
+        }
+    }
+}
+
 #[test]
 fn carry_out_parsec_pruning() {
     let init_network_size = 7;
@@ -537,6 +761,7 @@ fn carry_out_parsec_pruning() {
     let env = Environment::new(NetworkParams {
         elder_size,
         safe_section_size,
+        ..NetworkParams::default()
     });
     let mut nodes = create_connected_nodes(&env, init_network_size);
     poll_and_resend(&mut nodes);
@@ -587,14 +812,17 @@ fn carry_out_parsec_pruning() {
 
 // The paused node does not participate until resumed, so we need enough elders to reach
 // consensus even without it.
-const NODE_PAUSE_AND_RESUME_PARAMS: NetworkParams = NetworkParams {
-    elder_size: 4,
-    safe_section_size: 4,
-};
+fn node_pause_and_resume_params() -> NetworkParams {
+    NetworkParams {
+        elder_size: 4,
+        safe_section_size: 4,
+        ..NetworkParams::default()
+    }
+}
 
 #[test]
 fn node_pause_and_resume_simple() {
-    let env = Environment::new(NODE_PAUSE_AND_RESUME_PARAMS);
+    let env = Environment::new(node_pause_and_resume_params());
     let nodes = create_connected_nodes(&env, 2 * env.safe_section_size() - 2);
     let new_node_id = FullId::gen(&mut env.new_rng());
     node_pause_and_resume(env, nodes, new_node_id)
@@ -602,7 +830,7 @@ fn node_pause_and_resume_simple() {
 
 #[test]
 fn node_pause_and_resume_during_split() {
-    let env = Environment::new(NODE_PAUSE_AND_RESUME_PARAMS);
+    let env = Environment::new(node_pause_and_resume_params());
 
     let mut nodes = create_connected_nodes(&env, env.safe_section_size());
     let prefix =
@@ -644,3 +872,80 @@ fn node_pause_and_resume(env: Environment, mut nodes: Nodes, new_node_id: FullId
     poll_and_resend(&mut nodes);
     verify_invariant_for_all_nodes(&env, &mut nodes);
 }
+
+#[test]
+fn node_pause_and_resume_from_bytes() {
+    let env = Environment::new(node_pause_and_resume_params());
+    let mut nodes = create_connected_nodes(&env, env.safe_section_size());
+
+    let index = env.new_rng().gen_range(0, nodes.len());
+    let paused_id = nodes[index].id();
+    let paused_endpoint = nodes[index].endpoint();
+    let state = unwrap!(nodes.remove(index).inner.pause());
+    let bytes = unwrap!(state.to_bytes());
+
+    // Restart the node from the bytes at the same address, as when upgrading it.
+    drop(state);
+    let config = NetworkConfig::node()
+        .with_hard_coded_contact(nodes[0].endpoint())
+        .with_endpoint(paused_endpoint);
+    let sink = Arc::new(InMemoryMetricsSink::new());
+    nodes.push(
+        TestNode::builder(&env)
+            .network_config(config)
+            .metrics(sink.clone())
+            .resume_from_bytes(&bytes),
+    );
+    poll_and_resend(&mut nodes);
+
+    assert_eq!(unwrap!(nodes.last()).id(), paused_id);
+    assert!(sink.counter_total(metrics::MESSAGES_SENT) > 0);
+    assert!(nodes
+        .iter()
+        .all(|n| !n.inner.is_elder() || n.inner.is_peer_our_member(&paused_id)));
+
+    // Drop another elder. The rest of the elders can't reach a quorum on it going offline without
+    // the resumed one, so it has to take part in the section's parsec again.
+    let dropped_index = unwrap!(nodes.iter().position(|node| node.id() != paused_id));
+    let dropped_id = nodes.remove(dropped_index).id();
+    poll_and_resend(&mut nodes);
+
+    assert!(nodes
+        .iter()
+        .all(|node| !node.inner.is_peer_our_member(&dropped_id)));
+    for node in &nodes {
+        assert!(unwrap!(node.inner.our_elders_info())
+            .any(|p2p_node| *p2p_node.public_id() == paused_id));
+    }
+
+    // Verify the resumed node keeps up with the changes in the network.
+    let node = create_node_with_contact(&env, &mut nodes[0]);
+    nodes.push(node);
+    poll_and_resend(&mut nodes);
+    verify_invariant_for_all_nodes(&env, &mut nodes);
+}
+
+#[test]
+fn node_resume_from_bytes_with_other_policy() {
+    let env = Environment::new(node_pause_and_resume_params());
+    let mut nodes = create_connected_nodes(&env, env.safe_section_size());
+
+    let state = unwrap!(unwrap!(nodes.pop()).inner.pause());
+    let bytes = unwrap!(state.to_bytes());
+    drop(state);
+
+    let network_cfg = NetworkParams {
+        relocation_policy: Arc::new(UnderPopulatedRelocationPolicy),
+        ..env.network_cfg()
+    };
+    let config = NetworkConfig::node().with_hard_coded_contact(nodes[0].endpoint());
+    match Node::builder()
+        .network_config(config)
+        .network_cfg(network_cfg)
+        .resume_from_bytes(&bytes)
+    {
+        Err(RoutingError::PolicyMismatch { kind, .. }) => assert_eq!(kind, "relocation"),
+        Err(error) => panic!("Unexpected error: {}", error),
+        Ok(_) => panic!("Resumed with a different relocation policy."),
+    }
+}
@@ -17,21 +17,106 @@ use rand::{
     Rng,
 };
 use routing::{
-    mock::Environment, FullId, NetworkConfig, NetworkParams, Prefix, PublicId, RelocationOverrides,
-    XorName,
+    mock::Environment, AgeingPolicy, DefaultAgeingPolicy, FullId, NetworkConfig, NetworkParams,
+    Prefix, PublicId, RelocationOverrides, XorName, MIN_AGE,
 };
-use std::{iter, slice};
+use std::{iter, slice, sync::Arc};
 
 // These params are selected such that there can be a section size which allows relocation and at the same time
 // allows churn to happen which doesn't trigger split or allow churn to not increase age.
-const NETWORK_PARAMS: NetworkParams = NetworkParams {
-    elder_size: LOWERED_ELDER_SIZE,
-    safe_section_size: LOWERED_ELDER_SIZE + 4,
-};
+fn network_params(ageing_policy: Arc<dyn AgeingPolicy>) -> NetworkParams {
+    NetworkParams {
+        elder_size: LOWERED_ELDER_SIZE,
+        safe_section_size: LOWERED_ELDER_SIZE + 4,
+        ageing_policy,
+        ..NetworkParams::default()
+    }
+}
+
+// Ages twice as fast as the default policy.
+#[derive(Debug)]
+struct DoubleSpeedAgeingPolicy;
+
+impl AgeingPolicy for DoubleSpeedAgeingPolicy {
+    fn id(&self) -> &str {
+        "double-speed"
+    }
+
+    fn start_age(&self) -> u8 {
+        MIN_AGE
+    }
+
+    fn age_counter_increment(&self, _age: u8) -> u32 {
+        2
+    }
+
+    fn is_mature(&self, age: u8) -> bool {
+        age > MIN_AGE
+    }
+
+    fn should_relocate(&self, old_age: u8, new_age: u8) -> bool {
+        new_age > old_age
+    }
+}
+
+// Ages four times as fast as the default policy and treats all nodes as mature.
+#[derive(Debug)]
+struct FastMaturityAgeingPolicy;
+
+impl AgeingPolicy for FastMaturityAgeingPolicy {
+    fn id(&self) -> &str {
+        "fast-maturity"
+    }
+
+    fn start_age(&self) -> u8 {
+        MIN_AGE
+    }
+
+    fn age_counter_increment(&self, _age: u8) -> u32 {
+        4
+    }
+
+    fn is_mature(&self, _age: u8) -> bool {
+        true
+    }
+
+    fn should_relocate(&self, old_age: u8, new_age: u8) -> bool {
+        new_age > old_age
+    }
+}
 
 #[test]
 fn relocate_without_split() {
-    let env = Environment::new(NETWORK_PARAMS);
+    relocate_without_split_with_policy(Arc::new(DefaultAgeingPolicy))
+}
+
+#[test]
+fn relocate_without_split_double_speed_ageing() {
+    relocate_without_split_with_policy(Arc::new(DoubleSpeedAgeingPolicy))
+}
+
+#[test]
+fn relocate_without_split_fast_maturity_ageing() {
+    relocate_without_split_with_policy(Arc::new(FastMaturityAgeingPolicy))
+}
+
+#[test]
+fn relocate_causing_split() {
+    relocate_causing_split_with_policy(Arc::new(DefaultAgeingPolicy))
+}
+
+#[test]
+fn relocate_causing_split_double_speed_ageing() {
+    relocate_causing_split_with_policy(Arc::new(DoubleSpeedAgeingPolicy))
+}
+
+#[test]
+fn relocate_causing_split_fast_maturity_ageing() {
+    relocate_causing_split_with_policy(Arc::new(FastMaturityAgeingPolicy))
+}
+
+fn relocate_without_split_with_policy(ageing_policy: Arc<dyn AgeingPolicy>) {
+    let env = Environment::new(network_params(Arc::clone(&ageing_policy)));
     let mut overrides = RelocationOverrides::new();
 
     let mut rng = env.new_rng();
@@ -51,7 +136,7 @@ fn relocate_without_split() {
     // Create enough churn events so that the age of the oldest node increases which causes it to
     // be relocated.
     let oldest_age_counter = node_age_counter(&nodes, 0);
-    let num_churns = oldest_age_counter.next_power_of_two() - oldest_age_counter;
+    let num_churns = churns_until_relocation(&*ageing_policy, oldest_age_counter);
     section_churn_allowing_relocate(num_churns, &env, &mut nodes, &source_prefix);
     poll_and_resend(&mut nodes);
 
@@ -63,8 +148,7 @@ fn relocate_without_split() {
     );
 }
 
-#[test]
-fn relocate_causing_split() {
+fn relocate_causing_split_with_policy(ageing_policy: Arc<dyn AgeingPolicy>) {
     // Note: this test doesn't always trigger split in the target section. This is because when the
     // target section receives the bootstrap request from the relocating node, it still has its
     // pre-split prefix which it gives to the node. So the node then generates random name matching
@@ -74,7 +158,7 @@ fn relocate_causing_split() {
     // sub-interval, but the test is still useful as is for soak testing.
 
     // Relocate node into a section which is one node shy of splitting.
-    let env = Environment::new(NETWORK_PARAMS);
+    let env = Environment::new(network_params(Arc::clone(&ageing_policy)));
     let mut overrides = RelocationOverrides::new();
 
     let mut rng = env.new_rng();
@@ -98,7 +182,7 @@ fn relocate_causing_split() {
     overrides.set(source_prefix, destination);
 
     // Trigger relocation.
-    let num_churns = oldest_age_counter.next_power_of_two() - oldest_age_counter;
+    let num_churns = churns_until_relocation(&*ageing_policy, oldest_age_counter);
     section_churn_allowing_relocate(num_churns, &env, &mut nodes, &source_prefix);
     poll_and_resend(&mut nodes);
 
@@ -130,7 +214,7 @@ fn relocate_causing_split() {
 #[test]
 fn relocate_during_split() {
     // Relocate node into a section which is undergoing split.
-    let env = Environment::new(NETWORK_PARAMS);
+    let env = Environment::new(network_params(Arc::new(DefaultAgeingPolicy)));
     let mut overrides = RelocationOverrides::new();
 
     let mut rng = env.new_rng();
@@ -151,7 +235,7 @@ fn relocate_during_split() {
     overrides.set(source_prefix, destination);
 
     // Create churn so we are one churn away from relocation.
-    let num_churns = oldest_age_counter.next_power_of_two() - oldest_age_counter - 1;
+    let num_churns = churns_until_relocation(&DefaultAgeingPolicy, oldest_age_counter) - 1;
     section_churn_allowing_relocate(num_churns, &env, &mut nodes, &source_prefix);
 
     // Add new node, but do not poll yet.
@@ -186,6 +270,23 @@ fn node_age_counter(nodes: &[TestNode], index: usize) -> usize {
     }
 }
 
+// Number of churn events after which the node with the given age counter gets relocated.
+fn churns_until_relocation(ageing_policy: &dyn AgeingPolicy, age_counter: usize) -> usize {
+    let age = |age_counter: usize| (age_counter as f64).log2() as u8;
+
+    let mut age_counter = age_counter;
+    let mut churns = 0;
+    loop {
+        let old_age = age(age_counter);
+        age_counter += ageing_policy.age_counter_increment(old_age) as usize;
+        churns += 1;
+
+        if ageing_policy.should_relocate(old_age, age(age_counter)) {
+            return churns;
+        }
+    }
+}
+
 fn find_matching_prefix<'a>(
     prefixes: &'a [Prefix<XorName>],
     name: &XorName,
@@ -242,11 +343,11 @@ fn section_churn_allowing_relocate(
     prefix: &Prefix<XorName>,
 ) {
     // Keep the section size such that relocations can happen but splits can't.
-    // We need NETWORK_PARAMS.elder_size + 1 excluding relocating node for it to be demoted.
-    let min_size = (NETWORK_PARAMS.elder_size + 1) + 1;
+    // We need elder_size + 1 excluding relocating node for it to be demoted.
+    let min_size = (env.elder_size() + 1) + 1;
 
     // Ensure we are increasing age at each churn event.
-    let max_size = NETWORK_PARAMS.safe_section_size - 1;
+    let max_size = env.safe_section_size() - 1;
 
     section_churn(count, &env, nodes, &prefix, min_size, max_size)
 }
@@ -49,6 +49,7 @@ fn message_with_invalid_security(fail_type: FailType) {
     let mut env = Environment::new(NetworkParams {
         elder_size,
         safe_section_size,
+        ..NetworkParams::default()
     });
     env.expect_panic();
     let mut rng = env.new_rng();
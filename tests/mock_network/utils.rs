@@ -15,9 +15,11 @@ use rand::{
 };
 use routing::{
     event::{Connected, Event},
+    metrics::MetricsSink,
     mock::Environment,
-    test_consts, Builder, DstLocation, FullId, NetworkConfig, Node, PausedState, Prefix, PublicId,
-    RelocationOverrides, SrcLocation, XorName, Xorable,
+    test_consts, Builder, DstLocation, EventSink, FullId, NetworkConfig, Node, PausedState, Prefix,
+    PublicId, RelocationOverrides, ResendConfig, RoutingError, SrcLocation, Transport, XorName,
+    Xorable,
 };
 use std::{
     cmp,
@@ -26,6 +28,7 @@ use std::{
     iter,
     net::SocketAddr,
     ops::{Deref, DerefMut},
+    sync::Arc,
     time::Duration,
 };
 
@@ -185,6 +188,16 @@ impl<'a> TestNodeBuilder<'a> {
         }
     }
 
+    pub fn transport<F>(self, make_transport: F) -> Self
+    where
+        F: FnOnce(EventSink) -> Result<Box<dyn Transport>, RoutingError> + 'static,
+    {
+        Self {
+            inner: self.inner.transport(make_transport),
+            ..self
+        }
+    }
+
     pub fn full_id(self, full_id: FullId) -> Self {
         Self {
             inner: self.inner.full_id(full_id),
@@ -192,8 +205,28 @@ impl<'a> TestNodeBuilder<'a> {
         }
     }
 
+    pub fn metrics(self, sink: Arc<dyn MetricsSink>) -> Self {
+        Self {
+            inner: self.inner.metrics(sink),
+            ..self
+        }
+    }
+
+    pub fn resume_from_bytes(self, bytes: &[u8]) -> TestNode {
+        let (inner, user_event_rx) = unwrap!(self
+            .inner
+            .network_cfg(self.env.network_cfg())
+            .resume_from_bytes(bytes));
+
+        TestNode {
+            inner,
+            env: self.env.clone(),
+            user_event_rx,
+        }
+    }
+
     pub fn create(self) -> TestNode {
-        let (inner, user_event_rx, _client_rx) = self
+        let (inner, user_event_rx) = self
             .inner
             .network_cfg(self.env.network_cfg())
             .rng(&mut self.env.new_rng())
@@ -308,7 +341,9 @@ pub fn poll_and_resend_with_options(nodes: &mut [TestNode], mut options: PollOpt
         // Give the nodes time to detect lost peers.
         if resend_attempts < test_consts::RESEND_MAX_ATTEMPTS {
             resend_attempts += 1;
-            advance_time(test_consts::RESEND_DELAY + Duration::from_millis(1));
+            advance_time(
+                ResendConfig::default().backoff(resend_attempts) + Duration::from_millis(1),
+            );
             continue;
         }
 